@@ -1,2 +1,4 @@
+pub mod rtp_extensions_plot;
 pub mod rtp_streams_plot;
+pub use rtp_extensions_plot::*;
 pub use rtp_streams_plot::*;
@@ -0,0 +1,123 @@
+use crate::app::common::PlotBase;
+use crate::streams::{RefStreams, rtpStream::RtpStream};
+use egui::{ComboBox, Context, RichText, Ui};
+use egui_plot::{Legend, Line, Plot, PlotPoints};
+use ewebsock::WsSender;
+use netpix_common::rtp::extension::ExtensionKind;
+use std::{any::Any, time::Duration};
+
+pub struct RtpExtensionsPlot {
+    streams: RefStreams,
+    kind: ExtensionKind,
+    ws_sender: WsSender,
+}
+
+impl PlotBase for RtpExtensionsPlot {
+    fn new(streams: RefStreams, ws_sender: WsSender) -> Self
+    where
+        Self: Sized,
+    {
+        Self {
+            streams,
+            kind: ExtensionKind::AudioLevel,
+            ws_sender,
+        }
+    }
+
+    fn ui(&mut self, ctx: &Context) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            self.options_ui(ui);
+            ui.separator();
+            self.plot_ui(ui);
+        });
+    }
+
+    fn plot_id(&self) -> &'static str {
+        "rtp_extensions_plot"
+    }
+
+    fn plot_name(&self) -> &'static str {
+        "RTP Extensions Plot"
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl RtpExtensionsPlot {
+    fn options_ui(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.label(RichText::from("Header extension:").strong());
+            ComboBox::from_id_salt("rtp_extension_kind")
+                .selected_text(self.kind.to_string())
+                .show_ui(ui, |ui| {
+                    ExtensionKind::all()
+                        .into_iter()
+                        .filter(ExtensionKind::is_numeric)
+                        .for_each(|kind| {
+                            ui.selectable_value(&mut self.kind, kind, kind.to_string());
+                        });
+                });
+        });
+        ui.label(
+            "Values of the streams whose SDP maps the extension with a=extmap, \
+             streams are matched to the SDP set with \"Set SDP\" in the RTP Streams tab",
+        );
+    }
+
+    fn plot_ui(&mut self, ui: &mut Ui) {
+        let streams = self.streams.borrow();
+        let mut streams: Vec<_> = streams
+            .rtp_streams
+            .values()
+            .filter_map(|stream| Some((stream, stream.extension_id(self.kind)?)))
+            .collect();
+        streams.sort_by(|(a, _), (b, _)| a.alias.cmp(&b.alias));
+
+        if streams.is_empty() {
+            ui.label(format!("No stream has the {} extension mapped", self.kind));
+            return;
+        }
+
+        let start = streams
+            .iter()
+            .filter_map(|(stream, _)| stream.rtp_packets.first())
+            .map(|rtp| rtp.time)
+            .min()
+            .unwrap_or_default();
+
+        Plot::new("rtp-extensions-plot")
+            .legend(Legend::default())
+            .x_axis_label("Seconds from start")
+            .y_axis_label(format!("{} {}", self.kind, self.kind.unit()))
+            .show(ui, |plot_ui| {
+                for (stream, id) in streams {
+                    let points = extension_points(stream, id, self.kind, start);
+                    plot_ui.line(Line::new(PlotPoints::from(points)).name(&stream.alias));
+                }
+            });
+    }
+}
+
+fn extension_points(
+    stream: &RtpStream,
+    id: u8,
+    kind: ExtensionKind,
+    start: Duration,
+) -> Vec<[f64; 2]> {
+    stream
+        .rtp_packets
+        .iter()
+        .filter_map(|rtp| {
+            let extension = rtp.packet.extensions.iter().find(|ext| ext.id == id)?;
+            let value = kind.decode(&extension.data)?.as_f64()?;
+            let x = rtp.time.saturating_sub(start).as_secs_f64();
+            Some([x, value])
+        })
+        .collect()
+}
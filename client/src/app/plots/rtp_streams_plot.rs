@@ -33,7 +33,7 @@ fn find_first_available_rtp<'a>(
     packets: &'a crate::streams::packets::Packets,
 ) -> Option<(usize, &'a RtpInfo, &'a Packet, &'a RtpPacket)> {
     for (idx, rtp_info) in rtp_packets.iter().enumerate() {
-        if let Some(packet) = packets.get(rtp_info.id) {
+        if let Some(packet) = packets.get(rtp_info.id, rtp_info.sub_index) {
             if let SessionPacket::Rtp(ref rtp_packet) = packet.contents {
                 return Some((idx, rtp_info, packet, rtp_packet));
             }
@@ -519,8 +519,8 @@ fn get_highest_y(
             let previous_packet = if packet_ix == first_idx {
                 None
             } else {
-                let prev_rtp_id = rtp_packets.get(packet_ix - 1).unwrap().id;
-                streams.packets.get(prev_rtp_id)
+                let prev_rtp = rtp_packets.get(packet_ix - 1).unwrap();
+                streams.packets.get(prev_rtp.id, prev_rtp.sub_index)
             };
 
             let (_, _, y_top) = get_x_and_y(
@@ -680,8 +680,8 @@ fn build_stream_points(
             let previous_packet = if packet_ix == first_idx {
                 None
             } else {
-                let prev_rtp_id = rtp_packets.get(packet_ix - 1).unwrap().id;
-                streams.packets.get(prev_rtp_id)
+                let prev_rtp = rtp_packets.get(packet_ix - 1).unwrap();
+                streams.packets.get(prev_rtp.id, prev_rtp.sub_index)
             };
 
             let (x, y_low, y_top) = get_x_and_y(
@@ -20,6 +20,8 @@ pub enum RtpSection {
     RtcpStreams,
     Streams,
     Plot,
+    ExtensionsPlot,
+    Announcements,
 }
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum MpegTsSection {
@@ -32,6 +34,7 @@ pub enum MpegTsSection {
 pub enum IceSection {
     StunPackets,
     Candidates,
+    DtlsHandshakes,
 }
 
 impl Tab {
@@ -84,7 +87,9 @@ impl Tab {
                 RtpSection::RtcpPackets => "rtcp_packets",
                 RtpSection::Streams => "rtp_streams",
                 RtpSection::Plot => "rtp_streams_plot",
+                RtpSection::ExtensionsPlot => "rtp_extensions_plot",
                 RtpSection::RtcpStreams => "rtcp_streams",
+                RtpSection::Announcements => "announcements",
             },
             Tab::MpegTsSection(section) => match section {
                 MpegTsSection::Packets => "mpegts_packets",
@@ -94,6 +99,7 @@ impl Tab {
             Tab::IceSection(section) => match section {
                 IceSection::StunPackets => "stun_packets",
                 IceSection::Candidates => "ice_candidates",
+                IceSection::DtlsHandshakes => "dtls_handshakes",
             },
         }
     }
@@ -118,6 +124,8 @@ impl fmt::Display for RtpSection {
             Self::RtcpStreams => "📈 RTCP Streams",
             Self::Streams => "🔴 RTP Streams",
             Self::Plot => "📈 RTP Plot",
+            Self::ExtensionsPlot => "📉 RTP Extensions",
+            Self::Announcements => "📣 Announced Sessions",
         };
 
         write!(f, "{}", ret)
@@ -143,7 +151,9 @@ impl Section for RtpSection {
             Self::RtcpPackets,
             Self::Streams,
             Self::Plot,
+            Self::ExtensionsPlot,
             Self::RtcpStreams,
+            Self::Announcements,
         ]
         .into_iter()
     }
@@ -165,13 +175,14 @@ impl Section for MpegTsSection {
 
 impl Section for IceSection {
     fn iter() -> impl Iterator<Item = Self> {
-        [Self::StunPackets, Self::Candidates].into_iter()
+        [Self::StunPackets, Self::Candidates, Self::DtlsHandshakes].into_iter()
     }
 
     fn display_name(&self) -> String {
         match self {
             Self::StunPackets => "🔄 STUN Packets".to_string(),
             Self::Candidates => "🌐 ICE Candidates".to_string(),
+            Self::DtlsHandshakes => "🔐 DTLS Handshakes".to_string(),
         }
     }
 }
@@ -1,3 +1,5 @@
+pub mod announcements_table;
+pub mod dtls_handshakes_table;
 pub mod ice_candidates_table;
 pub mod mpegts_info_table;
 pub mod mpegts_packets_table;
@@ -9,6 +11,8 @@ pub mod rtp_packets_table;
 pub mod rtp_streams_table;
 pub mod stun_packets_table;
 
+pub use announcements_table::*;
+pub use dtls_handshakes_table::*;
 pub use ice_candidates_table::*;
 pub use mpegts_info_table::*;
 pub use mpegts_packets_table::*;
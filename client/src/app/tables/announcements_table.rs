@@ -0,0 +1,6 @@
+mod filters;
+mod table;
+mod types;
+
+pub use table::AnnouncementsTable;
+pub use types::*;
@@ -0,0 +1,56 @@
+use crate::app::tables::AnnouncementFilterContext;
+use crate::{
+    declare_filter_type,
+    filter_system::{self, CommonFilterParser, FilterExpression, FilterParser, ParseError},
+};
+
+declare_filter_type! {
+    pub enum FilterType {
+        Origin(String),
+        Name(String),
+        Group(String),
+    }
+}
+
+impl CommonFilterParser for FilterType {
+    fn not(expr: Self) -> Self {
+        FilterType::Not(Box::new(expr))
+    }
+}
+
+pub fn parse_filter(filter: &str) -> Result<FilterType, ParseError> {
+    filter_system::parse_filter(filter)
+}
+
+impl<'a> FilterExpression<'a> for FilterType {
+    type Context = AnnouncementFilterContext<'a>;
+
+    fn matches(&self, ctx: &Self::Context) -> bool {
+        match self {
+            FilterType::Origin(value) => ctx.origin.to_lowercase().contains(value),
+            FilterType::Name(value) => ctx.name.to_lowercase().contains(value),
+            FilterType::Group(value) => ctx.group.to_lowercase().contains(value),
+            FilterType::And(left, right) => left.matches(ctx) && right.matches(ctx),
+            FilterType::Or(left, right) => left.matches(ctx) || right.matches(ctx),
+            FilterType::Not(filter) => !filter.matches(ctx),
+        }
+    }
+}
+
+impl FilterParser for FilterType {
+    fn parse_filter_value(prefix: &str, value: &str) -> Result<Self, ParseError> {
+        let value = value.to_lowercase();
+        match prefix.trim() {
+            "origin" => Ok(FilterType::Origin(value)),
+            "name" => Ok(FilterType::Name(value)),
+            "group" => Ok(FilterType::Group(value)),
+            unknown => Err(ParseError::InvalidSyntax(format!(
+                "Unknown filter type: '{}'.\nAvailable filters:\n\
+                 - origin: Address of the announcer\n\
+                 - name: Session name\n\
+                 - group: Destination address of the media",
+                unknown
+            ))),
+        }
+    }
+}
@@ -0,0 +1,215 @@
+use super::filters::parse_filter;
+use crate::{
+    app::{
+        FilterHelpContent, FilterInput, TABLE_HEADER_TEXT_SIZE, common::*,
+        tables::announcements_table::AnnouncementFilterContext,
+    },
+    declare_table, declare_table_struct, define_column,
+    filter_system::FilterExpression,
+    impl_table_base,
+    streams::{RefStreams, announcement::Announcement},
+};
+use eframe::epaint::Color32;
+use egui::RichText;
+use egui_extras::{Column, TableBody, TableBuilder, TableRow};
+use ewebsock::WsSender;
+use netpix_common::sdp::MediaDescription;
+use std::any::Any;
+
+declare_table_struct!(AnnouncementsTable);
+
+impl_table_base!(
+    AnnouncementsTable,
+    FilterHelpContent::builder("Announced Session Filters")
+        .filter("origin", "Filter by address of the announcer")
+        .filter("name", "Filter by session name")
+        .filter("group", "Filter by destination address of the media")
+        .example("group:239.69")
+        .example("name:stage AND NOT origin:10.0.0.1")
+        .build(),
+    "announcements", "Announced Sessions"
+    ;
+    build_header: |self, header| {
+        let headers = [
+            ("Origin", "Address of the announcer and the message id hash"),
+            ("Session", "Session name"),
+            ("Group", "Destination address and port of every media section"),
+            ("Media", "Payload types, clock rates and packet time of every media section"),
+            ("Announcements", "Number of announcements received"),
+            ("Last seen", "Time of the last announcement or deletion"),
+            ("Status", "Whether the session is still announced"),
+        ];
+
+        for (label, desc) in headers {
+            header.col(|ui| {
+                ui.label(RichText::new(label.to_string()).size(TABLE_HEADER_TEXT_SIZE).strong())
+                    .on_hover_text(desc.to_string());
+            });
+        }
+    }
+    ;
+    build_table_body: |self, body| {
+        let streams = self.streams.borrow();
+
+        let first_ts = streams
+            .announcements
+            .values()
+            .map(|announcement| announcement.first_seen)
+            .min()
+            .unwrap_or_default();
+        let announcements: Vec<_> = streams
+            .announcements
+            .values()
+            .filter(|announcement| {
+                let ctx = AnnouncementFilterContext {
+                    announcement,
+                    origin: &announcement.origin.to_string(),
+                    name: &session_name(announcement),
+                    group: &groups(announcement).join(" "),
+                };
+                self.announcement_matches_filter(&ctx)
+            })
+            .collect();
+
+        if announcements.is_empty() {
+            body.rows(30.0, 1, |mut row| {
+                row.col(|ui| {
+                    ui.label("No SAP announcements received or matching filter");
+                });
+            });
+            return;
+        }
+
+        let rows_heights: Vec<f32> = announcements
+            .iter()
+            .map(|announcement| 10.0 + groups(announcement).len().max(1) as f32 * 16.0)
+            .collect();
+
+        body.heterogeneous_rows(rows_heights.into_iter(), |mut row| {
+            let announcement = announcements[row.index()];
+            let media = announcement
+                .sdp
+                .as_ref()
+                .map(|sdp| sdp.media.as_slice())
+                .unwrap_or_default();
+
+            row.col(|ui| {
+                ui.label(RichText::new(announcement.origin.to_string()).monospace())
+                    .on_hover_text(format!(
+                        "Message id hash {:04x}, sent from {} to {}",
+                        announcement.message_id_hash,
+                        announcement.source_addr,
+                        announcement.destination_addr
+                    ));
+            });
+
+            row.col(|ui| {
+                ui.label(session_name(announcement));
+            });
+
+            row.col(|ui| {
+                ui.vertical(|ui| {
+                    for group in groups(announcement) {
+                        ui.label(RichText::new(group).monospace());
+                    }
+                });
+            });
+
+            row.col(|ui| {
+                ui.vertical(|ui| {
+                    for media in media {
+                        ui.label(media_formats(media));
+                    }
+                });
+            });
+
+            row.col(|ui| {
+                ui.label(announcement.count.to_string());
+            });
+
+            row.col(|ui| {
+                let timestamp = announcement.last_seen.saturating_sub(first_ts);
+                ui.label(format!("{:.4} s", timestamp.as_secs_f64()));
+            });
+
+            row.col(|ui| {
+                let (status, color) = match (announcement.deleted, announcement.encrypted) {
+                    (true, _) => ("Deleted", Color32::GRAY),
+                    (false, true) => ("Encrypted", Color32::from_rgb(200, 160, 120)),
+                    (false, false) => ("Active", Color32::from_rgb(120, 200, 120)),
+                };
+                ui.label(RichText::new(status).color(color));
+            });
+        });
+    }
+);
+
+declare_table!(AnnouncementsTable, FilterType, {
+    height(30.0);
+    striped(true);
+    resizable(true);
+    stick_to_bottom(false);
+    columns(
+        column(Some(130.0), 100.0, None, false, true),
+        column(Some(180.0), 100.0, None, true, true),
+        column(Some(180.0), 130.0, None, false, true),
+        column(None, 200.0, None, true, true),
+        column(Some(110.0), 80.0, None, false, true),
+        column(Some(90.0), 80.0, None, false, true),
+        column(Some(90.0), 70.0, None, false, true),
+    )
+});
+
+impl AnnouncementsTable {
+    fn announcement_matches_filter(&self, ctx: &AnnouncementFilterContext) -> bool {
+        if self.filter_input.get_filter().is_empty() {
+            return true;
+        }
+
+        let filter = self.filter_input.get_filter().trim().to_lowercase();
+        parse_filter(&filter)
+            .map(|filter_type| filter_type.matches(ctx))
+            .unwrap_or(true)
+    }
+}
+
+fn session_name(announcement: &Announcement) -> String {
+    announcement
+        .sdp
+        .as_ref()
+        .and_then(|sdp| sdp.session_name.clone())
+        .unwrap_or_default()
+}
+
+fn groups(announcement: &Announcement) -> Vec<String> {
+    let Some(ref sdp) = announcement.sdp else {
+        return Vec::new();
+    };
+
+    sdp.media
+        .iter()
+        .map(|media| match media.connection {
+            Some(ip) => format!("{}:{}", ip, media.port),
+            None => format!("*:{}", media.port),
+        })
+        .collect()
+}
+
+// e.g. "audio 97 L24/48000, ptime 0.125 ms"
+fn media_formats(media: &MediaDescription) -> String {
+    let mut payload_types: Vec<_> = media.payload_types.values().collect();
+    payload_types.sort_by_key(|pt| pt.id);
+    let formats: Vec<_> = payload_types
+        .iter()
+        .map(|pt| match pt.clock_rate {
+            Some(clock_rate) => format!("{} {}/{}", pt.id, pt.name, clock_rate),
+            None => format!("{} {}", pt.id, pt.name),
+        })
+        .collect();
+
+    let mut summary = format!("{} {}", media.media, formats.join(" "));
+    if let Some(ptime) = media.ptime {
+        summary.push_str(&format!(", ptime {} ms", ptime));
+    }
+    summary
+}
@@ -0,0 +1,9 @@
+use crate::define_filter_context;
+use crate::streams::announcement::Announcement;
+
+define_filter_context!(AnnouncementFilterContext,
+    announcement: Announcement,
+    origin: str,
+    name: str,
+    group: str
+);
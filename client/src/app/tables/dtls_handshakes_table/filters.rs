@@ -0,0 +1,56 @@
+use crate::app::tables::DtlsHandshakeFilterContext;
+use crate::{
+    declare_filter_type,
+    filter_system::{self, CommonFilterParser, FilterExpression, FilterParser, ParseError},
+};
+
+declare_filter_type! {
+    pub enum FilterType {
+        Addr(String),
+        Profile(String),
+        Status(String),
+    }
+}
+
+impl CommonFilterParser for FilterType {
+    fn not(expr: Self) -> Self {
+        FilterType::Not(Box::new(expr))
+    }
+}
+
+pub fn parse_filter(filter: &str) -> Result<FilterType, ParseError> {
+    filter_system::parse_filter(filter)
+}
+
+impl<'a> FilterExpression<'a> for FilterType {
+    type Context = DtlsHandshakeFilterContext<'a>;
+
+    fn matches(&self, ctx: &Self::Context) -> bool {
+        match self {
+            FilterType::Addr(value) => ctx.addr.to_lowercase().contains(value),
+            FilterType::Profile(value) => ctx.profile.to_lowercase().contains(value),
+            FilterType::Status(value) => ctx.status.to_lowercase().contains(value),
+            FilterType::And(left, right) => left.matches(ctx) && right.matches(ctx),
+            FilterType::Or(left, right) => left.matches(ctx) || right.matches(ctx),
+            FilterType::Not(filter) => !filter.matches(ctx),
+        }
+    }
+}
+
+impl FilterParser for FilterType {
+    fn parse_filter_value(prefix: &str, value: &str) -> Result<Self, ParseError> {
+        let value = value.to_lowercase();
+        match prefix.trim() {
+            "addr" => Ok(FilterType::Addr(value)),
+            "profile" => Ok(FilterType::Profile(value)),
+            "status" => Ok(FilterType::Status(value)),
+            unknown => Err(ParseError::InvalidSyntax(format!(
+                "Unknown filter type: '{}'.\nAvailable filters:\n\
+                 - addr: Address of the client or the server\n\
+                 - profile: Negotiated SRTP protection profile\n\
+                 - status: Handshake status",
+                unknown
+            ))),
+        }
+    }
+}
@@ -0,0 +1,262 @@
+use super::filters::parse_filter;
+use crate::{
+    app::{
+        FilterHelpContent, FilterInput, TABLE_HEADER_TEXT_SIZE, common::*,
+        tables::dtls_handshakes_table::DtlsHandshakeFilterContext,
+    },
+    declare_table, declare_table_struct, define_column,
+    filter_system::FilterExpression,
+    impl_table_base,
+    streams::{
+        RefStreams,
+        dtls_handshake::{DtlsHandshake, FingerprintCheck, check_fingerprint},
+    },
+};
+use eframe::epaint::Color32;
+use egui::RichText;
+use egui_extras::{Column, TableBody, TableBuilder, TableRow};
+use ewebsock::WsSender;
+use netpix_common::Sdp;
+use netpix_common::dtls::version_name;
+use netpix_common::sdp::Fingerprint;
+use std::any::Any;
+use std::net::SocketAddr;
+
+declare_table_struct!(DtlsHandshakesTable);
+
+impl_table_base!(
+    DtlsHandshakesTable,
+    FilterHelpContent::builder("DTLS Handshake Filters")
+        .filter("addr", "Filter by address of the client or the server")
+        .filter("profile", "Filter by negotiated SRTP protection profile")
+        .filter("status", "Filter by status: complete, failed or incomplete")
+        .example("profile:gcm")
+        .example("status:failed OR NOT addr:10.0.0.1")
+        .build(),
+    "dtls_handshakes", "DTLS Handshakes"
+    ;
+    build_header: |self, header| {
+        let headers = [
+            ("Client", "Sender of the ClientHello"),
+            ("Server", "Receiver of the ClientHello"),
+            ("Version", "Version and cipher suite chosen by the server"),
+            ("SRTP profile", "Protection profile chosen in the use_srtp extension, the offered ones on hover"),
+            ("Started", "Time of the first record"),
+            ("Duration", "Time until both sides sent their protected Finished"),
+            ("Messages", "Handshake messages, not counting the retransmissions"),
+            ("Retransmissions", "Handshake messages and Finished records sent again"),
+            ("Certificates", "Client and server certificates checked against the a=fingerprint of the known SDP"),
+            ("Status", "Complete, failed with an alert, or still incomplete"),
+        ];
+
+        for (label, desc) in headers {
+            header.col(|ui| {
+                ui.label(RichText::new(label.to_string()).size(TABLE_HEADER_TEXT_SIZE).strong())
+                    .on_hover_text(desc.to_string());
+            });
+        }
+    }
+    ;
+    build_table_body: |self, body| {
+        let streams = self.streams.borrow();
+
+        let first_ts = streams
+            .packets
+            .values()
+            .map(|packet| packet.timestamp)
+            .min()
+            .unwrap_or_default();
+        let handshakes: Vec<_> = streams
+            .dtls_handshakes
+            .values()
+            .filter(|handshake| {
+                let ctx = DtlsHandshakeFilterContext {
+                    handshake,
+                    addr: &format!("{} {}", handshake.client_addr, handshake.server_addr),
+                    profile: &profile(handshake),
+                    status: status(handshake).0,
+                };
+                self.handshake_matches_filter(&ctx)
+            })
+            .collect();
+
+        if handshakes.is_empty() {
+            body.rows(30.0, 1, |mut row| {
+                row.col(|ui| {
+                    ui.label("No DTLS handshakes captured or matching filter");
+                });
+            });
+            return;
+        }
+
+        body.rows(25.0, handshakes.len(), |mut row| {
+            let handshake = handshakes[row.index()];
+
+            row.col(|ui| {
+                ui.label(RichText::new(handshake.client_addr.to_string()).monospace());
+            });
+
+            row.col(|ui| {
+                ui.label(RichText::new(handshake.server_addr.to_string()).monospace());
+            });
+
+            row.col(|ui| {
+                let version = handshake.version.map(version_name).unwrap_or_default();
+                let label = ui.label(version);
+                if let Some(cipher_suite) = handshake.cipher_suite {
+                    label.on_hover_text(format!("Cipher suite {:#06x}", cipher_suite));
+                }
+            });
+
+            row.col(|ui| {
+                let offered: Vec<_> = handshake
+                    .offered_profiles
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect();
+                ui.label(profile(handshake))
+                    .on_hover_text(format!("Offered: {}", offered.join(", ")));
+            });
+
+            row.col(|ui| {
+                let timestamp = handshake.first_seen.saturating_sub(first_ts);
+                ui.label(format!("{:.4} s", timestamp.as_secs_f64()));
+            });
+
+            row.col(|ui| {
+                let duration = match handshake.duration() {
+                    Some(duration) => format!("{:.1} ms", duration.as_secs_f64() * 1000.0),
+                    None => "-".to_string(),
+                };
+                ui.label(duration);
+            });
+
+            row.col(|ui| {
+                let label = ui.label(handshake.messages.to_string());
+                if handshake.hello_verify {
+                    label.on_hover_text("The server asked for a cookie with HelloVerifyRequest");
+                }
+            });
+
+            row.col(|ui| {
+                let color = match handshake.retransmissions {
+                    0 => Color32::GRAY,
+                    _ => Color32::from_rgb(200, 160, 120),
+                };
+                ui.label(RichText::new(handshake.retransmissions.to_string()).color(color));
+            });
+
+            row.col(|ui| {
+                ui.horizontal(|ui| {
+                    certificate_label(
+                        ui,
+                        "client",
+                        &handshake.client_fingerprints,
+                        handshake.client_addr,
+                        &streams.sdp_sessions,
+                    );
+                    certificate_label(
+                        ui,
+                        "server",
+                        &handshake.server_fingerprints,
+                        handshake.server_addr,
+                        &streams.sdp_sessions,
+                    );
+                });
+            });
+
+            row.col(|ui| {
+                let (status, color) = status(handshake);
+                let label = ui.label(RichText::new(status).color(color));
+                if let Some(alert) = handshake.alert {
+                    label.on_hover_text(format!("Alert: {}", alert));
+                }
+            });
+        });
+    }
+);
+
+declare_table!(DtlsHandshakesTable, FilterType, {
+    height(30.0);
+    striped(true);
+    resizable(true);
+    stick_to_bottom(false);
+    columns(
+        column(Some(170.0), 120.0, None, false, true),
+        column(Some(170.0), 120.0, None, false, true),
+        column(Some(80.0), 70.0, None, false, true),
+        column(Some(210.0), 120.0, None, true, true),
+        column(Some(90.0), 80.0, None, false, true),
+        column(Some(90.0), 80.0, None, false, true),
+        column(Some(80.0), 70.0, None, false, true),
+        column(Some(110.0), 90.0, None, false, true),
+        column(Some(150.0), 120.0, None, false, true),
+        column(None, 90.0, None, false, true),
+    )
+});
+
+impl DtlsHandshakesTable {
+    fn handshake_matches_filter(&self, ctx: &DtlsHandshakeFilterContext) -> bool {
+        if self.filter_input.get_filter().is_empty() {
+            return true;
+        }
+
+        let filter = self.filter_input.get_filter().trim().to_lowercase();
+        parse_filter(&filter)
+            .map(|filter_type| filter_type.matches(ctx))
+            .unwrap_or(true)
+    }
+}
+
+fn profile(handshake: &DtlsHandshake) -> String {
+    handshake
+        .srtp_profile
+        .map(|profile| profile.to_string())
+        .unwrap_or_default()
+}
+
+fn status(handshake: &DtlsHandshake) -> (&'static str, Color32) {
+    match (handshake.failed(), handshake.completed) {
+        (true, _) => ("Failed", Color32::from_rgb(220, 100, 100)),
+        (false, Some(_)) => ("Complete", Color32::from_rgb(120, 200, 120)),
+        (false, None) => ("Incomplete", Color32::GRAY),
+    }
+}
+
+fn certificate_label(
+    ui: &mut egui::Ui,
+    side: &str,
+    fingerprints: &[Fingerprint],
+    addr: SocketAddr,
+    sdp_sessions: &[Sdp],
+) {
+    let (text, color, hover) = match check_fingerprint(fingerprints, addr, sdp_sessions) {
+        FingerprintCheck::NoCertificate => {
+            ("–", Color32::GRAY, "No certificate in clear".to_string())
+        }
+        FingerprintCheck::NoSdp => (
+            "?",
+            Color32::GRAY,
+            "No SDP fingerprint known for this address".to_string(),
+        ),
+        FingerprintCheck::Matches => (
+            "✔",
+            Color32::from_rgb(120, 200, 120),
+            "Matches the SDP fingerprint".to_string(),
+        ),
+        FingerprintCheck::Mismatch => (
+            "✘",
+            Color32::from_rgb(220, 100, 100),
+            "Differs from the SDP fingerprint of this address".to_string(),
+        ),
+    };
+
+    // the SDP uses sha-256 nearly always
+    let fingerprint = fingerprints
+        .iter()
+        .find(|fingerprint| fingerprint.algorithm == "sha-256")
+        .map(|fingerprint| format!("\n{}", fingerprint))
+        .unwrap_or_default();
+    ui.label(RichText::new(format!("{} {}", side, text)).color(color))
+        .on_hover_text(format!("{}{}", hover, fingerprint));
+}
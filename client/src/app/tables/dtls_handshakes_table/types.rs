@@ -0,0 +1,9 @@
+use crate::define_filter_context;
+use crate::streams::dtls_handshake::DtlsHandshake;
+
+define_filter_context!(DtlsHandshakeFilterContext,
+    handshake: DtlsHandshake,
+    addr: str,
+    profile: str,
+    status: str
+);
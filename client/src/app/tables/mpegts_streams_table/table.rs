@@ -68,7 +68,7 @@ impl_table_base!(
                 "Stream alias for the stream is made up of the transport stream id and stream type",
             ),
             ("Program number", "Program number from PMT table"),
-            ("Source", "Source IP address and port"),
+            ("Source", "Source IP address and port, with the VLAN ID for tagged traffic"),
             ("Destination", "Destination IP address and port"),
             (
                 "Number of packets",
@@ -140,7 +140,11 @@ impl_table_base!(
             });
 
             row.col(|ui| {
-                ui.label(stream.packet_association_table.source_addr.to_string());
+                let table = &stream.packet_association_table;
+                match table.vlan_id {
+                    Some(vlan_id) => ui.label(format!("{} [VLAN {}]", table.source_addr, vlan_id)),
+                    None => ui.label(table.source_addr.to_string()),
+                };
             });
 
             row.col(|ui| {
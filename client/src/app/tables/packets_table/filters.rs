@@ -22,6 +22,12 @@
 //! - `log_parser:value` or `protocol:value` - Matches transport or session protocol name
 //! - `type:value` - Matches specific session protocol (RTP, RTCP, etc.)
 //!
+//! ## Encapsulation Filters
+//! - `tunnel:value` - Matches packets with VLAN tags or tunnels containing the value (e.g. `vxlan`, `gtp-u`)
+//!
+//! ## Origin Filters
+//! - `origin:value` - Matches packets of a merged source read from a file whose name contains the value
+//!
 //! ## Size Filters
 //! - `length:number` - Matches exact packet length
 //! - `length:>number` - Matches length greater than number
@@ -43,6 +49,7 @@
 //! - `(type:rtp OR type:rtcp) AND NOT dest:10.0.0.1` - RTP/RTCP packets not going to specific host
 //! - `log_parser:tcp AND length:>=1500` - TCP packets with maximum size
 
+use super::table::encapsulation_summary;
 use crate::{
     declare_filter_type,
     filter_system::{
@@ -62,7 +69,9 @@ declare_filter_type! {
         Destination(String),
         Protocol(String),
         Length(ComparisonFilter<usize>),
-        Type(SessionProtocol)
+        Type(SessionProtocol),
+        Tunnel(String),
+        Origin(String)
     }
 }
 
@@ -117,6 +126,15 @@ impl<'a> FilterExpression<'a> for FilterType {
                 }
             }
             FilterType::Type(protocol) => ctx.packet.session_protocol == *protocol,
+            FilterType::Tunnel(value) => encapsulation_summary(ctx.packet)
+                .to_lowercase()
+                .contains(value),
+            FilterType::Origin(value) => ctx
+                .packet
+                .metadata
+                .origin
+                .as_ref()
+                .is_some_and(|origin| origin.to_lowercase().contains(value)),
             FilterType::And(left, right) => left.matches(ctx) && right.matches(ctx),
             FilterType::Or(left, right) => left.matches(ctx) || right.matches(ctx),
             FilterType::Not(filter) => !filter.matches(ctx),
@@ -191,13 +209,17 @@ impl FilterParser for FilterType {
                             .into(),
                     )
                 }),
+            "tunnel" => Ok(FilterType::Tunnel(value.to_lowercase())),
+            "origin" => Ok(FilterType::Origin(value.to_lowercase())),
             _ => Err(ParseError::InvalidSyntax(
                 "Unknown filter type.\nAvailable filters:\n\
                 - source: Match source IP (e.g., source:192.168)\n\
                 - dest: Match destination IP (e.g., dest:10.0.0)\n\
                 - log_parser/protocol: Match protocol (e.g., log_parser:udp)\n\
                 - length: Match packet size (e.g., length:>100)\n\
-                - type: Match session type (e.g., type:rtp)"
+                - type: Match session type (e.g., type:rtp)\n\
+                - tunnel: Match VLAN or tunnel (e.g., tunnel:vxlan)\n\
+                - origin: Match capture file of a merged source (e.g., origin:a.pcap)"
                     .to_string(),
             )),
         }
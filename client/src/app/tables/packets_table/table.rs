@@ -16,7 +16,7 @@ use egui::RichText;
 use egui_extras::{Column, TableBody, TableBuilder, TableRow};
 use ewebsock::{WsMessage, WsSender};
 use netpix_common::{
-    Request,
+    ExportSelection, ReparseScope, Request,
     packet::{Packet, SessionProtocol},
 };
 use std::any::Any;
@@ -34,9 +34,12 @@ impl_table_base!(
         )
         .filter("type:<protocol>", "Filter by protocol type")
         .filter("length:<op><size>", "Filter by packet size")
+        .filter("tunnel:<text>", "Filter by tunnel or VLAN (e.g. vxlan, gre, 10.0.0.1)")
+        .filter("origin:<file>", "Filter by the capture file of a merged source")
         .example("source:192.168 AND log_parser:udp")
         .example("length:>100 AND type:rtp")
         .example("NOT dest:10.0.0.1")
+        .example("tunnel:vxlan AND type:rtp")
         .example("(log_parser:tcp AND length:>500) OR source:192.168")
     .build(),
     "packets", "Network Packets"
@@ -49,6 +52,8 @@ impl_table_base!(
             "Destination",
             "Protocol",
             "Length",
+            "Encapsulation",
+            "Origin",
             "Treated as",
         ];
 
@@ -116,6 +121,16 @@ impl_table_base!(
                 ui.label(packet.length.to_string());
             });
 
+            // Encapsulation column
+            row.col(|ui| {
+                ui.label(encapsulation_summary(packet));
+            });
+
+            // Origin column
+            row.col(|ui| {
+                ui.label(packet.metadata.origin.as_deref().unwrap_or_default());
+            });
+
             // Session protocol column with context menu
             let (_, resp) = row.col(|ui| {
                 ui.label(packet.session_protocol.to_string());
@@ -125,13 +140,22 @@ impl_table_base!(
                 if let Some(req) = self.build_parse_menu(ui, packet) {
                     requests.push(req);
                 }
+
+                if let Some(ref source) = streams.source
+                    && let Some(selection) = build_export_menu(ui, packet, &filtered_packets)
+                {
+                    requests.push(Request::Export {
+                        source: source.clone(),
+                        selection,
+                    });
+                }
             });
         });
 
         drop(streams);
         requests
             .iter()
-            .for_each(|req| self.send_parse_request(req.clone()));
+            .for_each(|req| self.send_request(req.clone()));
     }
 );
 
@@ -147,6 +171,8 @@ declare_table!(PacketsTable, FilterType, {
         column(None, 100.0, None, false, true),
         column(None, 80.0, None, false, true),
         column(None, 80.0, None, false, true),
+        column(None, 200.0, None, false, true),
+        column(None, 120.0, None, false, true),
         column(None, 100.0, None, false, true),
     )
 });
@@ -172,17 +198,28 @@ impl PacketsTable {
         SessionProtocol::all().iter().for_each(|protocol| {
             let is_current = packet.session_protocol == *protocol;
             if ui.radio(is_current, protocol.to_string()).clicked() {
-                request = Some(Request::Reparse(packet.id, *protocol));
+                request = Some(Request::Reparse(packet.id, *protocol, ReparseScope::Packet));
             }
         });
 
+        ui.menu_button("Parse whole flow as", |ui| {
+            SessionProtocol::all().iter().for_each(|protocol| {
+                if ui.button(protocol.to_string()).clicked() {
+                    request = Some(Request::Reparse(packet.id, *protocol, ReparseScope::Flow));
+                    ui.close_menu();
+                }
+            });
+        })
+        .response
+        .on_hover_text("Every packet between the same addresses and ports, in both directions");
+
         ui.separator();
         ui.label("This will have effect on every client!");
 
         request
     }
 
-    fn send_parse_request(&mut self, request: Request) {
+    fn send_request(&mut self, request: Request) {
         if let Ok(msg) = request.encode() {
             self.ws_sender.send(WsMessage::Binary(msg));
         } else {
@@ -190,3 +227,57 @@ impl PacketsTable {
         }
     }
 }
+
+fn build_export_menu(
+    ui: &mut egui::Ui,
+    packet: &Packet,
+    shown_packets: &[&Packet],
+) -> Option<ExportSelection> {
+    ui.separator();
+    ui.label("Export to pcapng:");
+
+    let mut selection = None;
+    if ui.button(format!("Packet {}", packet.id)).clicked() {
+        selection = Some(ExportSelection::Ids(vec![packet.id]));
+    }
+
+    if let Some(first) = shown_packets.first()
+        && first.id < packet.id
+        && ui
+            .button(format!("Packets {} to {}", first.id, packet.id))
+            .clicked()
+    {
+        selection = Some(ExportSelection::IdRange(first.id, packet.id));
+    }
+
+    if ui
+        .button(format!("All {} shown packets", shown_packets.len()))
+        .on_hover_text("Packets matching the current filter")
+        .clicked()
+    {
+        let ids = shown_packets.iter().map(|packet| packet.id).collect();
+        selection = Some(ExportSelection::Ids(ids));
+    }
+
+    if selection.is_some() {
+        ui.close_menu();
+    }
+    selection
+}
+
+// outermost first, e.g. "VLAN 100 / VXLAN VNI 42 10.0.0.1 → 10.0.0.2"
+pub fn encapsulation_summary(packet: &Packet) -> String {
+    let vlans = packet
+        .metadata
+        .vlan_ids
+        .iter()
+        .map(|vlan_id| format!("VLAN {}", vlan_id));
+    let tunnels = packet.metadata.tunnels.iter().map(ToString::to_string);
+    let framing = packet.metadata.framing.iter().map(ToString::to_string);
+
+    vlans
+        .chain(tunnels)
+        .chain(framing)
+        .collect::<Vec<_>>()
+        .join(" / ")
+}
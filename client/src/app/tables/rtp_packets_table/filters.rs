@@ -31,6 +31,14 @@
 //! - `payload:<number` - Matches payload size less than number
 //! - `payload:<=number` - Matches payload size less or equal to number
 //!
+//! ## Header Extension Filters
+//! - `ext:name` - Matches packets with the extension, by name (e.g. `mid`) or id (e.g. `3`)
+//! - `name:value` - Matches the decoded value of a known extension, numbers can be compared
+//!   (`audio_level:>-30`), `mid`, `rid` and `repaired_rid` match exactly
+//!
+//! Extension names: `abs_send_time`, `twcc`, `audio_level`, `video_orientation`, `toffset`,
+//! `mid`, `rid`, `repaired_rid`, `playout_delay`, `abs_capture_time`, `dependency_descriptor`
+//!
 //! # Examples
 //!
 //! Simple filters:
@@ -43,6 +51,7 @@
 //! - `source:10.0.0 AND payload:>1000` - Large packets from specific network
 //! - `(dest:192.168 OR dest:10.0.0) AND NOT seq:0` - Non-initial packets to specific networks
 //! - `ssrc:1234 AND timestamp:>1000000` - Packets from specific stream after timestamp
+//! - `audio_level:>-20 AND NOT ext:twcc` - Loud audio packets without transport-wide CC
 
 use crate::{
     app::tables::rtp_packets_table::RtpFilterContext,
@@ -50,7 +59,9 @@ use crate::{
     filter_system::{
         self, CommonFilterParser, ComparisonFilter, FilterExpression, FilterParser, ParseError,
     },
+    streams::rtpStream::DecodedExtension,
 };
+use netpix_common::rtp::extension::ExtensionKind;
 
 declare_filter_type! {
     pub enum FilterType {
@@ -62,7 +73,9 @@ declare_filter_type! {
         Marker(String),
         SequenceNumber(u16),
         Timestamp(ComparisonFilter<u32>),
-        Payload(ComparisonFilter<usize>)
+        Payload(ComparisonFilter<usize>),
+        HasExtension(String),
+        ExtensionValue(ExtensionKind, ComparisonFilter<f64>)
     }
 }
 
@@ -135,6 +148,15 @@ impl<'a> FilterExpression<'a> for FilterType {
                     ComparisonFilter::Equals(value) => size.to_string() == *value,
                 }
             }
+            FilterType::HasExtension(value) => ctx.extensions.iter().any(|extension| {
+                extension.id.to_string() == *value
+                    || extension.kind.is_some_and(|kind| kind.name() == value)
+            }),
+            FilterType::ExtensionValue(kind, filter) => ctx
+                .extensions
+                .iter()
+                .filter(|extension| extension.kind == Some(*kind))
+                .any(|extension| extension_matches(extension, filter)),
             FilterType::And(left, right) => left.matches(ctx) && right.matches(ctx),
             FilterType::Or(left, right) => left.matches(ctx) || right.matches(ctx),
             FilterType::Not(filter) => !filter.matches(ctx),
@@ -224,6 +246,29 @@ impl FilterParser for FilterType {
                     )
                 }),
 
+            "ext" => (!value.is_empty())
+                .then(|| FilterType::HasExtension(value.to_lowercase()))
+                .ok_or_else(|| {
+                    ParseError::InvalidSyntax(
+                        "Extension filter cannot be empty (e.g. ext:audio_level)".into(),
+                    )
+                }),
+
+            name if ExtensionKind::from_name(name).is_some() => {
+                let kind = ExtensionKind::from_name(name).unwrap();
+                ComparisonFilter::parse(value)
+                    .filter(|filter| {
+                        kind.is_numeric() || matches!(filter, ComparisonFilter::Equals(_))
+                    })
+                    .map(|filter| FilterType::ExtensionValue(kind, filter))
+                    .ok_or_else(|| {
+                        ParseError::InvalidSyntax(format!(
+                            "Invalid {} filter format (e.g. audio_level:>-30, mid:0)",
+                            name
+                        ))
+                    })
+            }
+
             unknown => Err(ParseError::InvalidSyntax(format!(
                 "Unknown filter type: '{}'.\nAvailable filters:\n\
                  - source: Source IP filter\n\
@@ -232,9 +277,31 @@ impl FilterParser for FilterType {
                  - ssrc: SSRC value filter\n\
                  - seq: Sequence number filter\n\
                  - timestamp: RTP timestamp filter\n\
-                 - payload: Payload size filter",
+                 - payload: Payload size filter\n\
+                 - ext: Header extension presence filter\n\
+                 - <extension name>: Header extension value filter",
                 unknown
             ))),
         }
     }
 }
+
+fn extension_matches(extension: &DecodedExtension, filter: &ComparisonFilter<f64>) -> bool {
+    let Some(ref value) = extension.value else {
+        return false;
+    };
+
+    match (filter, value.as_f64()) {
+        (ComparisonFilter::GreaterThan(val), Some(number)) => number > *val,
+        (ComparisonFilter::GreaterOrEqualThan(val), Some(number)) => number >= *val,
+        (ComparisonFilter::LessThan(val), Some(number)) => number < *val,
+        (ComparisonFilter::LessOrEqualThan(val), Some(number)) => number <= *val,
+        (ComparisonFilter::Equals(expected), Some(number)) => expected
+            .parse::<f64>()
+            .is_ok_and(|expected| expected == number),
+        (ComparisonFilter::Equals(expected), None) => {
+            value.to_string().eq_ignore_ascii_case(expected)
+        }
+        _ => false,
+    }
+}
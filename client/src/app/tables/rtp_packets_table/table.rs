@@ -7,7 +7,7 @@ use crate::{
     declare_table, declare_table_struct, define_column,
     filter_system::FilterExpression,
     impl_table_base,
-    streams::RefStreams,
+    streams::{RefStreams, rtpStream::DecodedExtension},
 };
 use eframe::epaint::Color32;
 use egui::RichText;
@@ -33,10 +33,16 @@ impl_table_base!(
         .filter("seq", "Filter by sequence number")
         .filter("timestamp", "Filter by RTP timestamp")
         .filter("payload", "Filter by payload size")
+        .filter("ext", "Filter by header extension presence, name or id (e.g. ext:mid)")
+        .filter(
+            "<extension name>",
+            "Filter by decoded extension value, e.g. audio_level (dBov), twcc, mid, rid"
+        )
         .example("source:10.0.0 AND payload:>1000")
         .example("(dest:192.168 OR dest:10.0.0) AND NOT seq:0")
         .example("padding:+ AND timestamp:>1000000")
         .example("padding:+ AND extension:-")
+        .example("audio_level:>-30 AND NOT rid:h")
         .build(),
     "rtp_packets", "RTP Packets"
     ;
@@ -56,6 +62,7 @@ impl_table_base!(
             ("Alias", "Locally assigned SSRC alias to make differentiating streams more convenient"),
             ("CSRC", "RTP CSRC (Contributing Source Identifier)\nSSRC identifiers of the sources that have contributed to a composite RTP packet"),
             ("Payload Length", "RTP payload length (Excluding header and extensions)"),
+            ("Extensions", "RTP header extensions (RFC 8285), decoded when the stream's SDP maps their ids with a=extmap"),
         ];
 
         for (label, desc) in headers {
@@ -75,23 +82,20 @@ impl_table_base!(
             .filter(|packet| matches!(packet.contents, SessionPacket::Rtp(_)))
             .filter(|packet| {
                 if let SessionPacket::Rtp(ref rtp_packet) = packet.contents {
-                    let key = (
-                        packet.source_addr,
-                        packet.destination_addr,
-                        packet.transport_protocol,
-                        rtp_packet.ssrc,
-                    );
-
-                    let stream_alias = streams
-                        .rtp_streams
-                        .get(&key)
-                        .map(|stream| stream.alias.to_string());
+                    let key = packet.rtp_stream_key(rtp_packet.ssrc);
+
+                    let stream = streams.rtp_streams.get(&key);
+                    let stream_alias = stream.map(|stream| stream.alias.to_string());
+                    let extensions = stream
+                        .map(|stream| stream.decode_extensions(rtp_packet))
+                        .unwrap_or_default();
 
                     let ctx = RtpFilterContext {
                         packet: rtp_packet,
                         source_addr: &packet.source_addr.to_string(),
                         destination_addr: &packet.destination_addr.to_string(),
                         alias: &stream_alias.unwrap_or_default(),
+                        extensions: &extensions,
                     };
 
                     self.packet_matches_filter(&ctx)
@@ -117,23 +121,20 @@ impl_table_base!(
                 return;
             };
 
-            let key = (
-                packet.source_addr,
-                packet.destination_addr,
-                packet.transport_protocol,
-                rtp_packet.ssrc,
-            );
+            let key = packet.rtp_stream_key(rtp_packet.ssrc);
 
-            let stream_alias = streams
-                .rtp_streams
-                .get(&key)
-                .map(|stream| stream.alias.to_string());
+            let stream = streams.rtp_streams.get(&key);
+            let stream_alias = stream.map(|stream| stream.alias.to_string());
+            let extensions = stream
+                .map(|stream| stream.decode_extensions(rtp_packet))
+                .unwrap_or_default();
 
             let ctx = RtpFilterContext {
                 packet: rtp_packet,
                 source_addr: &packet.source_addr.to_string(),
                 destination_addr: &packet.destination_addr.to_string(),
                 alias: &stream_alias.unwrap_or_default(),
+                extensions: &extensions,
             };
 
             if !self.packet_matches_filter(&ctx) {
@@ -215,6 +216,33 @@ impl_table_base!(
             row.col(|ui| {
                 ui.label(rtp_packet.payload_length.to_string());
             });
+
+            // Extensions column
+            row.col(|ui| {
+                if extensions.is_empty() {
+                    return;
+                }
+
+                let summary = extensions
+                    .iter()
+                    .map(|extension| extension.to_string())
+                    .collect::<Vec<_>>();
+                let on_hover = extensions
+                    .iter()
+                    .map(|extension| match extension.kind {
+                        Some(kind) => format!(
+                            "[{}] {}: {}",
+                            extension.id,
+                            kind,
+                            extension_value(extension)
+                        ),
+                        None => format!("[{}] not mapped by the SDP", extension.id),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n");
+
+                ui.label(summary.join(", ")).on_hover_text(on_hover);
+            });
         });
     }
 );
@@ -239,6 +267,7 @@ declare_table!(RtpPacketsTable, FilterType, {
         column(None, 50.0, None, false, true),
         column(None, 80.0, None, false, true),
         column(None, 80.0, None, false, true),
+        column(None, 200.0, None, false, true),
     )
 });
 
@@ -255,6 +284,13 @@ impl RtpPacketsTable {
     }
 }
 
+fn extension_value(extension: &DecodedExtension) -> String {
+    extension
+        .value
+        .as_ref()
+        .map_or_else(|| "malformed".to_string(), |value| value.to_string())
+}
+
 fn format_boolean(value: bool) -> RichText {
     if value {
         RichText::from("✔").color(Color32::GREEN)
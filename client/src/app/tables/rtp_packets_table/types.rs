@@ -1,4 +1,4 @@
-use crate::define_filter_context;
+use crate::{define_filter_context, streams::rtpStream::DecodedExtension};
 use netpix_common::{RtpPacket, RtpStreamKey, packet::SessionPacket};
 use web_time::Duration;
 
@@ -6,7 +6,8 @@ define_filter_context!(RtpFilterContext,
     packet: RtpPacket,
     source_addr: str,
     destination_addr: str,
-    alias: str
+    alias: str,
+    extensions: [DecodedExtension]
 );
 
 #[derive(Clone)]
@@ -14,7 +14,7 @@ use egui::{RichText, TextEdit, Vec2};
 use egui_extras::{Column, TableBody, TableBuilder, TableRow};
 use egui_plot::{Line, Plot, PlotPoints};
 use ewebsock::{WsMessage, WsSender};
-use netpix_common::{Request, RtpStreamKey};
+use netpix_common::{ExportSelection, Request, RtpStreamKey, sdp::MediaDescription};
 use std::any::Any;
 
 declare_table_struct!(RtpStreamsTable,
@@ -34,6 +34,8 @@ declare_table!(RtpStreamsTable, FilterType, {
         column(Some(140.0), 140.0, None, false, true),
         column(Some(80.0), 80.0, None, false, true),
         column(Some(80.0), 80.0, None, false, true),
+        column(Some(100.0), 100.0, None, false, true),
+        column(Some(100.0), 100.0, None, false, true),
         column(Some(80.0), 80.0, None, false, true),
         column(Some(70.0), 70.0, None, false, true),
         column(Some(70.0), 70.0, None, false, true),
@@ -80,10 +82,12 @@ impl_table_base!(
         let headers = [
             ("Alias", "Locally assigned SSRC alias to make differentiating streams more convenient"),
             ("SSRC", "RTP SSRC (Synchronization Source Identifier) identifies the source of an RTP stream"),
-            ("Source", "Source IP address and port"),
+            ("Source", "Source IP address and port, with the VLAN ID for tagged traffic"),
             ("Destination", "Destination IP address and port"),
             ("CNAME", "Source Description CNAME value, if received (latest one if changed mid-stream"),
             ("Payload type", "Payload type of this stream (latest one if changed mid-stream)"),
+            ("SDP", "Media section describing the stream, matched by SSRC, MID, RID or port, or set with \"Set SDP\""),
+            ("SRTP", "SRTP and SRTCP packets decrypted with the SDES or key log keys, with authentication failures and replays"),
             ("Packet count", "Number of packets in stream"),
             ("Packet loss", "Percentage of packets lost"),
             ("Duration", "Difference between last timestamp and first timestamp."),
@@ -104,6 +108,7 @@ impl_table_base!(
     ;
     build_table_body: |self, body| {
         let streams = self.streams.borrow();
+        let mut exports = Vec::new();
         let filtered_streams: Vec<_> = streams
             .rtp_streams
             .iter()
@@ -138,8 +143,13 @@ impl_table_base!(
             });
 
             // Source/Destination columns
-            row.col(|ui| {
-                ui.label(stream.source_addr.to_string());
+            row.col(|ui| match stream.vlan_id {
+                Some(vlan_id) => {
+                    ui.label(format!("{} [VLAN {}]", stream.source_addr, vlan_id));
+                }
+                None => {
+                    ui.label(stream.source_addr.to_string());
+                }
             });
             row.col(|ui| {
                 ui.label(stream.destination_addr.to_string());
@@ -168,6 +178,33 @@ impl_table_base!(
                 ui.label(pt).on_hover_text(on_hover);
             });
 
+            // SDP column
+            row.col(|ui| match stream.media() {
+                Some(media) => {
+                    ui.label(media_summary(media)).on_hover_text(media_details(media));
+                }
+                None => {
+                    ui.label("N/A");
+                }
+            });
+
+            // SRTP column
+            row.col(|ui| match stream.srtp_decrypted + stream.srtp_auth_failures + stream.srtp_replays {
+                0 => {
+                    ui.label("N/A");
+                }
+                _ => {
+                    let mut label = RichText::new(stream.srtp_decrypted.to_string());
+                    if stream.srtp_auth_failures + stream.srtp_replays > 0 {
+                        label = label.color(Color32::RED);
+                    }
+                    ui.label(label).on_hover_text(format!(
+                        "{} decrypted\n{} authentication failures\n{} replays",
+                        stream.srtp_decrypted, stream.srtp_auth_failures, stream.srtp_replays
+                    ));
+                }
+            });
+
             // Statistics columns
             row.col(|ui| {
                 ui.label(stream.rtp_packets.len().to_string());
@@ -249,18 +286,35 @@ impl_table_base!(
                             self.sdp_window.open = true;
                             ui.close_menu();
                         }
+                        if let Some(ref source) = streams.source
+                            && ui.button("Export to pcapng").clicked()
+                        {
+                            exports.push(Request::Export {
+                                source: source.clone(),
+                                selection: ExportSelection::RtpStream(**key),
+                            });
+                            ui.close_menu();
+                        }
                     });
                     ui.add_space(7.0);
                 });
             });
         });
+
+        drop(streams);
+        for request in exports {
+            self.send_request(request);
+        }
     }
 );
 
 impl RtpStreamsTable {
     pub fn send_sdp_request(&mut self) {
         let request = Request::ParseSdp(self.chosen_key.unwrap(), self.sdp_window.sdp.clone());
+        self.send_request(request);
+    }
 
+    fn send_request(&mut self, request: Request) {
         let Ok(msg) = request.encode() else {
             log::error!("Failed to encode a request message");
             return;
@@ -282,7 +336,7 @@ impl RtpStreamsTable {
     }
 
     fn build_sdp_window(&mut self, ctx: &egui::Context) {
-        let Some((_, _, _, ssrc)) = self.chosen_key else {
+        let Some((_, _, _, ssrc, _)) = self.chosen_key else {
             return;
         };
 
@@ -312,7 +366,85 @@ impl RtpStreamsTable {
     }
 }
 
-const SDP_PROMPT: &str = "Paste your SDP media section here, e.g.
+fn media_summary(media: &MediaDescription) -> String {
+    match media.mid {
+        Some(ref mid) => format!("{} (mid {})", media.media, mid),
+        None => format!("{} {}", media.media, media.port),
+    }
+}
+
+fn media_details(media: &MediaDescription) -> String {
+    let mut lines = vec![format!(
+        "m={} {} {}",
+        media.media, media.port, media.protocol
+    )];
+    if let Some(connection) = media.connection {
+        lines.push(format!("c={}", connection));
+    }
+
+    let mut payload_types: Vec<_> = media.payload_types.values().collect();
+    payload_types.sort_by_key(|pt| pt.id);
+    for pt in payload_types {
+        match media.fmtp.get(&pt.id) {
+            Some(fmtp) => lines.push(format!("{} {}", pt, fmtp)),
+            None => lines.push(pt.to_string()),
+        }
+    }
+    if let Some(ptime) = media.ptime {
+        lines.push(format!("ptime {} ms", ptime));
+    }
+    for feedback in &media.rtcp_fb {
+        let pt = feedback
+            .payload_type
+            .map_or("*".to_string(), |pt| pt.to_string());
+        lines.push(format!("rtcp-fb {} {}", pt, feedback.feedback));
+    }
+
+    let mut extmap: Vec<_> = media.extmap.iter().collect();
+    extmap.sort();
+    for (id, uri) in extmap {
+        lines.push(format!("extmap {} {}", id, uri));
+    }
+    for ssrc in &media.ssrcs {
+        lines.push(format!(
+            "ssrc {:x} cname {}",
+            ssrc.ssrc,
+            ssrc.cname().unwrap_or("N/A")
+        ));
+    }
+    for group in &media.ssrc_groups {
+        let ssrcs: Vec<_> = group
+            .ssrcs
+            .iter()
+            .map(|ssrc| format!("{:x}", ssrc))
+            .collect();
+        lines.push(format!(
+            "ssrc-group {} {}",
+            group.semantics,
+            ssrcs.join(" ")
+        ));
+    }
+    for rid in &media.rids {
+        let direction = if rid.send { "send" } else { "recv" };
+        lines.push(format!("rid {} {}", rid.id, direction));
+    }
+    if let Some(setup) = media.setup {
+        lines.push(format!("setup {}", setup));
+    }
+    if let Some(ref fingerprint) = media.fingerprint {
+        lines.push(format!("fingerprint {}", fingerprint));
+    }
+    if let Some(ref ufrag) = media.ice_ufrag {
+        lines.push(format!("ice-ufrag {}", ufrag));
+    }
+    if let Some(ref control) = media.control {
+        lines.push(format!("control {}", control));
+    }
+
+    lines.join("\n")
+}
+
+const SDP_PROMPT: &str = "Paste your SDP session or media section here, e.g.
 m=audio 5004 RTP/AVP 96
 c=IN IP4 239.30.22.1
 a=rtpmap:96 L24/48000/2
@@ -3,18 +3,18 @@ use egui::{Color32, ComboBox, Label, RichText, TextWrapMode, Ui, Widget};
 use ewebsock::{WsEvent, WsMessage, WsReceiver, WsSender};
 use log::{error, warn};
 use netpix_common::packet::PacketDirection;
-use netpix_common::{Request, Response, Source};
+use netpix_common::{ReplayControl, ReplayState, Request, Response, Source};
 
 use super::{
     SOURCE_KEY, TAB_KEY,
     common::{PlotRegistry, TableRegistry},
     get_initial_state,
-    plots::RtpStreamsPlot,
+    plots::{RtpExtensionsPlot, RtpStreamsPlot},
     tab::Tab,
     tables::{
-        IceCandidatesTable, MpegTsInformationTable, MpegTsPacketsTable, MpegTsStreamsTable,
-        PacketsTable, RtcpPacketsTable, RtcpStreamsTable, RtpPacketsTable, RtpStreamsTable,
-        StunPacketsTable,
+        AnnouncementsTable, DtlsHandshakesTable, IceCandidatesTable, MpegTsInformationTable,
+        MpegTsPacketsTable, MpegTsStreamsTable, PacketsTable, RtcpPacketsTable, RtcpStreamsTable,
+        RtpPacketsTable, RtpStreamsTable, StunPacketsTable,
     },
     ui_components::types::{AppBottomBar, AppSidePanel, AppTopBar},
 };
@@ -33,6 +33,14 @@ pub struct App {
     pub(crate) plot_registry: PlotRegistry,
     pub(crate) discharged_count: usize,
     pub(crate) overwritten_count: usize,
+    pub(crate) fragment_count: usize,
+    pub(crate) reassembly_failure_count: usize,
+    pub(crate) tcp_gap_count: usize,
+    pub(crate) tcp_lost_bytes: usize,
+    pub(crate) replay_state: Option<ReplayState>,
+    pub(crate) seek_position_secs: f64,
+    // export to be downloaded on the next frame
+    pub(crate) pending_download: Option<String>,
 }
 
 impl eframe::App for App {
@@ -41,6 +49,10 @@ impl eframe::App for App {
             self.receive_packets()
         }
 
+        if let Some(url) = self.pending_download.take() {
+            ctx.open_url(egui::OpenUrl::same_tab(url));
+        }
+
         AppSidePanel::build(self, ctx);
         AppTopBar::build(self, ctx, frame);
         AppBottomBar::build(self, ctx);
@@ -75,12 +87,15 @@ impl App {
         table_registry.register::<RtcpPacketsTable>(streams.clone(), ws_sender.clone());
         table_registry.register::<RtcpStreamsTable>(streams.clone(), ws_sender.clone());
         table_registry.register::<RtpStreamsTable>(streams.clone(), ws_sender.clone());
+        table_registry.register::<AnnouncementsTable>(streams.clone(), ws_sender.clone());
         table_registry.register::<MpegTsPacketsTable>(streams.clone(), ws_sender.clone());
         table_registry.register::<MpegTsStreamsTable>(streams.clone(), ws_sender.clone());
         table_registry.register::<MpegTsInformationTable>(streams.clone(), ws_sender.clone());
         table_registry.register::<StunPacketsTable>(streams.clone(), ws_sender.clone());
         table_registry.register::<IceCandidatesTable>(streams.clone(), ws_sender.clone());
+        table_registry.register::<DtlsHandshakesTable>(streams.clone(), ws_sender.clone());
         plot_registry.register::<RtpStreamsPlot>(streams.clone(), ws_sender.clone());
+        plot_registry.register::<RtpExtensionsPlot>(streams.clone(), ws_sender.clone());
 
         let (tab, selected_source) = get_initial_state(cc);
 
@@ -96,6 +111,13 @@ impl App {
             sources: Vec::new(),
             discharged_count: 0,
             overwritten_count: 0,
+            fragment_count: 0,
+            reassembly_failure_count: 0,
+            tcp_gap_count: 0,
+            tcp_lost_bytes: 0,
+            replay_state: None,
+            seek_position_secs: 0.0,
+            pending_download: None,
         }
     }
 
@@ -134,13 +156,36 @@ impl App {
                 }
                 (Response::Sdp(stream_key, sdp), _) => {
                     let mut streams = self.streams.borrow_mut();
-                    if let Some(stream) = streams.rtp_streams.get_mut(&stream_key) {
-                        stream.add_sdp(sdp);
-                    }
+                    streams.add_sdp(stream_key, sdp);
                 }
                 (Response::PacketsStats(stats), _) => {
                     self.discharged_count = stats.discharged;
                     self.overwritten_count = stats.overwritten;
+                    self.fragment_count = stats.fragments;
+                    self.reassembly_failure_count = stats.reassembly_failures;
+                    self.tcp_gap_count = stats.tcp_gaps;
+                    self.tcp_lost_bytes = stats.tcp_lost_bytes;
+                }
+                (Response::ReplayState(source, state), _) => {
+                    if self.selected_source.as_ref() == Some(&source) {
+                        self.replay_state = Some(state);
+                    }
+                }
+                (Response::Reparsed(packets), _) => {
+                    let mut streams = self.streams.borrow_mut();
+                    streams.update_packets(packets);
+                }
+                (Response::Reset(source), _) => {
+                    if self.selected_source.as_ref() == Some(&source) {
+                        self.streams.borrow_mut().clear();
+                    }
+                }
+                (Response::Export(export), _) => {
+                    if export.packet_count == 0 {
+                        warn!("Nothing to export, no packets match the selection");
+                    } else {
+                        self.pending_download = Some(export.url);
+                    }
                 }
             }
         }
@@ -158,7 +203,9 @@ impl App {
     }
 
     pub fn change_source_request(&mut self) {
+        self.replay_state = None;
         let selected = self.selected_source.as_ref().unwrap().clone();
+        self.streams.borrow_mut().set_source(selected.clone());
         let request = Request::ChangeSource(selected);
         let Ok(msg) = request.encode() else {
             log::error!("Failed to encode a request message");
@@ -167,6 +214,16 @@ impl App {
         let msg = WsMessage::Binary(msg);
         self.ws_sender.send(msg);
     }
+
+    pub fn replay_request(&mut self, control: ReplayControl) {
+        let request = Request::Replay(control);
+        let Ok(msg) = request.encode() else {
+            log::error!("Failed to encode a request message");
+            return;
+        };
+        let msg = WsMessage::Binary(msg);
+        self.ws_sender.send(msg);
+    }
 }
 
 pub fn build_alias_row(
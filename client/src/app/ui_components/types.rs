@@ -1,4 +1,6 @@
-use egui::{ComboBox, Label, TextWrapMode, Ui, Widget};
+use egui::{ComboBox, DragValue, Label, TextWrapMode, Ui, Widget};
+use netpix_common::ReplayControl;
+use std::time::Duration;
 
 use crate::app::{App, SOURCE_KEY, TAB_KEY, side_button, tab::Tab};
 
@@ -6,6 +8,8 @@ pub struct AppSidePanel {}
 pub struct AppTopBar {}
 pub struct AppBottomBar {}
 
+const REPLAY_SPEEDS: [f64; 7] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0, 16.0];
+
 impl AppSidePanel {
     pub fn build(app: &mut App, ctx: &egui::Context) {
         let mut style = (*ctx.style()).clone();
@@ -74,6 +78,10 @@ impl AppTopBar {
             egui::menu::bar(ui, |ui| {
                 Self::build_dropdown_source(app, ui, frame);
                 ui.separator();
+                if app.replay_state.is_some() {
+                    Self::build_replay_controls(app, ui);
+                    ui.separator();
+                }
                 Self::build_menu_button(app, ui, frame);
                 Label::new(selected).ui(ui);
             });
@@ -101,6 +109,60 @@ impl AppTopBar {
         });
     }
 
+    fn build_replay_controls(app: &mut App, ui: &mut Ui) {
+        let Some(state) = app.replay_state.clone() else {
+            return;
+        };
+
+        let mut controls = Vec::new();
+
+        if state.paused {
+            if ui.button("▶").on_hover_text("Resume replay").clicked() {
+                controls.push(ReplayControl::Resume);
+            }
+            if ui.button("⏭").on_hover_text("Send next packet").clicked() {
+                controls.push(ReplayControl::Step);
+            }
+        } else if ui.button("⏸").on_hover_text("Pause replay").clicked() {
+            controls.push(ReplayControl::Pause);
+        }
+
+        ComboBox::from_id_salt("replay_speed")
+            .width(60.0)
+            .selected_text(format!("{}×", state.speed))
+            .show_ui(ui, |ui| {
+                for speed in REPLAY_SPEEDS {
+                    if ui
+                        .selectable_label(state.speed == speed, format!("{}×", speed))
+                        .clicked()
+                    {
+                        controls.push(ReplayControl::Speed(speed));
+                    }
+                }
+            });
+
+        ui.label(format!("{:.1} s", state.position.as_secs_f64()));
+        ui.add(
+            DragValue::new(&mut app.seek_position_secs)
+                .range(0.0..=f64::MAX)
+                .suffix(" s"),
+        );
+        if ui
+            .button("Seek")
+            .on_hover_text("Continue the replay from the given capture time")
+            .clicked()
+        {
+            match Duration::try_from_secs_f64(app.seek_position_secs) {
+                Ok(offset) => controls.push(ReplayControl::Seek(offset)),
+                Err(_) => log::warn!("Seek position {} s is out of range", app.seek_position_secs),
+            }
+        }
+
+        for control in controls {
+            app.replay_request(control);
+        }
+    }
+
     fn build_dropdown_source(app: &mut App, ui: &mut Ui, frame: &mut eframe::Frame) {
         let selected = match app.selected_source {
             Some(ref source) => source.to_string(),
@@ -151,9 +213,22 @@ impl AppBottomBar {
 
                 let discharged_label = format!("Discharged: {}", app.discharged_count);
                 let overwritten_label = format!("Overwritten: {}", app.overwritten_count);
+                let fragments_label = format!(
+                    "Fragments: {} ({} failed)",
+                    app.fragment_count, app.reassembly_failure_count
+                );
+                let tcp_gaps_label = format!(
+                    "TCP gaps: {} ({} bytes)",
+                    app.tcp_gap_count, app.tcp_lost_bytes
+                );
                 let label = format!(
-                    "{} • {} • {} • {}",
-                    count_label, captured_label, discharged_label, overwritten_label
+                    "{} • {} • {} • {} • {} • {}",
+                    count_label,
+                    captured_label,
+                    discharged_label,
+                    overwritten_label,
+                    fragments_label,
+                    tcp_gaps_label
                 );
                 ui.label(label);
             });
@@ -1,19 +1,25 @@
 #![allow(dead_code)]
 use crate::streams::rtcp_stream::RtcpStream;
+use announcement::Announcement;
+use dtls_handshake::DtlsHandshake;
 use eframe::epaint::{Color32, Hsva};
 use mpegts_stream::MpegTsStream;
 use netpix_common::packet::StreamMetaData;
 use netpix_common::rtcp::ReceptionReport;
 use netpix_common::rtcp::payload_feedbacks::PayloadFeedback;
+use netpix_common::sdp::MediaDescription;
 use netpix_common::{
-    MpegtsStreamKey, Packet, RtcpPacket, RtpStreamKey,
-    packet::{SessionPacket, TransportProtocol},
+    MpegtsStreamKey, Packet, RtcpPacket, RtpPacket, RtpStreamKey, SapPacket, Sdp, Source,
+    packet::SessionPacket,
 };
 use packets::Packets;
 use rtpStream::RtpStream;
 use std::cell::RefMut;
-use std::{cell::RefCell, collections::HashMap, net::SocketAddr, rc::Rc};
+use std::net::{IpAddr, SocketAddr};
+use std::{cell::RefCell, collections::BTreeMap, collections::HashMap, rc::Rc};
 
+pub mod announcement;
+pub mod dtls_handshake;
 pub mod mpegts_stream;
 pub mod packets;
 pub mod rtcp_stream;
@@ -30,6 +36,16 @@ pub struct Streams {
     pub mpeg_ts_streams: HashMap<MpegtsStreamKey, MpegTsStream>,
     pub rtcp_streams: HashMap<RtpStreamKey, RtcpStream>,
     pub alias_helper: Rc<RefCell<StreamAliasHelper>>,
+    // source the packets come from, kept when the packets are cleared
+    pub source: Option<Source>,
+    // descriptions of the source's sessions, the streams are matched to their media sections
+    pub sdp_sessions: Vec<Sdp>,
+    // media sections set for a single stream, preferred over the matched ones
+    pinned_media: HashMap<RtpStreamKey, MediaDescription>,
+    // sessions announced over SAP by their origin and message id hash
+    pub announcements: BTreeMap<(IpAddr, u16), Announcement>,
+    // DTLS handshakes by their addresses, the lower one first
+    pub dtls_handshakes: BTreeMap<(SocketAddr, SocketAddr), DtlsHandshake>,
 }
 
 impl Streams {
@@ -38,6 +54,42 @@ impl Streams {
         self.rtp_streams.clear();
         self.mpeg_ts_streams.clear();
         self.rtcp_streams.clear();
+        self.dtls_handshakes.clear();
+    }
+
+    pub fn set_source(&mut self, source: Source) {
+        if self.source.as_ref() != Some(&source) {
+            self.sdp_sessions.clear();
+            self.pinned_media.clear();
+            self.announcements.clear();
+        }
+        self.source = Some(source);
+    }
+
+    /// Adds the session, the stream which it was set for gets its matching media
+    /// section, or the first one if none matches.
+    pub fn add_sdp(&mut self, stream_key: Option<RtpStreamKey>, sdp: Sdp) {
+        if let Some(key) = stream_key
+            && let Some(stream) = self.rtp_streams.get_mut(&key)
+        {
+            let media = stream
+                .rtp_packets
+                .first()
+                .and_then(|rtp| {
+                    sdp.find_media(stream.source_addr, stream.destination_addr, &rtp.packet)
+                })
+                .or(sdp.media.first());
+            if let Some(media) = media {
+                self.pinned_media.insert(key, media.clone());
+                stream.add_sdp(media.clone());
+            }
+        }
+
+        self.sdp_sessions.push(sdp);
+        let keys: Vec<_> = self.rtp_streams.keys().copied().collect();
+        for key in keys {
+            self.match_sdp(key, None);
+        }
     }
 
     pub fn add_packet(&mut self, packet: Packet) {
@@ -51,23 +103,54 @@ impl Streams {
                 self.alias_helper.borrow_mut(),
                 &packet,
             );
+            // MID and RID may come in later packets of the stream
+            if let SessionPacket::Rtp(ref rtp) = packet.contents {
+                self.match_sdp(packet.rtp_stream_key(rtp.ssrc), Some(rtp));
+            }
+            if let SessionPacket::Sap(ref sap) = packet.contents {
+                self.add_announcement(&packet, sap);
+            }
+            handle_dtls(&mut self.dtls_handshakes, &packet);
             self.packets.add_packet(packet);
         } else {
             // if the packet is not new (its id is smaller that the last packet's id)
-            // that this must be result of `parse_as` request or refetch (tho packets should be
-            // pruned before refetch) in that case, recalculate everything,
-            // this can be optimised if it proves to be to slow
+            // that this must be result of refetch (tho packets should be pruned before
+            // refetch) in that case, recalculate everything,
+            // packets decoded again by the server come through `update_packets` instead
             self.packets.add_packet(packet);
             self.recalculate();
         }
     }
 
+    /// Replaces the packets decoded again by the server, the streams are recalculated once.
+    pub fn update_packets(&mut self, packets: Vec<Packet>) {
+        for packet in packets {
+            self.packets.add_packet(packet);
+        }
+        self.recalculate();
+    }
+
+    // announced sessions describe the streams just like the ones set by hand
+    fn add_announcement(&mut self, packet: &Packet, sap: &SapPacket) {
+        let sdp = self
+            .announcements
+            .entry(sap.announcement_id())
+            .or_insert_with(|| Announcement::new(packet, sap))
+            .update(packet, sap);
+
+        if let Some(sdp) = sdp {
+            self.add_sdp(None, sdp);
+        }
+    }
+
     fn recalculate(&mut self) {
         let mut new_rtp_streams = HashMap::new();
         let mut new_mpegts_streams = HashMap::new();
         let mut new_rtcp_streams = HashMap::new();
+        let mut new_dtls_handshakes = BTreeMap::new();
 
         self.packets.values().for_each(|packet| {
+            handle_dtls(&mut new_dtls_handshakes, packet);
             handle_packet(
                 &mut new_rtp_streams,
                 &mut new_mpegts_streams,
@@ -80,6 +163,39 @@ impl Streams {
         self.rtp_streams = new_rtp_streams;
         self.mpeg_ts_streams = new_mpegts_streams;
         self.rtcp_streams = new_rtcp_streams;
+        self.dtls_handshakes = new_dtls_handshakes;
+
+        let keys: Vec<_> = self.rtp_streams.keys().copied().collect();
+        for key in keys {
+            self.match_sdp(key, None);
+        }
+    }
+
+    // streams keep the first media section they got, the latest session is matched first
+    fn match_sdp(&mut self, key: RtpStreamKey, rtp: Option<&RtpPacket>) {
+        let Some(stream) = self.rtp_streams.get_mut(&key) else {
+            return;
+        };
+        if stream.media().is_some() {
+            return;
+        }
+
+        if let Some(media) = self.pinned_media.get(&key) {
+            stream.add_sdp(media.clone());
+            return;
+        }
+
+        let Some(rtp) = rtp.or(stream.rtp_packets.first().map(|rtp| &rtp.packet)) else {
+            return;
+        };
+        let media = self
+            .sdp_sessions
+            .iter()
+            .rev()
+            .find_map(|sdp| sdp.find_media(stream.source_addr, stream.destination_addr, rtp));
+        if let Some(media) = media {
+            stream.add_sdp(media.clone());
+        }
     }
 }
 
@@ -94,11 +210,7 @@ fn handle_packet(
 ) {
     match packet.contents {
         SessionPacket::Mpegts(ref mpegts) => {
-            let stream_key = (
-                packet.source_addr,
-                packet.destination_addr,
-                packet.transport_protocol,
-            );
+            let stream_key = packet.mpegts_stream_key();
 
             if let Some(stream) = mpegts_streams.get_mut(&stream_key) {
                 stream.add_mpegts_packet(packet, mpegts);
@@ -109,12 +221,7 @@ fn handle_packet(
             }
         }
         SessionPacket::Rtp(ref rtp) => {
-            let stream_key = (
-                packet.source_addr,
-                packet.destination_addr,
-                packet.transport_protocol,
-                rtp.ssrc,
-            );
+            let stream_key = packet.rtp_stream_key(rtp.ssrc);
 
             if let Some(stream) = rtp_streams.get_mut(&stream_key) {
                 stream.add_rtp_packet(packet, rtp);
@@ -146,13 +253,7 @@ fn handle_packet(
                 };
 
                 for ssrc in ssrcs {
-                    let maybe_stream = get_rtcp_stream(
-                        rtp_streams,
-                        packet.source_addr,
-                        packet.destination_addr,
-                        packet.transport_protocol,
-                        ssrc,
-                    );
+                    let maybe_stream = get_rtcp_stream(rtp_streams, packet.rtp_stream_key(ssrc));
                     if let Some(stream) = maybe_stream {
                         stream.add_rtcp_packet(packet.id, packet.timestamp, pack);
                     }
@@ -164,6 +265,31 @@ fn handle_packet(
         }
         _ => {}
     };
+
+    // SRTCP packets are counted for the stream of their sender as well
+    if let Some(ref srtp) = packet.metadata.srtp
+        && let Some(stream) = get_rtcp_stream(rtp_streams, packet.rtp_stream_key(srtp.ssrc))
+    {
+        stream.add_srtp(srtp);
+    }
+}
+
+fn handle_dtls(
+    handshakes: &mut BTreeMap<(SocketAddr, SocketAddr), DtlsHandshake>,
+    packet: &Packet,
+) {
+    let SessionPacket::Dtls(ref dtls) = packet.contents else {
+        return;
+    };
+
+    let key = match packet.source_addr < packet.destination_addr {
+        true => (packet.source_addr, packet.destination_addr),
+        false => (packet.destination_addr, packet.source_addr),
+    };
+    handshakes
+        .entry(key)
+        .or_insert_with(|| DtlsHandshake::new(packet))
+        .update(packet, dtls);
 }
 
 fn insert_or_update_rtcp_stream(
@@ -172,12 +298,7 @@ fn insert_or_update_rtcp_stream(
     packet: &Packet,
     pack: &RtcpPacket,
 ) {
-    let key_same_port = (
-        packet.source_addr,
-        packet.destination_addr,
-        packet.transport_protocol,
-        ssrc,
-    );
+    let key_same_port = packet.rtp_stream_key(ssrc);
 
     if let Some(stream) = rtcp_streams.get_mut(&key_same_port) {
         stream.update(pack, packet.timestamp);
@@ -195,12 +316,7 @@ fn update_rtcp_streams_with_rr(
     reception_reports: &[ReceptionReport],
 ) {
     for report in reception_reports.iter() {
-        let key_same_port = (
-            pkt.source_addr,
-            pkt.destination_addr,
-            pkt.transport_protocol,
-            report.ssrc,
-        );
+        let key_same_port = pkt.rtp_stream_key(report.ssrc);
         if let Some(stream) = streams_arg.get_mut(&key_same_port) {
             stream.update_with_rr(pkt.timestamp, report);
         }
@@ -209,18 +325,15 @@ fn update_rtcp_streams_with_rr(
 
 fn get_rtcp_stream<T>(
     streams: &mut HashMap<RtpStreamKey, T>,
-    mut source_addr: SocketAddr,
-    mut destination_addr: SocketAddr,
-    protocol: TransportProtocol,
-    ssrc: u32,
+    key_same_port: RtpStreamKey,
 ) -> Option<&mut T> {
-    let key_same_port = (source_addr, destination_addr, protocol, ssrc);
     if streams.contains_key(&key_same_port) {
         streams.get_mut(&key_same_port)
     } else {
+        let (mut source_addr, mut destination_addr, protocol, ssrc, vlan_id) = key_same_port;
         source_addr.set_port(source_addr.port() - 1);
         destination_addr.set_port(destination_addr.port() - 1);
-        let key_next_port = (source_addr, destination_addr, protocol, ssrc);
+        let key_next_port = (source_addr, destination_addr, protocol, ssrc, vlan_id);
         streams.get_mut(&key_next_port)
     }
 }
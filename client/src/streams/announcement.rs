@@ -0,0 +1,53 @@
+use netpix_common::{Packet, SapPacket, Sdp};
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+/// Session announced over SAP, with all its repetitions.
+#[derive(Debug, Clone)]
+pub struct Announcement {
+    pub origin: IpAddr,
+    pub message_id_hash: u16,
+    pub source_addr: SocketAddr,
+    pub destination_addr: SocketAddr,
+    pub sdp: Option<Sdp>,
+    pub first_seen: Duration,
+    pub last_seen: Duration,
+    pub count: usize,
+    pub encrypted: bool,
+    pub deleted: bool,
+}
+
+impl Announcement {
+    pub fn new(packet: &Packet, sap: &SapPacket) -> Self {
+        Self {
+            origin: sap.origin,
+            message_id_hash: sap.message_id_hash,
+            source_addr: packet.source_addr,
+            destination_addr: packet.destination_addr,
+            sdp: None,
+            first_seen: packet.timestamp,
+            last_seen: packet.timestamp,
+            count: 0,
+            encrypted: sap.encrypted,
+            deleted: false,
+        }
+    }
+
+    /// Returns the description if the message announces it for the first time.
+    pub fn update(&mut self, packet: &Packet, sap: &SapPacket) -> Option<Sdp> {
+        self.last_seen = packet.timestamp;
+        if sap.deletion {
+            self.deleted = true;
+            return None;
+        }
+
+        self.count += 1;
+        self.deleted = false;
+        // a changed description is announced with another hash
+        if self.sdp.is_some() {
+            return None;
+        }
+        self.sdp.clone_from(&sap.sdp);
+        sap.sdp.clone()
+    }
+}
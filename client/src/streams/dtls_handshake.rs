@@ -0,0 +1,198 @@
+use netpix_common::dtls::{DtlsAlert, DtlsContent, HandshakeBody, SrtpProfile};
+use netpix_common::{DtlsPacket, Packet, Sdp, sdp::Fingerprint};
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FingerprintCheck {
+    // the certificate was encrypted (DTLS 1.3) or not captured
+    NoCertificate,
+    NoSdp,
+    Matches,
+    Mismatch,
+}
+
+/// DTLS handshake of a 5-tuple, with its timing and retransmissions.
+#[derive(Debug, Clone)]
+pub struct DtlsHandshake {
+    // sender of the ClientHello
+    pub client_addr: SocketAddr,
+    pub server_addr: SocketAddr,
+    pub first_seen: Duration,
+    pub last_seen: Duration,
+    // once both sides have sent their protected Finished flight
+    pub completed: Option<Duration>,
+    pub version: Option<u16>,
+    pub cipher_suite: Option<u16>,
+    pub offered_profiles: Vec<SrtpProfile>,
+    pub srtp_profile: Option<SrtpProfile>,
+    pub hello_verify: bool,
+    pub client_fingerprints: Vec<Fingerprint>,
+    pub server_fingerprints: Vec<Fingerprint>,
+    pub packets: usize,
+    pub messages: usize,
+    pub retransmissions: usize,
+    pub alert: Option<DtlsAlert>,
+    // records by direction, epoch and sequence number, copies of a datagram
+    // captured on several hops don't count as retransmissions
+    records: HashSet<(bool, u16, u64)>,
+    // handshake messages by direction, message sequence and fragment offset
+    handshakes: HashSet<(bool, u16, u32)>,
+    client_protected: bool,
+    server_protected: bool,
+    client_hello_seen: bool,
+}
+
+impl DtlsHandshake {
+    pub fn new(packet: &Packet) -> Self {
+        Self {
+            client_addr: packet.source_addr,
+            server_addr: packet.destination_addr,
+            first_seen: packet.timestamp,
+            last_seen: packet.timestamp,
+            completed: None,
+            version: None,
+            cipher_suite: None,
+            offered_profiles: Vec::new(),
+            srtp_profile: None,
+            hello_verify: false,
+            client_fingerprints: Vec::new(),
+            server_fingerprints: Vec::new(),
+            packets: 0,
+            messages: 0,
+            retransmissions: 0,
+            alert: None,
+            records: HashSet::new(),
+            handshakes: HashSet::new(),
+            client_protected: false,
+            server_protected: false,
+            client_hello_seen: false,
+        }
+    }
+
+    pub fn update(&mut self, packet: &Packet, dtls: &DtlsPacket) {
+        self.last_seen = packet.timestamp;
+        self.packets += 1;
+
+        // the capture may start after the ClientHello was sent
+        let sends_client_hello = dtls
+            .handshakes()
+            .any(|handshake| matches!(handshake.body, HandshakeBody::ClientHello { .. }));
+        if sends_client_hello && !self.client_hello_seen {
+            self.client_hello_seen = true;
+            self.client_addr = packet.source_addr;
+            self.server_addr = packet.destination_addr;
+        }
+        let from_client = packet.source_addr == self.client_addr;
+
+        for record in &dtls.records {
+            let is_copy = !self
+                .records
+                .insert((from_client, record.epoch, record.sequence_number));
+
+            match record.content {
+                DtlsContent::Handshake(ref handshakes) => {
+                    for handshake in handshakes {
+                        let key = (
+                            from_client,
+                            handshake.message_seq,
+                            handshake.fragment_offset,
+                        );
+                        match self.handshakes.insert(key) {
+                            true => self.messages += 1,
+                            false if !is_copy => self.retransmissions += 1,
+                            false => {}
+                        }
+                        self.add_body(from_client, &handshake.body);
+                    }
+                }
+                // the Finished, or any of the encrypted DTLS 1.3 flights
+                DtlsContent::EncryptedHandshake | DtlsContent::Other => {
+                    let protected = match from_client {
+                        true => &mut self.client_protected,
+                        false => &mut self.server_protected,
+                    };
+                    if *protected && !is_copy && record.content == DtlsContent::EncryptedHandshake {
+                        self.retransmissions += 1;
+                    }
+                    *protected = true;
+                }
+                DtlsContent::Alert(alert) => self.alert = Some(alert),
+                _ => {}
+            }
+        }
+
+        if self.completed.is_none() && self.client_protected && self.server_protected {
+            self.completed = Some(packet.timestamp);
+        }
+    }
+
+    fn add_body(&mut self, from_client: bool, body: &HandshakeBody) {
+        match body {
+            HandshakeBody::ClientHello { srtp_profiles, .. } => {
+                self.offered_profiles.clone_from(srtp_profiles);
+            }
+            HandshakeBody::ServerHello {
+                version,
+                cipher_suite,
+                srtp_profile,
+                ..
+            } => {
+                self.version = Some(*version);
+                self.cipher_suite = Some(*cipher_suite);
+                self.srtp_profile = *srtp_profile;
+            }
+            HandshakeBody::HelloVerifyRequest { .. } => self.hello_verify = true,
+            HandshakeBody::Certificate { fingerprints } => match from_client {
+                true => self.client_fingerprints.clone_from(fingerprints),
+                false => self.server_fingerprints.clone_from(fingerprints),
+            },
+            HandshakeBody::Other => {}
+        }
+    }
+
+    pub fn duration(&self) -> Option<Duration> {
+        self.completed
+            .map(|completed| completed.saturating_sub(self.first_seen))
+    }
+
+    pub fn failed(&self) -> bool {
+        self.alert.is_some_and(|alert| alert.is_fatal())
+    }
+}
+
+/// Checks the certificate of the peer at `addr` against the `a=fingerprint`
+/// of the media sections announcing that address, the other peer's sections are skipped.
+pub fn check_fingerprint(
+    fingerprints: &[Fingerprint],
+    addr: SocketAddr,
+    sdp_sessions: &[Sdp],
+) -> FingerprintCheck {
+    if fingerprints.is_empty() {
+        return FingerprintCheck::NoCertificate;
+    }
+
+    let mut described = sdp_sessions
+        .iter()
+        .flat_map(|sdp| &sdp.media)
+        .filter(|media| media.connection == Some(addr.ip()) && media.port == addr.port())
+        .filter_map(|media| media.fingerprint.as_ref())
+        .peekable();
+    if described.peek().is_none() {
+        return FingerprintCheck::NoSdp;
+    }
+
+    let matches = described.any(|described| {
+        fingerprints.iter().any(|fingerprint| {
+            fingerprint
+                .algorithm
+                .eq_ignore_ascii_case(&described.algorithm)
+                && fingerprint.value == described.value
+        })
+    });
+    match matches {
+        true => FingerprintCheck::Matches,
+        false => FingerprintCheck::Mismatch,
+    }
+}
@@ -39,11 +39,7 @@ pub struct StatisticsContext<'a> {
 impl MpegTsPacketInfo {
     pub fn new(packet: &Packet, mpegts_packet: &MpegtsPacket) -> Self {
         Self {
-            packet_association_table: PacketAssociationTable {
-                source_addr: packet.source_addr,
-                destination_addr: packet.destination_addr,
-                protocol: packet.transport_protocol,
-            },
+            packet_association_table: PacketAssociationTable::from(packet),
             content: mpegts_packet.clone(),
             id: packet.id,
             time: packet.timestamp,
@@ -62,11 +58,7 @@ impl MpegTsStreamInfo {
             pat: None,
             pmt: FxHashMap::default(),
             statistics: Self::create_statistics(packet, mpegts_packet),
-            packet_association_table: PacketAssociationTable {
-                source_addr: packet.source_addr,
-                destination_addr: packet.destination_addr,
-                protocol: packet.transport_protocol,
-            },
+            packet_association_table: PacketAssociationTable::from(packet),
         }
     }
 
@@ -80,11 +72,7 @@ impl MpegTsStreamInfo {
             pat,
             pmt: FxHashMap::default(),
             statistics: Self::create_statistics(packet, mpegts_packet),
-            packet_association_table: PacketAssociationTable {
-                source_addr: packet.source_addr,
-                destination_addr: packet.destination_addr,
-                protocol: packet.transport_protocol,
-            },
+            packet_association_table: PacketAssociationTable::from(packet),
         }
     }
 
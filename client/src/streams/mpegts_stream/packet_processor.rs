@@ -217,11 +217,7 @@ impl MpegtsPacketProcessor {
         substreams: &mut MpegtsSubStreams,
     ) {
         let program_number = context.program_map_table.fields.program_number;
-        let packet_association_table = PacketAssociationTable {
-            source_addr: context.packet.source_addr,
-            destination_addr: context.packet.destination_addr,
-            protocol: context.packet.transport_protocol,
-        };
+        let packet_association_table = PacketAssociationTable::from(context.packet);
 
         for es_info in &context.program_map_table.elementary_streams_info {
             let key = (
@@ -61,11 +61,7 @@ pub struct SubstreamMpegTsPacketInfo {
 impl SubstreamMpegTsPacketInfo {
     pub fn new(packet: &Packet, mpegts_packet: &MpegtsFragment) -> Self {
         Self {
-            packet_association_table: PacketAssociationTable {
-                source_addr: packet.source_addr,
-                destination_addr: packet.destination_addr,
-                protocol: packet.transport_protocol,
-            },
+            packet_association_table: PacketAssociationTable::from(packet),
             content: mpegts_packet.clone(),
             id: packet.id,
             time: packet.timestamp,
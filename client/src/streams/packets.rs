@@ -8,15 +8,18 @@ use std::time::Duration;
 
 const MAX_PACKETS: usize = 10_000;
 
+// frame id and position of the message in its TCP segment
+type PacketKey = (usize, usize);
+
 #[derive(Debug, Default)]
 pub struct Packets {
-    packets: BTreeMap<usize, Packet>,
+    packets: BTreeMap<PacketKey, Packet>,
     base_timestamp: Option<Duration>,
 }
 
 impl Packets {
-    pub fn get(&self, id: usize) -> Option<&Packet> {
-        self.packets.get(&id)
+    pub fn get(&self, id: usize, sub_index: usize) -> Option<&Packet> {
+        self.packets.get(&(id, sub_index))
     }
 
     pub fn first(&self) -> Option<&Packet> {
@@ -26,16 +29,16 @@ impl Packets {
         }
     }
 
-    pub fn values(&self) -> Values<'_, usize, Packet> {
+    pub fn values(&self) -> Values<'_, PacketKey, Packet> {
         self.packets.values()
     }
 
-    pub fn keys(&self) -> Keys<'_, usize, Packet> {
+    pub fn keys(&self) -> Keys<'_, PacketKey, Packet> {
         self.packets.keys()
     }
 
     pub fn is_new(&self, packet: &Packet) -> bool {
-        !self.packets.contains_key(&packet.id)
+        !self.packets.contains_key(&key(packet))
     }
 
     pub fn is_empty(&self) -> bool {
@@ -52,7 +55,7 @@ impl Packets {
 
     pub fn id_count(&self) -> usize {
         match self.packets.last_key_value() {
-            Some((id, _)) => *id,
+            Some(((id, _), _)) => *id,
             None => 0,
         }
     }
@@ -61,7 +64,7 @@ impl Packets {
         if self.base_timestamp.is_none() && !packet.timestamp.is_zero() {
             self.base_timestamp = Some(packet.timestamp);
         }
-        self.packets.insert(packet.id, packet);
+        self.packets.insert(key(&packet), packet);
 
         if self.packets.len() > MAX_PACKETS {
             self.packets.pop_first();
@@ -72,3 +75,7 @@ impl Packets {
         self.base_timestamp.unwrap_or(Duration::from_secs(0))
     }
 }
+
+fn key(packet: &Packet) -> PacketKey {
+    (packet.id, packet.metadata.sub_index)
+}
@@ -1,13 +1,19 @@
 #![allow(dead_code)]
 use crate::utils::ntp_to_f64;
 use netpix_common::{
-    Packet, RtcpPacket, RtpPacket, Sdp,
+    Packet, RtcpPacket, RtpPacket,
     packet::TransportProtocol,
     rtcp::{SourceDescription, source_description::SdesType},
-    rtp::payload_type::PayloadType,
+    rtp::{
+        extension::{ExtensionKind, ExtensionValue},
+        payload_type::PayloadType,
+    },
+    sdp::MediaDescription,
+    srtp::{SrtpInfo, SrtpStatus},
 };
 use std::{
     cmp::{max, min},
+    fmt,
     net::SocketAddr,
     time::Duration,
 };
@@ -33,6 +39,7 @@ impl RtcpInfo {
 pub struct RtpInfo {
     pub packet: RtpPacket,
     pub id: usize,
+    pub sub_index: usize,
     pub time: Duration,
     pub ntp_time: Option<u64>,
     pub time_delta: Duration,
@@ -43,17 +50,40 @@ pub struct RtpInfo {
     pub packet_rate: usize, // packets/s
 }
 
+/// Header extension with its meaning known from the `a=extmap` of the stream's SDP.
+#[derive(Debug, Clone)]
+pub struct DecodedExtension {
+    pub id: u8,
+    pub kind: Option<ExtensionKind>,
+    pub value: Option<ExtensionValue>,
+}
+
+impl fmt::Display for DecodedExtension {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.kind, &self.value) {
+            (Some(kind), Some(value)) => write!(f, "{}={}", kind.name(), value),
+            (Some(kind), None) => write!(f, "{}=malformed", kind.name()),
+            (None, _) => write!(f, "id {}", self.id),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct RtpStream {
     pub source_addr: SocketAddr,
     pub destination_addr: SocketAddr,
     pub protocol: TransportProtocol,
     pub ssrc: u32,
+    pub vlan_id: Option<u16>,
     pub alias: String,
     pub rtp_packets: Vec<RtpInfo>,
     pub rtcp_packets: Vec<RtcpInfo>,
     pub max_jitter: f64,
     pub cname: Option<String>,
+    // SRTP and SRTCP packets of the stream by the outcome of their decryption
+    pub srtp_decrypted: usize,
+    pub srtp_auth_failures: usize,
+    pub srtp_replays: usize,
     bytes: usize,
     rtp_bytes: usize,
     sum_jitter: f64,
@@ -62,7 +92,7 @@ pub struct RtpStream {
     last_sequence_number: u16,
     first_time: Duration,
     last_time: Duration,
-    sdp: Option<Sdp>,
+    media: Option<MediaDescription>,
     pub payload_types: Vec<PayloadType>,
     // ntp synchronization
     pub ntp_rtp: Option<(u64, u32)>,
@@ -74,6 +104,7 @@ impl RtpStream {
         let rtp_info = RtpInfo {
             packet: rtp.clone(),
             id: packet.id,
+            sub_index: packet.metadata.sub_index,
             time: packet.timestamp,
             ntp_time: None,
             time_delta: Duration::from_secs(0),
@@ -89,6 +120,7 @@ impl RtpStream {
             destination_addr: packet.destination_addr,
             protocol: packet.transport_protocol,
             ssrc: rtp.ssrc,
+            vlan_id: packet.metadata.vlan_id(),
             alias: default_alias,
             rtp_packets: vec![rtp_info],
             rtcp_packets: Vec::new(),
@@ -98,22 +130,67 @@ impl RtpStream {
             sum_jitter: 0.0,
             jitter_count: 0,
             cname: None,
+            srtp_decrypted: 0,
+            srtp_auth_failures: 0,
+            srtp_replays: 0,
             first_sequence_number: rtp.sequence_number,
             last_sequence_number: rtp.sequence_number,
             first_time: packet.timestamp,
             last_time: packet.timestamp,
-            sdp: None,
+            media: None,
             payload_types: Vec::new(),
             ntp_rtp: None,
             estimated_clock_rate: None,
         }
     }
 
-    pub fn add_sdp(&mut self, sdp: Sdp) {
-        self.sdp = Some(sdp);
+    pub fn add_sdp(&mut self, media: MediaDescription) {
+        self.media = Some(media);
         self.recalculate();
     }
 
+    /// Media section of the SDP describing the stream.
+    pub fn media(&self) -> Option<&MediaDescription> {
+        self.media.as_ref()
+    }
+
+    /// Counts the packet by the outcome of its SRTP decryption.
+    pub fn add_srtp(&mut self, srtp: &SrtpInfo) {
+        match srtp.status {
+            SrtpStatus::Decrypted => self.srtp_decrypted += 1,
+            SrtpStatus::AuthFailed => self.srtp_auth_failures += 1,
+            SrtpStatus::Replayed => self.srtp_replays += 1,
+        }
+    }
+
+    pub fn extension_id(&self, kind: ExtensionKind) -> Option<u8> {
+        let media = self.media.as_ref()?;
+        media
+            .extmap
+            .iter()
+            .find(|(_, uri)| ExtensionKind::from_uri(uri) == Some(kind))
+            .map(|(id, _)| *id)
+    }
+
+    pub fn decode_extensions(&self, rtp: &RtpPacket) -> Vec<DecodedExtension> {
+        rtp.extensions
+            .iter()
+            .map(|extension| {
+                let kind = self
+                    .media
+                    .as_ref()
+                    .and_then(|media| media.extmap.get(&extension.id))
+                    .and_then(|uri| ExtensionKind::from_uri(uri));
+
+                DecodedExtension {
+                    id: extension.id,
+                    kind,
+                    value: kind.and_then(|kind| kind.decode(&extension.data)),
+                }
+            })
+            .collect()
+    }
+
     pub fn get_duration(&self) -> Duration {
         self.last_time.saturating_sub(self.first_time)
     }
@@ -150,6 +227,7 @@ impl RtpStream {
         let rtp_info = RtpInfo {
             packet: rtp.clone(),
             id: packet.id,
+            sub_index: packet.metadata.sub_index,
             time: packet.timestamp,
             ntp_time: None,
             time_delta: Duration::from_secs(0),
@@ -243,8 +321,8 @@ impl RtpStream {
                 .push(rtp_info.packet.payload_type.clone())
         }
 
-        if let Some(sdp) = &self.sdp
-            && let Some(pt) = sdp.payload_types.get(id)
+        if let Some(media) = &self.media
+            && let Some(pt) = media.payload_types.get(id)
         {
             return pt.clone();
         };
@@ -0,0 +1,699 @@
+use crate::sdp::Fingerprint;
+use bincode::{Decode, Encode};
+use std::fmt;
+
+#[cfg(not(target_arch = "wasm32"))]
+const RECORD_HEADER_LEN: usize = 13;
+#[cfg(not(target_arch = "wasm32"))]
+const HANDSHAKE_HEADER_LEN: usize = 12;
+#[cfg(not(target_arch = "wasm32"))]
+const RANDOM_LEN: usize = 32;
+#[cfg(not(target_arch = "wasm32"))]
+const EXTENSION_USE_SRTP: u16 = 14;
+#[cfg(not(target_arch = "wasm32"))]
+const EXTENSION_SUPPORTED_VERSIONS: u16 = 43;
+// AEAD tag and the encrypted content type
+#[cfg(not(target_arch = "wasm32"))]
+const MIN_CIPHERTEXT_LEN: usize = 17;
+
+pub const CONTENT_CHANGE_CIPHER_SPEC: u8 = 20;
+pub const CONTENT_ALERT: u8 = 21;
+pub const CONTENT_HANDSHAKE: u8 = 22;
+pub const CONTENT_APPLICATION_DATA: u8 = 23;
+
+pub const HANDSHAKE_CLIENT_HELLO: u8 = 1;
+pub const HANDSHAKE_SERVER_HELLO: u8 = 2;
+pub const HANDSHAKE_HELLO_VERIFY_REQUEST: u8 = 3;
+pub const HANDSHAKE_CERTIFICATE: u8 = 11;
+pub const HANDSHAKE_FINISHED: u8 = 20;
+
+pub const VERSION_1_0: u16 = 0xFEFF;
+pub const VERSION_1_2: u16 = 0xFEFD;
+pub const VERSION_1_3: u16 = 0xFEFC;
+
+pub fn version_name(version: u16) -> String {
+    match version {
+        VERSION_1_0 => "DTLS 1.0".to_string(),
+        VERSION_1_2 => "DTLS 1.2".to_string(),
+        VERSION_1_3 => "DTLS 1.3".to_string(),
+        other => format!("{:#06x}", other),
+    }
+}
+
+/// Protection profile negotiated by the use_srtp extension, RFC 5764 section 4.1.2.
+#[derive(Decode, Encode, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SrtpProfile(pub u16);
+
+pub const SRTP_AES128_CM_HMAC_SHA1_80: SrtpProfile = SrtpProfile(0x0001);
+pub const SRTP_AES128_CM_HMAC_SHA1_32: SrtpProfile = SrtpProfile(0x0002);
+pub const SRTP_NULL_HMAC_SHA1_80: SrtpProfile = SrtpProfile(0x0005);
+pub const SRTP_NULL_HMAC_SHA1_32: SrtpProfile = SrtpProfile(0x0006);
+pub const SRTP_AEAD_AES_128_GCM: SrtpProfile = SrtpProfile(0x0007);
+pub const SRTP_AEAD_AES_256_GCM: SrtpProfile = SrtpProfile(0x0008);
+
+impl fmt::Display for SrtpProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match *self {
+            SRTP_AES128_CM_HMAC_SHA1_80 => "SRTP_AES128_CM_HMAC_SHA1_80",
+            SRTP_AES128_CM_HMAC_SHA1_32 => "SRTP_AES128_CM_HMAC_SHA1_32",
+            SRTP_NULL_HMAC_SHA1_80 => "SRTP_NULL_HMAC_SHA1_80",
+            SRTP_NULL_HMAC_SHA1_32 => "SRTP_NULL_HMAC_SHA1_32",
+            SRTP_AEAD_AES_128_GCM => "SRTP_AEAD_AES_128_GCM",
+            SRTP_AEAD_AES_256_GCM => "SRTP_AEAD_AES_256_GCM",
+            SrtpProfile(other) => return write!(f, "{:#06x}", other),
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Decode, Encode, Debug, Clone, Copy, PartialEq)]
+pub struct DtlsAlert {
+    pub level: u8,
+    pub description: u8,
+}
+
+impl DtlsAlert {
+    pub fn is_fatal(&self) -> bool {
+        self.level == 2
+    }
+}
+
+impl fmt::Display for DtlsAlert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = match self.level {
+            1 => "warning",
+            2 => "fatal",
+            _ => "unknown",
+        };
+        // RFC 5246 section 7.2
+        let description = match self.description {
+            0 => "close_notify",
+            10 => "unexpected_message",
+            20 => "bad_record_mac",
+            22 => "record_overflow",
+            40 => "handshake_failure",
+            42 => "bad_certificate",
+            43 => "unsupported_certificate",
+            44 => "certificate_revoked",
+            45 => "certificate_expired",
+            46 => "certificate_unknown",
+            47 => "illegal_parameter",
+            48 => "unknown_ca",
+            49 => "access_denied",
+            50 => "decode_error",
+            51 => "decrypt_error",
+            70 => "protocol_version",
+            71 => "insufficient_security",
+            80 => "internal_error",
+            90 => "user_canceled",
+            110 => "unsupported_extension",
+            other => return write!(f, "{} alert {}", level, other),
+        };
+        write!(f, "{} {}", level, description)
+    }
+}
+
+#[derive(Decode, Encode, Debug, Clone, PartialEq)]
+pub enum HandshakeBody {
+    ClientHello {
+        version: u16,
+        // the randoms of both hellos identify the session in a key log
+        random: Vec<u8>,
+        cookie: Vec<u8>,
+        cipher_suites: Vec<u16>,
+        // profiles offered in the use_srtp extension
+        srtp_profiles: Vec<SrtpProfile>,
+    },
+    ServerHello {
+        version: u16,
+        random: Vec<u8>,
+        cipher_suite: u16,
+        srtp_profile: Option<SrtpProfile>,
+    },
+    HelloVerifyRequest {
+        cookie: Vec<u8>,
+    },
+    // fingerprints of the end-entity certificate with the hash functions SDP uses
+    Certificate {
+        fingerprints: Vec<Fingerprint>,
+    },
+    // other messages, or fragments of a message split over several records
+    Other,
+}
+
+#[derive(Decode, Encode, Debug, Clone, PartialEq)]
+pub struct Handshake {
+    pub message_type: u8,
+    pub length: u32,
+    pub message_seq: u16,
+    pub fragment_offset: u32,
+    pub fragment_length: u32,
+    pub body: HandshakeBody,
+}
+
+impl Handshake {
+    pub fn is_fragment(&self) -> bool {
+        self.fragment_offset != 0 || self.fragment_length != self.length
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self.message_type {
+            0 => "HelloRequest",
+            HANDSHAKE_CLIENT_HELLO => "ClientHello",
+            HANDSHAKE_SERVER_HELLO => "ServerHello",
+            HANDSHAKE_HELLO_VERIFY_REQUEST => "HelloVerifyRequest",
+            4 => "NewSessionTicket",
+            HANDSHAKE_CERTIFICATE => "Certificate",
+            12 => "ServerKeyExchange",
+            13 => "CertificateRequest",
+            14 => "ServerHelloDone",
+            15 => "CertificateVerify",
+            16 => "ClientKeyExchange",
+            HANDSHAKE_FINISHED => "Finished",
+            _ => "Unknown Handshake",
+        }
+    }
+}
+
+#[derive(Decode, Encode, Debug, Clone, PartialEq)]
+pub enum DtlsContent {
+    ChangeCipherSpec,
+    Alert(DtlsAlert),
+    Handshake(Vec<Handshake>),
+    // handshake protected by the new epoch, the Finished message up to DTLS 1.2
+    EncryptedHandshake,
+    EncryptedAlert,
+    ApplicationData,
+    // DTLS 1.3 ciphertext with the unified header, or an unknown content type
+    Other,
+}
+
+#[derive(Decode, Encode, Debug, Clone, PartialEq)]
+pub struct DtlsRecord {
+    pub content_type: u8,
+    // None for DTLS 1.3 ciphertext, its header leaves the version out
+    pub version: Option<u16>,
+    pub epoch: u16,
+    // 48 bits, only the low 8 or 16 of them in DTLS 1.3 ciphertext
+    pub sequence_number: u64,
+    pub length: u16,
+    pub content: DtlsContent,
+}
+
+/// Records of a DTLS datagram, RFC 6347, with the handshake messages sent in clear decoded.
+#[derive(Decode, Encode, Debug, Clone)]
+pub struct DtlsPacket {
+    pub records: Vec<DtlsRecord>,
+}
+
+impl DtlsPacket {
+    pub fn handshakes(&self) -> impl Iterator<Item = &Handshake> {
+        self.records.iter().flat_map(|record| match record.content {
+            DtlsContent::Handshake(ref handshakes) => handshakes.as_slice(),
+            _ => &[],
+        })
+    }
+}
+
+impl fmt::Display for DtlsContent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ChangeCipherSpec => write!(f, "Change Cipher Spec"),
+            Self::Alert(alert) => write!(f, "Alert ({})", alert),
+            Self::Handshake(handshakes) => {
+                let names: Vec<_> = handshakes.iter().map(Handshake::name).collect();
+                write!(f, "{}", names.join(", "))
+            }
+            Self::EncryptedHandshake => write!(f, "Encrypted Handshake Message"),
+            Self::EncryptedAlert => write!(f, "Encrypted Alert"),
+            Self::ApplicationData => write!(f, "Application Data"),
+            Self::Other => write!(f, "Encrypted Record"),
+        }
+    }
+}
+
+impl fmt::Display for DtlsPacket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let records: Vec<_> = self
+            .records
+            .iter()
+            .map(|record| record.content.to_string())
+            .collect();
+        write!(f, "{}", records.join(", "))
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl DtlsPacket {
+    pub fn build(packet: &super::Packet) -> Option<Self> {
+        let payload = packet.payload.as_deref()?;
+        Self::parse(payload)
+    }
+
+    // every byte of the datagram has to belong to a record, which keeps other protocols
+    // sharing the first bytes out
+    fn parse(mut payload: &[u8]) -> Option<Self> {
+        let mut records = Vec::new();
+        while !payload.is_empty() {
+            let (record, rest) = match payload[0] {
+                CONTENT_CHANGE_CIPHER_SPEC..=25 => parse_record(payload)?,
+                // unified header, RFC 9147 section 4
+                32..=63 => parse_ciphertext(payload)?,
+                _ => return None,
+            };
+            records.push(record);
+            payload = rest;
+        }
+
+        match records.is_empty() {
+            true => None,
+            false => Some(Self { records }),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn parse_record(data: &[u8]) -> Option<(DtlsRecord, &[u8])> {
+    let mut header = Reader(data.get(..RECORD_HEADER_LEN)?);
+    let content_type = header.u8()?;
+    let version = header.u16()?;
+    if !matches!(version, VERSION_1_0 | VERSION_1_2 | VERSION_1_3) {
+        return None;
+    }
+    let epoch = header.u16()?;
+    let sequence_number = header.u48()?;
+    let length = header.u16()?;
+    let (fragment, rest) = data[RECORD_HEADER_LEN..].split_at_checked(length as usize)?;
+
+    let content = match (content_type, epoch) {
+        (CONTENT_CHANGE_CIPHER_SPEC, _) => DtlsContent::ChangeCipherSpec,
+        (CONTENT_ALERT, 0) => {
+            let mut alert = Reader(fragment);
+            DtlsContent::Alert(DtlsAlert {
+                level: alert.u8()?,
+                description: alert.u8()?,
+            })
+        }
+        (CONTENT_ALERT, _) => DtlsContent::EncryptedAlert,
+        (CONTENT_HANDSHAKE, 0) => DtlsContent::Handshake(parse_handshakes(fragment)?),
+        (CONTENT_HANDSHAKE, _) => DtlsContent::EncryptedHandshake,
+        (CONTENT_APPLICATION_DATA, _) => DtlsContent::ApplicationData,
+        _ => DtlsContent::Other,
+    };
+
+    let record = DtlsRecord {
+        content_type,
+        version: Some(version),
+        epoch,
+        sequence_number,
+        length,
+        content,
+    };
+    Some((record, rest))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn parse_ciphertext(data: &[u8]) -> Option<(DtlsRecord, &[u8])> {
+    let mut reader = Reader(data);
+    let flags = reader.u8()?;
+    // the connection id length isn't known without the handshake
+    if flags & 0x10 != 0 {
+        return None;
+    }
+    // the length may only be left out of the last record, but without it nearly any
+    // datagram starting with 0x20-0x3F would pass, DNS queries and text among them
+    if flags & 0x04 == 0 {
+        return None;
+    }
+    let sequence_number = match flags & 0x08 != 0 {
+        true => reader.u16()? as u64,
+        false => reader.u8()? as u64,
+    };
+    let length = reader.u16()?;
+    if (length as usize) < MIN_CIPHERTEXT_LEN {
+        return None;
+    }
+    let (_, rest) = reader.0.split_at_checked(length as usize)?;
+
+    let record = DtlsRecord {
+        content_type: flags,
+        version: None,
+        epoch: (flags & 0x03) as u16,
+        sequence_number,
+        length,
+        content: DtlsContent::Other,
+    };
+    Some((record, rest))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn parse_handshakes(mut data: &[u8]) -> Option<Vec<Handshake>> {
+    let mut handshakes = Vec::new();
+    while !data.is_empty() {
+        let mut header = Reader(data.get(..HANDSHAKE_HEADER_LEN)?);
+        let message_type = header.u8()?;
+        let length = header.u24()?;
+        let message_seq = header.u16()?;
+        let fragment_offset = header.u24()?;
+        let fragment_length = header.u24()?;
+        let (fragment, rest) =
+            data[HANDSHAKE_HEADER_LEN..].split_at_checked(fragment_length as usize)?;
+
+        let mut handshake = Handshake {
+            message_type,
+            length,
+            message_seq,
+            fragment_offset,
+            fragment_length,
+            body: HandshakeBody::Other,
+        };
+        // fragmented messages aren't reassembled, their bodies are left out
+        if !handshake.is_fragment() {
+            handshake.body = parse_body(message_type, fragment).unwrap_or(HandshakeBody::Other);
+        }
+        handshakes.push(handshake);
+        data = rest;
+    }
+
+    Some(handshakes)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn parse_body(message_type: u8, body: &[u8]) -> Option<HandshakeBody> {
+    let mut reader = Reader(body);
+    match message_type {
+        HANDSHAKE_CLIENT_HELLO => {
+            let version = reader.u16()?;
+            let random = reader.bytes(RANDOM_LEN)?.to_vec();
+            reader.vec8()?;
+            let cookie = reader.vec8()?.to_vec();
+            let cipher_suites = reader
+                .vec16()?
+                .chunks_exact(2)
+                .map(|suite| u16::from_be_bytes([suite[0], suite[1]]))
+                .collect();
+            reader.vec8()?;
+            let extensions = parse_extensions(&mut reader)?;
+            let srtp_profiles = find_extension(&extensions, EXTENSION_USE_SRTP)
+                .and_then(parse_use_srtp)
+                .unwrap_or_default();
+
+            Some(HandshakeBody::ClientHello {
+                version,
+                random,
+                cookie,
+                cipher_suites,
+                srtp_profiles,
+            })
+        }
+        HANDSHAKE_SERVER_HELLO => {
+            let legacy_version = reader.u16()?;
+            let random = reader.bytes(RANDOM_LEN)?.to_vec();
+            reader.vec8()?;
+            let cipher_suite = reader.u16()?;
+            reader.u8()?;
+            let extensions = parse_extensions(&mut reader)?;
+            // DTLS 1.3 keeps the 1.2 version in the hello
+            let version = find_extension(&extensions, EXTENSION_SUPPORTED_VERSIONS)
+                .and_then(|mut data| data.u16())
+                .unwrap_or(legacy_version);
+            // the server picks one of the offered profiles
+            let srtp_profile = find_extension(&extensions, EXTENSION_USE_SRTP)
+                .and_then(parse_use_srtp)
+                .and_then(|profiles| profiles.first().copied());
+
+            Some(HandshakeBody::ServerHello {
+                version,
+                random,
+                cipher_suite,
+                srtp_profile,
+            })
+        }
+        HANDSHAKE_HELLO_VERIFY_REQUEST => {
+            reader.u16()?;
+            let cookie = reader.vec8()?.to_vec();
+            Some(HandshakeBody::HelloVerifyRequest { cookie })
+        }
+        HANDSHAKE_CERTIFICATE => {
+            let mut certificates = Reader(reader.vec24()?);
+            let certificate = certificates.vec24()?;
+            Some(HandshakeBody::Certificate {
+                fingerprints: fingerprints(certificate),
+            })
+        }
+        _ => None,
+    }
+}
+
+// extensions by type, the block may be left out when there are none
+#[cfg(not(target_arch = "wasm32"))]
+fn parse_extensions<'a>(reader: &mut Reader<'a>) -> Option<Vec<(u16, &'a [u8])>> {
+    if reader.0.is_empty() {
+        return Some(Vec::new());
+    }
+
+    let mut block = Reader(reader.vec16()?);
+    let mut extensions = Vec::new();
+    while !block.0.is_empty() {
+        extensions.push((block.u16()?, block.vec16()?));
+    }
+    Some(extensions)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn find_extension<'a>(extensions: &[(u16, &'a [u8])], extension_type: u16) -> Option<Reader<'a>> {
+    extensions
+        .iter()
+        .find(|(typ, _)| *typ == extension_type)
+        .map(|(_, data)| Reader(data))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn parse_use_srtp(mut data: Reader) -> Option<Vec<SrtpProfile>> {
+    let profiles = data
+        .vec16()?
+        .chunks_exact(2)
+        .map(|profile| SrtpProfile(u16::from_be_bytes([profile[0], profile[1]])))
+        .collect();
+    Some(profiles)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn fingerprints(certificate: &[u8]) -> Vec<Fingerprint> {
+    use ring::digest;
+
+    let fingerprint = |algorithm: &str, hash: &'static digest::Algorithm| Fingerprint {
+        algorithm: algorithm.to_string(),
+        value: digest::digest(hash, certificate).as_ref().to_vec(),
+    };
+    vec![
+        fingerprint("sha-1", &digest::SHA1_FOR_LEGACY_USE_ONLY),
+        fingerprint("sha-256", &digest::SHA256),
+        fingerprint("sha-384", &digest::SHA384),
+        fingerprint("sha-512", &digest::SHA512),
+    ]
+}
+
+// big endian reads which move past the read bytes
+#[cfg(not(target_arch = "wasm32"))]
+struct Reader<'a>(&'a [u8]);
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let (bytes, rest) = self.0.split_at_checked(len)?;
+        self.0 = rest;
+        Some(bytes)
+    }
+
+    fn uint(&mut self, len: usize) -> Option<u64> {
+        let bytes = self.bytes(len)?;
+        Some(
+            bytes
+                .iter()
+                .fold(0, |value, &byte| value << 8 | byte as u64),
+        )
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.uint(1).map(|value| value as u8)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.uint(2).map(|value| value as u16)
+    }
+
+    fn u24(&mut self) -> Option<u32> {
+        self.uint(3).map(|value| value as u32)
+    }
+
+    fn u48(&mut self) -> Option<u64> {
+        self.uint(6)
+    }
+
+    fn vec8(&mut self) -> Option<&'a [u8]> {
+        let len = self.u8()?;
+        self.bytes(len as usize)
+    }
+
+    fn vec16(&mut self) -> Option<&'a [u8]> {
+        let len = self.u16()?;
+        self.bytes(len as usize)
+    }
+
+    fn vec24(&mut self) -> Option<&'a [u8]> {
+        let len = self.u24()?;
+        self.bytes(len as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(content_type: u8, epoch: u16, sequence_number: u8, fragment: &[u8]) -> Vec<u8> {
+        let mut record = vec![content_type, 0xFE, 0xFD];
+        record.extend_from_slice(&epoch.to_be_bytes());
+        record.extend_from_slice(&[0, 0, 0, 0, 0, sequence_number]);
+        record.extend_from_slice(&(fragment.len() as u16).to_be_bytes());
+        record.extend_from_slice(fragment);
+        record
+    }
+
+    fn handshake(message_type: u8, message_seq: u16, body: &[u8]) -> Vec<u8> {
+        let len = &(body.len() as u32).to_be_bytes()[1..];
+        let mut handshake = vec![message_type];
+        handshake.extend_from_slice(len);
+        handshake.extend_from_slice(&message_seq.to_be_bytes());
+        handshake.extend_from_slice(&[0, 0, 0]);
+        handshake.extend_from_slice(len);
+        handshake.extend_from_slice(body);
+        handshake
+    }
+
+    // use_srtp with the given profiles and no MKI
+    fn use_srtp(profiles: &[u16]) -> Vec<u8> {
+        let mut data = ((profiles.len() * 2) as u16).to_be_bytes().to_vec();
+        for profile in profiles {
+            data.extend_from_slice(&profile.to_be_bytes());
+        }
+        data.push(0);
+
+        let mut extensions = EXTENSION_USE_SRTP.to_be_bytes().to_vec();
+        extensions.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        extensions.extend_from_slice(&data);
+        let mut block = (extensions.len() as u16).to_be_bytes().to_vec();
+        block.extend_from_slice(&extensions);
+        block
+    }
+
+    #[test]
+    fn test_parses_hellos() {
+        let mut client_hello = vec![0xFE, 0xFD];
+        client_hello.extend_from_slice(&[7; RANDOM_LEN]);
+        // no session id, a 2 byte cookie, two suites, null compression
+        client_hello.extend_from_slice(&[0, 2, 0xAA, 0xBB, 0, 4, 0xC0, 0x2B, 0xC0, 0x2F, 1, 0]);
+        client_hello.extend_from_slice(&use_srtp(&[0x0007, 0x0001]));
+        let datagram = record(CONTENT_HANDSHAKE, 0, 1, &handshake(1, 1, &client_hello));
+
+        let dtls = DtlsPacket::parse(&datagram).unwrap();
+        assert_eq!(dtls.to_string(), "ClientHello");
+        let message = dtls.handshakes().next().unwrap();
+        assert_eq!(message.message_seq, 1);
+        assert_eq!(
+            message.body,
+            HandshakeBody::ClientHello {
+                version: VERSION_1_2,
+                random: vec![7; RANDOM_LEN],
+                cookie: vec![0xAA, 0xBB],
+                cipher_suites: vec![0xC02B, 0xC02F],
+                srtp_profiles: vec![SRTP_AEAD_AES_128_GCM, SRTP_AES128_CM_HMAC_SHA1_80],
+            }
+        );
+
+        let mut server_hello = vec![0xFE, 0xFD];
+        server_hello.extend_from_slice(&[0; RANDOM_LEN]);
+        server_hello.extend_from_slice(&[0, 0xC0, 0x2B, 0]);
+        server_hello.extend_from_slice(&use_srtp(&[0x0007]));
+        let mut datagram = record(CONTENT_HANDSHAKE, 0, 1, &handshake(2, 0, &server_hello));
+        datagram.extend(record(CONTENT_HANDSHAKE, 0, 2, &handshake(14, 1, &[])));
+
+        let dtls = DtlsPacket::parse(&datagram).unwrap();
+        assert_eq!(dtls.to_string(), "ServerHello, ServerHelloDone");
+        let HandshakeBody::ServerHello { srtp_profile, .. } =
+            dtls.handshakes().next().unwrap().body
+        else {
+            panic!("not a ServerHello");
+        };
+        assert_eq!(srtp_profile, Some(SRTP_AEAD_AES_128_GCM));
+        assert_eq!(srtp_profile.unwrap().to_string(), "SRTP_AEAD_AES_128_GCM");
+    }
+
+    #[test]
+    fn test_computes_certificate_fingerprint() {
+        let certificate = b"not really a certificate";
+        let mut list = (certificate.len() as u32).to_be_bytes()[1..].to_vec();
+        list.extend_from_slice(certificate);
+        let mut body = (list.len() as u32).to_be_bytes()[1..].to_vec();
+        body.extend_from_slice(&list);
+        let datagram = record(CONTENT_HANDSHAKE, 0, 3, &handshake(11, 2, &body));
+
+        let dtls = DtlsPacket::parse(&datagram).unwrap();
+        let HandshakeBody::Certificate { ref fingerprints } =
+            dtls.handshakes().next().unwrap().body
+        else {
+            panic!("not a Certificate");
+        };
+        assert_eq!(
+            fingerprints[1].to_string(),
+            "sha-256 D6:18:22:55:E5:73:9D:55:FC:97:81:75:9C:7A:82:3D:\
+             4D:FF:3D:2B:7D:17:94:9E:70:85:EE:7C:0C:C2:2A:CF"
+        );
+    }
+
+    #[test]
+    fn test_parses_encrypted_records() {
+        let mut datagram = record(CONTENT_CHANGE_CIPHER_SPEC, 0, 4, &[1]);
+        datagram.extend(record(CONTENT_HANDSHAKE, 1, 0, &[0x5A; 40]));
+        let dtls = DtlsPacket::parse(&datagram).unwrap();
+        assert_eq!(
+            dtls.to_string(),
+            "Change Cipher Spec, Encrypted Handshake Message"
+        );
+
+        let dtls = DtlsPacket::parse(&record(CONTENT_ALERT, 0, 5, &[2, 40])).unwrap();
+        assert_eq!(dtls.to_string(), "Alert (fatal handshake_failure)");
+
+        // DTLS 1.3 unified header with a 16-bit sequence number and the length
+        let mut datagram = vec![0x2f, 0x12, 0x34, 0, 20];
+        datagram.extend_from_slice(&[0x5A; 20]);
+        let dtls = DtlsPacket::parse(&datagram).unwrap();
+        assert_eq!(dtls.records[0].epoch, 3);
+        assert_eq!(dtls.records[0].sequence_number, 0x1234);
+        datagram.push(0);
+        assert!(DtlsPacket::parse(&datagram).is_none());
+    }
+
+    #[test]
+    fn test_rejects_other_protocols() {
+        // DNS query and SIP keep-alive
+        let dns = [0x1a, 0xdd, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 1, 0x31, 1, 0x30];
+        assert!(DtlsPacket::parse(&dns).is_none());
+        assert!(DtlsPacket::parse(b"     ").is_none());
+        let dns = [
+            0x38, 0xd3, 1, 0, 0, 1, 0, 0, 0, 0, 0, 0, 4, 0x5f, 0x73, 0x69,
+        ];
+        assert!(DtlsPacket::parse(&dns).is_none());
+        // first bytes in the range of the unified header, without the length
+        let dns = [
+            0x2a, 0x51, 1, 0, 0, 1, 0, 0, 0, 0, 0, 0, 7, 0x65, 0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65,
+            3, 0x63, 0x6f, 0x6d, 0, 0, 1, 0, 1,
+        ];
+        assert!(DtlsPacket::parse(&dns).is_none());
+        assert!(DtlsPacket::parse(b"# netpix keep-alive, not a DTLS record").is_none());
+        // record longer than the datagram
+        let mut truncated = record(CONTENT_APPLICATION_DATA, 1, 6, &[0; 20]);
+        truncated.truncate(25);
+        assert!(DtlsPacket::parse(&truncated).is_none());
+    }
+}
@@ -3,22 +3,33 @@ use bincode::{
     error::{DecodeError, EncodeError},
 };
 use std::fmt;
+use std::time::Duration;
 
+pub use crate::dtls::DtlsPacket;
 pub use crate::mpegts::MpegtsPacket;
 pub use crate::rtcp::RtcpPacket;
 pub use crate::rtp::RtpPacket;
 pub use crate::stun::StunPacket;
 pub use packet::Packet;
 pub use packet::SessionProtocol;
+pub use rtsp::RtspPacket;
+pub use sap::SapPacket;
 pub use sdp::Sdp;
+pub use sip::SipPacket;
 
+pub mod dtls;
 pub mod mpegts;
 pub mod packet;
 pub mod rtcp;
 pub mod rtp;
+pub mod rtsp;
+pub mod sap;
 pub mod sdp;
+pub mod sip;
+pub mod srtp;
 mod stream_keys;
 pub mod stun;
+mod text_message;
 pub mod utils;
 
 pub use stream_keys::{MpegtsStreamKey, PacketAssociationTable, RtpStreamKey};
@@ -29,6 +40,7 @@ pub const PACKET_MAX_AGE_SECS: u64 = 120; // 2 minutes
 pub enum Source {
     File(String),
     Interface(String),
+    Socket(String),
 }
 
 impl Source {
@@ -44,6 +56,7 @@ impl Source {
         match *words.first().unwrap() {
             "📁" => Some(Source::File(name)),
             "🌐" => Some(Source::Interface(name)),
+            "📡" => Some(Source::Socket(name)),
             _ => None,
         }
     }
@@ -54,6 +67,7 @@ impl fmt::Display for Source {
         let (icon, name) = match self {
             Self::File(file) => ("📁", file),
             Self::Interface(interface) => ("🌐", interface),
+            Self::Socket(socket) => ("📡", socket),
         };
 
         write!(f, "{} {}", icon, name)
@@ -63,24 +77,88 @@ impl fmt::Display for Source {
 #[derive(Encode, Decode, Debug, Clone)]
 pub enum Request {
     FetchAll,
-    Reparse(usize, packet::SessionProtocol),
+    Reparse(usize, packet::SessionProtocol, ReparseScope),
     ChangeSource(Source),
     ParseSdp(RtpStreamKey, String),
     PacketsStats(PacketsStats),
+    Replay(ReplayControl),
+    Export {
+        source: Source,
+        selection: ExportSelection,
+    },
+}
+
+/// Packets of a source written to a pcapng file by [`Request::Export`].
+#[derive(Decode, Encode, Debug, Clone, PartialEq)]
+pub enum ExportSelection {
+    /// packets with ids in the inclusive range
+    IdRange(usize, usize),
+    /// packets picked by the client, e.g. the ones matching its filter
+    Ids(Vec<usize>),
+    RtpStream(RtpStreamKey),
+}
+
+/// Export ready to be downloaded over HTTP at `url`.
+#[derive(Decode, Encode, Debug, Clone)]
+pub struct ExportReady {
+    pub url: String,
+    pub packet_count: usize,
+}
+
+/// Packets decoded again by [`Request::Reparse`].
+#[derive(Decode, Encode, Debug, Clone, Copy, PartialEq)]
+pub enum ReparseScope {
+    Packet,
+    /// every packet of the same 5-tuple, in either direction
+    Flow,
+}
+
+/// Controls of a file source replayed with `--replay-speed`,
+/// applied to the source currently selected by the client.
+#[derive(Decode, Encode, Debug, Clone, Copy, PartialEq)]
+pub enum ReplayControl {
+    Pause,
+    Resume,
+    // releases a single packet while paused
+    Step,
+    // offset from the first packet of the capture
+    Seek(Duration),
+    Speed(f64),
+}
+
+#[derive(Decode, Encode, Debug, Clone, PartialEq)]
+pub struct ReplayState {
+    pub paused: bool,
+    pub speed: f64,
+    // capture time of the last sent packet, relative to the first packet
+    pub position: Duration,
 }
 
 #[derive(Decode, Encode, Debug, Clone)]
 pub struct PacketsStats {
     pub discharged: usize,
     pub overwritten: usize,
+    pub fragments: usize,
+    pub reassembly_failures: usize,
+    pub tcp_gaps: usize,
+    pub tcp_lost_bytes: usize,
 }
 
+// nearly every response is a packet, boxing it wouldn't save any memory
+#[allow(clippy::large_enum_variant)]
 #[derive(Decode, Encode, Debug, Clone)]
 pub enum Response {
     Packet(Packet),
     Sources(Vec<Source>),
-    Sdp(RtpStreamKey, Sdp),
+    /// session set for the stream, or for every stream it describes without one
+    Sdp(Option<RtpStreamKey>, Sdp),
     PacketsStats(PacketsStats),
+    ReplayState(Source, ReplayState),
+    Export(ExportReady),
+    /// packets of the source were dropped, as the replay seeked back and sends them again
+    Reset(Source),
+    /// stored packets decoded again as another protocol, sent together to be applied at once
+    Reparsed(Vec<Packet>),
 }
 
 impl Request {
@@ -1,23 +1,53 @@
-use super::{MpegtsPacket, RtcpPacket, RtpPacket, StunPacket};
+use super::{
+    DtlsPacket, MpegtsPacket, RtcpPacket, RtpPacket, RtspPacket, SapPacket, SipPacket, StunPacket,
+};
+use crate::srtp::SrtpInfo;
 use bincode::{Decode, Encode};
 
+#[cfg(not(target_arch = "wasm32"))]
+pub use classifier::{Reclassified, RtpClassifier};
+pub use decode_as::{DecodeRule, DecodeRules};
+#[cfg(not(target_arch = "wasm32"))]
+use ipv6::Ipv6Payload;
+#[cfg(not(target_arch = "wasm32"))]
+use link::LinkFrame;
 #[cfg(not(target_arch = "wasm32"))]
 use pnet_packet::{
     Packet as _,
-    ethernet::{EtherTypes, EthernetPacket},
-    ip::IpNextHeaderProtocols,
-    ipv4::Ipv4Packet,
+    ethernet::EtherTypes,
+    ip::{IpNextHeaderProtocol, IpNextHeaderProtocols},
+    ipv4::{Ipv4Flags, Ipv4Packet},
     ipv6::Ipv6Packet,
     tcp::TcpPacket,
     udp::UdpPacket,
 };
+#[cfg(not(target_arch = "wasm32"))]
+use reassembly::{Fragment, FragmentKey};
+#[cfg(not(target_arch = "wasm32"))]
+pub use reassembly::{IpReassembler, ReassemblyStats};
 use std::fmt::{Display, Formatter};
 #[cfg(not(target_arch = "wasm32"))]
 use std::io::Write;
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::str::FromStr;
 use std::time::Duration;
 use std::{fmt, time::SystemTime};
+#[cfg(not(target_arch = "wasm32"))]
+pub use tcp::{TcpReassembler, TcpStats};
+
+#[cfg(not(target_arch = "wasm32"))]
+mod classifier;
+mod decode_as;
+#[cfg(not(target_arch = "wasm32"))]
+mod ipv6;
+#[cfg(not(target_arch = "wasm32"))]
+mod link;
+#[cfg(not(target_arch = "wasm32"))]
+mod reassembly;
+#[cfg(not(target_arch = "wasm32"))]
+mod tcp;
+#[cfg(not(target_arch = "wasm32"))]
+mod tunnel;
 
 #[derive(Encode, Decode, PartialEq, Debug, Copy, Clone)]
 pub enum SessionProtocol {
@@ -26,6 +56,10 @@ pub enum SessionProtocol {
     Rtcp,
     Mpegts,
     Stun,
+    Sip,
+    Rtsp,
+    Sap,
+    Dtls,
     Meta,
 }
 
@@ -38,6 +72,10 @@ impl FromStr for SessionProtocol {
             "rtcp" => Ok(Self::Rtcp),
             "mpeg-ts" => Ok(Self::Mpegts),
             "stun" => Ok(Self::Stun),
+            "sip" => Ok(Self::Sip),
+            "rtsp" => Ok(Self::Rtsp),
+            "sap" => Ok(Self::Sap),
+            "dtls" => Ok(Self::Dtls),
             "meta" => Ok(Self::Meta),
             _ => Err(()),
         }
@@ -52,6 +90,10 @@ impl SessionProtocol {
             Self::Rtcp,
             Self::Mpegts,
             Self::Stun,
+            Self::Sip,
+            Self::Rtsp,
+            Self::Sap,
+            Self::Dtls,
             Self::Meta,
         ]
     }
@@ -65,6 +107,10 @@ impl fmt::Display for SessionProtocol {
             Self::Rtcp => "RTCP",
             Self::Mpegts => "MPEG-TS",
             Self::Stun => "STUN",
+            Self::Sip => "SIP",
+            Self::Rtsp => "RTSP",
+            Self::Sap => "SAP",
+            Self::Dtls => "DTLS",
             Self::Meta => "META",
         };
 
@@ -96,6 +142,10 @@ pub enum SessionPacket {
     Rtcp(Vec<RtcpPacket>),
     Mpegts(MpegtsPacket),
     Stun(StunPacket),
+    Sip(SipPacket),
+    Rtsp(RtspPacket),
+    Sap(SapPacket),
+    Dtls(DtlsPacket),
     Meta(StreamMetaData),
 }
 
@@ -132,7 +182,7 @@ impl From<StreamMetaData> for Packet {
     fn from(value: StreamMetaData) -> Self {
         let metadata = PacketMetadata {
             is_synthetic_addr: true,
-            direction: PacketDirection::Unknown,
+            ..Default::default()
         };
 
         Packet {
@@ -168,10 +218,109 @@ impl fmt::Display for PacketDirection {
         write!(f, "{}", direction_str)
     }
 }
+#[derive(Encode, Decode, Debug, Clone, PartialEq)]
+pub enum Encapsulation {
+    Gre { key: Option<u32> },
+    Erspan { session_id: Option<u16> },
+    Vxlan { vni: u32 },
+    GtpU { teid: u32 },
+    // data relayed by a TURN server, on a channel or in Send and Data indications
+    Turn { channel: Option<u16> },
+}
+
+impl fmt::Display for Encapsulation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Gre { key: Some(key) } => write!(f, "GRE key {}", key),
+            Self::Gre { key: None } => write!(f, "GRE"),
+            Self::Erspan {
+                session_id: Some(session_id),
+            } => write!(f, "ERSPAN session {}", session_id),
+            Self::Erspan { session_id: None } => write!(f, "ERSPAN"),
+            Self::Vxlan { vni } => write!(f, "VXLAN VNI {}", vni),
+            Self::GtpU { teid } => write!(f, "GTP-U TEID {:#x}", teid),
+            Self::Turn {
+                channel: Some(channel),
+            } => write!(f, "TURN channel {:#x}", channel),
+            Self::Turn { channel: None } => write!(f, "TURN"),
+        }
+    }
+}
+
+#[derive(Encode, Decode, Debug, Clone, PartialEq)]
+pub struct Tunnel {
+    pub source_addr: IpAddr,
+    pub destination_addr: IpAddr,
+    pub encapsulation: Encapsulation,
+}
+
+impl fmt::Display for Tunnel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} → {}",
+            self.encapsulation, self.source_addr, self.destination_addr
+        )
+    }
+}
+
+#[derive(Encode, Decode, Debug, Clone, Copy, PartialEq)]
+pub struct TcpSegment {
+    // of the first byte of the message, including its framing, for the framed messages
+    pub sequence_number: u32,
+    pub acknowledgement_number: u32,
+    pub flags: u8,
+    pub payload_len: u32,
+    // captured with a snaplen shorter than the frame, the tail of the payload is missing
+    pub truncated: bool,
+}
+
+/// How a logical packet was extracted from a reassembled TCP stream.
+#[derive(Encode, Decode, Debug, Clone, Copy, PartialEq)]
+pub enum TcpFraming {
+    // RFC 4571 length prefixed
+    Rfc4571,
+    // RTSP `$` interleaved binary data
+    Interleaved { channel: u8 },
+    RtspMessage,
+    // delimited by the Content-Length of the message
+    SipMessage,
+}
+
+impl fmt::Display for TcpFraming {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rfc4571 => write!(f, "RFC 4571"),
+            Self::Interleaved { channel } => write!(f, "RTSP interleaved ch {}", channel),
+            Self::RtspMessage => write!(f, "RTSP message"),
+            Self::SipMessage => write!(f, "SIP message"),
+        }
+    }
+}
+
 #[derive(Encode, Decode, Debug, Clone)]
 pub struct PacketMetadata {
     pub is_synthetic_addr: bool,
     pub direction: PacketDirection,
+    // outermost tag/label/tunnel first
+    pub vlan_ids: Vec<u16>,
+    pub mpls_labels: Vec<u32>,
+    pub tunnels: Vec<Tunnel>,
+    pub tcp: Option<TcpSegment>,
+    pub framing: Option<TcpFraming>,
+    // position of the message in its TCP segment, the messages share the frame's id
+    pub sub_index: usize,
+    // capture file the packet was read from, when several files are merged
+    pub origin: Option<String>,
+    // outcome of the SRTP or SRTCP decryption, the payload is the decrypted one
+    pub srtp: Option<SrtpInfo>,
+}
+
+impl PacketMetadata {
+    // innermost (customer) VLAN, the one the stream is carried on
+    pub fn vlan_id(&self) -> Option<u16> {
+        self.vlan_ids.last().copied()
+    }
 }
 
 impl Default for PacketMetadata {
@@ -179,6 +328,14 @@ impl Default for PacketMetadata {
         Self {
             is_synthetic_addr: false,
             direction: PacketDirection::Unknown,
+            vlan_ids: Vec::new(),
+            mpls_labels: Vec::new(),
+            tunnels: Vec::new(),
+            tcp: None,
+            framing: None,
+            sub_index: 0,
+            origin: None,
+            srtp: None,
         }
     }
 }
@@ -200,19 +357,41 @@ pub struct Packet {
 
 #[cfg(not(target_arch = "wasm32"))]
 impl Packet {
-    pub fn build(raw_packet: &pcap::Packet, id: usize) -> Option<Self> {
-        let ethernet_packet = EthernetPacket::new(raw_packet)?;
-        Self::build_from_ethernet(raw_packet, id, &ethernet_packet)
+    pub fn build(raw_packet: &pcap::Packet, id: usize, link_type: pcap::Linktype) -> Option<Self> {
+        Self::build_from_link(raw_packet, id, link_type, None)
+    }
+
+    /// Same as [`Packet::build`], but fragments are buffered in `reassembler`
+    /// and the packet is only returned once the whole datagram is received.
+    pub fn build_reassembled(
+        raw_packet: &pcap::Packet,
+        id: usize,
+        link_type: pcap::Linktype,
+        reassembler: &mut IpReassembler,
+    ) -> Option<Self> {
+        Self::build_from_link(raw_packet, id, link_type, Some(reassembler))
     }
 
-    fn build_from_ethernet(
+    fn build_from_link(
         raw_packet: &pcap::Packet,
         id: usize,
-        ethernet_packet: &EthernetPacket,
+        link_type: pcap::Linktype,
+        reassembler: Option<&mut IpReassembler>,
     ) -> Option<Self> {
-        match ethernet_packet.get_ethertype() {
-            EtherTypes::Ipv4 => Self::build_from_ip4(raw_packet, id, ethernet_packet),
-            EtherTypes::Ipv6 => Self::build_from_ip6(raw_packet, id, ethernet_packet),
+        let frame = LinkFrame::parse(link_type, raw_packet)?;
+
+        Self::build_from_frame(raw_packet, id, &frame, reassembler)
+    }
+
+    fn build_from_frame(
+        raw_packet: &pcap::Packet,
+        id: usize,
+        frame: &LinkFrame,
+        reassembler: Option<&mut IpReassembler>,
+    ) -> Option<Self> {
+        match frame.ethertype {
+            EtherTypes::Ipv4 => Self::build_from_ip4(raw_packet, id, frame, reassembler),
+            EtherTypes::Ipv6 => Self::build_from_ip6(raw_packet, id, frame, reassembler),
             _ => None,
         }
     }
@@ -220,9 +399,10 @@ impl Packet {
     fn build_from_ip4(
         raw_packet: &pcap::Packet,
         id: usize,
-        ethernet_packet: &EthernetPacket,
+        frame: &LinkFrame,
+        reassembler: Option<&mut IpReassembler>,
     ) -> Option<Self> {
-        let ipv4_packet = Ipv4Packet::new(ethernet_packet.payload())?;
+        let ipv4_packet = Ipv4Packet::new(frame.payload)?;
         let source_addr = ipv4_packet.get_source();
         let destination_addr = ipv4_packet.get_destination();
         let ip_payload = ipv4_packet.payload();
@@ -231,35 +411,68 @@ impl Packet {
             return Self::build_from_transport(
                 raw_packet,
                 id,
+                frame,
                 source_addr.into(),
                 destination_addr.into(),
                 TransportProtocol::Udp, // default to UDP for empty payload
-                ethernet_packet.payload(),
+                frame.payload,
             );
         }
 
-        let transport_protocol = match ipv4_packet.get_next_level_protocol() {
-            IpNextHeaderProtocols::Tcp => TransportProtocol::Tcp,
-            IpNextHeaderProtocols::Udp => TransportProtocol::Udp,
-            _ => return None,
+        let next_level_protocol = ipv4_packet.get_next_level_protocol();
+        if !is_supported(next_level_protocol) {
+            return None;
+        }
+
+        let fragment = Fragment {
+            key: FragmentKey {
+                source_addr: source_addr.into(),
+                destination_addr: destination_addr.into(),
+                protocol: next_level_protocol.0,
+                id: ipv4_packet.get_identification().into(),
+            },
+            offset: ipv4_packet.get_fragment_offset() as usize * 8,
+            more_fragments: ipv4_packet.get_flags() & Ipv4Flags::MoreFragments != 0,
+            payload: ip_payload,
         };
 
-        Self::build_from_transport(
-            raw_packet,
-            id,
-            source_addr.into(),
-            destination_addr.into(),
-            transport_protocol,
-            ip_payload,
-        )
+        match reassembler {
+            Some(reassembler) if fragment.is_fragmented() => {
+                let header_len = frame.payload.len() - ip_payload.len();
+                let datagram = reassembler.push(fragment, get_duration(raw_packet))?;
+
+                Self::build_from_ip_payload(
+                    raw_packet,
+                    id,
+                    frame,
+                    source_addr.into(),
+                    destination_addr.into(),
+                    next_level_protocol,
+                    &datagram,
+                    Some(reassembler),
+                )
+                .map(|packet| packet.with_length(header_len + datagram.len()))
+            }
+            reassembler => Self::build_from_ip_payload(
+                raw_packet,
+                id,
+                frame,
+                source_addr.into(),
+                destination_addr.into(),
+                next_level_protocol,
+                ip_payload,
+                reassembler,
+            ),
+        }
     }
 
     fn build_from_ip6(
         raw_packet: &pcap::Packet,
         id: usize,
-        ethernet_packet: &EthernetPacket,
+        frame: &LinkFrame,
+        reassembler: Option<&mut IpReassembler>,
     ) -> Option<Self> {
-        let ipv6_packet = Ipv6Packet::new(ethernet_packet.payload())?;
+        let ipv6_packet = Ipv6Packet::new(frame.payload)?;
         let source_addr = ipv6_packet.get_source();
         let destination_addr = ipv6_packet.get_destination();
         let ip_payload = ipv6_packet.payload();
@@ -268,38 +481,135 @@ impl Packet {
             return Self::build_from_transport(
                 raw_packet,
                 id,
+                frame,
                 source_addr.into(),
                 destination_addr.into(),
                 TransportProtocol::Udp, // default to UDP for empty payload
-                ethernet_packet.payload(),
+                frame.payload,
             );
         }
 
-        let transport_protocol = match ipv6_packet.get_next_header() {
-            IpNextHeaderProtocols::Tcp => TransportProtocol::Tcp,
-            IpNextHeaderProtocols::Udp => TransportProtocol::Udp,
-            _ => return None,
+        let fragment =
+            match ipv6::skip_extension_headers(ipv6_packet.get_next_header(), ip_payload)? {
+                Ipv6Payload::Transport(next_header, transport_payload) => {
+                    return Self::build_from_transport(
+                        raw_packet,
+                        id,
+                        frame,
+                        source_addr.into(),
+                        destination_addr.into(),
+                        transport_protocol(next_header)?,
+                        transport_payload,
+                    );
+                }
+                Ipv6Payload::Fragment(fragment) => fragment,
+            };
+
+        // without a reassembler, only the first fragment carries the transport header
+        let Some(reassembler) = reassembler else {
+            if fragment.offset != 0 {
+                return None;
+            }
+            let Ipv6Payload::Transport(next_header, transport_payload) =
+                ipv6::skip_extension_headers(fragment.next_header, fragment.payload)?
+            else {
+                return None;
+            };
+
+            return Self::build_from_ip_payload(
+                raw_packet,
+                id,
+                frame,
+                source_addr.into(),
+                destination_addr.into(),
+                next_header,
+                transport_payload,
+                None,
+            );
         };
 
-        Self::build_from_transport(
+        let header_len = frame.payload.len() - fragment.payload.len();
+        let next_header = fragment.next_header;
+        let datagram = reassembler.push(
+            Fragment {
+                key: FragmentKey {
+                    source_addr: source_addr.into(),
+                    destination_addr: destination_addr.into(),
+                    protocol: next_header.0,
+                    id: fragment.id,
+                },
+                offset: fragment.offset,
+                more_fragments: fragment.more_fragments,
+                payload: fragment.payload,
+            },
+            get_duration(raw_packet),
+        )?;
+
+        // extension headers of the fragmentable part
+        let Ipv6Payload::Transport(next_header, transport_payload) =
+            ipv6::skip_extension_headers(next_header, &datagram)?
+        else {
+            return None;
+        };
+
+        Self::build_from_ip_payload(
             raw_packet,
             id,
+            frame,
             source_addr.into(),
             destination_addr.into(),
-            transport_protocol,
-            ip_payload,
+            next_header,
+            transport_payload,
+            Some(reassembler),
         )
+        .map(|packet| packet.with_length(header_len + datagram.len()))
+    }
+
+    /// Decapsulates tunnels, so the returned packet describes the innermost transport.
+    #[allow(clippy::too_many_arguments)]
+    fn build_from_ip_payload(
+        raw_packet: &pcap::Packet,
+        id: usize,
+        frame: &LinkFrame,
+        source_addr: IpAddr,
+        destination_addr: IpAddr,
+        protocol: IpNextHeaderProtocol,
+        payload: &[u8],
+        reassembler: Option<&mut IpReassembler>,
+    ) -> Option<Self> {
+        if let Some(mut inner) = frame.decapsulate(source_addr, destination_addr, protocol, payload)
+        {
+            // length of the packet is the length of the inner IP packet
+            inner.header_len = (raw_packet.header.len as usize).saturating_sub(inner.payload.len());
+            return Self::build_from_frame(raw_packet, id, &inner, reassembler);
+        }
+
+        Self::build_from_transport(
+            raw_packet,
+            id,
+            frame,
+            source_addr,
+            destination_addr,
+            transport_protocol(protocol)?,
+            payload,
+        )
+    }
+
+    fn with_length(mut self, length: usize) -> Self {
+        self.length = length as u32;
+        self
     }
 
     fn build_from_transport(
         raw_packet: &pcap::Packet,
         id: usize,
-        source_addr: std::net::IpAddr,
-        destination_addr: std::net::IpAddr,
+        frame: &LinkFrame,
+        source_addr: IpAddr,
+        destination_addr: IpAddr,
         transport_protocol: TransportProtocol,
         payload: &[u8],
     ) -> Option<Self> {
-        let (source_addr, destination_addr, payload) = match transport_protocol {
+        let (source_addr, destination_addr, payload, tcp) = match transport_protocol {
             TransportProtocol::Tcp => {
                 let tcp_packet = TcpPacket::new(payload)?;
                 let source_port = tcp_packet.get_source();
@@ -307,10 +617,22 @@ impl Packet {
                 let source_addr = SocketAddr::new(source_addr, source_port);
                 let destination_addr = SocketAddr::new(destination_addr, destination_port);
                 let tcp_payload = tcp_packet.payload();
+                let tcp = TcpSegment {
+                    sequence_number: tcp_packet.get_sequence(),
+                    acknowledgement_number: tcp_packet.get_acknowledgement(),
+                    flags: tcp_packet.get_flags(),
+                    payload_len: tcp_payload.len() as u32,
+                    truncated: raw_packet.header.caplen < raw_packet.header.len,
+                };
                 if tcp_payload.is_empty() {
-                    (source_addr, destination_addr, payload.to_vec())
+                    (source_addr, destination_addr, payload.to_vec(), Some(tcp))
                 } else {
-                    (source_addr, destination_addr, tcp_payload.to_vec())
+                    (
+                        source_addr,
+                        destination_addr,
+                        tcp_payload.to_vec(),
+                        Some(tcp),
+                    )
                 }
             }
             TransportProtocol::Udp => {
@@ -321,9 +643,9 @@ impl Packet {
                 let destination_addr = SocketAddr::new(destination_addr, destination_port);
                 let udp_payload = udp_packet.payload();
                 if udp_payload.is_empty() {
-                    (source_addr, destination_addr, payload.to_vec())
+                    (source_addr, destination_addr, payload.to_vec(), None)
                 } else {
-                    (source_addr, destination_addr, udp_payload.to_vec())
+                    (source_addr, destination_addr, udp_payload.to_vec(), None)
                 }
             }
         };
@@ -331,7 +653,10 @@ impl Packet {
         Some(Self {
             payload: Some(payload),
             id,
-            length: raw_packet.header.len - 14,
+            length: raw_packet
+                .header
+                .len
+                .saturating_sub(frame.header_len as u32),
             timestamp: get_duration(raw_packet),
             source_addr,
             destination_addr,
@@ -339,30 +664,91 @@ impl Packet {
             session_protocol: SessionProtocol::Unknown,
             contents: SessionPacket::Unknown,
             creation_time: SystemTime::now(),
-            metadata: Default::default(),
+            metadata: PacketMetadata {
+                vlan_ids: frame.vlan_ids.clone(),
+                mpls_labels: frame.mpls_labels.clone(),
+                tunnels: frame.tunnels.clone(),
+                tcp,
+                ..Default::default()
+            },
         })
     }
 
-    pub fn guess_payload(&mut self) {
+    pub fn guess_payload(&mut self, rules: &DecodeRules) {
         // could use port to determine validity
-        // TODO: TURN channels
         //
         // also, some UDP ports are used by other protocols
         // see Wireshark -> View -> Internals -> Dissector Table -> UDP port
-        if self.transport_protocol != TransportProtocol::Udp {
+        //
+        // TCP payloads are only meaningful once framed by the TCP reassembler
+        match (self.transport_protocol, self.metadata.framing) {
+            (TransportProtocol::Udp, _) => {}
+            (
+                TransportProtocol::Tcp,
+                Some(
+                    TcpFraming::Rfc4571
+                    | TcpFraming::Interleaved { .. }
+                    | TcpFraming::RtspMessage
+                    | TcpFraming::SipMessage,
+                ),
+            ) => {}
+            _ => return,
+        }
+
+        // rules win even when the payload can't be decoded that way
+        if let Some(protocol) = rules.find(self) {
+            self.parse_as(protocol);
             return;
         }
 
-        if let Some(stun) = StunPacket::build(self) {
-            self.session_protocol = SessionProtocol::Stun;
-            self.contents = SessionPacket::Stun(stun);
+        // SAP's first byte falls into the DTLS range
+        if self.transport_protocol == TransportProtocol::Udp
+            && self.destination_addr.port() == crate::sap::SAP_PORT
+            && let Some(sap) = SapPacket::build(self)
+        {
+            self.session_protocol = SessionProtocol::Sap;
+            self.contents = SessionPacket::Sap(sap);
             return;
         }
 
-        if let Some(mpegts) = MpegtsPacket::build(self) {
-            self.session_protocol = SessionProtocol::Mpegts;
-            self.contents = SessionPacket::Mpegts(mpegts);
+        let Some(&first_byte) = self.payload.as_deref().and_then(|payload| payload.first()) else {
             return;
+        };
+
+        // demultiplexed by the first byte as in RFC 7983, apart from MPEG-TS and
+        // the text of SIP and RTSP, ZRTP (16-19) isn't decoded yet,
+        // TURN channels (64-79) are left to `TurnRelays` as only bound ones are known
+        match first_byte {
+            0..=3 => {
+                if let Some(stun) = StunPacket::build(self) {
+                    self.session_protocol = SessionProtocol::Stun;
+                    self.contents = SessionPacket::Stun(stun);
+                }
+                return;
+            }
+            crate::mpegts::constants::SYNC_BYTE => {
+                if let Some(mpegts) = MpegtsPacket::build(self) {
+                    self.session_protocol = SessionProtocol::Mpegts;
+                    self.contents = SessionPacket::Mpegts(mpegts);
+                } else {
+                    // `GET_PARAMETER` shares the sync byte
+                    self.guess_text();
+                }
+                return;
+            }
+            20..=63 => {
+                if let Some(dtls) = DtlsPacket::build(self) {
+                    self.session_protocol = SessionProtocol::Dtls;
+                    self.contents = SessionPacket::Dtls(dtls);
+                }
+                return;
+            }
+            b'A'..=b'Z' => {
+                self.guess_text();
+                return;
+            }
+            128..=191 => {}
+            _ => return,
         }
 
         if let Some(rtcp) = RtcpPacket::build(self)
@@ -381,6 +767,17 @@ impl Packet {
         }
     }
 
+    // the version in the start line tells SIP and RTSP apart
+    fn guess_text(&mut self) {
+        if let Some(sip) = SipPacket::build(self) {
+            self.session_protocol = SessionProtocol::Sip;
+            self.contents = SessionPacket::Sip(sip);
+        } else if let Some(rtsp) = RtspPacket::build(self) {
+            self.session_protocol = SessionProtocol::Rtsp;
+            self.contents = SessionPacket::Rtsp(rtsp);
+        }
+    }
+
     pub fn parse_as(&mut self, packet_type: SessionProtocol) {
         match packet_type {
             SessionProtocol::Rtp => {
@@ -411,6 +808,34 @@ impl Packet {
                 self.session_protocol = packet_type;
                 self.contents = SessionPacket::Stun(stun);
             }
+            SessionProtocol::Sip => {
+                let Some(sip) = SipPacket::build(self) else {
+                    return;
+                };
+                self.session_protocol = packet_type;
+                self.contents = SessionPacket::Sip(sip);
+            }
+            SessionProtocol::Rtsp => {
+                let Some(rtsp) = RtspPacket::build(self) else {
+                    return;
+                };
+                self.session_protocol = packet_type;
+                self.contents = SessionPacket::Rtsp(rtsp);
+            }
+            SessionProtocol::Sap => {
+                let Some(sap) = SapPacket::build(self) else {
+                    return;
+                };
+                self.session_protocol = packet_type;
+                self.contents = SessionPacket::Sap(sap);
+            }
+            SessionProtocol::Dtls => {
+                let Some(dtls) = DtlsPacket::build(self) else {
+                    return;
+                };
+                self.session_protocol = packet_type;
+                self.contents = SessionPacket::Dtls(dtls);
+            }
             SessionProtocol::Unknown => {
                 self.session_protocol = packet_type;
                 self.contents = SessionPacket::Unknown;
@@ -464,6 +889,20 @@ impl fmt::Display for Packet {
         )
     }
 }
+#[cfg(not(target_arch = "wasm32"))]
+fn transport_protocol(protocol: IpNextHeaderProtocol) -> Option<TransportProtocol> {
+    match protocol {
+        IpNextHeaderProtocols::Tcp => Some(TransportProtocol::Tcp),
+        IpNextHeaderProtocols::Udp => Some(TransportProtocol::Udp),
+        _ => None,
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn is_supported(protocol: IpNextHeaderProtocol) -> bool {
+    protocol == IpNextHeaderProtocols::Gre || transport_protocol(protocol).is_some()
+}
+
 #[cfg(not(target_arch = "wasm32"))]
 fn is_rtp(packet: &RtpPacket) -> bool {
     if packet.version != 2 {
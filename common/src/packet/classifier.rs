@@ -0,0 +1,260 @@
+use super::{Packet, SessionPacket, SessionProtocol, TransportProtocol};
+use crate::{PacketAssociationTable, RtpPacket};
+use std::collections::HashMap;
+use std::time::Duration;
+
+// packets of the same SSRC in sequence needed to confirm the flow carries RTP
+const PROBATION: usize = 4;
+// RTP looking packets after which a flow that wasn't confirmed is rejected
+const MAX_PROBE_PACKETS: usize = 16;
+const MAX_SEQUENCE_GAP: u16 = 100;
+// one second of a 90 kHz clock per packet, higher than any real media
+const MAX_TIMESTAMP_STEP: u32 = 90_000;
+const FLOW_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Earlier packets whose protocol changed after their flow got classified.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reclassified {
+    pub ids: Vec<usize>,
+    pub protocol: SessionProtocol,
+}
+
+/// Confirms RTP per 5-tuple instead of trusting single packets.
+///
+/// Packets of a new flow which look like RTP stay undecoded until several of them
+/// share the SSRC, with increasing sequence numbers and timestamps. Once that happens
+/// the held back packets are returned to be decoded again, while flows which never
+/// get there are rejected and their packets stay undecoded. UDP only, as RTP framed
+/// over TCP is already unambiguous.
+#[derive(Debug, Default)]
+pub struct RtpClassifier {
+    flows: HashMap<PacketAssociationTable, Flow>,
+    last_prune: Duration,
+}
+
+#[derive(Debug)]
+struct Flow {
+    state: FlowState,
+    last_seen: Duration,
+}
+
+#[derive(Debug)]
+enum FlowState {
+    Probing(Probe),
+    Rtp,
+    NotRtp,
+}
+
+#[derive(Debug)]
+struct Probe {
+    ssrc: u32,
+    sequence_number: u16,
+    timestamp: u32,
+    in_sequence: usize,
+    ids: Vec<usize>,
+}
+
+impl Probe {
+    fn new(rtp: &RtpPacket) -> Self {
+        Self {
+            ssrc: rtp.ssrc,
+            sequence_number: rtp.sequence_number,
+            timestamp: rtp.timestamp,
+            in_sequence: 0,
+            ids: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, id: usize, rtp: &RtpPacket) {
+        let sequence_gap = rtp.sequence_number.wrapping_sub(self.sequence_number);
+        let timestamp_step = rtp.timestamp.wrapping_sub(self.timestamp);
+        let follows = rtp.ssrc == self.ssrc
+            && (1..=MAX_SEQUENCE_GAP).contains(&sequence_gap)
+            && timestamp_step <= MAX_TIMESTAMP_STEP * sequence_gap as u32;
+
+        self.in_sequence = if follows { self.in_sequence + 1 } else { 1 };
+        self.ssrc = rtp.ssrc;
+        self.sequence_number = rtp.sequence_number;
+        self.timestamp = rtp.timestamp;
+        self.ids.push(id);
+    }
+}
+
+impl RtpClassifier {
+    /// Keeps RTP of flows which aren't confirmed yet undecoded, returns the earlier
+    /// packets to decode as RTP once the packet confirms its flow.
+    pub fn classify(&mut self, packet: &mut Packet) -> Option<Reclassified> {
+        if packet.transport_protocol != TransportProtocol::Udp {
+            return None;
+        }
+        let SessionPacket::Rtp(ref rtp) = packet.contents else {
+            return None;
+        };
+
+        self.prune(packet.timestamp);
+        let flow = self
+            .flows
+            .entry(PacketAssociationTable::from(&*packet))
+            .or_insert_with(|| Flow {
+                state: FlowState::Probing(Probe::new(rtp)),
+                last_seen: packet.timestamp,
+            });
+        flow.last_seen = packet.timestamp;
+
+        let probe = match flow.state {
+            FlowState::Rtp => return None,
+            FlowState::NotRtp => {
+                packet.parse_as(SessionProtocol::Unknown);
+                return None;
+            }
+            FlowState::Probing(ref mut probe) => probe,
+        };
+
+        probe.push(packet.id, rtp);
+        if probe.in_sequence >= PROBATION {
+            let mut ids = std::mem::take(&mut probe.ids);
+            ids.pop();
+            flow.state = FlowState::Rtp;
+            return Some(Reclassified {
+                ids,
+                protocol: SessionProtocol::Rtp,
+            });
+        }
+
+        if probe.ids.len() >= MAX_PROBE_PACKETS {
+            flow.state = FlowState::NotRtp;
+        }
+        packet.parse_as(SessionProtocol::Unknown);
+        None
+    }
+
+    pub fn reset(&mut self) {
+        self.flows.clear();
+        self.last_prune = Duration::ZERO;
+    }
+
+    fn prune(&mut self, now: Duration) {
+        if now.saturating_sub(self.last_prune) < FLOW_TIMEOUT {
+            return;
+        }
+
+        self.flows
+            .retain(|_, flow| now.saturating_sub(flow.last_seen) < FLOW_TIMEOUT);
+        self.last_prune = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::{DecodeRules, PacketMetadata};
+    use std::time::SystemTime;
+
+    fn rtp(id: usize, source: &str, ssrc: u32, sequence_number: u16, timestamp: u32) -> Packet {
+        let mut payload = vec![0x80, 0x60];
+        payload.extend_from_slice(&sequence_number.to_be_bytes());
+        payload.extend_from_slice(&timestamp.to_be_bytes());
+        payload.extend_from_slice(&ssrc.to_be_bytes());
+        payload.extend_from_slice(&[0; 20]);
+
+        let mut packet = Packet {
+            length: payload.len() as u32,
+            payload: Some(payload),
+            id,
+            timestamp: Duration::from_millis(20 * id as u64),
+            source_addr: source.parse().unwrap(),
+            destination_addr: "10.0.0.2:5004".parse().unwrap(),
+            transport_protocol: TransportProtocol::Udp,
+            session_protocol: SessionProtocol::Unknown,
+            contents: SessionPacket::Unknown,
+            creation_time: SystemTime::now(),
+            metadata: PacketMetadata::default(),
+        };
+        packet.guess_payload(&DecodeRules::default());
+        packet
+    }
+
+    #[test]
+    fn test_confirms_rtp_flow() {
+        let mut classifier = RtpClassifier::default();
+
+        for id in 0..3 {
+            let mut packet = rtp(
+                id,
+                "10.0.0.1:4000",
+                0x1234,
+                100 + id as u16,
+                960 * id as u32,
+            );
+            assert_eq!(classifier.classify(&mut packet), None);
+            assert_eq!(packet.session_protocol, SessionProtocol::Unknown);
+        }
+
+        let mut packet = rtp(3, "10.0.0.1:4000", 0x1234, 103, 960 * 3);
+        assert_eq!(
+            classifier.classify(&mut packet),
+            Some(Reclassified {
+                ids: vec![0, 1, 2],
+                protocol: SessionProtocol::Rtp,
+            })
+        );
+        assert_eq!(packet.session_protocol, SessionProtocol::Rtp);
+
+        // sequence jumps of a confirmed flow are losses, not a reason to doubt it
+        let mut packet = rtp(4, "10.0.0.1:4000", 0x1234, 2000, 0);
+        assert_eq!(classifier.classify(&mut packet), None);
+        assert_eq!(packet.session_protocol, SessionProtocol::Rtp);
+    }
+
+    #[test]
+    fn test_rejects_random_flow() {
+        let mut classifier = RtpClassifier::default();
+
+        // changing "SSRC" and "sequence numbers", as random payloads have
+        for id in 0..MAX_PROBE_PACKETS + 4 {
+            let ssrc = 0x9e37_79b9_u32.wrapping_mul(id as u32 + 1);
+            let sequence_number = (ssrc >> 16) as u16;
+            let mut packet = rtp(id, "10.0.0.1:4000", ssrc, sequence_number, ssrc);
+            assert_eq!(classifier.classify(&mut packet), None);
+            assert_eq!(packet.session_protocol, SessionProtocol::Unknown);
+        }
+
+        // later packets of the flow stay undecoded, others are classified separately
+        let mut packet = rtp(100, "10.0.0.1:4000", 0x1234, 1, 0);
+        classifier.classify(&mut packet);
+        assert_eq!(packet.session_protocol, SessionProtocol::Unknown);
+
+        let mut classified = None;
+        for id in 0..PROBATION {
+            let mut packet = rtp(id, "10.0.0.3:4000", 0x1234, id as u16, 0);
+            classified = classifier.classify(&mut packet);
+        }
+        assert_eq!(classified.map(|c| c.ids.len()), Some(PROBATION - 1));
+    }
+
+    #[test]
+    fn test_timestamps_move_with_sequence_numbers() {
+        let mut classifier = RtpClassifier::default();
+
+        for id in 0..PROBATION * 2 {
+            let timestamp = if id % 2 == 0 { 0 } else { u32::MAX / 2 };
+            let mut packet = rtp(id, "10.0.0.1:4000", 0x1234, id as u16, timestamp);
+            assert_eq!(classifier.classify(&mut packet), None);
+        }
+    }
+
+    #[test]
+    fn test_ignores_other_protocols() {
+        let mut classifier = RtpClassifier::default();
+
+        let mut packet = rtp(0, "10.0.0.1:4000", 0x1234, 1, 0);
+        packet.transport_protocol = TransportProtocol::Tcp;
+        assert_eq!(classifier.classify(&mut packet), None);
+        assert_eq!(packet.session_protocol, SessionProtocol::Rtp);
+
+        let mut packet = rtp(0, "10.0.0.1:4000", 0x1234, 1, 0);
+        packet.parse_as(SessionProtocol::Unknown);
+        assert_eq!(classifier.classify(&mut packet), None);
+        assert!(classifier.flows.is_empty());
+    }
+}
@@ -0,0 +1,417 @@
+use super::{Packet, SessionProtocol, TransportProtocol};
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// User rules forcing the session protocol of matching packets,
+/// checked in order before the heuristics of [`Packet::guess_payload`].
+#[derive(Debug, Clone, Default)]
+pub struct DecodeRules {
+    rules: Vec<DecodeRule>,
+}
+
+impl DecodeRules {
+    pub fn new(rules: Vec<DecodeRule>) -> Self {
+        Self { rules }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Protocol of the first rule matching the packet.
+    pub fn find(&self, packet: &Packet) -> Option<SessionProtocol> {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(packet))
+            .map(|rule| rule.protocol)
+    }
+}
+
+/// Conditions joined with `&&` and the protocol, e.g. `udp.port==5004:rtp`,
+/// `dst=239.1.1.0/24:mpegts` or `udp.port==1900:unknown`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodeRule {
+    conditions: Vec<Condition>,
+    protocol: SessionProtocol,
+}
+
+impl DecodeRule {
+    pub fn protocol(&self) -> SessionProtocol {
+        self.protocol
+    }
+
+    pub fn matches(&self, packet: &Packet) -> bool {
+        self.conditions
+            .iter()
+            .all(|condition| condition.matches(packet))
+    }
+}
+
+impl FromStr for DecodeRule {
+    type Err = String;
+
+    fn from_str(rule: &str) -> Result<Self, Self::Err> {
+        // split at the last colon, IPv6 addresses contain them as well
+        let (conditions, protocol) = rule.rsplit_once(':').ok_or_else(|| {
+            format!(
+                "missing protocol in \"{}\", expected <match>:<protocol>",
+                rule
+            )
+        })?;
+
+        let protocol = match protocol.trim().to_lowercase().as_str() {
+            "mpegts" => SessionProtocol::Mpegts,
+            "meta" => return Err("can't decode packets as META".to_string()),
+            protocol => SessionProtocol::from_str(protocol)
+                .map_err(|_| format!("unknown protocol \"{}\"", protocol))?,
+        };
+
+        let conditions = conditions
+            .split("&&")
+            .map(str::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            conditions,
+            protocol,
+        })
+    }
+}
+
+impl fmt::Display for DecodeRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, condition) in self.conditions.iter().enumerate() {
+            if i > 0 {
+                write!(f, " && ")?;
+            }
+            write!(f, "{}", condition)?;
+        }
+        write!(f, ":{}", self.protocol)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Side {
+    Source,
+    Destination,
+    Either,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Condition {
+    Port {
+        transport: Option<TransportProtocol>,
+        side: Side,
+        first: u16,
+        last: u16,
+    },
+    Addr {
+        side: Side,
+        network: IpAddr,
+        prefix_len: u8,
+    },
+}
+
+impl Condition {
+    fn matches(&self, packet: &Packet) -> bool {
+        let source = packet.source_addr;
+        let destination = packet.destination_addr;
+        match *self {
+            Self::Port {
+                transport,
+                side,
+                first,
+                last,
+            } => {
+                if transport.is_some_and(|transport| transport != packet.transport_protocol) {
+                    return false;
+                }
+                let range = first..=last;
+                side.matches(
+                    range.contains(&source.port()),
+                    range.contains(&destination.port()),
+                )
+            }
+            Self::Addr {
+                side,
+                network,
+                prefix_len,
+            } => side.matches(
+                in_network(source.ip(), network, prefix_len),
+                in_network(destination.ip(), network, prefix_len),
+            ),
+        }
+    }
+}
+
+impl Side {
+    fn matches(self, source: bool, destination: bool) -> bool {
+        match self {
+            Self::Source => source,
+            Self::Destination => destination,
+            Self::Either => source || destination,
+        }
+    }
+}
+
+impl FromStr for Condition {
+    type Err = String;
+
+    fn from_str(condition: &str) -> Result<Self, Self::Err> {
+        let condition = condition.trim();
+        let (field, value) = condition
+            .split_once("==")
+            .or_else(|| condition.split_once('='))
+            .ok_or_else(|| {
+                format!(
+                    "invalid condition \"{}\", expected <field>==<value>",
+                    condition
+                )
+            })?;
+        let (field, value) = (field.trim().to_lowercase(), value.trim());
+
+        let (transport, field) = match field.split_once('.') {
+            Some(("udp", field)) => (Some(TransportProtocol::Udp), field),
+            Some(("tcp", field)) => (Some(TransportProtocol::Tcp), field),
+            Some(("ip" | "ipv6", field)) => (None, field),
+            Some(_) => return Err(format!("unknown field \"{}\"", field)),
+            None => (None, field.as_str()),
+        };
+
+        let side = match field {
+            "port" | "addr" | "host" => Side::Either,
+            "srcport" | "src" => Side::Source,
+            "dstport" | "dst" => Side::Destination,
+            _ => return Err(format!("unknown field \"{}\"", field)),
+        };
+
+        if field.ends_with("port") {
+            let (first, last) = value.split_once('-').unwrap_or((value, value));
+            let port = |port: &str| {
+                port.trim()
+                    .parse::<u16>()
+                    .map_err(|_| format!("invalid port \"{}\"", port))
+            };
+            let (first, last) = (port(first)?, port(last)?);
+            if first > last {
+                return Err(format!("invalid port range \"{}\"", value));
+            }
+
+            return Ok(Self::Port {
+                transport,
+                side,
+                first,
+                last,
+            });
+        }
+
+        if transport.is_some() {
+            return Err(format!("unknown field \"{}\"", field));
+        }
+
+        let (network, prefix_len) = match value.split_once('/') {
+            Some((network, prefix_len)) => (network, Some(prefix_len)),
+            None => (value, None),
+        };
+        let network: IpAddr = network
+            .parse()
+            .map_err(|_| format!("invalid address \"{}\"", network))?;
+        let max_len = if network.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match prefix_len {
+            Some(len) => len
+                .parse::<u8>()
+                .ok()
+                .filter(|len| *len <= max_len)
+                .ok_or_else(|| format!("invalid prefix length \"{}\"", len))?,
+            None => max_len,
+        };
+
+        Ok(Self::Addr {
+            side,
+            network,
+            prefix_len,
+        })
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Port {
+                transport,
+                side,
+                first,
+                last,
+            } => {
+                if let Some(transport) = transport {
+                    write!(f, "{}.", transport.to_string().to_lowercase())?;
+                }
+                let field = match side {
+                    Side::Source => "srcport",
+                    Side::Destination => "dstport",
+                    Side::Either => "port",
+                };
+                write!(f, "{}=={}", field, first)?;
+                if first != last {
+                    write!(f, "-{}", last)?;
+                }
+                Ok(())
+            }
+            Self::Addr {
+                side,
+                network,
+                prefix_len,
+            } => {
+                let field = match side {
+                    Side::Source => "src",
+                    Side::Destination => "dst",
+                    Side::Either => "addr",
+                };
+                write!(f, "{}=={}/{}", field, network, prefix_len)
+            }
+        }
+    }
+}
+
+fn in_network(addr: IpAddr, network: IpAddr, prefix_len: u8) -> bool {
+    match (addr, network) {
+        (IpAddr::V4(addr), IpAddr::V4(network)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix_len as u32).unwrap_or(0);
+            addr.to_bits() & mask == network.to_bits() & mask
+        }
+        (IpAddr::V6(addr), IpAddr::V6(network)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix_len as u32).unwrap_or(0);
+            addr.to_bits() & mask == network.to_bits() & mask
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::{PacketMetadata, SessionPacket};
+    use std::time::{Duration, SystemTime};
+
+    fn packet(transport_protocol: TransportProtocol, source: &str, destination: &str) -> Packet {
+        Packet {
+            payload: Some(vec![0x80, 0x60, 0x00, 0x01]),
+            id: 0,
+            timestamp: Duration::from_secs(1),
+            length: 4,
+            source_addr: source.parse().unwrap(),
+            destination_addr: destination.parse().unwrap(),
+            transport_protocol,
+            session_protocol: SessionProtocol::Unknown,
+            contents: SessionPacket::Unknown,
+            creation_time: SystemTime::now(),
+            metadata: PacketMetadata::default(),
+        }
+    }
+
+    fn parse(rule: &str) -> DecodeRule {
+        rule.parse().unwrap()
+    }
+
+    #[test]
+    fn test_port_rule() {
+        let rule = parse("udp.port==5004:rtp");
+        assert_eq!(rule.protocol(), SessionProtocol::Rtp);
+
+        let udp = packet(TransportProtocol::Udp, "10.0.0.1:40000", "10.0.0.2:5004");
+        let reversed = packet(TransportProtocol::Udp, "10.0.0.2:5004", "10.0.0.1:40000");
+        let tcp = packet(TransportProtocol::Tcp, "10.0.0.1:40000", "10.0.0.2:5004");
+        assert!(rule.matches(&udp));
+        assert!(rule.matches(&reversed));
+        assert!(!rule.matches(&tcp));
+
+        let rule = parse("dstport=5000-5010:rtcp");
+        assert!(rule.matches(&udp));
+        assert!(rule.matches(&tcp));
+        assert!(!rule.matches(&reversed));
+    }
+
+    #[test]
+    fn test_network_rule() {
+        let rule = parse("dst=239.1.1.0/24:mpegts");
+        assert_eq!(rule.protocol(), SessionProtocol::Mpegts);
+        assert!(rule.matches(&packet(
+            TransportProtocol::Udp,
+            "10.0.0.1:1234",
+            "239.1.1.7:1234"
+        )));
+        assert!(!rule.matches(&packet(
+            TransportProtocol::Udp,
+            "239.1.1.7:1234",
+            "239.1.2.7:1234"
+        )));
+
+        let rule = parse("src==ff3e::/16 && udp.dstport==5004:rtp");
+        assert!(rule.matches(&packet(
+            TransportProtocol::Udp,
+            "[ff3e::1]:1234",
+            "[ff3e::2]:5004"
+        )));
+        assert!(!rule.matches(&packet(
+            TransportProtocol::Udp,
+            "[ff3e::1]:1234",
+            "[ff3e::2]:5005"
+        )));
+        assert!(!rule.matches(&packet(
+            TransportProtocol::Udp,
+            "10.0.0.1:1234",
+            "10.0.0.2:5004"
+        )));
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        for rule in [
+            "udp.port==5004",
+            "udp.port==5004:quic",
+            "udp.port==5004:meta",
+            "udp.port==70000:rtp",
+            "udp.port==5010-5004:rtp",
+            "udp.dst==10.0.0.1:rtp",
+            "sctp.port==5004:rtp",
+            "dst=10.0.0.0/33:rtp",
+            "dst=example:rtp",
+            "port:rtp",
+        ] {
+            assert!(rule.parse::<DecodeRule>().is_err(), "{}", rule);
+        }
+    }
+
+    #[test]
+    fn test_rules_forcing_protocols() {
+        let rules = DecodeRules::new(vec![
+            parse("udp.port==1900:unknown"),
+            parse("udp.port==5004:rtcp"),
+        ]);
+
+        let mut forced = packet(TransportProtocol::Udp, "10.0.0.1:40000", "10.0.0.2:1900");
+        forced.guess_payload(&rules);
+        assert_eq!(forced.session_protocol, SessionProtocol::Unknown);
+
+        let mut guessed = packet(TransportProtocol::Udp, "10.0.0.1:40000", "10.0.0.2:6000");
+        guessed.payload = Some(vec![0x80, 0x60, 0x00, 0x01, 0, 0, 0, 0, 0, 0, 0, 1]);
+        guessed.guess_payload(&rules);
+        assert_eq!(guessed.session_protocol, SessionProtocol::Rtp);
+
+        // RTP not passing as RTCP is left undecoded
+        let mut rtcp = guessed.clone();
+        rtcp.session_protocol = SessionProtocol::Unknown;
+        rtcp.destination_addr = "10.0.0.2:5004".parse().unwrap();
+        rtcp.guess_payload(&rules);
+        assert_eq!(rtcp.session_protocol, SessionProtocol::Unknown);
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            parse("UDP.PORT == 5004-5006 && dst=239.1.1.0/24 : mpegts").to_string(),
+            "udp.port==5004-5006 && dst==239.1.1.0/24:MPEG-TS"
+        );
+    }
+}
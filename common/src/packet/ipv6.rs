@@ -0,0 +1,132 @@
+use pnet_packet::ip::{IpNextHeaderProtocol, IpNextHeaderProtocols};
+
+const FRAGMENT_HEADER_LEN: usize = 8;
+const FRAGMENT_OFFSET_MASK: u16 = 0xFFF8;
+const FRAGMENT_MORE_FRAGMENTS: u16 = 0x0001;
+
+/// Upper layer payload found at the end of the IPv6 extension header chain.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Ipv6Payload<'a> {
+    Transport(IpNextHeaderProtocol, &'a [u8]),
+    Fragment(Ipv6Fragment<'a>),
+}
+
+/// Fragment header together with the fragmentable part that follows it,
+/// `next_header` describes the first header of the fragmentable part.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Ipv6Fragment<'a> {
+    pub next_header: IpNextHeaderProtocol,
+    pub offset: usize,
+    pub more_fragments: bool,
+    pub id: u32,
+    pub payload: &'a [u8],
+}
+
+/// Follows hop-by-hop, routing, destination options and authentication headers,
+/// stops at the fragment header or at the first non-extension header.
+pub(crate) fn skip_extension_headers(
+    mut next_header: IpNextHeaderProtocol,
+    mut data: &[u8],
+) -> Option<Ipv6Payload<'_>> {
+    loop {
+        let header_len = match next_header {
+            IpNextHeaderProtocols::Hopopt
+            | IpNextHeaderProtocols::Ipv6Route
+            | IpNextHeaderProtocols::Ipv6Opts => (*data.get(1)? as usize + 1) * 8,
+            IpNextHeaderProtocols::Ah => (*data.get(1)? as usize + 2) * 4,
+            IpNextHeaderProtocols::Ipv6Frag => {
+                return parse_fragment(data).map(Ipv6Payload::Fragment);
+            }
+            _ => return Some(Ipv6Payload::Transport(next_header, data)),
+        };
+
+        if data.len() < header_len {
+            return None;
+        }
+
+        next_header = IpNextHeaderProtocol(data[0]);
+        data = &data[header_len..];
+    }
+}
+
+fn parse_fragment(data: &[u8]) -> Option<Ipv6Fragment<'_>> {
+    if data.len() < FRAGMENT_HEADER_LEN {
+        return None;
+    }
+
+    let offset_with_flags = u16::from_be_bytes([data[2], data[3]]);
+
+    Some(Ipv6Fragment {
+        next_header: IpNextHeaderProtocol(data[0]),
+        offset: (offset_with_flags & FRAGMENT_OFFSET_MASK) as usize,
+        more_fragments: offset_with_flags & FRAGMENT_MORE_FRAGMENTS != 0,
+        id: u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+        payload: &data[FRAGMENT_HEADER_LEN..],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UDP_HEADER: [u8; 8] = [0x13, 0x88, 0x13, 0x89, 0x00, 0x08, 0x00, 0x00];
+
+    #[test]
+    fn test_skip_hop_by_hop_router_alert() {
+        // next header UDP, router alert option + PadN
+        let mut data = vec![17, 0, 0x05, 0x02, 0x00, 0x00, 0x01, 0x00];
+        data.extend_from_slice(&UDP_HEADER);
+
+        let payload = skip_extension_headers(IpNextHeaderProtocols::Hopopt, &data).unwrap();
+        assert_eq!(
+            payload,
+            Ipv6Payload::Transport(IpNextHeaderProtocols::Udp, &UDP_HEADER)
+        );
+    }
+
+    #[test]
+    fn test_skip_header_chain() {
+        // hop-by-hop -> routing (16 bytes) -> destination options -> TCP
+        let mut data = vec![43, 0, 0, 0, 0, 0, 0, 0];
+        data.extend_from_slice(&[60, 1, 0, 0, 0, 0, 0, 0]);
+        data.extend_from_slice(&[0; 8]);
+        data.extend_from_slice(&[6, 0, 0, 0, 0, 0, 0, 0]);
+        data.extend_from_slice(&UDP_HEADER);
+
+        let payload = skip_extension_headers(IpNextHeaderProtocols::Hopopt, &data).unwrap();
+        assert_eq!(
+            payload,
+            Ipv6Payload::Transport(IpNextHeaderProtocols::Tcp, &UDP_HEADER)
+        );
+    }
+
+    #[test]
+    fn test_fragment_header() {
+        // destination options -> fragment, offset 1448, more fragments
+        let mut data = vec![44, 0, 0, 0, 0, 0, 0, 0];
+        data.extend_from_slice(&[17, 0, 0x05, 0xa9, 0x12, 0x34, 0x56, 0x78]);
+        data.extend_from_slice(&UDP_HEADER);
+
+        let payload = skip_extension_headers(IpNextHeaderProtocols::Ipv6Opts, &data).unwrap();
+        assert_eq!(
+            payload,
+            Ipv6Payload::Fragment(Ipv6Fragment {
+                next_header: IpNextHeaderProtocols::Udp,
+                offset: 1448,
+                more_fragments: true,
+                id: 0x12345678,
+                payload: &UDP_HEADER,
+            })
+        );
+    }
+
+    #[test]
+    fn test_truncated_extension_header() {
+        let data = [17, 1, 0, 0, 0, 0, 0, 0];
+
+        assert_eq!(
+            skip_extension_headers(IpNextHeaderProtocols::Ipv6Route, &data),
+            None
+        );
+    }
+}
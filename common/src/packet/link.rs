@@ -0,0 +1,302 @@
+use super::Tunnel;
+use pcap::Linktype;
+use pnet_packet::{
+    ethernet::{EtherType, EtherTypes, EthernetPacket},
+    sll::SLLPacket,
+    sll2::SLL2Packet,
+};
+
+const SLL_HEADER_LEN: usize = 16;
+const SLL2_HEADER_LEN: usize = 20;
+const ETHERNET_HEADER_LEN: usize = 14;
+const LOOPBACK_HEADER_LEN: usize = 4;
+const VLAN_TAG_LEN: usize = 4;
+const MPLS_ENTRY_LEN: usize = 4;
+const PW_CONTROL_WORD_LEN: usize = 4;
+
+const VLAN_ID_MASK: u16 = 0x0FFF;
+const MPLS_BOTTOM_OF_STACK: u32 = 0x0100;
+
+// address families used in DLT_NULL/DLT_LOOP headers,
+// AF_INET6 value differs between the operating systems
+const AF_INET: u32 = 2;
+const AF_INET6_LINUX: u32 = 10;
+const AF_INET6_BSD: u32 = 24;
+const AF_INET6_FREEBSD: u32 = 28;
+const AF_INET6_DARWIN: u32 = 30;
+
+/// Network layer payload extracted from a link layer frame,
+/// with VLAN tags and MPLS labels stripped.
+/// `tunnels` is filled when the frame was decapsulated from another packet.
+#[derive(Debug)]
+pub(crate) struct LinkFrame<'a> {
+    pub ethertype: EtherType,
+    pub header_len: usize,
+    pub payload: &'a [u8],
+    pub vlan_ids: Vec<u16>,
+    pub mpls_labels: Vec<u32>,
+    pub tunnels: Vec<Tunnel>,
+}
+
+impl<'a> LinkFrame<'a> {
+    fn new(ethertype: EtherType, header_len: usize, payload: &'a [u8]) -> Self {
+        Self {
+            ethertype,
+            header_len,
+            payload,
+            vlan_ids: Vec::new(),
+            mpls_labels: Vec::new(),
+            tunnels: Vec::new(),
+        }
+    }
+
+    pub fn parse(link_type: Linktype, data: &'a [u8]) -> Option<Self> {
+        match link_type {
+            Linktype::ETHERNET => Self::parse_ethernet(data),
+            Linktype::LINUX_SLL => Self::parse_sll(data),
+            Linktype::LINUX_SLL2 => Self::parse_sll2(data),
+            Linktype::RAW | Linktype::IPV4 | Linktype::IPV6 => Self::parse_raw_ip(data),
+            Linktype::NULL => Self::parse_loopback(data, false),
+            Linktype::LOOP => Self::parse_loopback(data, true),
+            _ => None,
+        }
+    }
+
+    fn parse_ethernet(data: &'a [u8]) -> Option<Self> {
+        let ethernet_packet = EthernetPacket::new(data)?;
+        let ethertype = ethernet_packet.get_ethertype();
+
+        Self::new(ethertype, ETHERNET_HEADER_LEN, &data[ETHERNET_HEADER_LEN..]).strip_tags()
+    }
+
+    fn parse_sll(data: &'a [u8]) -> Option<Self> {
+        let sll_packet = SLLPacket::new(data)?;
+        let ethertype = sll_packet.get_protocol();
+
+        Self::new(ethertype, SLL_HEADER_LEN, &data[SLL_HEADER_LEN..]).strip_tags()
+    }
+
+    fn parse_sll2(data: &'a [u8]) -> Option<Self> {
+        let sll2_packet = SLL2Packet::new(data)?;
+        let ethertype = sll2_packet.get_protocol_type();
+
+        Self::new(ethertype, SLL2_HEADER_LEN, &data[SLL2_HEADER_LEN..]).strip_tags()
+    }
+
+    fn parse_raw_ip(data: &'a [u8]) -> Option<Self> {
+        let ethertype = match data.first()? >> 4 {
+            4 => EtherTypes::Ipv4,
+            6 => EtherTypes::Ipv6,
+            _ => return None,
+        };
+
+        Some(Self::new(ethertype, 0, data))
+    }
+
+    fn parse_loopback(data: &'a [u8], network_order: bool) -> Option<Self> {
+        let header: [u8; LOOPBACK_HEADER_LEN] = data.get(..LOOPBACK_HEADER_LEN)?.try_into().ok()?;
+
+        // DLT_NULL family is in the byte order of the capturing host,
+        // family values are small, so the order can be guessed
+        let family = match (network_order, u32::from_le_bytes(header)) {
+            (false, family) if family <= 0xFFFF => family,
+            _ => u32::from_be_bytes(header),
+        };
+
+        let ethertype = match family {
+            AF_INET => EtherTypes::Ipv4,
+            AF_INET6_LINUX | AF_INET6_BSD | AF_INET6_FREEBSD | AF_INET6_DARWIN => EtherTypes::Ipv6,
+            _ => return None,
+        };
+
+        Some(Self::new(
+            ethertype,
+            LOOPBACK_HEADER_LEN,
+            &data[LOOPBACK_HEADER_LEN..],
+        ))
+    }
+
+    // walks single and stacked 802.1Q/802.1ad tags and MPLS label stacks
+    fn strip_tags(mut self) -> Option<Self> {
+        loop {
+            match self.ethertype {
+                EtherTypes::Vlan | EtherTypes::PBridge | EtherTypes::QinQ => {
+                    let tag = self.payload.get(..VLAN_TAG_LEN)?;
+                    let tci = u16::from_be_bytes([tag[0], tag[1]]);
+                    let inner_ethertype = u16::from_be_bytes([tag[2], tag[3]]);
+
+                    self.vlan_ids.push(tci & VLAN_ID_MASK);
+                    self.ethertype = EtherType(inner_ethertype);
+                    self.advance(VLAN_TAG_LEN);
+                }
+                EtherTypes::Mpls | EtherTypes::MplsMcast => return self.strip_mpls_labels(),
+                _ => return Some(self),
+            }
+        }
+    }
+
+    fn strip_mpls_labels(mut self) -> Option<Self> {
+        loop {
+            let entry = self.payload.get(..MPLS_ENTRY_LEN)?;
+            let entry = u32::from_be_bytes(entry.try_into().ok()?);
+
+            self.mpls_labels.push(entry >> 12);
+            self.advance(MPLS_ENTRY_LEN);
+
+            if entry & MPLS_BOTTOM_OF_STACK != 0 {
+                break;
+            }
+        }
+
+        // MPLS does not carry the payload type, so it has to be guessed
+        // from the first nibble, just like Wireshark does
+        match self.payload.first()? >> 4 {
+            4 => self.ethertype = EtherTypes::Ipv4,
+            6 => self.ethertype = EtherTypes::Ipv6,
+            0 => {
+                // pseudowire control word followed by an Ethernet frame
+                self.advance(PW_CONTROL_WORD_LEN);
+                let ethernet_packet = EthernetPacket::new(self.payload)?;
+                self.ethertype = ethernet_packet.get_ethertype();
+                self.advance(ETHERNET_HEADER_LEN);
+
+                return self.strip_tags();
+            }
+            _ => return None,
+        }
+
+        Some(self)
+    }
+
+    fn advance(&mut self, len: usize) {
+        let len = len.min(self.payload.len());
+        self.payload = &self.payload[len..];
+        self.header_len += len;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IPV4_HEADER_START: [u8; 4] = [0x45, 0x00, 0x00, 0x1c];
+    const IPV6_HEADER_START: [u8; 4] = [0x60, 0x00, 0x00, 0x00];
+
+    #[test]
+    fn test_parse_ethernet() {
+        let mut data = vec![0u8; 12];
+        data.extend_from_slice(&[0x08, 0x00]);
+        data.extend_from_slice(&IPV4_HEADER_START);
+
+        let frame = LinkFrame::parse(Linktype::ETHERNET, &data).unwrap();
+        assert_eq!(frame.ethertype, EtherTypes::Ipv4);
+        assert_eq!(frame.header_len, 14);
+        assert_eq!(frame.payload, &IPV4_HEADER_START);
+    }
+
+    #[test]
+    fn test_parse_sll() {
+        let mut data = vec![0u8; 14];
+        data.extend_from_slice(&[0x86, 0xdd]);
+        data.extend_from_slice(&IPV6_HEADER_START);
+
+        let frame = LinkFrame::parse(Linktype::LINUX_SLL, &data).unwrap();
+        assert_eq!(frame.ethertype, EtherTypes::Ipv6);
+        assert_eq!(frame.header_len, 16);
+        assert_eq!(frame.payload, &IPV6_HEADER_START);
+    }
+
+    #[test]
+    fn test_parse_sll2() {
+        let mut data = vec![0x08, 0x00];
+        data.extend_from_slice(&[0u8; 18]);
+        data.extend_from_slice(&IPV4_HEADER_START);
+
+        let frame = LinkFrame::parse(Linktype::LINUX_SLL2, &data).unwrap();
+        assert_eq!(frame.ethertype, EtherTypes::Ipv4);
+        assert_eq!(frame.header_len, 20);
+        assert_eq!(frame.payload, &IPV4_HEADER_START);
+    }
+
+    #[test]
+    fn test_parse_raw_ip() {
+        let frame = LinkFrame::parse(Linktype::RAW, &IPV6_HEADER_START).unwrap();
+        assert_eq!(frame.ethertype, EtherTypes::Ipv6);
+        assert_eq!(frame.header_len, 0);
+
+        let frame = LinkFrame::parse(Linktype::IPV4, &IPV4_HEADER_START).unwrap();
+        assert_eq!(frame.ethertype, EtherTypes::Ipv4);
+
+        assert!(LinkFrame::parse(Linktype::RAW, &[0x10, 0x00]).is_none());
+    }
+
+    #[test]
+    fn test_parse_loopback() {
+        let mut little_endian = vec![30, 0, 0, 0];
+        little_endian.extend_from_slice(&IPV6_HEADER_START);
+        let frame = LinkFrame::parse(Linktype::NULL, &little_endian).unwrap();
+        assert_eq!(frame.ethertype, EtherTypes::Ipv6);
+        assert_eq!(frame.header_len, 4);
+        assert_eq!(frame.payload, &IPV6_HEADER_START);
+
+        let mut big_endian = vec![0, 0, 0, 2];
+        big_endian.extend_from_slice(&IPV4_HEADER_START);
+        let frame = LinkFrame::parse(Linktype::NULL, &big_endian).unwrap();
+        assert_eq!(frame.ethertype, EtherTypes::Ipv4);
+
+        let frame = LinkFrame::parse(Linktype::LOOP, &big_endian).unwrap();
+        assert_eq!(frame.ethertype, EtherTypes::Ipv4);
+
+        assert!(LinkFrame::parse(Linktype::NULL, &[7, 0, 0, 0, 0x45]).is_none());
+    }
+
+    #[test]
+    fn test_parse_stacked_vlan() {
+        let mut data = vec![0u8; 12];
+        data.extend_from_slice(&[0x88, 0xa8, 0x00, 0x64]); // S-tag, VLAN 100
+        data.extend_from_slice(&[0x81, 0x00, 0x20, 0xc8]); // C-tag, PCP 1, VLAN 200
+        data.extend_from_slice(&[0x08, 0x00]);
+        data.extend_from_slice(&IPV4_HEADER_START);
+
+        let frame = LinkFrame::parse(Linktype::ETHERNET, &data).unwrap();
+        assert_eq!(frame.ethertype, EtherTypes::Ipv4);
+        assert_eq!(frame.header_len, 22);
+        assert_eq!(frame.vlan_ids, vec![100, 200]);
+        assert!(frame.mpls_labels.is_empty());
+        assert_eq!(frame.payload, &IPV4_HEADER_START);
+    }
+
+    #[test]
+    fn test_parse_mpls() {
+        let mut data = vec![0u8; 12];
+        data.extend_from_slice(&[0x88, 0x47]);
+        data.extend_from_slice(&[0x00, 0x01, 0x00, 0x40]); // label 16
+        data.extend_from_slice(&[0x00, 0x01, 0x11, 0x40]); // label 17, bottom of stack
+        data.extend_from_slice(&IPV6_HEADER_START);
+
+        let frame = LinkFrame::parse(Linktype::ETHERNET, &data).unwrap();
+        assert_eq!(frame.ethertype, EtherTypes::Ipv6);
+        assert_eq!(frame.header_len, 22);
+        assert_eq!(frame.mpls_labels, vec![16, 17]);
+        assert_eq!(frame.payload, &IPV6_HEADER_START);
+    }
+
+    #[test]
+    fn test_parse_mpls_pseudowire() {
+        let mut data = vec![0u8; 12];
+        data.extend_from_slice(&[0x81, 0x00, 0x00, 0x0a]); // VLAN 10
+        data.extend_from_slice(&[0x88, 0x47]);
+        data.extend_from_slice(&[0x00, 0x3e, 0x81, 0x40]); // label 1000, bottom of stack
+        data.extend_from_slice(&[0x00, 0x00, 0x00, 0x00]); // control word
+        data.extend_from_slice(&[0u8; 12]);
+        data.extend_from_slice(&[0x81, 0x00, 0x00, 0x14]); // VLAN 20
+        data.extend_from_slice(&[0x08, 0x00]);
+        data.extend_from_slice(&IPV4_HEADER_START);
+
+        let frame = LinkFrame::parse(Linktype::ETHERNET, &data).unwrap();
+        assert_eq!(frame.ethertype, EtherTypes::Ipv4);
+        assert_eq!(frame.vlan_ids, vec![10, 20]);
+        assert_eq!(frame.mpls_labels, vec![1000]);
+        assert_eq!(frame.payload, &IPV4_HEADER_START);
+    }
+}
@@ -0,0 +1,310 @@
+use std::collections::{BTreeMap, HashMap};
+use std::net::IpAddr;
+use std::time::Duration;
+
+// same defaults as the Linux kernel (ipfrag_time, ipfrag_high_thresh)
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_MEMORY_LIMIT: usize = 4 * 1024 * 1024;
+
+const MAX_DATAGRAM_LEN: usize = 65535;
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub(crate) struct FragmentKey {
+    pub source_addr: IpAddr,
+    pub destination_addr: IpAddr,
+    pub protocol: u8,
+    pub id: u32,
+}
+
+/// Single IPv4 fragment or IPv6 fragmentable part,
+/// offset is expressed in bytes.
+#[derive(Debug)]
+pub(crate) struct Fragment<'a> {
+    pub key: FragmentKey,
+    pub offset: usize,
+    pub more_fragments: bool,
+    pub payload: &'a [u8],
+}
+
+impl Fragment<'_> {
+    pub fn is_fragmented(&self) -> bool {
+        self.more_fragments || self.offset != 0
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ReassemblyStats {
+    pub fragments: usize,
+    pub reassembled: usize,
+    pub failures: usize,
+}
+
+#[derive(Debug)]
+struct FragmentBuffer {
+    first_seen: Duration,
+    pieces: BTreeMap<usize, Vec<u8>>,
+    total_len: Option<usize>,
+    bytes: usize,
+}
+
+impl FragmentBuffer {
+    fn new(first_seen: Duration) -> Self {
+        Self {
+            first_seen,
+            pieces: BTreeMap::new(),
+            total_len: None,
+            bytes: 0,
+        }
+    }
+
+    fn assemble(&self) -> Option<Vec<u8>> {
+        let total_len = self.total_len?;
+        let mut datagram = vec![0; total_len];
+        let mut covered = 0;
+
+        for (&offset, piece) in self.pieces.iter() {
+            if offset > covered {
+                return None;
+            }
+            // pieces are never copied past the length set by the last fragment
+            let end = (offset + piece.len()).min(total_len);
+            if offset < end {
+                datagram[offset..end].copy_from_slice(&piece[..end - offset]);
+            }
+            covered = covered.max(end);
+        }
+
+        (covered == total_len).then_some(datagram)
+    }
+}
+
+/// Buffers IP fragments of a single capture source
+/// until the whole datagram is received.
+///
+/// Incomplete datagrams are dropped after `timeout` (measured with capture timestamps)
+/// or, oldest first, when the buffered fragments exceed `memory_limit` bytes.
+#[derive(Debug)]
+pub struct IpReassembler {
+    buffers: HashMap<FragmentKey, FragmentBuffer>,
+    buffered_bytes: usize,
+    timeout: Duration,
+    memory_limit: usize,
+    stats: ReassemblyStats,
+}
+
+impl Default for IpReassembler {
+    fn default() -> Self {
+        Self::new(DEFAULT_TIMEOUT, DEFAULT_MEMORY_LIMIT)
+    }
+}
+
+impl IpReassembler {
+    pub fn new(timeout: Duration, memory_limit: usize) -> Self {
+        Self {
+            buffers: HashMap::new(),
+            buffered_bytes: 0,
+            timeout,
+            memory_limit,
+            stats: ReassemblyStats::default(),
+        }
+    }
+
+    pub fn stats(&self) -> ReassemblyStats {
+        self.stats
+    }
+
+    /// Returns the reassembled payload once the last missing fragment arrives.
+    pub(crate) fn push(&mut self, fragment: Fragment, timestamp: Duration) -> Option<Vec<u8>> {
+        self.stats.fragments += 1;
+        self.expire(timestamp);
+
+        let end = fragment.offset + fragment.payload.len();
+        if end > MAX_DATAGRAM_LEN || fragment.payload.len() > self.memory_limit {
+            self.stats.failures += 1;
+            return None;
+        }
+
+        let buffer = self
+            .buffers
+            .entry(fragment.key)
+            .or_insert_with(|| FragmentBuffer::new(timestamp));
+
+        let inconsistent = match buffer.total_len {
+            Some(total_len) => end > total_len || (!fragment.more_fragments && end != total_len),
+            // the last fragment must not cut any of the buffered pieces
+            None => {
+                !fragment.more_fragments
+                    && buffer
+                        .pieces
+                        .iter()
+                        .any(|(&offset, piece)| offset >= end || offset + piece.len() > end)
+            }
+        };
+        if inconsistent {
+            self.drop_buffer(&fragment.key);
+            self.stats.failures += 1;
+            return None;
+        }
+
+        if !fragment.more_fragments {
+            buffer.total_len = Some(end);
+        }
+
+        if !buffer.pieces.contains_key(&fragment.offset) {
+            buffer.bytes += fragment.payload.len();
+            self.buffered_bytes += fragment.payload.len();
+            buffer
+                .pieces
+                .insert(fragment.offset, fragment.payload.to_vec());
+        }
+
+        if let Some(datagram) = buffer.assemble() {
+            self.drop_buffer(&fragment.key);
+            self.stats.reassembled += 1;
+            return Some(datagram);
+        }
+
+        self.enforce_memory_limit();
+        None
+    }
+
+    fn expire(&mut self, now: Duration) {
+        let timeout = self.timeout;
+        let expired: Vec<_> = self
+            .buffers
+            .iter()
+            .filter(|(_, buffer)| now.saturating_sub(buffer.first_seen) > timeout)
+            .map(|(key, _)| *key)
+            .collect();
+
+        for key in expired {
+            self.drop_buffer(&key);
+            self.stats.failures += 1;
+        }
+    }
+
+    fn enforce_memory_limit(&mut self) {
+        while self.buffered_bytes > self.memory_limit {
+            let Some(oldest) = self
+                .buffers
+                .iter()
+                .min_by_key(|(_, buffer)| buffer.first_seen)
+                .map(|(key, _)| *key)
+            else {
+                break;
+            };
+
+            self.drop_buffer(&oldest);
+            self.stats.failures += 1;
+        }
+    }
+
+    fn drop_buffer(&mut self, key: &FragmentKey) {
+        if let Some(buffer) = self.buffers.remove(key) {
+            self.buffered_bytes -= buffer.bytes;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(id: u32) -> FragmentKey {
+        FragmentKey {
+            source_addr: "10.0.0.1".parse().unwrap(),
+            destination_addr: "10.0.0.2".parse().unwrap(),
+            protocol: 17,
+            id,
+        }
+    }
+
+    fn fragment(id: u32, offset: usize, more_fragments: bool, payload: &[u8]) -> Fragment<'_> {
+        Fragment {
+            key: key(id),
+            offset,
+            more_fragments,
+            payload,
+        }
+    }
+
+    #[test]
+    fn test_reassemble_out_of_order() {
+        let mut reassembler = IpReassembler::default();
+        let ts = Duration::from_secs(1);
+
+        assert_eq!(reassembler.push(fragment(1, 16, false, &[3; 4]), ts), None);
+        assert_eq!(reassembler.push(fragment(1, 0, true, &[1; 8]), ts), None);
+        let datagram = reassembler.push(fragment(1, 8, true, &[2; 8]), ts).unwrap();
+
+        assert_eq!(datagram.len(), 20);
+        assert_eq!(&datagram[..8], &[1; 8]);
+        assert_eq!(&datagram[8..16], &[2; 8]);
+        assert_eq!(&datagram[16..], &[3; 4]);
+        assert_eq!(
+            reassembler.stats(),
+            ReassemblyStats {
+                fragments: 3,
+                reassembled: 1,
+                failures: 0,
+            }
+        );
+        assert_eq!(reassembler.buffered_bytes, 0);
+    }
+
+    #[test]
+    fn test_reassembly_timeout() {
+        let mut reassembler = IpReassembler::new(Duration::from_secs(5), DEFAULT_MEMORY_LIMIT);
+
+        reassembler.push(fragment(1, 0, true, &[1; 8]), Duration::from_secs(1));
+        let result = reassembler.push(fragment(1, 8, false, &[2; 8]), Duration::from_secs(7));
+
+        assert_eq!(result, None);
+        assert_eq!(reassembler.stats().failures, 1);
+    }
+
+    #[test]
+    fn test_reassembly_memory_limit() {
+        let mut reassembler = IpReassembler::new(DEFAULT_TIMEOUT, 16);
+        let ts = Duration::from_secs(1);
+
+        reassembler.push(fragment(1, 0, true, &[1; 8]), ts);
+        reassembler.push(fragment(2, 0, true, &[2; 8]), ts + Duration::from_secs(1));
+        reassembler.push(fragment(3, 0, true, &[3; 8]), ts + Duration::from_secs(2));
+
+        assert_eq!(reassembler.stats().failures, 1);
+        assert!(!reassembler.buffers.contains_key(&key(1)));
+        assert!(reassembler.buffered_bytes <= 16);
+    }
+
+    #[test]
+    fn test_inconsistent_length() {
+        let mut reassembler = IpReassembler::default();
+        let ts = Duration::from_secs(1);
+
+        reassembler.push(fragment(1, 8, false, &[2; 8]), ts);
+        let result = reassembler.push(fragment(1, 16, true, &[3; 8]), ts);
+
+        assert_eq!(result, None);
+        assert_eq!(reassembler.stats().failures, 1);
+    }
+
+    #[test]
+    fn test_oversized_overlapping_fragment() {
+        let mut reassembler = IpReassembler::default();
+        let ts = Duration::from_secs(1);
+
+        assert_eq!(reassembler.push(fragment(1, 0, true, &[1; 100]), ts), None);
+        assert_eq!(reassembler.push(fragment(1, 8, false, &[2; 8]), ts), None);
+        assert_eq!(reassembler.stats().failures, 1);
+        assert_eq!(reassembler.buffered_bytes, 0);
+
+        let mut buffer = FragmentBuffer::new(ts);
+        buffer.pieces.insert(0, vec![1; 12]);
+        buffer.pieces.insert(8, vec![2; 100]);
+        buffer.total_len = Some(16);
+        let datagram = buffer.assemble().unwrap();
+        assert_eq!(&datagram[..8], &[1; 8]);
+        assert_eq!(&datagram[8..], &[2; 8]);
+    }
+}
@@ -0,0 +1,751 @@
+use super::{Packet, TcpFraming};
+use pnet_packet::tcp::TcpFlags;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+const FLOW_TIMEOUT: Duration = Duration::from_secs(120);
+const EXPIRY_INTERVAL: Duration = Duration::from_secs(10);
+
+// out of order data kept while waiting for the missing segment
+const MAX_PENDING_BYTES: usize = 1024 * 1024;
+// unframed data of a single flow, RFC 4571 frames are at most 64 KiB
+const MAX_BUFFERED_BYTES: usize = 1024 * 1024;
+const MAX_RTSP_HEADER_LEN: usize = 64 * 1024;
+const MAX_SIP_START_LINE_LEN: usize = 1024;
+
+const RFC4571_HEADER_LEN: usize = 2;
+const INTERLEAVED_HEADER_LEN: usize = 4;
+const INTERLEAVED_MAGIC: u8 = b'$';
+const STUN_MAGIC_COOKIE: [u8; 4] = [0x21, 0x12, 0xA4, 0x42];
+const RTSP_HEADER_END: &[u8] = b"\r\n\r\n";
+const SIP_RESPONSE_START: &[u8] = b"SIP/2.0 ";
+const SIP_REQUEST_END: &[u8] = b" SIP/2.0";
+const CRLF: &[u8] = b"\r\n";
+const RTSP_START_TOKENS: [&[u8]; 12] = [
+    b"RTSP/1.0 ",
+    b"OPTIONS ",
+    b"DESCRIBE ",
+    b"SETUP ",
+    b"PLAY ",
+    b"PAUSE ",
+    b"TEARDOWN ",
+    b"GET_PARAMETER ",
+    b"SET_PARAMETER ",
+    b"ANNOUNCE ",
+    b"RECORD ",
+    b"REDIRECT ",
+];
+
+type FlowKey = (SocketAddr, SocketAddr);
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TcpStats {
+    // holes in the sequence space that were never captured
+    pub gaps: usize,
+    pub lost_bytes: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Framing {
+    Raw,
+    Rfc4571,
+    Interleaved,
+    Sip,
+}
+
+#[derive(Debug)]
+struct TcpFlow {
+    next_seq: Option<u32>,
+    pending: Vec<(u32, Vec<u8>)>,
+    pending_bytes: usize,
+    buffer: Vec<u8>,
+    framing: Option<Framing>,
+    // segments were cut by the snaplen, their missing tails aren't losses
+    truncated: bool,
+    last_seen: Duration,
+}
+
+impl TcpFlow {
+    fn new(last_seen: Duration) -> Self {
+        Self {
+            next_seq: None,
+            pending: Vec::new(),
+            pending_bytes: 0,
+            buffer: Vec::new(),
+            framing: None,
+            truncated: false,
+            last_seen,
+        }
+    }
+
+    fn reset(&mut self, next_seq: u32) {
+        self.next_seq = Some(next_seq);
+        self.pending.clear();
+        self.pending_bytes = 0;
+        self.buffer.clear();
+    }
+
+    /// Adds the segment to the flow, returns the number of bytes skipped as lost.
+    fn receive(&mut self, seq: u32, data: &[u8]) -> Option<usize> {
+        let next_seq = *self.next_seq.get_or_insert(seq);
+        let offset = seq.wrapping_sub(next_seq) as i32;
+
+        if offset > 0 && self.truncated {
+            self.skip_to(seq);
+        } else if offset > 0 {
+            self.pending_bytes += data.len();
+            self.pending.push((seq, data.to_vec()));
+
+            if self.pending_bytes > MAX_PENDING_BYTES {
+                return Some(self.skip_to_pending());
+            }
+            return None;
+        }
+
+        self.append(seq, data);
+        self.drain_pending();
+        None
+    }
+
+    /// Every byte before `ack` was delivered to the peer,
+    /// so anything below it that wasn't captured is lost.
+    fn acknowledge(&mut self, ack: u32) -> Option<usize> {
+        let next_seq = self.next_seq?;
+        if ack.wrapping_sub(next_seq) as i32 <= 0 {
+            return None;
+        }
+
+        let mut lost = 0;
+        while let Some(next_seq) = self.next_seq
+            && ack.wrapping_sub(next_seq) as i32 > 0
+        {
+            let gap_end = self
+                .pending
+                .iter()
+                .map(|(seq, _)| *seq)
+                .filter(|seq| seq.wrapping_sub(next_seq) as i32 > 0)
+                .min_by_key(|seq| seq.wrapping_sub(next_seq))
+                .filter(|seq| ack.wrapping_sub(*seq) as i32 > 0)
+                .unwrap_or(ack);
+
+            lost += self.skip_to(gap_end);
+        }
+
+        Some(lost)
+    }
+
+    fn append(&mut self, seq: u32, data: &[u8]) {
+        let next_seq = self.next_seq.unwrap_or(seq);
+        let skip = next_seq.wrapping_sub(seq) as usize;
+        if skip >= data.len() {
+            // retransmission of already received data
+            return;
+        }
+
+        let data = &data[skip..];
+        self.next_seq = Some(next_seq.wrapping_add(data.len() as u32));
+
+        if self.framing != Some(Framing::Raw) {
+            self.buffer.extend_from_slice(data);
+        }
+    }
+
+    fn drain_pending(&mut self) {
+        while let Some(next_seq) = self.next_seq
+            && let Some(index) = self
+                .pending
+                .iter()
+                .position(|(seq, _)| seq.wrapping_sub(next_seq) as i32 <= 0)
+        {
+            let (seq, data) = self.pending.swap_remove(index);
+            self.pending_bytes -= data.len();
+            self.append(seq, &data);
+        }
+    }
+
+    fn skip_to_pending(&mut self) -> usize {
+        let Some(next_seq) = self.next_seq else {
+            return 0;
+        };
+
+        match self
+            .pending
+            .iter()
+            .map(|(seq, _)| *seq)
+            .min_by_key(|seq| seq.wrapping_sub(next_seq))
+        {
+            Some(seq) => self.skip_to(seq),
+            None => 0,
+        }
+    }
+
+    fn skip_to(&mut self, seq: u32) -> usize {
+        let lost = self
+            .next_seq
+            .map(|next_seq| seq.wrapping_sub(next_seq) as usize)
+            .unwrap_or_default();
+
+        // message boundary is lost with the data, resynchronize on the next segment
+        self.next_seq = Some(seq);
+        self.buffer.clear();
+        self.drain_pending();
+
+        lost
+    }
+
+    /// Returns the messages with the sequence numbers of their first bytes.
+    fn extract_messages(&mut self) -> Vec<(u32, TcpFraming, Vec<u8>)> {
+        if self.framing.is_none() {
+            self.framing = detect_framing(&self.buffer);
+        }
+
+        // the buffer holds the bytes right before the next expected one
+        let buffer_seq = self
+            .next_seq
+            .unwrap_or_default()
+            .wrapping_sub(self.buffer.len() as u32);
+        let messages = match self.framing {
+            Some(Framing::Rfc4571) => extract_rfc4571(&mut self.buffer),
+            Some(Framing::Interleaved) => extract_interleaved(&mut self.buffer),
+            Some(Framing::Sip) => extract_sip(&mut self.buffer),
+            Some(Framing::Raw) => {
+                self.buffer.clear();
+                Vec::new()
+            }
+            None => Vec::new(),
+        };
+
+        if self.buffer.len() > MAX_BUFFERED_BYTES {
+            self.buffer.clear();
+        }
+
+        messages
+            .into_iter()
+            .map(|(start, framing, message)| {
+                (buffer_seq.wrapping_add(start as u32), framing, message)
+            })
+            .collect()
+    }
+}
+
+/// Reassembles TCP connections of a single capture source
+/// and splits RFC 4571, RTSP interleaved and SIP streams into logical packets.
+///
+/// Segments of connections without recognized framing are passed through unchanged.
+#[derive(Debug, Default)]
+pub struct TcpReassembler {
+    flows: HashMap<FlowKey, TcpFlow>,
+    last_expiry: Duration,
+    stats: TcpStats,
+}
+
+impl TcpReassembler {
+    pub fn stats(&self) -> TcpStats {
+        self.stats
+    }
+
+    pub fn push(&mut self, packet: Packet) -> Vec<Packet> {
+        let Some(segment) = packet.metadata.tcp else {
+            return vec![packet];
+        };
+
+        self.expire(packet.timestamp);
+
+        let key = (packet.source_addr, packet.destination_addr);
+        let reverse_key = (packet.destination_addr, packet.source_addr);
+
+        if segment.flags & TcpFlags::ACK != 0
+            && !segment.truncated
+            && let Some(reverse) = self.flows.get_mut(&reverse_key)
+            && !reverse.truncated
+        {
+            let lost = reverse.acknowledge(segment.acknowledgement_number);
+            self.record_loss(lost);
+        }
+
+        let flow = self
+            .flows
+            .entry(key)
+            .or_insert_with(|| TcpFlow::new(packet.timestamp));
+        flow.last_seen = packet.timestamp;
+        flow.truncated |= segment.truncated;
+
+        if segment.flags & TcpFlags::SYN != 0 {
+            flow.reset(segment.sequence_number.wrapping_add(1));
+        }
+
+        let data = match (&packet.payload, segment.payload_len) {
+            (Some(payload), len) if len > 0 => payload.as_slice(),
+            _ => &[],
+        };
+
+        let mut messages = Vec::new();
+        if !data.is_empty() {
+            let lost = flow.receive(segment.sequence_number, data);
+            messages = flow.extract_messages();
+            self.record_loss(lost);
+        }
+
+        let framing = self.flows.get(&key).and_then(|flow| flow.framing);
+
+        if segment.flags & TcpFlags::RST != 0 {
+            self.flows.remove(&key);
+            self.flows.remove(&reverse_key);
+        } else if segment.flags & TcpFlags::FIN != 0 {
+            self.flows.remove(&key);
+        }
+
+        match framing {
+            Some(Framing::Rfc4571 | Framing::Interleaved | Framing::Sip) if !data.is_empty() => {
+                messages
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, (seq, framing, message))| {
+                        framed_packet(&packet, index, seq, framing, message)
+                    })
+                    .collect()
+            }
+            _ => vec![packet],
+        }
+    }
+
+    fn record_loss(&mut self, lost: Option<usize>) {
+        if let Some(lost) = lost
+            && lost > 0
+        {
+            self.stats.gaps += 1;
+            self.stats.lost_bytes += lost;
+        }
+    }
+
+    fn expire(&mut self, now: Duration) {
+        if now.saturating_sub(self.last_expiry) < EXPIRY_INTERVAL {
+            return;
+        }
+
+        self.last_expiry = now;
+        self.flows
+            .retain(|_, flow| now.saturating_sub(flow.last_seen) <= FLOW_TIMEOUT);
+    }
+}
+
+fn framed_packet(
+    segment: &Packet,
+    index: usize,
+    seq: u32,
+    framing: TcpFraming,
+    message: Vec<u8>,
+) -> Packet {
+    let mut packet = segment.clone();
+    if let Some(tcp) = packet.metadata.tcp.as_mut() {
+        tcp.sequence_number = seq;
+    }
+    packet.length = message.len() as u32;
+    packet.payload = Some(message);
+    packet.metadata.framing = Some(framing);
+    packet.metadata.sub_index = index;
+    packet
+}
+
+fn detect_framing(buffer: &[u8]) -> Option<Framing> {
+    // SIP shares the OPTIONS method with RTSP, the version tells them apart
+    match is_sip_start(buffer) {
+        Some(true) => return Some(Framing::Sip),
+        Some(false) => {}
+        None => return None,
+    }
+
+    if buffer.first() == Some(&INTERLEAVED_MAGIC) || is_rtsp_start(buffer) {
+        return Some(Framing::Interleaved);
+    }
+
+    if buffer.len() < INTERLEAVED_HEADER_LEN {
+        return None;
+    }
+
+    // wait for the whole method name
+    if RTSP_START_TOKENS
+        .iter()
+        .any(|token| token.len() > buffer.len() && token.starts_with(buffer))
+    {
+        return None;
+    }
+
+    let length = u16::from_be_bytes([buffer[0], buffer[1]]);
+    let version = buffer[2] >> 6;
+    let is_stun = buffer.len() >= 10 && buffer[6..10] == STUN_MAGIC_COOKIE;
+
+    if length > 0 && (version == 2 || (version == 0 && is_stun)) {
+        return Some(Framing::Rfc4571);
+    }
+
+    Some(Framing::Raw)
+}
+
+// None until the start line is complete
+fn is_sip_start(buffer: &[u8]) -> Option<bool> {
+    let Some(end) = find(buffer, CRLF) else {
+        let maybe_text = buffer.first().is_some_and(u8::is_ascii_uppercase);
+        return (!maybe_text || buffer.len() > MAX_SIP_START_LINE_LEN).then_some(false);
+    };
+
+    let line = &buffer[..end];
+    Some(line.starts_with(SIP_RESPONSE_START) || line.ends_with(SIP_REQUEST_END))
+}
+
+fn is_rtsp_start(buffer: &[u8]) -> bool {
+    RTSP_START_TOKENS
+        .iter()
+        .any(|token| buffer.starts_with(token))
+}
+
+fn extract_rfc4571(buffer: &mut Vec<u8>) -> Vec<(usize, TcpFraming, Vec<u8>)> {
+    let mut messages = Vec::new();
+    let mut start = 0;
+
+    while let Some(header) = buffer.get(start..start + RFC4571_HEADER_LEN) {
+        let length = u16::from_be_bytes([header[0], header[1]]) as usize;
+        let end = start + RFC4571_HEADER_LEN + length;
+        let Some(message) = buffer.get(start + RFC4571_HEADER_LEN..end) else {
+            break;
+        };
+
+        messages.push((start, TcpFraming::Rfc4571, message.to_vec()));
+        start = end;
+    }
+
+    buffer.drain(..start);
+    messages
+}
+
+fn extract_interleaved(buffer: &mut Vec<u8>) -> Vec<(usize, TcpFraming, Vec<u8>)> {
+    let mut messages = Vec::new();
+    let mut start = 0;
+
+    while start < buffer.len() {
+        let rest = &buffer[start..];
+
+        if rest[0] == INTERLEAVED_MAGIC {
+            let Some(header) = rest.get(..INTERLEAVED_HEADER_LEN) else {
+                break;
+            };
+            let channel = header[1];
+            let length = u16::from_be_bytes([header[2], header[3]]) as usize;
+            let Some(message) = rest.get(INTERLEAVED_HEADER_LEN..INTERLEAVED_HEADER_LEN + length)
+            else {
+                break;
+            };
+
+            messages.push((start, TcpFraming::Interleaved { channel }, message.to_vec()));
+            start += INTERLEAVED_HEADER_LEN + length;
+        } else if is_rtsp_start(rest) {
+            let Some(header_len) =
+                find(rest, RTSP_HEADER_END).map(|pos| pos + RTSP_HEADER_END.len())
+            else {
+                if rest.len() > MAX_RTSP_HEADER_LEN {
+                    start += skip_to_magic(&rest[1..]) + 1;
+                    continue;
+                }
+                break;
+            };
+            let message_len = header_len + content_length(&rest[..header_len]);
+            let Some(message) = rest.get(..message_len) else {
+                break;
+            };
+
+            messages.push((start, TcpFraming::RtspMessage, message.to_vec()));
+            start += message_len;
+        } else {
+            // lost synchronization, next message starts with `$` or RTSP method name
+            start += skip_to_magic(rest);
+        }
+    }
+
+    buffer.drain(..start);
+    messages
+}
+
+fn extract_sip(buffer: &mut Vec<u8>) -> Vec<(usize, TcpFraming, Vec<u8>)> {
+    let mut messages = Vec::new();
+    let mut start = 0;
+
+    while start < buffer.len() {
+        let rest = &buffer[start..];
+
+        // keep-alive pings between the messages, RFC 5626
+        if rest.starts_with(CRLF) {
+            start += CRLF.len();
+            continue;
+        }
+
+        match is_sip_start(rest) {
+            Some(true) => {}
+            // lost synchronization, the next message starts on a new line
+            Some(false) => {
+                start += find(rest, CRLF).map_or(rest.len(), |pos| pos + CRLF.len());
+                continue;
+            }
+            None => break,
+        }
+
+        let Some(header_len) = find(rest, RTSP_HEADER_END).map(|pos| pos + RTSP_HEADER_END.len())
+        else {
+            if rest.len() > MAX_RTSP_HEADER_LEN {
+                start += find(rest, CRLF).map_or(rest.len(), |pos| pos + CRLF.len());
+                continue;
+            }
+            break;
+        };
+        let message_len = header_len + content_length(&rest[..header_len]);
+        let Some(message) = rest.get(..message_len) else {
+            break;
+        };
+
+        messages.push((start, TcpFraming::SipMessage, message.to_vec()));
+        start += message_len;
+    }
+
+    buffer.drain(..start);
+    messages
+}
+
+fn skip_to_magic(data: &[u8]) -> usize {
+    (0..data.len())
+        .find(|&pos| data[pos] == INTERLEAVED_MAGIC || is_rtsp_start(&data[pos..]))
+        .unwrap_or(data.len())
+}
+
+fn content_length(header: &[u8]) -> usize {
+    String::from_utf8_lossy(header)
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        // `l` is the compact form in SIP
+        .find(|(name, _)| {
+            let name = name.trim();
+            name.eq_ignore_ascii_case("content-length") || name.eq_ignore_ascii_case("l")
+        })
+        .and_then(|(_, value)| value.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+fn find(data: &[u8], needle: &[u8]) -> Option<usize> {
+    data.windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::{
+        PacketMetadata, SessionPacket, SessionProtocol, TcpSegment, TransportProtocol,
+    };
+    use std::time::SystemTime;
+
+    const CLIENT: &str = "10.0.0.1:50000";
+    const SERVER: &str = "10.0.0.2:554";
+
+    fn segment(source: &str, destination: &str, seq: u32, ack: u32, payload: &[u8]) -> Packet {
+        Packet {
+            payload: Some(payload.to_vec()),
+            id: 0,
+            timestamp: Duration::from_secs(1),
+            length: payload.len() as u32,
+            source_addr: source.parse().unwrap(),
+            destination_addr: destination.parse().unwrap(),
+            transport_protocol: TransportProtocol::Tcp,
+            session_protocol: SessionProtocol::Unknown,
+            contents: SessionPacket::Unknown,
+            creation_time: SystemTime::now(),
+            metadata: PacketMetadata {
+                tcp: Some(TcpSegment {
+                    sequence_number: seq,
+                    acknowledgement_number: ack,
+                    flags: TcpFlags::ACK,
+                    payload_len: payload.len() as u32,
+                    truncated: false,
+                }),
+                ..Default::default()
+            },
+        }
+    }
+
+    fn rtp(sequence_number: u8) -> Vec<u8> {
+        let mut rtp = vec![0x80, 0x60, 0x00, sequence_number];
+        rtp.extend_from_slice(&[0; 8]);
+        rtp
+    }
+
+    fn interleaved(channel: u8, message: &[u8]) -> Vec<u8> {
+        let mut data = vec![INTERLEAVED_MAGIC, channel];
+        data.extend_from_slice(&(message.len() as u16).to_be_bytes());
+        data.extend_from_slice(message);
+        data
+    }
+
+    fn rfc4571(message: &[u8]) -> Vec<u8> {
+        let mut data = (message.len() as u16).to_be_bytes().to_vec();
+        data.extend_from_slice(message);
+        data
+    }
+
+    #[test]
+    fn test_rfc4571_split_across_segments() {
+        let mut reassembler = TcpReassembler::default();
+        let mut data = rfc4571(&rtp(1));
+        data.extend_from_slice(&rfc4571(&rtp(2)));
+
+        let first = reassembler.push(segment(CLIENT, SERVER, 100, 1, &data[..20]));
+        let second = reassembler.push(segment(CLIENT, SERVER, 120, 1, &data[20..]));
+
+        assert_eq!(first.len(), 1);
+        assert_eq!(first[0].payload, Some(rtp(1)));
+        assert_eq!(first[0].metadata.framing, Some(TcpFraming::Rfc4571));
+        assert_eq!(second.len(), 1);
+        assert_eq!(second[0].payload, Some(rtp(2)));
+        // sequence numbers of the messages, not of the segments completing them
+        let seq = |packet: &Packet| packet.metadata.tcp.as_ref().unwrap().sequence_number;
+        assert_eq!(seq(&first[0]), 100);
+        assert_eq!(seq(&second[0]), 100 + rfc4571(&rtp(1)).len() as u32);
+    }
+
+    #[test]
+    fn test_rtsp_interleaved() {
+        let mut reassembler = TcpReassembler::default();
+        let response = b"RTSP/1.0 200 OK\r\nCSeq: 4\r\nContent-Length: 3\r\n\r\nabc".to_vec();
+        let mut data = response.clone();
+        data.extend_from_slice(&interleaved(0, &rtp(1)));
+        data.extend_from_slice(&interleaved(1, &[0x81, 0xc8, 0x00, 0x00]));
+
+        let packets = reassembler.push(segment(SERVER, CLIENT, 1, 100, &data));
+
+        assert_eq!(packets.len(), 3);
+        let sub_indices: Vec<_> = packets.iter().map(|p| p.metadata.sub_index).collect();
+        assert_eq!(sub_indices, [0, 1, 2]);
+        let seqs: Vec<_> = packets
+            .iter()
+            .map(|p| p.metadata.tcp.as_ref().unwrap().sequence_number)
+            .collect();
+        let rtp_seq = 1 + response.len() as u32;
+        assert_eq!(
+            seqs,
+            [1, rtp_seq, rtp_seq + interleaved(0, &rtp(1)).len() as u32]
+        );
+        assert_eq!(packets[0].payload, Some(response));
+        assert_eq!(packets[0].metadata.framing, Some(TcpFraming::RtspMessage));
+        assert_eq!(packets[1].payload, Some(rtp(1)));
+        assert_eq!(
+            packets[1].metadata.framing,
+            Some(TcpFraming::Interleaved { channel: 0 })
+        );
+        assert_eq!(
+            packets[2].metadata.framing,
+            Some(TcpFraming::Interleaved { channel: 1 })
+        );
+    }
+
+    #[test]
+    fn test_sip_messages() {
+        let mut reassembler = TcpReassembler::default();
+        let options = b"OPTIONS sip:bob@host SIP/2.0\r\nCSeq: 1 OPTIONS\r\nl: 0\r\n\r\n".to_vec();
+        let invite = b"INVITE sip:bob@host SIP/2.0\r\nContent-Length: 4\r\n\r\nv=0\n".to_vec();
+
+        // the start line isn't complete yet
+        let packets = reassembler.push(segment(CLIENT, SERVER, 1, 100, &options[..10]));
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].metadata.framing, None);
+
+        let mut data = options[10..].to_vec();
+        data.extend_from_slice(b"\r\n\r\n");
+        data.extend_from_slice(&invite[..30]);
+        let packets = reassembler.push(segment(CLIENT, SERVER, 11, 100, &data));
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].payload, Some(options.clone()));
+        assert_eq!(packets[0].metadata.framing, Some(TcpFraming::SipMessage));
+
+        let seq = 11 + data.len() as u32;
+        let packets = reassembler.push(segment(CLIENT, SERVER, seq, 100, &invite[30..]));
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].payload, Some(invite));
+    }
+
+    #[test]
+    fn test_out_of_order_and_retransmission() {
+        let mut reassembler = TcpReassembler::default();
+        let first = rfc4571(&rtp(1));
+        let second = rfc4571(&rtp(2));
+        let third = rfc4571(&rtp(3));
+        let second_seq = 100 + first.len() as u32;
+        let third_seq = second_seq + second.len() as u32;
+
+        assert_eq!(
+            reassembler
+                .push(segment(CLIENT, SERVER, 100, 1, &first))
+                .len(),
+            1
+        );
+        assert!(
+            reassembler
+                .push(segment(CLIENT, SERVER, third_seq, 1, &third))
+                .is_empty()
+        );
+        let packets = reassembler.push(segment(CLIENT, SERVER, second_seq, 1, &second));
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[0].payload, Some(rtp(2)));
+        assert_eq!(packets[1].payload, Some(rtp(3)));
+
+        let packets = reassembler.push(segment(CLIENT, SERVER, second_seq, 1, &second));
+        assert!(packets.is_empty());
+        assert_eq!(reassembler.stats(), TcpStats::default());
+    }
+
+    #[test]
+    fn test_ack_gap_reported_as_loss() {
+        let mut reassembler = TcpReassembler::default();
+        let message = rfc4571(&rtp(1));
+        let len = message.len() as u32;
+
+        reassembler.push(segment(CLIENT, SERVER, 100, 1, &message));
+        // the peer acknowledges a segment which wasn't captured
+        reassembler.push(segment(SERVER, CLIENT, 1, 100 + 2 * len, &[]));
+        let packets = reassembler.push(segment(CLIENT, SERVER, 100 + 2 * len, 1, &message));
+
+        assert_eq!(packets.len(), 1);
+        assert_eq!(
+            reassembler.stats(),
+            TcpStats {
+                gaps: 1,
+                lost_bytes: len as usize,
+            }
+        );
+    }
+
+    #[test]
+    fn test_truncated_segments_not_lost() {
+        let mut reassembler = TcpReassembler::default();
+        let message = rfc4571(&rtp(1));
+        let len = message.len() as u32;
+
+        // only the first bytes of each segment were captured
+        let mut first = segment(CLIENT, SERVER, 100, 1, &message[..4]);
+        first.metadata.tcp.as_mut().unwrap().truncated = true;
+        reassembler.push(first);
+        reassembler.push(segment(SERVER, CLIENT, 1, 100 + len, &[]));
+        let mut second = segment(CLIENT, SERVER, 100 + len, 1, &message[..4]);
+        second.metadata.tcp.as_mut().unwrap().truncated = true;
+        reassembler.push(second);
+
+        assert_eq!(reassembler.stats(), TcpStats::default());
+    }
+
+    #[test]
+    fn test_unframed_segments_pass_through() {
+        let mut reassembler = TcpReassembler::default();
+        let request = b"GET / HTTP/1.1\r\n\r\n";
+
+        let packets = reassembler.push(segment(CLIENT, SERVER, 100, 1, request));
+
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].metadata.framing, None);
+    }
+}
@@ -0,0 +1,348 @@
+use super::link::LinkFrame;
+use super::{Encapsulation, Tunnel};
+use pcap::Linktype;
+use pnet_packet::ip::{IpNextHeaderProtocol, IpNextHeaderProtocols};
+use pnet_packet::udp::UdpPacket;
+use std::net::IpAddr;
+
+const VXLAN_PORT: u16 = 4789;
+const GTP_U_PORT: u16 = 2152;
+
+const MAX_TUNNEL_DEPTH: usize = 4;
+
+const UDP_HEADER_LEN: usize = 8;
+
+const GRE_CHECKSUM_PRESENT: u16 = 0x8000;
+const GRE_ROUTING_PRESENT: u16 = 0x4000;
+const GRE_KEY_PRESENT: u16 = 0x2000;
+const GRE_SEQUENCE_PRESENT: u16 = 0x1000;
+const GRE_VERSION_MASK: u16 = 0x0007;
+
+const GRE_PROTOCOL_IPV4: u16 = 0x0800;
+const GRE_PROTOCOL_IPV6: u16 = 0x86DD;
+const GRE_PROTOCOL_ETHERNET: u16 = 0x6558;
+const GRE_PROTOCOL_ERSPAN_II: u16 = 0x88BE;
+const GRE_PROTOCOL_ERSPAN_III: u16 = 0x22EB;
+
+const ERSPAN_II_HEADER_LEN: usize = 8;
+const ERSPAN_III_HEADER_LEN: usize = 12;
+const ERSPAN_III_SUBHEADER_LEN: usize = 8;
+const ERSPAN_SESSION_ID_MASK: u16 = 0x03FF;
+
+const VXLAN_HEADER_LEN: usize = 8;
+const VXLAN_VALID_VNI: u8 = 0x08;
+
+const GTP_U_HEADER_LEN: usize = 8;
+const GTP_U_OPTIONAL_FIELDS_LEN: usize = 4;
+const GTP_U_VERSION_1: u8 = 0x20;
+const GTP_U_PROTOCOL_TYPE: u8 = 0x10;
+const GTP_U_EXTENSION_HEADER: u8 = 0x04;
+const GTP_U_OPTIONAL_FIELDS: u8 = 0x07;
+const GTP_U_G_PDU: u8 = 0xFF;
+
+impl LinkFrame<'_> {
+    /// Returns the frame carried inside of a GRE (including ERSPAN), VXLAN or GTP-U tunnel.
+    ///
+    /// `payload` is the IP payload of this frame, the inner frame inherits VLAN tags,
+    /// MPLS labels and tunnels of the outer one, with the new tunnel appended.
+    pub fn decapsulate<'b>(
+        &self,
+        source_addr: IpAddr,
+        destination_addr: IpAddr,
+        protocol: IpNextHeaderProtocol,
+        payload: &'b [u8],
+    ) -> Option<LinkFrame<'b>> {
+        if self.tunnels.len() >= MAX_TUNNEL_DEPTH {
+            return None;
+        }
+
+        let (encapsulation, link_type, inner_payload) = match protocol {
+            IpNextHeaderProtocols::Gre => parse_gre(payload)?,
+            IpNextHeaderProtocols::Udp => {
+                let udp_packet = UdpPacket::new(payload)?;
+                let udp_payload = &payload[UDP_HEADER_LEN..];
+                match udp_packet.get_destination() {
+                    VXLAN_PORT => parse_vxlan(udp_payload)?,
+                    GTP_U_PORT => parse_gtp_u(udp_payload)?,
+                    _ => return None,
+                }
+            }
+            _ => return None,
+        };
+
+        let mut inner = LinkFrame::parse(link_type, inner_payload)?;
+
+        inner.vlan_ids.splice(0..0, self.vlan_ids.iter().copied());
+        inner
+            .mpls_labels
+            .splice(0..0, self.mpls_labels.iter().copied());
+        inner.tunnels = self.tunnels.clone();
+        inner.tunnels.push(Tunnel {
+            source_addr,
+            destination_addr,
+            encapsulation,
+        });
+
+        Some(inner)
+    }
+}
+
+fn parse_gre(data: &[u8]) -> Option<(Encapsulation, Linktype, &[u8])> {
+    let flags = u16::from_be_bytes([*data.first()?, *data.get(1)?]);
+    let protocol = u16::from_be_bytes([*data.get(2)?, *data.get(3)?]);
+
+    if flags & (GRE_VERSION_MASK | GRE_ROUTING_PRESENT) != 0 {
+        return None;
+    }
+
+    let mut header_len = 4;
+    if flags & GRE_CHECKSUM_PRESENT != 0 {
+        header_len += 4;
+    }
+    let key = if flags & GRE_KEY_PRESENT != 0 {
+        let key = data.get(header_len..header_len + 4)?;
+        header_len += 4;
+        Some(u32::from_be_bytes(key.try_into().unwrap()))
+    } else {
+        None
+    };
+    if flags & GRE_SEQUENCE_PRESENT != 0 {
+        header_len += 4;
+    }
+
+    let payload = data.get(header_len..)?;
+
+    match protocol {
+        GRE_PROTOCOL_IPV4 | GRE_PROTOCOL_IPV6 => {
+            Some((Encapsulation::Gre { key }, Linktype::RAW, payload))
+        }
+        GRE_PROTOCOL_ETHERNET => Some((Encapsulation::Gre { key }, Linktype::ETHERNET, payload)),
+        // ERSPAN type I has no sequence number and no ERSPAN header
+        GRE_PROTOCOL_ERSPAN_II if flags & GRE_SEQUENCE_PRESENT == 0 => Some((
+            Encapsulation::Erspan { session_id: None },
+            Linktype::ETHERNET,
+            payload,
+        )),
+        GRE_PROTOCOL_ERSPAN_II => {
+            let session_id = erspan_session_id(payload)?;
+            Some((
+                Encapsulation::Erspan {
+                    session_id: Some(session_id),
+                },
+                Linktype::ETHERNET,
+                payload.get(ERSPAN_II_HEADER_LEN..)?,
+            ))
+        }
+        GRE_PROTOCOL_ERSPAN_III => {
+            let session_id = erspan_session_id(payload)?;
+            let has_subheader = payload.get(ERSPAN_III_HEADER_LEN - 1)? & 0x01 != 0;
+            let header_len = if has_subheader {
+                ERSPAN_III_HEADER_LEN + ERSPAN_III_SUBHEADER_LEN
+            } else {
+                ERSPAN_III_HEADER_LEN
+            };
+            Some((
+                Encapsulation::Erspan {
+                    session_id: Some(session_id),
+                },
+                Linktype::ETHERNET,
+                payload.get(header_len..)?,
+            ))
+        }
+        _ => None,
+    }
+}
+
+fn erspan_session_id(data: &[u8]) -> Option<u16> {
+    let session_id = u16::from_be_bytes([*data.get(2)?, *data.get(3)?]);
+    Some(session_id & ERSPAN_SESSION_ID_MASK)
+}
+
+fn parse_vxlan(data: &[u8]) -> Option<(Encapsulation, Linktype, &[u8])> {
+    let header = data.get(..VXLAN_HEADER_LEN)?;
+    if header[0] & VXLAN_VALID_VNI == 0 {
+        return None;
+    }
+
+    let vni = u32::from_be_bytes([0, header[4], header[5], header[6]]);
+
+    Some((
+        Encapsulation::Vxlan { vni },
+        Linktype::ETHERNET,
+        &data[VXLAN_HEADER_LEN..],
+    ))
+}
+
+fn parse_gtp_u(data: &[u8]) -> Option<(Encapsulation, Linktype, &[u8])> {
+    let header = data.get(..GTP_U_HEADER_LEN)?;
+    let flags = header[0];
+
+    // only version 1 G-PDUs carry user data
+    if flags & 0xF0 != GTP_U_VERSION_1 | GTP_U_PROTOCOL_TYPE || header[1] != GTP_U_G_PDU {
+        return None;
+    }
+
+    let teid = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+
+    let mut header_len = GTP_U_HEADER_LEN;
+    if flags & GTP_U_OPTIONAL_FIELDS != 0 {
+        header_len += GTP_U_OPTIONAL_FIELDS_LEN;
+        let mut next_extension = *data.get(header_len - 1)?;
+
+        while flags & GTP_U_EXTENSION_HEADER != 0 && next_extension != 0 {
+            // extension length is expressed in 4 byte units, last byte is the next extension type
+            let extension_len = *data.get(header_len)? as usize * 4;
+            if extension_len == 0 {
+                return None;
+            }
+            header_len += extension_len;
+            next_extension = *data.get(header_len - 1)?;
+        }
+    }
+
+    Some((
+        Encapsulation::GtpU { teid },
+        Linktype::RAW,
+        data.get(header_len..)?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pnet_packet::ethernet::EtherTypes;
+
+    const IPV4_HEADER_START: [u8; 4] = [0x45, 0x00, 0x00, 0x1c];
+
+    fn outer_frame() -> LinkFrame<'static> {
+        LinkFrame::parse(Linktype::RAW, &IPV4_HEADER_START).unwrap()
+    }
+
+    fn ethernet_frame(payload: &[u8]) -> Vec<u8> {
+        let mut data = vec![0u8; 12];
+        data.extend_from_slice(&[0x81, 0x00, 0x00, 0x64, 0x08, 0x00]);
+        data.extend_from_slice(payload);
+        data
+    }
+
+    fn addr(addr: &str) -> IpAddr {
+        addr.parse().unwrap()
+    }
+
+    #[test]
+    fn test_decapsulate_vxlan() {
+        let mut data = vec![0x12, 0x34, 0x12, 0xb5, 0x00, 0x00, 0x00, 0x00];
+        data.extend_from_slice(&[0x08, 0, 0, 0, 0x00, 0x01, 0x02, 0x00]);
+        data.extend_from_slice(&ethernet_frame(&IPV4_HEADER_START));
+        let udp_len = data.len() as u16;
+        data[4..6].copy_from_slice(&udp_len.to_be_bytes());
+
+        let inner = outer_frame()
+            .decapsulate(
+                addr("10.0.0.1"),
+                addr("10.0.0.2"),
+                IpNextHeaderProtocols::Udp,
+                &data,
+            )
+            .unwrap();
+
+        assert_eq!(inner.ethertype, EtherTypes::Ipv4);
+        assert_eq!(inner.payload, &IPV4_HEADER_START);
+        assert_eq!(inner.vlan_ids, vec![100]);
+        assert_eq!(
+            inner.tunnels,
+            vec![Tunnel {
+                source_addr: addr("10.0.0.1"),
+                destination_addr: addr("10.0.0.2"),
+                encapsulation: Encapsulation::Vxlan { vni: 258 },
+            }]
+        );
+    }
+
+    #[test]
+    fn test_decapsulate_gre_with_key() {
+        let mut data = vec![0x20, 0x00, 0x08, 0x00, 0x00, 0x00, 0x00, 0x2a];
+        data.extend_from_slice(&IPV4_HEADER_START);
+
+        let inner = outer_frame()
+            .decapsulate(
+                addr("10.0.0.1"),
+                addr("10.0.0.2"),
+                IpNextHeaderProtocols::Gre,
+                &data,
+            )
+            .unwrap();
+
+        assert_eq!(inner.payload, &IPV4_HEADER_START);
+        assert_eq!(
+            inner.tunnels[0].encapsulation,
+            Encapsulation::Gre { key: Some(42) }
+        );
+    }
+
+    #[test]
+    fn test_decapsulate_erspan_ii() {
+        // GRE with sequence number, ERSPAN type II session 5
+        let mut data = vec![0x10, 0x00, 0x88, 0xbe, 0x00, 0x00, 0x00, 0x01];
+        data.extend_from_slice(&[0x10, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x00]);
+        data.extend_from_slice(&ethernet_frame(&IPV4_HEADER_START));
+
+        let inner = outer_frame()
+            .decapsulate(
+                addr("10.0.0.1"),
+                addr("10.0.0.2"),
+                IpNextHeaderProtocols::Gre,
+                &data,
+            )
+            .unwrap();
+
+        assert_eq!(inner.payload, &IPV4_HEADER_START);
+        assert_eq!(
+            inner.tunnels[0].encapsulation,
+            Encapsulation::Erspan {
+                session_id: Some(5)
+            }
+        );
+    }
+
+    #[test]
+    fn test_decapsulate_gtp_u_with_extension() {
+        let mut data = vec![0x12, 0x34, 0x08, 0x68, 0x00, 0x00, 0x00, 0x00];
+        // E flag set, PDU session container extension
+        data.extend_from_slice(&[0x34, 0xff, 0x00, 0x10, 0xde, 0xad, 0xbe, 0xef]);
+        data.extend_from_slice(&[0x00, 0x00, 0x00, 0x85]);
+        data.extend_from_slice(&[0x01, 0x00, 0x09, 0x00]);
+        data.extend_from_slice(&IPV4_HEADER_START);
+
+        let inner = outer_frame()
+            .decapsulate(
+                addr("10.0.0.1"),
+                addr("10.0.0.2"),
+                IpNextHeaderProtocols::Udp,
+                &data,
+            )
+            .unwrap();
+
+        assert_eq!(inner.payload, &IPV4_HEADER_START);
+        assert_eq!(
+            inner.tunnels[0].encapsulation,
+            Encapsulation::GtpU { teid: 0xdeadbeef }
+        );
+    }
+
+    #[test]
+    fn test_not_a_tunnel() {
+        let data = [0x12, 0x34, 0x13, 0x88, 0x00, 0x08, 0x00, 0x00];
+
+        assert!(
+            outer_frame()
+                .decapsulate(
+                    addr("10.0.0.1"),
+                    addr("10.0.0.2"),
+                    IpNextHeaderProtocols::Udp,
+                    &data,
+                )
+                .is_none()
+        );
+    }
+}
@@ -1,6 +1,8 @@
 use bincode::{Decode, Encode};
+use extension::RtpExtension;
 use payload_type::PayloadType;
 
+pub mod extension;
 pub mod payload_type;
 
 #[derive(Decode, Encode, Debug, Clone)]
@@ -14,7 +16,8 @@ pub struct RtpPacket {
     pub timestamp: u32,
     pub ssrc: u32,
     pub csrc: Vec<u32>,
-    pub payload_length: usize, // extension information skipped
+    pub extensions: Vec<RtpExtension>,
+    pub payload_length: usize,
 }
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -43,6 +46,14 @@ impl RtpPacket {
             timestamp: header.timestamp,
             ssrc: header.ssrc,
             csrc: header.csrc,
+            extensions: header
+                .extensions
+                .into_iter()
+                .map(|extension| RtpExtension {
+                    id: extension.id,
+                    data: extension.payload.to_vec(),
+                })
+                .collect(),
             payload_length: payload.len(),
         })
     }
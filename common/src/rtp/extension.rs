@@ -0,0 +1,433 @@
+use bincode::{Decode, Encode};
+use std::fmt;
+
+pub const ABS_SEND_TIME_URI: &str = "http://www.webrtc.org/experiments/rtp-hdrext/abs-send-time";
+pub const TRANSPORT_WIDE_CC_URI: &str =
+    "http://www.ietf.org/id/draft-holmer-rmcat-transport-wide-cc-extensions-01";
+pub const TRANSPORT_WIDE_CC_02_URI: &str =
+    "http://www.webrtc.org/experiments/rtp-hdrext/transport-wide-cc-02";
+pub const AUDIO_LEVEL_URI: &str = "urn:ietf:params:rtp-hdrext:ssrc-audio-level";
+pub const VIDEO_ORIENTATION_URI: &str = "urn:3gpp:video-orientation";
+pub const TOFFSET_URI: &str = "urn:ietf:params:rtp-hdrext:toffset";
+pub const MID_URI: &str = "urn:ietf:params:rtp-hdrext:sdes:mid";
+pub const RID_URI: &str = "urn:ietf:params:rtp-hdrext:sdes:rtp-stream-id";
+pub const REPAIRED_RID_URI: &str = "urn:ietf:params:rtp-hdrext:sdes:repaired-rtp-stream-id";
+pub const PLAYOUT_DELAY_URI: &str = "http://www.webrtc.org/experiments/rtp-hdrext/playout-delay";
+pub const ABS_CAPTURE_TIME_URI: &str =
+    "http://www.webrtc.org/experiments/rtp-hdrext/abs-capture-time";
+pub const DEPENDENCY_DESCRIPTOR_URI: &str =
+    "https://aomediacodec.github.io/av1-rtp-spec/#dependency-descriptor-rtp-header-extension";
+
+/// RFC 8285 header extension element, the id is mapped to
+/// its meaning by the SDP `a=extmap` attribute.
+#[derive(Decode, Encode, Debug, Clone, PartialEq)]
+pub struct RtpExtension {
+    pub id: u8,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ExtensionKind {
+    AbsSendTime,
+    TransportWideCc,
+    AudioLevel,
+    VideoOrientation,
+    TransmissionOffset,
+    Mid,
+    Rid,
+    RepairedRid,
+    PlayoutDelay,
+    AbsCaptureTime,
+    DependencyDescriptor,
+}
+
+impl ExtensionKind {
+    pub fn all() -> Vec<Self> {
+        vec![
+            Self::AbsSendTime,
+            Self::TransportWideCc,
+            Self::AudioLevel,
+            Self::VideoOrientation,
+            Self::TransmissionOffset,
+            Self::Mid,
+            Self::Rid,
+            Self::RepairedRid,
+            Self::PlayoutDelay,
+            Self::AbsCaptureTime,
+            Self::DependencyDescriptor,
+        ]
+    }
+
+    pub fn from_uri(uri: &str) -> Option<Self> {
+        let kind = match uri.trim() {
+            ABS_SEND_TIME_URI => Self::AbsSendTime,
+            TRANSPORT_WIDE_CC_URI | TRANSPORT_WIDE_CC_02_URI => Self::TransportWideCc,
+            AUDIO_LEVEL_URI => Self::AudioLevel,
+            VIDEO_ORIENTATION_URI => Self::VideoOrientation,
+            TOFFSET_URI => Self::TransmissionOffset,
+            MID_URI => Self::Mid,
+            RID_URI => Self::Rid,
+            REPAIRED_RID_URI => Self::RepairedRid,
+            PLAYOUT_DELAY_URI => Self::PlayoutDelay,
+            ABS_CAPTURE_TIME_URI => Self::AbsCaptureTime,
+            DEPENDENCY_DESCRIPTOR_URI => Self::DependencyDescriptor,
+            _ => return None,
+        };
+
+        Some(kind)
+    }
+
+    /// Short name used by the filters.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::AbsSendTime => "abs_send_time",
+            Self::TransportWideCc => "twcc",
+            Self::AudioLevel => "audio_level",
+            Self::VideoOrientation => "video_orientation",
+            Self::TransmissionOffset => "toffset",
+            Self::Mid => "mid",
+            Self::Rid => "rid",
+            Self::RepairedRid => "repaired_rid",
+            Self::PlayoutDelay => "playout_delay",
+            Self::AbsCaptureTime => "abs_capture_time",
+            Self::DependencyDescriptor => "dependency_descriptor",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::all().into_iter().find(|kind| kind.name() == name)
+    }
+
+    /// Whether the values have a number to be plotted or compared.
+    pub fn is_numeric(&self) -> bool {
+        !matches!(self, Self::Mid | Self::Rid | Self::RepairedRid)
+    }
+
+    /// Unit of the number returned by [`ExtensionValue::as_f64`].
+    pub fn unit(&self) -> &'static str {
+        match self {
+            Self::AbsSendTime | Self::AbsCaptureTime => "s",
+            Self::AudioLevel => "dBov",
+            Self::VideoOrientation => "°",
+            Self::TransmissionOffset => "RTP ticks",
+            Self::PlayoutDelay => "ms",
+            Self::TransportWideCc | Self::DependencyDescriptor => "",
+            Self::Mid | Self::Rid | Self::RepairedRid => "",
+        }
+    }
+
+    pub fn decode(&self, data: &[u8]) -> Option<ExtensionValue> {
+        let value = match self {
+            Self::AbsSendTime => {
+                // 6.18 fixed point seconds
+                ExtensionValue::AbsSendTime(read_u24(data)? as f64 / (1 << 18) as f64)
+            }
+            Self::TransportWideCc => {
+                ExtensionValue::TransportWideCc(u16::from_be_bytes([*data.first()?, *data.get(1)?]))
+            }
+            Self::AudioLevel => {
+                let byte = *data.first()?;
+                ExtensionValue::AudioLevel {
+                    voice: byte & 0x80 != 0,
+                    level: byte & 0x7F,
+                }
+            }
+            Self::VideoOrientation => {
+                let byte = *data.first()?;
+                ExtensionValue::VideoOrientation {
+                    back_camera: byte & 0x08 != 0,
+                    flip: byte & 0x04 != 0,
+                    rotation: (byte & 0x03) as u16 * 90,
+                }
+            }
+            Self::TransmissionOffset => {
+                // 24 bit signed
+                let offset = read_u24(data)?;
+                ExtensionValue::TransmissionOffset(((offset << 8) as i32) >> 8)
+            }
+            Self::Mid => ExtensionValue::Mid(read_string(data)?),
+            Self::Rid => ExtensionValue::Rid(read_string(data)?),
+            Self::RepairedRid => ExtensionValue::RepairedRid(read_string(data)?),
+            Self::PlayoutDelay => {
+                // 12 bit minimum and maximum in 10 ms units
+                let delays = read_u24(data)?;
+                ExtensionValue::PlayoutDelay {
+                    min_ms: (delays >> 12) as u16 * 10,
+                    max_ms: (delays & 0xFFF) as u16 * 10,
+                }
+            }
+            Self::AbsCaptureTime => ExtensionValue::AbsCaptureTime {
+                ntp_timestamp: u64::from_be_bytes(data.get(..8)?.try_into().ok()?),
+                clock_offset: data
+                    .get(8..16)
+                    .and_then(|offset| offset.try_into().ok())
+                    .map(i64::from_be_bytes),
+            },
+            Self::DependencyDescriptor => {
+                // only the mandatory fields, the rest needs the template structure
+                let byte = *data.first()?;
+                ExtensionValue::DependencyDescriptor {
+                    start_of_frame: byte & 0x80 != 0,
+                    end_of_frame: byte & 0x40 != 0,
+                    template_id: byte & 0x3F,
+                    frame_number: u16::from_be_bytes([*data.get(1)?, *data.get(2)?]),
+                }
+            }
+        };
+
+        Some(value)
+    }
+}
+
+impl fmt::Display for ExtensionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::AbsSendTime => "Absolute send time",
+            Self::TransportWideCc => "Transport-wide CC sequence number",
+            Self::AudioLevel => "Audio level",
+            Self::VideoOrientation => "Video orientation",
+            Self::TransmissionOffset => "Transmission time offset",
+            Self::Mid => "MID",
+            Self::Rid => "RID",
+            Self::RepairedRid => "Repaired RID",
+            Self::PlayoutDelay => "Playout delay",
+            Self::AbsCaptureTime => "Absolute capture time",
+            Self::DependencyDescriptor => "AV1 dependency descriptor",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExtensionValue {
+    AbsSendTime(f64),
+    TransportWideCc(u16),
+    // level in -dBov
+    AudioLevel {
+        voice: bool,
+        level: u8,
+    },
+    VideoOrientation {
+        back_camera: bool,
+        flip: bool,
+        rotation: u16,
+    },
+    TransmissionOffset(i32),
+    Mid(String),
+    Rid(String),
+    RepairedRid(String),
+    PlayoutDelay {
+        min_ms: u16,
+        max_ms: u16,
+    },
+    AbsCaptureTime {
+        ntp_timestamp: u64,
+        clock_offset: Option<i64>,
+    },
+    DependencyDescriptor {
+        start_of_frame: bool,
+        end_of_frame: bool,
+        template_id: u8,
+        frame_number: u16,
+    },
+}
+
+impl ExtensionValue {
+    pub fn kind(&self) -> ExtensionKind {
+        match self {
+            Self::AbsSendTime(_) => ExtensionKind::AbsSendTime,
+            Self::TransportWideCc(_) => ExtensionKind::TransportWideCc,
+            Self::AudioLevel { .. } => ExtensionKind::AudioLevel,
+            Self::VideoOrientation { .. } => ExtensionKind::VideoOrientation,
+            Self::TransmissionOffset(_) => ExtensionKind::TransmissionOffset,
+            Self::Mid(_) => ExtensionKind::Mid,
+            Self::Rid(_) => ExtensionKind::Rid,
+            Self::RepairedRid(_) => ExtensionKind::RepairedRid,
+            Self::PlayoutDelay { .. } => ExtensionKind::PlayoutDelay,
+            Self::AbsCaptureTime { .. } => ExtensionKind::AbsCaptureTime,
+            Self::DependencyDescriptor { .. } => ExtensionKind::DependencyDescriptor,
+        }
+    }
+
+    /// Number to plot or compare, in the [`ExtensionKind::unit`] of the kind.
+    pub fn as_f64(&self) -> Option<f64> {
+        let value = match *self {
+            Self::AbsSendTime(secs) => secs,
+            Self::TransportWideCc(seq) => seq as f64,
+            Self::AudioLevel { level, .. } => -(level as f64),
+            Self::VideoOrientation { rotation, .. } => rotation as f64,
+            Self::TransmissionOffset(offset) => offset as f64,
+            Self::PlayoutDelay { min_ms, .. } => min_ms as f64,
+            Self::AbsCaptureTime { ntp_timestamp, .. } => {
+                ntp_timestamp as f64 / (1u64 << 32) as f64
+            }
+            Self::DependencyDescriptor { frame_number, .. } => frame_number as f64,
+            Self::Mid(_) | Self::Rid(_) | Self::RepairedRid(_) => return None,
+        };
+
+        Some(value)
+    }
+}
+
+impl fmt::Display for ExtensionValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AbsSendTime(secs) => write!(f, "{:.6} s", secs),
+            Self::TransportWideCc(seq) => write!(f, "{}", seq),
+            Self::AudioLevel { voice, level } => {
+                write!(f, "-{} dBov", level)?;
+                if *voice {
+                    write!(f, " (voice)")?;
+                }
+                Ok(())
+            }
+            Self::VideoOrientation {
+                back_camera,
+                flip,
+                rotation,
+            } => {
+                write!(f, "{}°", rotation)?;
+                if *flip {
+                    write!(f, ", flipped")?;
+                }
+                if *back_camera {
+                    write!(f, ", back camera")?;
+                }
+                Ok(())
+            }
+            Self::TransmissionOffset(offset) => write!(f, "{}", offset),
+            Self::Mid(mid) | Self::Rid(mid) | Self::RepairedRid(mid) => write!(f, "{}", mid),
+            Self::PlayoutDelay { min_ms, max_ms } => write!(f, "{}-{} ms", min_ms, max_ms),
+            Self::AbsCaptureTime {
+                ntp_timestamp,
+                clock_offset,
+            } => {
+                write!(f, "{:.6} s", *ntp_timestamp as f64 / (1u64 << 32) as f64)?;
+                if let Some(offset) = clock_offset {
+                    write!(f, ", offset {:.6} s", *offset as f64 / (1u64 << 32) as f64)?;
+                }
+                Ok(())
+            }
+            Self::DependencyDescriptor {
+                start_of_frame,
+                end_of_frame,
+                template_id,
+                frame_number,
+            } => {
+                write!(f, "frame {}, template {}", frame_number, template_id)?;
+                if *start_of_frame {
+                    write!(f, ", start")?;
+                }
+                if *end_of_frame {
+                    write!(f, ", end")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn read_u24(data: &[u8]) -> Option<u32> {
+    let bytes = data.get(..3)?;
+    Some(u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]))
+}
+
+fn read_string(data: &[u8]) -> Option<String> {
+    std::str::from_utf8(data).ok().map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(uri: &str, data: &[u8]) -> ExtensionValue {
+        ExtensionKind::from_uri(uri).unwrap().decode(data).unwrap()
+    }
+
+    #[test]
+    fn test_decode_timing_extensions() {
+        assert_eq!(
+            decode(ABS_SEND_TIME_URI, &[0x04, 0x00, 0x00]),
+            ExtensionValue::AbsSendTime(1.0)
+        );
+        assert_eq!(
+            decode(TOFFSET_URI, &[0xFF, 0xFF, 0xFE]),
+            ExtensionValue::TransmissionOffset(-2)
+        );
+        assert_eq!(
+            decode(PLAYOUT_DELAY_URI, &[0x00, 0x10, 0x0A]),
+            ExtensionValue::PlayoutDelay {
+                min_ms: 10,
+                max_ms: 100
+            }
+        );
+
+        let capture_time = decode(
+            ABS_CAPTURE_TIME_URI,
+            &[
+                0, 0, 0, 2, 0x80, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 0,
+            ],
+        );
+        assert_eq!(
+            capture_time,
+            ExtensionValue::AbsCaptureTime {
+                ntp_timestamp: 0x2_8000_0000,
+                clock_offset: Some(-(1 << 32)),
+            }
+        );
+        assert_eq!(capture_time.as_f64(), Some(2.5));
+    }
+
+    #[test]
+    fn test_decode_media_extensions() {
+        let level = decode(AUDIO_LEVEL_URI, &[0x80 | 30]);
+        assert_eq!(
+            level,
+            ExtensionValue::AudioLevel {
+                voice: true,
+                level: 30
+            }
+        );
+        assert_eq!(level.as_f64(), Some(-30.0));
+        assert_eq!(level.to_string(), "-30 dBov (voice)");
+
+        assert_eq!(
+            decode(VIDEO_ORIENTATION_URI, &[0x0D]),
+            ExtensionValue::VideoOrientation {
+                back_camera: true,
+                flip: true,
+                rotation: 90
+            }
+        );
+        assert_eq!(
+            decode(TRANSPORT_WIDE_CC_02_URI, &[0x01, 0x02, 0x80]),
+            ExtensionValue::TransportWideCc(0x0102)
+        );
+        assert_eq!(
+            decode(DEPENDENCY_DESCRIPTOR_URI, &[0x81, 0x00, 0x2A, 0xFF]),
+            ExtensionValue::DependencyDescriptor {
+                start_of_frame: true,
+                end_of_frame: false,
+                template_id: 1,
+                frame_number: 42
+            }
+        );
+        assert_eq!(
+            decode(MID_URI, b"audio"),
+            ExtensionValue::Mid("audio".into())
+        );
+        assert_eq!(decode(RID_URI, b"hi").as_f64(), None);
+    }
+
+    #[test]
+    fn test_truncated_and_unknown() {
+        assert_eq!(ExtensionKind::AbsSendTime.decode(&[0x01, 0x02]), None);
+        assert_eq!(ExtensionKind::AbsCaptureTime.decode(&[0; 7]), None);
+        assert_eq!(ExtensionKind::from_uri("urn:example:unknown"), None);
+        assert_eq!(
+            ExtensionKind::from_name("audio_level"),
+            Some(ExtensionKind::AudioLevel)
+        );
+    }
+}
@@ -0,0 +1,254 @@
+use crate::Sdp;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::text_message::TextMessage;
+use crate::text_message::header;
+use bincode::{Decode, Encode};
+use std::fmt;
+use std::net::IpAddr;
+
+pub use crate::text_message::StartLine as RtspStartLine;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use session::RtspSessions;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod session;
+
+#[cfg(not(target_arch = "wasm32"))]
+const RTSP_VERSION: &str = "RTSP/1.0";
+
+/// One of the alternatives of the `Transport` header, RFC 2326 section 12.39.
+#[derive(Decode, Encode, Debug, Clone, Default, PartialEq)]
+pub struct RtspTransport {
+    // e.g. RTP/AVP or RTP/AVP/TCP
+    pub protocol: String,
+    pub multicast: bool,
+    pub destination: Option<IpAddr>,
+    pub source: Option<IpAddr>,
+    // RTP and RTCP ports, the RTCP one follows the RTP one when not given
+    pub client_port: Option<(u16, u16)>,
+    pub server_port: Option<(u16, u16)>,
+    // multicast ports
+    pub port: Option<(u16, u16)>,
+    // RTP and RTCP channels of the data interleaved on the RTSP connection
+    pub interleaved: Option<(u8, u8)>,
+    pub ssrc: Option<u32>,
+}
+
+#[derive(Decode, Encode, Debug, Clone)]
+pub struct RtspPacket {
+    pub start_line: RtspStartLine,
+    pub headers: Vec<(String, String)>,
+    pub cseq: Option<u32>,
+    // session identifier without the timeout
+    pub session: Option<String>,
+    pub transports: Vec<RtspTransport>,
+    pub sdp: Option<Sdp>,
+}
+
+impl RtspPacket {
+    pub fn is_request(&self) -> bool {
+        matches!(self.start_line, RtspStartLine::Request { .. })
+    }
+
+    pub fn status_code(&self) -> Option<u16> {
+        match self.start_line {
+            RtspStartLine::Response { status_code, .. } => Some(status_code),
+            RtspStartLine::Request { .. } => None,
+        }
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        header(&self.headers, name)
+    }
+}
+
+impl fmt::Display for RtspTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.protocol)?;
+        if let Some((rtp, rtcp)) = self.interleaved {
+            write!(f, " interleaved {}-{}", rtp, rtcp)?;
+        }
+        if let Some((rtp, rtcp)) = self.client_port {
+            write!(f, " client {}-{}", rtp, rtcp)?;
+        }
+        if let Some((rtp, rtcp)) = self.server_port {
+            write!(f, " server {}-{}", rtp, rtcp)?;
+        }
+        if let Some(ssrc) = self.ssrc {
+            write!(f, " ssrc {:x}", ssrc)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for RtspPacket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.start_line)?;
+        if self.sdp.is_some() {
+            write!(f, " with SDP")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl RtspPacket {
+    pub fn build(packet: &super::Packet) -> Option<Self> {
+        let payload = packet.payload.as_deref()?;
+        Self::parse(payload)
+    }
+
+    fn parse(payload: &[u8]) -> Option<Self> {
+        let message = TextMessage::parse(payload, RTSP_VERSION)?;
+        let sdp = message.sdp();
+
+        let mut rtsp = Self {
+            start_line: message.start_line,
+            headers: message.headers,
+            cseq: None,
+            session: None,
+            transports: Vec::new(),
+            sdp,
+        };
+        rtsp.cseq = rtsp.header("CSeq").and_then(|cseq| cseq.parse().ok());
+        rtsp.session = rtsp
+            .header("Session")
+            .and_then(|session| session.split(';').next())
+            .map(|id| id.trim().to_string());
+        rtsp.transports = rtsp
+            .header("Transport")
+            .map(|transport| transport.split(',').filter_map(parse_transport).collect())
+            .unwrap_or_default();
+
+        Some(rtsp)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn parse_transport(spec: &str) -> Option<RtspTransport> {
+    let mut params = spec.split(';').map(str::trim);
+    let mut transport = RtspTransport {
+        protocol: params
+            .next()
+            .filter(|protocol| !protocol.is_empty())?
+            .to_string(),
+        ..Default::default()
+    };
+
+    for param in params {
+        let (name, value) = param.split_once('=').unwrap_or((param, ""));
+        let value = value.trim_matches('"');
+        match name.to_ascii_lowercase().as_str() {
+            "multicast" => transport.multicast = true,
+            "destination" => transport.destination = value.parse().ok(),
+            "source" => transport.source = value.parse().ok(),
+            "client_port" => transport.client_port = range(value),
+            "server_port" => transport.server_port = range(value),
+            "port" => transport.port = range(value),
+            "interleaved" => transport.interleaved = range(value),
+            "ssrc" => transport.ssrc = u32::from_str_radix(value, 16).ok(),
+            _ => {}
+        }
+    }
+
+    Some(transport)
+}
+
+// "5000-5001" or just "5000", the second one follows the first
+#[cfg(not(target_arch = "wasm32"))]
+fn range<T>(value: &str) -> Option<(T, T)>
+where
+    T: std::str::FromStr + Copy + TryFrom<u32>,
+    u32: From<T>,
+{
+    match value.split_once('-') {
+        Some((first, second)) => Some((first.parse().ok()?, second.parse().ok()?)),
+        None => {
+            let first: T = value.parse().ok()?;
+            let second = T::try_from(u32::from(first) + 1).ok()?;
+            Some((first, second))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DESCRIBE_RESPONSE: &str = "RTSP/1.0 200 OK\r
+CSeq: 3\r
+Content-Base: rtsp://192.168.1.64:554/Streaming/Channels/101/\r
+Content-Type: application/sdp\r
+Content-Length: 225\r
+\r
+v=0\r
+o=- 1 1 IN IP4 192.168.1.64\r
+s=Media Presentation\r
+c=IN IP4 0.0.0.0\r
+t=0 0\r
+a=control:*\r
+m=video 0 RTP/AVP 96\r
+a=rtpmap:96 H264/90000\r
+a=control:trackID=1\r
+m=audio 0 RTP/AVP 8\r
+a=rtpmap:8 PCMA/8000\r
+a=control:trackID=2\r
+";
+
+    #[test]
+    fn test_parses_describe_response() {
+        let rtsp = RtspPacket::parse(DESCRIBE_RESPONSE.as_bytes()).unwrap();
+
+        assert_eq!(rtsp.status_code(), Some(200));
+        assert_eq!(rtsp.cseq, Some(3));
+        assert_eq!(
+            rtsp.header("content-base"),
+            Some("rtsp://192.168.1.64:554/Streaming/Channels/101/")
+        );
+
+        let sdp = rtsp.sdp.unwrap();
+        assert_eq!(sdp.media.len(), 2);
+        assert_eq!(sdp.media[0].control.as_deref(), Some("trackID=1"));
+        assert_eq!(sdp.media[1].control.as_deref(), Some("trackID=2"));
+        assert_eq!(sdp.media[1].payload_types[&8].name, "PCMA");
+    }
+
+    #[test]
+    fn test_parses_transport() {
+        let rtsp = RtspPacket::parse(
+            b"SETUP rtsp://cam/stream/trackID=1 RTSP/1.0\r\nCSeq: 4\r\n\
+              Transport: RTP/AVP/TCP;unicast;interleaved=0-1, RTP/AVP;unicast;client_port=5000\r\n\r\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            rtsp.start_line,
+            RtspStartLine::Request {
+                method: "SETUP".to_string(),
+                uri: "rtsp://cam/stream/trackID=1".to_string(),
+            }
+        );
+        assert_eq!(rtsp.transports.len(), 2);
+        assert_eq!(rtsp.transports[0].protocol, "RTP/AVP/TCP");
+        assert_eq!(rtsp.transports[0].interleaved, Some((0, 1)));
+        assert_eq!(rtsp.transports[1].client_port, Some((5000, 5001)));
+
+        let rtsp = RtspPacket::parse(
+            b"RTSP/1.0 200 OK\r\nCSeq: 4\r\nSession: 12345678;timeout=60\r\n\
+              Transport: RTP/AVP;unicast;client_port=5000-5001;server_port=6970-6971;ssrc=0A13C760\r\n\r\n",
+        )
+        .unwrap();
+
+        assert_eq!(rtsp.session.as_deref(), Some("12345678"));
+        assert_eq!(rtsp.transports[0].server_port, Some((6970, 6971)));
+        assert_eq!(rtsp.transports[0].ssrc, Some(0x0A13C760));
+    }
+
+    #[test]
+    fn test_rejects_other_text() {
+        assert!(RtspPacket::parse(b"INVITE sip:bob@host SIP/2.0\r\n\r\n").is_none());
+        assert!(RtspPacket::parse(b"GET / HTTP/1.1\r\nHost: x\r\n\r\n").is_none());
+        assert!(RtspPacket::parse(&[0x80, 0x60, 0, 1]).is_none());
+    }
+}
@@ -0,0 +1,504 @@
+use super::{RtspPacket, RtspStartLine, RtspTransport};
+use crate::packet::{SessionPacket, TcpFraming, TransportProtocol};
+use crate::sdp::{MediaDescription, SsrcDescription};
+use crate::{Packet, RtpStreamKey, Sdp, SessionProtocol};
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::time::Duration;
+
+// streams and connections without any packet for that long are forgotten
+const TIMEOUT: Duration = Duration::from_secs(120);
+const PRUNE_INTERVAL: Duration = Duration::from_secs(10);
+
+// client and server of the RTSP connection
+type ConnectionKey = (SocketAddr, SocketAddr);
+
+#[derive(Debug)]
+struct Description {
+    // control URLs of the media sections are relative to it
+    base: String,
+    sdp: Sdp,
+}
+
+#[derive(Debug)]
+struct Connection {
+    // pending requests by CSeq, with their method and URL
+    requests: HashMap<u32, (String, String)>,
+    description: Option<Description>,
+    setups: usize,
+    last_seen: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Delivery {
+    // RTP and RTCP addresses of the receiver
+    Udp(SocketAddr, SocketAddr),
+    // RTP and RTCP channels on the RTSP connection
+    Interleaved(u8, u8),
+}
+
+#[derive(Debug)]
+struct Binding {
+    connection: ConnectionKey,
+    delivery: Delivery,
+    session_name: Option<String>,
+    media: MediaDescription,
+    described: HashSet<RtpStreamKey>,
+    last_seen: Duration,
+}
+
+/// Follows RTSP connections and sets up the streams they describe.
+///
+/// The SDP of DESCRIBE (or ANNOUNCE) and the `Transport` of every successful SETUP
+/// bind a media section to the ports or interleaved channels its RTP and RTCP use,
+/// so their packets are decoded without guessing and their streams get described.
+#[derive(Debug, Default)]
+pub struct RtspSessions {
+    connections: HashMap<ConnectionKey, Connection>,
+    bindings: Vec<Binding>,
+    last_prune: Duration,
+}
+
+impl RtspSessions {
+    /// Updates the connection of the message, returns the transport of the stream it sets up.
+    pub fn push(&mut self, packet: &Packet, rtsp: &RtspPacket) -> Option<RtspTransport> {
+        self.prune(packet.timestamp);
+
+        let key = match rtsp.is_request() {
+            true => (packet.source_addr, packet.destination_addr),
+            false => (packet.destination_addr, packet.source_addr),
+        };
+        let connection = self.connections.entry(key).or_insert_with(|| Connection {
+            requests: HashMap::new(),
+            description: None,
+            setups: 0,
+            last_seen: packet.timestamp,
+        });
+        connection.last_seen = packet.timestamp;
+        let cseq = rtsp.cseq?;
+
+        let (method, uri) = match rtsp.start_line {
+            RtspStartLine::Request {
+                ref method,
+                ref uri,
+            } => {
+                // the client describes what it's going to record
+                if method == "ANNOUNCE"
+                    && let Some(ref sdp) = rtsp.sdp
+                {
+                    connection.description = Some(Description {
+                        base: uri.clone(),
+                        sdp: sdp.clone(),
+                    });
+                }
+                connection
+                    .requests
+                    .insert(cseq, (method.clone(), uri.clone()));
+                return None;
+            }
+            RtspStartLine::Response { status_code, .. } => {
+                let request = connection.requests.remove(&cseq)?;
+                if !(200..300).contains(&status_code) {
+                    return None;
+                }
+                request
+            }
+        };
+
+        match method.as_str() {
+            "DESCRIBE" => {
+                let sdp = rtsp.sdp.clone()?;
+                let base = rtsp
+                    .header("Content-Base")
+                    .or(rtsp.header("Content-Location"))
+                    .map_or(uri, str::to_string);
+                connection.description = Some(Description { base, sdp });
+                None
+            }
+            "SETUP" => {
+                let transport = rtsp.transports.first()?;
+                let delivery = delivery(key, transport)?;
+                let description = connection.description.as_ref()?;
+                let mut media = description.find_media(&uri, connection.setups)?.clone();
+                connection.setups += 1;
+
+                if let Some(ssrc) = transport.ssrc
+                    && !media.has_ssrc(ssrc)
+                {
+                    media.ssrcs.push(SsrcDescription {
+                        ssrc,
+                        attributes: Vec::new(),
+                    });
+                }
+                // the section describes where the stream goes now, not the port 0 of the offer
+                if let Delivery::Udp(rtp, _) = delivery {
+                    media.port = rtp.port();
+                    media.connection = Some(rtp.ip());
+                }
+
+                let binding = Binding {
+                    connection: key,
+                    delivery,
+                    session_name: description.sdp.session_name.clone(),
+                    media,
+                    described: HashSet::new(),
+                    last_seen: packet.timestamp,
+                };
+                self.bindings
+                    .retain(|other| other.connection != key || other.delivery != delivery);
+                self.bindings.push(binding);
+                Some(transport.clone())
+            }
+            "TEARDOWN" => {
+                self.bindings.retain(|binding| binding.connection != key);
+                connection.setups = 0;
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// Decodes RTP and RTCP of the streams set up as such, returns whether the packet is one of them.
+    pub fn configure(&mut self, packet: &mut Packet) -> bool {
+        let Some((binding, protocol)) = self.find_binding(packet) else {
+            return false;
+        };
+        binding.last_seen = packet.timestamp;
+
+        if packet.session_protocol != protocol {
+            packet.parse_as(protocol);
+        }
+        packet.session_protocol == protocol
+    }
+
+    /// Returns the media section of an RTP stream set up over RTSP, once per stream.
+    pub fn describe(&mut self, packet: &Packet) -> Option<(RtpStreamKey, Sdp)> {
+        let SessionPacket::Rtp(ref rtp) = packet.contents else {
+            return None;
+        };
+        let (binding, SessionProtocol::Rtp) = self.find_binding(packet)? else {
+            return None;
+        };
+
+        let stream_key = packet.rtp_stream_key(rtp.ssrc);
+        if !binding.described.insert(stream_key) {
+            return None;
+        }
+
+        let sdp = Sdp {
+            session_name: binding.session_name.clone(),
+            connection: None,
+            media: vec![binding.media.clone()],
+        };
+        Some((stream_key, sdp))
+    }
+
+    pub fn reset(&mut self) {
+        self.connections.clear();
+        self.bindings.clear();
+        self.last_prune = Duration::ZERO;
+    }
+
+    fn find_binding(&mut self, packet: &Packet) -> Option<(&mut Binding, SessionProtocol)> {
+        let endpoints = [packet.source_addr, packet.destination_addr];
+        let channel = match (packet.transport_protocol, packet.metadata.framing) {
+            (TransportProtocol::Tcp, Some(TcpFraming::Interleaved { channel })) => Some(channel),
+            (TransportProtocol::Udp, _) => None,
+            _ => return None,
+        };
+
+        self.bindings.iter_mut().find_map(|binding| {
+            let protocol = match (binding.delivery, channel) {
+                (Delivery::Interleaved(rtp, rtcp), Some(channel)) => {
+                    let (client, server) = binding.connection;
+                    if !endpoints.contains(&client) || !endpoints.contains(&server) {
+                        return None;
+                    }
+                    match channel {
+                        _ if channel == rtp => SessionProtocol::Rtp,
+                        _ if channel == rtcp => SessionProtocol::Rtcp,
+                        _ => return None,
+                    }
+                }
+                (Delivery::Udp(rtp, _), None) if endpoints.contains(&rtp) => SessionProtocol::Rtp,
+                (Delivery::Udp(_, rtcp), None) if endpoints.contains(&rtcp) => {
+                    SessionProtocol::Rtcp
+                }
+                _ => return None,
+            };
+            Some((binding, protocol))
+        })
+    }
+
+    fn prune(&mut self, now: Duration) {
+        if now.saturating_sub(self.last_prune) < PRUNE_INTERVAL {
+            return;
+        }
+
+        self.connections
+            .retain(|_, connection| now.saturating_sub(connection.last_seen) < TIMEOUT);
+        self.bindings
+            .retain(|binding| now.saturating_sub(binding.last_seen) < TIMEOUT);
+        self.last_prune = now;
+    }
+}
+
+impl Description {
+    // cameras set the tracks up in order, which helps when the URLs don't match
+    fn find_media(&self, uri: &str, setups: usize) -> Option<&MediaDescription> {
+        let uri = uri.trim_end_matches('/');
+        let by_control = self.sdp.media.iter().find(|media| {
+            media.control.as_deref().is_some_and(|control| {
+                let url = match control.contains("://") {
+                    true => control.to_string(),
+                    false => format!("{}/{}", self.base.trim_end_matches('/'), control),
+                };
+                url.trim_end_matches('/') == uri
+            })
+        });
+
+        by_control.or_else(|| self.sdp.media.get(setups))
+    }
+}
+
+// where the RTP and RTCP of the stream go, the client receives unless it records
+fn delivery(connection: ConnectionKey, transport: &RtspTransport) -> Option<Delivery> {
+    if let Some((rtp, rtcp)) = transport.interleaved {
+        return Some(Delivery::Interleaved(rtp, rtcp));
+    }
+
+    let (client, _) = connection;
+    let (ip, (rtp, rtcp)) = match transport.multicast {
+        true => (transport.destination?, transport.port?),
+        false => (
+            transport.destination.unwrap_or(client.ip()),
+            transport.client_port?,
+        ),
+    };
+    Some(Delivery::Udp(
+        SocketAddr::new(ip, rtp),
+        SocketAddr::new(ip, rtcp),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::{DecodeRules, PacketMetadata};
+    use std::time::SystemTime;
+
+    const CLIENT: &str = "192.168.1.10:50000";
+    const SERVER: &str = "192.168.1.64:554";
+    const SDP: &str = "v=0\r\ns=Camera\r\nc=IN IP4 0.0.0.0\r\nt=0 0\r\n\
+        m=video 0 RTP/AVP 96\r\na=rtpmap:96 H264/90000\r\na=control:trackID=1\r\n\
+        m=audio 0 RTP/AVP 8\r\na=rtpmap:8 PCMA/8000\r\na=control:trackID=2\r\n";
+
+    fn packet(
+        source: &str,
+        destination: &str,
+        transport_protocol: TransportProtocol,
+        framing: Option<TcpFraming>,
+        payload: Vec<u8>,
+    ) -> Packet {
+        let mut packet = Packet {
+            length: payload.len() as u32,
+            payload: Some(payload),
+            id: 0,
+            timestamp: Duration::ZERO,
+            source_addr: source.parse().unwrap(),
+            destination_addr: destination.parse().unwrap(),
+            transport_protocol,
+            session_protocol: SessionProtocol::Unknown,
+            contents: SessionPacket::Unknown,
+            creation_time: SystemTime::now(),
+            metadata: PacketMetadata {
+                framing,
+                ..Default::default()
+            },
+        };
+        packet.guess_payload(&DecodeRules::default());
+        packet
+    }
+
+    fn message(sessions: &mut RtspSessions, request: bool, text: String) -> Option<RtspTransport> {
+        let (source, destination) = match request {
+            true => (CLIENT, SERVER),
+            false => (SERVER, CLIENT),
+        };
+        let packet = packet(
+            source,
+            destination,
+            TransportProtocol::Tcp,
+            Some(TcpFraming::RtspMessage),
+            text.into_bytes(),
+        );
+        let SessionPacket::Rtsp(ref rtsp) = packet.contents else {
+            panic!("not decoded as RTSP");
+        };
+        sessions.push(&packet, rtsp)
+    }
+
+    fn describe(sessions: &mut RtspSessions) {
+        message(
+            sessions,
+            true,
+            "DESCRIBE rtsp://192.168.1.64/live RTSP/1.0\r\nCSeq: 2\r\n\r\n".to_string(),
+        );
+        message(
+            sessions,
+            false,
+            format!(
+                "RTSP/1.0 200 OK\r\nCSeq: 2\r\nContent-Base: rtsp://192.168.1.64/live/\r\n\
+                 Content-Type: application/sdp\r\nContent-Length: {}\r\n\r\n{}",
+                SDP.len(),
+                SDP
+            ),
+        );
+    }
+
+    fn setup(sessions: &mut RtspSessions, cseq: u32, track: &str, transport: &str) -> bool {
+        message(
+            sessions,
+            true,
+            format!(
+                "SETUP rtsp://192.168.1.64/live/{} RTSP/1.0\r\nCSeq: {}\r\nTransport: {}\r\n\r\n",
+                track, cseq, transport
+            ),
+        );
+        message(
+            sessions,
+            false,
+            format!(
+                "RTSP/1.0 200 OK\r\nCSeq: {}\r\nSession: 1234\r\nTransport: {}\r\n\r\n",
+                cseq, transport
+            ),
+        )
+        .is_some()
+    }
+
+    fn rtp(ssrc: u32) -> Vec<u8> {
+        let mut payload = vec![0x80, 0x60, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01];
+        payload.extend_from_slice(&ssrc.to_be_bytes());
+        payload.extend_from_slice(&[0; 20]);
+        payload
+    }
+
+    #[test]
+    fn test_sets_up_interleaved_streams() {
+        let mut sessions = RtspSessions::default();
+        describe(&mut sessions);
+        // the audio track first, so the order doesn't decide
+        assert!(setup(
+            &mut sessions,
+            3,
+            "trackID=2",
+            "RTP/AVP/TCP;unicast;interleaved=2-3"
+        ));
+        assert!(setup(
+            &mut sessions,
+            4,
+            "trackID=1",
+            "RTP/AVP/TCP;unicast;interleaved=0-1;ssrc=0000ABCD"
+        ));
+
+        let interleaved = |channel, payload| {
+            packet(
+                SERVER,
+                CLIENT,
+                TransportProtocol::Tcp,
+                Some(TcpFraming::Interleaved { channel }),
+                payload,
+            )
+        };
+
+        let mut video = interleaved(0, rtp(0xABCD));
+        assert!(sessions.configure(&mut video));
+        let (stream_key, sdp) = sessions.describe(&video).unwrap();
+        assert_eq!(stream_key.3, 0xABCD);
+        assert_eq!(sdp.media.len(), 1);
+        assert_eq!(sdp.media[0].media, "video");
+        assert!(sdp.media[0].has_ssrc(0xABCD));
+        // described once per stream
+        assert!(sessions.describe(&video).is_none());
+
+        let audio = interleaved(2, rtp(0x1111));
+        assert_eq!(sessions.describe(&audio).unwrap().1.media[0].media, "audio");
+
+        let mut rtcp = interleaved(3, vec![0x80, 0xc9, 0x00, 0x01, 0, 0, 0x11, 0x11]);
+        assert!(sessions.configure(&mut rtcp));
+        assert_eq!(rtcp.session_protocol, SessionProtocol::Rtcp);
+
+        // the keep-alive starts with the MPEG-TS sync byte
+        let keep_alive = "GET_PARAMETER rtsp://192.168.1.64/live/ RTSP/1.0\r\nCSeq: 5\r\n\
+                          Session: 1234\r\n\r\n";
+        assert!(message(&mut sessions, true, keep_alive.to_string()).is_none());
+        let response = "RTSP/1.0 200 OK\r\nCSeq: 5\r\nSession: 1234\r\n\r\n";
+        assert!(message(&mut sessions, false, response.to_string()).is_none());
+        assert!(sessions.configure(&mut interleaved(0, rtp(0xABCD))));
+
+        message(
+            &mut sessions,
+            true,
+            "TEARDOWN rtsp://192.168.1.64/live/ RTSP/1.0\r\nCSeq: 6\r\n\r\n".to_string(),
+        );
+        message(
+            &mut sessions,
+            false,
+            "RTSP/1.0 200 OK\r\nCSeq: 6\r\n\r\n".to_string(),
+        );
+        let mut video = interleaved(0, rtp(0xABCD));
+        assert!(!sessions.configure(&mut video));
+    }
+
+    #[test]
+    fn test_sets_up_udp_streams() {
+        let mut sessions = RtspSessions::default();
+        describe(&mut sessions);
+        assert!(setup(
+            &mut sessions,
+            3,
+            "trackID=1",
+            "RTP/AVP;unicast;client_port=5000-5001;server_port=6970-6971"
+        ));
+
+        // decoded before the classifier would confirm the flow
+        let mut video = packet(
+            "192.168.1.64:6970",
+            "192.168.1.10:5000",
+            TransportProtocol::Udp,
+            None,
+            rtp(0x2222),
+        );
+        assert!(sessions.configure(&mut video));
+        assert_eq!(video.session_protocol, SessionProtocol::Rtp);
+
+        let (_, sdp) = sessions.describe(&video).unwrap();
+        assert_eq!(sdp.session_name.as_deref(), Some("Camera"));
+        assert_eq!(sdp.media[0].port, 5000);
+
+        let mut other = packet(
+            "192.168.1.64:6972",
+            "192.168.1.10:5002",
+            TransportProtocol::Udp,
+            None,
+            rtp(0x3333),
+        );
+        assert!(!sessions.configure(&mut other));
+    }
+
+    #[test]
+    fn test_failed_setup() {
+        let mut sessions = RtspSessions::default();
+        describe(&mut sessions);
+
+        message(
+            &mut sessions,
+            true,
+            "SETUP rtsp://192.168.1.64/live/trackID=1 RTSP/1.0\r\nCSeq: 3\r\n\
+             Transport: RTP/AVP;multicast\r\n\r\n"
+                .to_string(),
+        );
+        let response = "RTSP/1.0 461 Unsupported Transport\r\nCSeq: 3\r\n\r\n".to_string();
+        assert!(message(&mut sessions, false, response).is_none());
+    }
+}
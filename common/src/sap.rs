@@ -0,0 +1,212 @@
+use crate::Sdp;
+use bincode::{Decode, Encode};
+use std::fmt;
+use std::net::IpAddr;
+
+/// Well known port of the announcements, RFC 2974 section 3.
+pub const SAP_PORT: u16 = 9875;
+
+#[cfg(not(target_arch = "wasm32"))]
+const SAP_VERSION: u8 = 1;
+#[cfg(not(target_arch = "wasm32"))]
+const HEADER_LEN: usize = 4;
+#[cfg(not(target_arch = "wasm32"))]
+const MAX_INFLATED_LEN: u64 = 64 * 1024;
+
+/// Session Announcement Protocol message, RFC 2974.
+#[derive(Decode, Encode, Debug, Clone)]
+pub struct SapPacket {
+    // the session is deleted instead of announced
+    pub deletion: bool,
+    pub encrypted: bool,
+    pub compressed: bool,
+    pub message_id_hash: u16,
+    pub origin: IpAddr,
+    pub payload_type: Option<String>,
+    // None when encrypted or for deletions, which only carry the origin line
+    pub sdp: Option<Sdp>,
+}
+
+impl SapPacket {
+    /// Identifies the announced session across its repetitions.
+    pub fn announcement_id(&self) -> (IpAddr, u16) {
+        (self.origin, self.message_id_hash)
+    }
+}
+
+impl fmt::Display for SapPacket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = if self.deletion {
+            "Deletion"
+        } else {
+            "Announcement"
+        };
+        write!(
+            f,
+            "{} {:04x} from {}",
+            kind, self.message_id_hash, self.origin
+        )?;
+        if let Some(name) = self.sdp.as_ref().and_then(|sdp| sdp.session_name.as_ref()) {
+            write!(f, ": {}", name)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl SapPacket {
+    pub fn build(packet: &super::Packet) -> Option<Self> {
+        let payload = packet.payload.as_deref()?;
+        Self::parse(payload)
+    }
+
+    fn parse(payload: &[u8]) -> Option<Self> {
+        let header = payload.get(..HEADER_LEN)?;
+        if header[0] >> 5 != SAP_VERSION {
+            return None;
+        }
+        let ipv6 = header[0] & 0x10 != 0;
+        let deletion = header[0] & 0x04 != 0;
+        let encrypted = header[0] & 0x02 != 0;
+        let compressed = header[0] & 0x01 != 0;
+        let auth_len = header[1] as usize * 4;
+        let message_id_hash = u16::from_be_bytes([header[2], header[3]]);
+
+        let (origin, rest) = match ipv6 {
+            true => {
+                let octets: [u8; 16] = payload.get(HEADER_LEN..HEADER_LEN + 16)?.try_into().ok()?;
+                (IpAddr::from(octets), &payload[HEADER_LEN + 16..])
+            }
+            false => {
+                let octets: [u8; 4] = payload.get(HEADER_LEN..HEADER_LEN + 4)?.try_into().ok()?;
+                (IpAddr::from(octets), &payload[HEADER_LEN + 4..])
+            }
+        };
+        let rest = rest.get(auth_len..)?;
+
+        let mut sap = Self {
+            deletion,
+            encrypted,
+            compressed,
+            message_id_hash,
+            origin,
+            payload_type: None,
+            sdp: None,
+        };
+        // keys of encrypted announcements aren't known
+        if encrypted {
+            return Some(sap);
+        }
+
+        let inflated;
+        let rest = match compressed {
+            true => {
+                inflated = inflate(rest)?;
+                &inflated[..]
+            }
+            false => rest,
+        };
+
+        // the payload type may be left out before an SDP payload
+        let body = match rest.starts_with(b"v=0") {
+            true => rest,
+            false => {
+                let end = rest.iter().position(|&byte| byte == 0)?;
+                let payload_type = std::str::from_utf8(&rest[..end]).ok()?;
+                sap.payload_type = Some(payload_type.to_string());
+                &rest[end + 1..]
+            }
+        };
+
+        let is_sdp = sap.payload_type.as_deref().is_none_or(|payload_type| {
+            payload_type.eq_ignore_ascii_case(crate::sip::SDP_CONTENT_TYPE)
+        });
+        if is_sdp {
+            sap.sdp = Sdp::build(String::from_utf8_lossy(body).into_owned());
+        }
+
+        Some(sap)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn inflate(data: &[u8]) -> Option<Vec<u8>> {
+    use std::io::Read;
+
+    let mut inflated = Vec::new();
+    flate2::read::ZlibDecoder::new(data)
+        .take(MAX_INFLATED_LEN)
+        .read_to_end(&mut inflated)
+        .ok()?;
+    Some(inflated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    const SDP: &str = "v=0\r
+o=- 1311738121 1311738121 IN IP4 192.168.1.21\r
+s=Stage left\r
+c=IN IP4 239.69.83.133/32\r
+t=0 0\r
+m=audio 5004 RTP/AVP 97\r
+a=rtpmap:97 L24/48000/8\r
+a=ptime:0.125\r
+a=recvonly\r
+";
+
+    fn announcement(flags: u8, payload: &[u8]) -> Vec<u8> {
+        let mut data = vec![0x20 | flags, 0x00, 0xAB, 0xCD, 192, 168, 1, 21];
+        data.extend_from_slice(payload);
+        data
+    }
+
+    #[test]
+    fn test_parses_announcement() {
+        let mut payload = b"application/sdp\0".to_vec();
+        payload.extend_from_slice(SDP.as_bytes());
+        let sap = SapPacket::parse(&announcement(0, &payload)).unwrap();
+
+        assert!(!sap.deletion);
+        assert_eq!(
+            sap.announcement_id(),
+            ("192.168.1.21".parse().unwrap(), 0xABCD)
+        );
+        assert_eq!(sap.payload_type.as_deref(), Some("application/sdp"));
+        assert_eq!(
+            sap.to_string(),
+            "Announcement abcd from 192.168.1.21: Stage left"
+        );
+
+        let media = &sap.sdp.unwrap().media[0];
+        assert_eq!(media.port, 5004);
+        assert_eq!(media.connection, Some("239.69.83.133".parse().unwrap()));
+        assert_eq!(media.payload_types[&97].clock_rate, Some(48000));
+        assert_eq!(media.ptime, Some(0.125));
+    }
+
+    #[test]
+    fn test_parses_compressed_announcement_without_type() {
+        let mut encoder =
+            flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(SDP.as_bytes()).unwrap();
+        let sap = SapPacket::parse(&announcement(0x01, &encoder.finish().unwrap())).unwrap();
+
+        assert!(sap.compressed);
+        assert_eq!(sap.payload_type, None);
+        assert!(sap.sdp.is_some());
+    }
+
+    #[test]
+    fn test_parses_deletion() {
+        let payload = b"application/sdp\0o=- 1311738121 1311738121 IN IP4 192.168.1.21\r\n";
+        let sap = SapPacket::parse(&announcement(0x04, payload)).unwrap();
+
+        assert!(sap.deletion);
+        assert!(sap.sdp.is_none());
+        // version 2 doesn't exist
+        assert!(SapPacket::parse(&[0x40, 0, 0, 0, 1, 2, 3, 4]).is_none());
+    }
+}
@@ -1,62 +1,763 @@
+use crate::RtpPacket;
+use crate::rtp::extension::{ExtensionKind, ExtensionValue};
 use crate::rtp::payload_type::PayloadType;
 use bincode::{Decode, Encode};
 use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
 
+/// Session description, either a whole session or a lone media section.
+///
+/// Media sections inherit the session level connection address, header extensions,
+/// DTLS and ICE attributes they don't override, so they can be used on their own.
 #[derive(Decode, Encode, Debug, Clone)]
 pub struct Sdp {
+    pub session_name: Option<String>,
+    pub connection: Option<IpAddr>,
+    pub media: Vec<MediaDescription>,
+}
+
+#[derive(Decode, Encode, Debug, Clone)]
+pub struct MediaDescription {
+    // audio, video or application
+    pub media: String,
+    pub port: u16,
+    pub protocol: String,
+    pub connection: Option<IpAddr>,
     pub payload_types: HashMap<u8, PayloadType>,
+    // packet time in milliseconds, AES67 uses fractions like 0.125
+    pub ptime: Option<f64>,
+    // format parameters as written, e.g. "minptime=10;useinbandfec=1"
+    pub fmtp: HashMap<u8, String>,
+    pub rtcp_fb: Vec<RtcpFeedback>,
+    // header extension ids to their URIs
+    pub extmap: HashMap<u8, String>,
+    pub ssrcs: Vec<SsrcDescription>,
+    pub ssrc_groups: Vec<SsrcGroup>,
+    pub mid: Option<String>,
+    pub rids: Vec<Rid>,
+    pub simulcast: Option<Simulcast>,
+    pub setup: Option<DtlsSetup>,
+    pub fingerprint: Option<Fingerprint>,
+    pub ice_ufrag: Option<String>,
+    pub ice_pwd: Option<String>,
+    // SDES keys the sender of the description encrypts its streams with
+    pub crypto: Vec<Crypto>,
+    // RTSP control URL of the section, absolute or relative to the base URL
+    pub control: Option<String>,
+}
+
+#[derive(Decode, Encode, Debug, Clone, PartialEq)]
+pub struct RtcpFeedback {
+    // None for the "*" wildcard
+    pub payload_type: Option<u8>,
+    // e.g. "nack pli" or "transport-cc"
+    pub feedback: String,
+}
+
+#[derive(Decode, Encode, Debug, Clone, PartialEq)]
+pub struct SsrcDescription {
+    pub ssrc: u32,
+    // source attributes, e.g. cname or msid, with their values
+    pub attributes: Vec<(String, String)>,
+}
+
+impl SsrcDescription {
+    pub fn cname(&self) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(name, _)| name == "cname")
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+#[derive(Decode, Encode, Debug, Clone, Copy, PartialEq)]
+pub enum SsrcGroupSemantics {
+    // retransmissions, RFC 4588
+    Fid,
+    Sim,
+    Fec,
+    FecFr,
+    Dup,
+}
+
+impl fmt::Display for SsrcGroupSemantics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let res = match self {
+            Self::Fid => "FID",
+            Self::Sim => "SIM",
+            Self::Fec => "FEC",
+            Self::FecFr => "FEC-FR",
+            Self::Dup => "DUP",
+        };
+
+        write!(f, "{}", res)
+    }
+}
+
+#[derive(Decode, Encode, Debug, Clone, PartialEq)]
+pub struct SsrcGroup {
+    pub semantics: SsrcGroupSemantics,
+    pub ssrcs: Vec<u32>,
+}
+
+#[derive(Decode, Encode, Debug, Clone, PartialEq)]
+pub struct Rid {
+    pub id: String,
+    pub send: bool,
+    pub formats: Vec<u16>,
+}
+
+/// Simulcast streams by their RIDs, each entry lists the alternatives of one stream.
+#[derive(Decode, Encode, Debug, Clone, Default, PartialEq)]
+pub struct Simulcast {
+    pub send: Vec<Vec<String>>,
+    pub receive: Vec<Vec<String>>,
+}
+
+#[derive(Decode, Encode, Debug, Clone, Copy, PartialEq)]
+pub enum DtlsSetup {
+    Active,
+    Passive,
+    Actpass,
+    Holdconn,
+}
+
+impl fmt::Display for DtlsSetup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let res = match self {
+            Self::Active => "active",
+            Self::Passive => "passive",
+            Self::Actpass => "actpass",
+            Self::Holdconn => "holdconn",
+        };
+
+        write!(f, "{}", res)
+    }
+}
+
+/// Certificate fingerprint of the DTLS handshake.
+#[derive(Decode, Encode, Debug, Clone, PartialEq)]
+pub struct Fingerprint {
+    // hash function name, e.g. "sha-256"
+    pub algorithm: String,
+    pub value: Vec<u8>,
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hex: Vec<_> = self
+            .value
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect();
+        write!(f, "{} {}", self.algorithm, hex.join(":"))
+    }
+}
+
+/// SDES master key of an `a=crypto` attribute (RFC 4568).
+#[derive(Decode, Encode, Debug, Clone, PartialEq)]
+pub struct Crypto {
+    pub tag: u32,
+    // e.g. "AES_CM_128_HMAC_SHA1_80"
+    pub suite: String,
+    // master key followed by the master salt
+    pub key: Vec<u8>,
+    // length of the MKI the packets carry, 0 without one
+    pub mki_length: usize,
+}
+
+impl Sdp {
+    /// Media section describing the RTP stream, matched by the SSRC, then by
+    /// the MID or RID header extensions and at last by the port.
+    pub fn find_media(
+        &self,
+        source_addr: SocketAddr,
+        destination_addr: SocketAddr,
+        rtp: &RtpPacket,
+    ) -> Option<&MediaDescription> {
+        if let Some(media) = self.media.iter().find(|media| media.has_ssrc(rtp.ssrc)) {
+            return Some(media);
+        }
+
+        for extension in &rtp.extensions {
+            let Some(value) = self
+                .extension_kind(extension.id)
+                .and_then(|kind| kind.decode(&extension.data))
+            else {
+                continue;
+            };
+
+            let media = match value {
+                ExtensionValue::Mid(ref mid) => self
+                    .media
+                    .iter()
+                    .find(|media| media.mid.as_ref() == Some(mid)),
+                ExtensionValue::Rid(ref rid) | ExtensionValue::RepairedRid(ref rid) => self
+                    .media
+                    .iter()
+                    .find(|media| media.rids.iter().any(|r| r.id == *rid)),
+                _ => None,
+            };
+            if media.is_some() {
+                return media;
+            }
+        }
+
+        // bundled sections share the port, the payload type tells them apart
+        let mut on_port = self
+            .media
+            .iter()
+            .filter(|media| media.receives_on(destination_addr) || media.receives_on(source_addr));
+        let first = on_port.clone().next();
+        on_port
+            .find(|media| media.payload_types.contains_key(&rtp.payload_type.id))
+            .or(first)
+    }
+
+    // the same extension is usually mapped to the same id in every section
+    fn extension_kind(&self, id: u8) -> Option<ExtensionKind> {
+        self.media
+            .iter()
+            .filter_map(|media| media.extmap.get(&id))
+            .find_map(|uri| ExtensionKind::from_uri(uri))
+    }
+}
+
+impl MediaDescription {
+    pub fn has_ssrc(&self, ssrc: u32) -> bool {
+        self.ssrcs.iter().any(|desc| desc.ssrc == ssrc)
+            || self
+                .ssrc_groups
+                .iter()
+                .any(|group| group.ssrcs.contains(&ssrc))
+    }
+
+    // port 0 rejects the section, an unspecified address means any
+    fn receives_on(&self, addr: SocketAddr) -> bool {
+        self.port != 0
+            && self.port == addr.port()
+            && self
+                .connection
+                .is_none_or(|ip| ip.is_unspecified() || ip == addr.ip())
+    }
 }
 
 #[cfg(not(target_arch = "wasm32"))]
 impl Sdp {
     pub fn build(raw_sdp: String) -> Option<Self> {
-        use crate::rtp::payload_type::MediaType;
-        use webrtc_sdp::{
-            SdpLine, SdpType, attribute_type::SdpAttribute, media_type::SdpMediaValue,
-            parse_sdp_line,
+        use webrtc_sdp::{SdpLine, SdpType, address::ExplicitlyTypedAddress, parse_sdp_line};
+
+        let mut sdp = Self {
+            session_name: None,
+            connection: None,
+            media: Vec::new(),
         };
+        // attributes which apply to every media section
+        let mut session = MediaDescription::new(String::new(), 0, String::new());
+
+        for line in raw_sdp.lines() {
+            let line = line.trim();
+            // kept as written, webrtc-sdp only knows some codec parameters and feedback types
+            if let Some(value) = line.strip_prefix("a=fmtp:") {
+                if let Some(media) = sdp.media.last_mut() {
+                    media.add_fmtp(value);
+                }
+                continue;
+            }
+            if let Some(value) = line.strip_prefix("a=rtcp-fb:") {
+                if let Some(media) = sdp.media.last_mut() {
+                    media.add_rtcp_fb(value);
+                }
+                continue;
+            }
+            // fractions aren't accepted by webrtc-sdp
+            if let Some(value) = line.strip_prefix("a=ptime:") {
+                sdp.media.last_mut().unwrap_or(&mut session).ptime = value.trim().parse().ok();
+                continue;
+            }
+            // the keys are given in base64 which webrtc-sdp leaves encoded
+            if let Some(value) = line.strip_prefix("a=crypto:") {
+                if let Some(media) = sdp.media.last_mut() {
+                    media.add_crypto(value);
+                }
+                continue;
+            }
+            // the aggregate control of the session isn't needed
+            if let Some(value) = line.strip_prefix("a=control:") {
+                if let Some(media) = sdp.media.last_mut() {
+                    media.control = Some(value.to_string());
+                }
+                continue;
+            }
 
-        let mut lines = raw_sdp.lines();
+            let Ok(SdpLine { sdp_type, .. }) = parse_sdp_line(line, 0) else {
+                continue;
+            };
 
-        // first line should be media
-        let first_line = lines.next()?;
-        let Ok(SdpLine {
-            sdp_type: SdpType::Media(media),
-            ..
-        }) = parse_sdp_line(first_line, 0)
-        else {
+            match sdp_type {
+                SdpType::Session(name) => sdp.session_name = Some(name),
+                SdpType::Media(line) => sdp.media.push(MediaDescription::new(
+                    line.media.to_string(),
+                    u16::try_from(line.port).unwrap_or_default(),
+                    line.proto.to_string(),
+                )),
+                SdpType::Connection(connection) => {
+                    let ExplicitlyTypedAddress::Ip(ip) = connection.address else {
+                        continue;
+                    };
+                    match sdp.media.last_mut() {
+                        Some(media) => media.connection = Some(ip),
+                        None => sdp.connection = Some(ip),
+                    }
+                }
+                SdpType::Attribute(attribute) => sdp
+                    .media
+                    .last_mut()
+                    .unwrap_or(&mut session)
+                    .add_attribute(attribute),
+                _ => {}
+            }
+        }
+
+        if sdp.media.is_empty() {
             return None;
+        }
+
+        for media in &mut sdp.media {
+            media.inherit(&session, sdp.connection);
+        }
+
+        Some(sdp)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl MediaDescription {
+    fn new(media: String, port: u16, protocol: String) -> Self {
+        Self {
+            media,
+            port,
+            protocol,
+            connection: None,
+            payload_types: HashMap::new(),
+            ptime: None,
+            fmtp: HashMap::new(),
+            rtcp_fb: Vec::new(),
+            extmap: HashMap::new(),
+            ssrcs: Vec::new(),
+            ssrc_groups: Vec::new(),
+            mid: None,
+            rids: Vec::new(),
+            simulcast: None,
+            setup: None,
+            fingerprint: None,
+            ice_ufrag: None,
+            ice_pwd: None,
+            crypto: Vec::new(),
+            control: None,
+        }
+    }
+
+    fn add_fmtp(&mut self, value: &str) {
+        let (pt, params) = value.split_once(' ').unwrap_or((value, ""));
+        if let Ok(pt) = pt.parse() {
+            self.fmtp.insert(pt, params.trim().to_string());
+        }
+    }
+
+    fn add_rtcp_fb(&mut self, value: &str) {
+        let (pt, feedback) = value.split_once(' ').unwrap_or((value, ""));
+        let payload_type = match pt {
+            "*" => None,
+            pt => match pt.parse() {
+                Ok(pt) => Some(pt),
+                Err(_) => return,
+            },
         };
 
-        let media_type = match media.media {
-            SdpMediaValue::Audio => MediaType::Audio,
-            SdpMediaValue::Video => MediaType::Video,
-            _ => {
-                return None;
-            }
+        self.rtcp_fb.push(RtcpFeedback {
+            payload_type,
+            feedback: feedback.trim().to_string(),
+        });
+    }
+
+    fn add_crypto(&mut self, value: &str) {
+        use base64::{Engine, engine::general_purpose::STANDARD_NO_PAD};
+
+        let mut fields = value.split_whitespace();
+        let (Some(tag), Some(suite), Some(params)) = (fields.next(), fields.next(), fields.next())
+        else {
+            return;
+        };
+        // several keys may be listed, the first one is used from the start
+        let Some(inline) = params
+            .split(';')
+            .next()
+            .and_then(|param| param.strip_prefix("inline:"))
+        else {
+            return;
         };
 
-        let payload_types = lines
-            .filter_map(|line| {
-                let Ok(SdpLine {
-                    sdp_type: SdpType::Attribute(SdpAttribute::Rtpmap(rtpmap)),
-                    ..
-                }) = parse_sdp_line(line, 1)
-                else {
-                    return None;
-                };
+        // followed by the optional lifetime and "MKI:length"
+        let mut parts = inline.split('|');
+        let key = parts.next().unwrap_or_default().trim_end_matches('=');
+        let (Ok(tag), Ok(key)) = (tag.parse(), STANDARD_NO_PAD.decode(key)) else {
+            return;
+        };
+        let mki_length = parts
+            .find_map(|part| part.split_once(':'))
+            .and_then(|(_, length)| length.parse().ok())
+            .unwrap_or(0);
+
+        self.crypto.push(Crypto {
+            tag,
+            suite: suite.to_string(),
+            key,
+            mki_length,
+        });
+    }
 
+    fn add_attribute(&mut self, attribute: webrtc_sdp::attribute_type::SdpAttribute) {
+        use crate::rtp::payload_type::MediaType;
+        use webrtc_sdp::attribute_type::{
+            SdpAttribute, SdpAttributeSetup, SdpAttributeSimulcastVersion, SdpSingleDirection,
+            SdpSsrcGroupSemantic,
+        };
+
+        match attribute {
+            SdpAttribute::Rtpmap(rtpmap) => {
+                let media_type = match self.media.as_str() {
+                    "audio" => MediaType::Audio,
+                    "video" => MediaType::Video,
+                    _ => MediaType::AudioOrVideo,
+                };
                 let pt = PayloadType {
                     id: rtpmap.payload_type,
                     name: rtpmap.codec_name,
                     clock_rate: Some(rtpmap.frequency),
                     media_type,
                 };
+                self.payload_types.insert(pt.id, pt);
+            }
+            // ids above 255 can't be sent in the two-byte header
+            SdpAttribute::Extmap(extmap_attribute) => {
+                if let Ok(id) = u8::try_from(extmap_attribute.id) {
+                    self.extmap.insert(id, extmap_attribute.url);
+                }
+            }
+            SdpAttribute::Ssrc(ssrc) => {
+                let index = match self.ssrcs.iter().position(|desc| desc.ssrc == ssrc.id) {
+                    Some(index) => index,
+                    None => {
+                        self.ssrcs.push(SsrcDescription {
+                            ssrc: ssrc.id,
+                            attributes: Vec::new(),
+                        });
+                        self.ssrcs.len() - 1
+                    }
+                };
+                if let Some(attribute) = ssrc.attribute {
+                    let value = ssrc.value.unwrap_or_default();
+                    self.ssrcs[index].attributes.push((attribute, value));
+                }
+            }
+            SdpAttribute::SsrcGroup(semantic, ssrcs) => {
+                let semantics = match semantic {
+                    SdpSsrcGroupSemantic::FlowIdentification => SsrcGroupSemantics::Fid,
+                    SdpSsrcGroupSemantic::Sim => SsrcGroupSemantics::Sim,
+                    SdpSsrcGroupSemantic::ForwardErrorCorrection => SsrcGroupSemantics::Fec,
+                    SdpSsrcGroupSemantic::ForwardErrorCorrectionFr => SsrcGroupSemantics::FecFr,
+                    SdpSsrcGroupSemantic::Duplication => SsrcGroupSemantics::Dup,
+                };
+                self.ssrc_groups.push(SsrcGroup {
+                    semantics,
+                    ssrcs: ssrcs.iter().map(|ssrc| ssrc.id).collect(),
+                });
+            }
+            SdpAttribute::Mid(mid) => self.mid = Some(mid),
+            SdpAttribute::Rid(rid) => self.rids.push(Rid {
+                id: rid.id,
+                send: rid.direction == SdpSingleDirection::Send,
+                formats: rid.formats,
+            }),
+            SdpAttribute::Simulcast(simulcast) => {
+                // paused streams are still streams
+                let ids = |versions: Vec<SdpAttributeSimulcastVersion>| {
+                    versions
+                        .into_iter()
+                        .map(|version| version.ids.into_iter().map(|id| id.id).collect())
+                        .collect()
+                };
+                self.simulcast = Some(Simulcast {
+                    send: ids(simulcast.send),
+                    receive: ids(simulcast.receive),
+                });
+            }
+            SdpAttribute::Setup(setup) => {
+                self.setup = Some(match setup {
+                    SdpAttributeSetup::Active => DtlsSetup::Active,
+                    SdpAttributeSetup::Passive => DtlsSetup::Passive,
+                    SdpAttributeSetup::Actpass => DtlsSetup::Actpass,
+                    SdpAttributeSetup::Holdconn => DtlsSetup::Holdconn,
+                })
+            }
+            SdpAttribute::Fingerprint(fingerprint) => {
+                self.fingerprint = Some(Fingerprint {
+                    algorithm: fingerprint.hash_algorithm.to_string(),
+                    value: fingerprint.fingerprint,
+                })
+            }
+            SdpAttribute::IceUfrag(ufrag) => self.ice_ufrag = Some(ufrag),
+            SdpAttribute::IcePwd(pwd) => self.ice_pwd = Some(pwd),
+            _ => {}
+        }
+    }
 
-                Some((pt.id, pt))
+    fn inherit(&mut self, session: &MediaDescription, connection: Option<IpAddr>) {
+        self.connection = self.connection.or(connection);
+        self.ptime = self.ptime.or(session.ptime);
+        for (id, uri) in &session.extmap {
+            self.extmap.entry(*id).or_insert_with(|| uri.clone());
+        }
+        self.setup = self.setup.or(session.setup);
+        if self.fingerprint.is_none() {
+            self.fingerprint.clone_from(&session.fingerprint);
+        }
+        if self.ice_ufrag.is_none() {
+            self.ice_ufrag.clone_from(&session.ice_ufrag);
+        }
+        if self.ice_pwd.is_none() {
+            self.ice_pwd.clone_from(&session.ice_pwd);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rtp::extension::RtpExtension;
+
+    const SESSION: &str = "v=0
+o=- 4611731400430051336 2 IN IP4 127.0.0.1
+s=-
+t=0 0
+a=group:BUNDLE 0 1
+a=extmap:3 urn:ietf:params:rtp-hdrext:sdes:mid
+a=fingerprint:sha-256 19:E2:1C:3B:4B:9F:81:E6:B8:5C:F4:A5:A8:D8:73:04:BB:05:2F:70:9F:04:A9:0E:05:E9:26:33:E8:70:88:A2
+a=setup:actpass
+a=ice-ufrag:EsAw
+a=ice-pwd:bP+XJMM09aR8AiX1jdukzR6Y
+m=audio 9 UDP/TLS/RTP/SAVPF 111
+c=IN IP4 0.0.0.0
+a=mid:0
+a=rtpmap:111 opus/48000/2
+a=fmtp:111 minptime=10;useinbandfec=1
+a=rtcp-fb:111 transport-cc
+a=ssrc:1001 cname:4TOk42mSjXCkVIa6
+a=ssrc:1001 msid:stream audio
+m=video 9 UDP/TLS/RTP/SAVPF 96 97
+c=IN IP4 0.0.0.0
+a=mid:1
+a=setup:active
+a=extmap:4 urn:ietf:params:rtp-hdrext:sdes:rtp-stream-id
+a=rtpmap:96 VP8/90000
+a=rtpmap:97 rtx/90000
+a=fmtp:97 apt=96
+a=rtcp-fb:* nack pli
+a=rtcp-fb:96 goog-remb
+a=rid:h send
+a=rid:l send
+a=simulcast:send h;~l
+a=ssrc-group:FID 2001 2002
+";
+
+    fn rtp(ssrc: u32, payload_type: u8, extensions: Vec<RtpExtension>) -> RtpPacket {
+        RtpPacket {
+            version: 2,
+            padding: false,
+            extension: !extensions.is_empty(),
+            marker: false,
+            payload_type: PayloadType::new(payload_type),
+            sequence_number: 0,
+            timestamp: 0,
+            ssrc,
+            csrc: Vec::new(),
+            extensions,
+            payload_length: 0,
+        }
+    }
+
+    fn addr(addr: &str) -> SocketAddr {
+        addr.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parses_session() {
+        let sdp = Sdp::build(SESSION.to_string()).unwrap();
+        assert_eq!(sdp.session_name.as_deref(), Some("-"));
+        assert_eq!(sdp.media.len(), 2);
+
+        let audio = &sdp.media[0];
+        assert_eq!(audio.media, "audio");
+        assert_eq!(audio.port, 9);
+        assert_eq!(audio.protocol, "UDP/TLS/RTP/SAVPF");
+        assert_eq!(audio.connection, Some("0.0.0.0".parse().unwrap()));
+        assert_eq!(audio.mid.as_deref(), Some("0"));
+        assert_eq!(audio.payload_types[&111].name, "opus");
+        assert_eq!(audio.fmtp[&111], "minptime=10;useinbandfec=1");
+        assert_eq!(
+            audio.rtcp_fb,
+            vec![RtcpFeedback {
+                payload_type: Some(111),
+                feedback: "transport-cc".to_string(),
+            }]
+        );
+        assert_eq!(audio.ssrcs.len(), 1);
+        assert_eq!(audio.ssrcs[0].cname(), Some("4TOk42mSjXCkVIa6"));
+        // session level attributes are inherited
+        assert_eq!(audio.extmap[&3], crate::rtp::extension::MID_URI);
+        assert_eq!(audio.setup, Some(DtlsSetup::Actpass));
+        assert_eq!(audio.ice_ufrag.as_deref(), Some("EsAw"));
+        assert_eq!(audio.ice_pwd.as_deref(), Some("bP+XJMM09aR8AiX1jdukzR6Y"));
+        let fingerprint = audio.fingerprint.as_ref().unwrap();
+        assert_eq!(fingerprint.algorithm, "sha-256");
+        assert_eq!(fingerprint.value.len(), 32);
+        assert!(fingerprint.to_string().starts_with("sha-256 19:E2:1C"));
+
+        let video = &sdp.media[1];
+        assert_eq!(video.setup, Some(DtlsSetup::Active));
+        assert_eq!(video.extmap.len(), 2);
+        assert_eq!(video.fmtp[&97], "apt=96");
+        assert_eq!(video.rtcp_fb[0].payload_type, None);
+        assert_eq!(video.rtcp_fb[1].feedback, "goog-remb");
+        assert_eq!(video.rids.len(), 2);
+        assert!(video.rids.iter().all(|rid| rid.send));
+        assert_eq!(
+            video.simulcast,
+            Some(Simulcast {
+                send: vec![vec!["h".to_string()], vec!["l".to_string()]],
+                receive: Vec::new(),
             })
-            .collect();
+        );
+        assert_eq!(
+            video.ssrc_groups,
+            vec![SsrcGroup {
+                semantics: SsrcGroupSemantics::Fid,
+                ssrcs: vec![2001, 2002],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parses_lone_media_section() {
+        let sdp = Sdp::build(
+            "m=audio 5004 RTP/AVP 96\nc=IN IP4 239.30.22.1\na=rtpmap:96 L24/48000/2\na=recvonly\n"
+                .to_string(),
+        )
+        .unwrap();
+
+        assert_eq!(sdp.media.len(), 1);
+        assert_eq!(sdp.media[0].port, 5004);
+        assert_eq!(
+            sdp.media[0].connection,
+            Some("239.30.22.1".parse().unwrap())
+        );
+        assert_eq!(sdp.media[0].payload_types[&96].clock_rate, Some(48000));
+
+        assert!(Sdp::build("v=0\ns=-\n".to_string()).is_none());
+    }
+
+    #[test]
+    fn test_parses_crypto() {
+        let sdp = Sdp::build(
+            "m=audio 4000 RTP/SAVP 0\n\
+             a=crypto:1 AES_CM_128_HMAC_SHA1_80 inline:WVNfX19zZW1jdGwgKCkgewkyMjA7fQp9CnVubGVz|2^20|1:4\n\
+             a=crypto:2 AES_CM_128_HMAC_SHA1_32 inline:NzB4d1BINUAvLEw6UzF3WSJ+PSdFcGdUJShpX1Zj\n\
+             a=crypto:x AES_CM_128_HMAC_SHA1_32 inline:NzB4d1BINUAvLEw6UzF3WSJ+PSdFcGdUJShpX1Zj\n"
+                .to_string(),
+        )
+        .unwrap();
+
+        let crypto = &sdp.media[0].crypto;
+        assert_eq!(crypto.len(), 2);
+        assert_eq!(crypto[0].tag, 1);
+        assert_eq!(crypto[0].suite, "AES_CM_128_HMAC_SHA1_80");
+        // 16 bytes of key and 14 of salt
+        assert_eq!(crypto[0].key.len(), 30);
+        assert_eq!(&crypto[0].key[..4], b"YS__");
+        assert_eq!(crypto[0].mki_length, 4);
+        assert_eq!(crypto[1].mki_length, 0);
+    }
+
+    #[test]
+    fn test_finds_media_by_ssrc() {
+        let sdp = Sdp::build(SESSION.to_string()).unwrap();
+        let (source, destination) = (addr("10.0.0.1:4000"), addr("10.0.0.2:5000"));
+
+        let media = sdp.find_media(source, destination, &rtp(1001, 111, Vec::new()));
+        assert_eq!(media.and_then(|m| m.mid.as_deref()), Some("0"));
+
+        // the retransmission SSRC of the FID group
+        let media = sdp.find_media(source, destination, &rtp(2002, 97, Vec::new()));
+        assert_eq!(media.and_then(|m| m.mid.as_deref()), Some("1"));
+
+        assert!(
+            sdp.find_media(source, destination, &rtp(7, 96, Vec::new()))
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_finds_media_by_mid_and_rid() {
+        let sdp = Sdp::build(SESSION.to_string()).unwrap();
+        let (source, destination) = (addr("10.0.0.1:4000"), addr("10.0.0.2:5000"));
+
+        let mid = RtpExtension {
+            id: 3,
+            data: b"1".to_vec(),
+        };
+        let media = sdp.find_media(source, destination, &rtp(7, 96, vec![mid]));
+        assert_eq!(media.and_then(|m| m.mid.as_deref()), Some("1"));
+
+        let rid = RtpExtension {
+            id: 4,
+            data: b"l".to_vec(),
+        };
+        let media = sdp.find_media(source, destination, &rtp(8, 96, vec![rid]));
+        assert_eq!(media.and_then(|m| m.mid.as_deref()), Some("1"));
+    }
+
+    #[test]
+    fn test_finds_media_by_port() {
+        let sdp = Sdp::build(SESSION.to_string()).unwrap();
+
+        // bundled sections on the same port are told apart by the payload type
+        let media = sdp.find_media(
+            addr("10.0.0.1:4000"),
+            addr("10.0.0.2:9"),
+            &rtp(7, 96, Vec::new()),
+        );
+        assert_eq!(media.and_then(|m| m.mid.as_deref()), Some("1"));
+
+        // symmetric RTP is sent from the port it's received on
+        let media = sdp.find_media(
+            addr("10.0.0.2:9"),
+            addr("10.0.0.1:4000"),
+            &rtp(7, 8, Vec::new()),
+        );
+        assert_eq!(media.and_then(|m| m.mid.as_deref()), Some("0"));
 
-        Some(Self { payload_types })
+        let sdp =
+            Sdp::build("m=audio 5004 RTP/AVP 96\nc=IN IP4 239.30.22.1\n".to_string()).unwrap();
+        let packet = rtp(7, 96, Vec::new());
+        assert!(
+            sdp.find_media(addr("10.0.0.1:4000"), addr("239.30.22.1:5004"), &packet)
+                .is_some()
+        );
+        assert!(
+            sdp.find_media(addr("10.0.0.1:4000"), addr("239.30.22.2:5004"), &packet)
+                .is_none()
+        );
     }
 }
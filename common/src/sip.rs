@@ -0,0 +1,253 @@
+use crate::Sdp;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::text_message::TextMessage;
+use crate::text_message::header;
+use bincode::{Decode, Encode};
+use std::fmt;
+
+pub use crate::text_message::StartLine as SipStartLine;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use dialog::{Negotiation, SipDialogs};
+
+#[cfg(not(target_arch = "wasm32"))]
+mod dialog;
+
+pub const SDP_CONTENT_TYPE: &str = "application/sdp";
+
+#[cfg(not(target_arch = "wasm32"))]
+const SIP_VERSION: &str = "SIP/2.0";
+// long forms of the compact header names, RFC 3261 section 7.3.3
+#[cfg(not(target_arch = "wasm32"))]
+const COMPACT_HEADERS: [(&str, &str); 9] = [
+    ("i", "Call-ID"),
+    ("f", "From"),
+    ("t", "To"),
+    ("v", "Via"),
+    ("c", "Content-Type"),
+    ("l", "Content-Length"),
+    ("m", "Contact"),
+    ("k", "Supported"),
+    ("s", "Subject"),
+];
+
+#[derive(Decode, Encode, Debug, Clone, PartialEq)]
+pub struct CSeq {
+    pub number: u32,
+    pub method: String,
+}
+
+#[derive(Decode, Encode, Debug, Clone)]
+pub struct SipPacket {
+    pub start_line: SipStartLine,
+    // as received, compact names expanded
+    pub headers: Vec<(String, String)>,
+    pub call_id: Option<String>,
+    pub from_tag: Option<String>,
+    pub to_tag: Option<String>,
+    pub cseq: Option<CSeq>,
+    // branch of the topmost Via, identifies the transaction
+    pub branch: Option<String>,
+    pub content_type: Option<String>,
+    pub sdp: Option<Sdp>,
+}
+
+impl SipPacket {
+    pub fn is_request(&self) -> bool {
+        matches!(self.start_line, SipStartLine::Request { .. })
+    }
+
+    pub fn status_code(&self) -> Option<u16> {
+        match self.start_line {
+            SipStartLine::Response { status_code, .. } => Some(status_code),
+            SipStartLine::Request { .. } => None,
+        }
+    }
+
+    /// Method of the request, or of the request the response answers.
+    pub fn method(&self) -> Option<&str> {
+        match self.start_line {
+            SipStartLine::Request { ref method, .. } => Some(method),
+            SipStartLine::Response { .. } => self.cseq.as_ref().map(|cseq| cseq.method.as_str()),
+        }
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        header(&self.headers, name)
+    }
+}
+
+impl fmt::Display for SipPacket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.start_line)?;
+        if let (SipStartLine::Response { .. }, Some(cseq)) = (&self.start_line, &self.cseq) {
+            write!(f, " ({})", cseq.method)?;
+        }
+        if self.sdp.is_some() {
+            write!(f, " with SDP")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl SipPacket {
+    pub fn build(packet: &super::Packet) -> Option<Self> {
+        let payload = packet.payload.as_deref()?;
+        Self::parse(payload)
+    }
+
+    fn parse(payload: &[u8]) -> Option<Self> {
+        let mut message = TextMessage::parse(payload, SIP_VERSION)?;
+        for (name, _) in message.headers.iter_mut() {
+            if let Some((_, long)) = COMPACT_HEADERS
+                .iter()
+                .find(|(compact, _)| compact.eq_ignore_ascii_case(name))
+            {
+                *name = long.to_string();
+            }
+        }
+        let sdp = message.sdp();
+
+        let mut sip = Self {
+            start_line: message.start_line,
+            headers: message.headers,
+            call_id: None,
+            from_tag: None,
+            to_tag: None,
+            cseq: None,
+            branch: None,
+            content_type: None,
+            sdp,
+        };
+        sip.call_id = sip.header("Call-ID").map(str::to_string);
+        sip.from_tag = sip.header("From").and_then(|from| parameter(from, "tag"));
+        sip.to_tag = sip.header("To").and_then(|to| parameter(to, "tag"));
+        sip.cseq = sip.header("CSeq").and_then(|cseq| {
+            let (number, method) = cseq.split_once(' ')?;
+            Some(CSeq {
+                number: number.trim().parse().ok()?,
+                method: method.trim().to_string(),
+            })
+        });
+        // several Via values may share a header
+        sip.branch = sip
+            .header("Via")
+            .and_then(|via| via.split(',').next())
+            .and_then(|via| parameter(via, "branch"));
+        sip.content_type = sip.header("Content-Type").map(|content_type| {
+            let media_type = content_type.split(';').next().unwrap_or_default();
+            media_type.trim().to_ascii_lowercase()
+        });
+
+        Some(sip)
+    }
+}
+
+// value of a `;name=value` parameter of a header, e.g. the tag of From
+#[cfg(not(target_arch = "wasm32"))]
+fn parameter(header: &str, name: &str) -> Option<String> {
+    // parameters of the URI in angle brackets belong to the URI
+    let params = match header.rfind('>') {
+        Some(pos) => &header[pos..],
+        None => header,
+    };
+
+    params
+        .split(';')
+        .skip(1)
+        .filter_map(|param| param.split_once('='))
+        .find(|(param, _)| param.trim().eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INVITE: &str = "INVITE sip:francisco@bestel.com:55060 SIP/2.0\r
+Via: SIP/2.0/UDP 200.57.7.195;branch=z9hG4bKff9b46fb055c0521cc24024da96cd290\r
+Via: SIP/2.0/UDP 200.57.7.195:55061;branch=z9hG4bK291d90e31a47b225bd0ddff4353e9cc0\r
+From: <sip:200.57.7.195:55061;user=phone>;tag=GR52RWG346-34\r
+To: \"francisco@bestel.com\" <sip:francisco@bestel.com:55060>\r
+Call-ID: 12013223@200.57.7.195\r
+CSeq: 1 INVITE\r
+Contact: <sip:200.57.7.195:5060>\r
+Content-Type: application/sdp\r
+Content-Length:   229\r
+\r
+v=0\r
+o=Clarent 120386 120387 IN IP4 200.57.7.196\r
+s=Clarent C5CM\r
+c=IN IP4 200.57.7.196\r
+t=0 0\r
+m=audio 40376 RTP/AVP 8 18 4 0\r
+a=rtpmap:8 PCMA/8000\r
+a=rtpmap:18 G729/8000\r
+a=rtpmap:4 G723/8000\r
+a=rtpmap:0 PCMU/8000\r
+a=SendRecv\r
+";
+
+    #[test]
+    fn test_parses_request_with_sdp() {
+        let sip = SipPacket::parse(INVITE.as_bytes()).unwrap();
+
+        assert_eq!(
+            sip.start_line,
+            SipStartLine::Request {
+                method: "INVITE".to_string(),
+                uri: "sip:francisco@bestel.com:55060".to_string(),
+            }
+        );
+        assert_eq!(sip.call_id.as_deref(), Some("12013223@200.57.7.195"));
+        assert_eq!(sip.from_tag.as_deref(), Some("GR52RWG346-34"));
+        assert_eq!(sip.to_tag, None);
+        assert_eq!(
+            sip.cseq,
+            Some(CSeq {
+                number: 1,
+                method: "INVITE".to_string(),
+            })
+        );
+        assert_eq!(
+            sip.branch.as_deref(),
+            Some("z9hG4bKff9b46fb055c0521cc24024da96cd290")
+        );
+
+        let sdp = sip.sdp.unwrap();
+        assert_eq!(sdp.media.len(), 1);
+        assert_eq!(sdp.media[0].port, 40376);
+        assert_eq!(
+            sdp.media[0].connection,
+            Some("200.57.7.196".parse().unwrap())
+        );
+        assert_eq!(sdp.media[0].payload_types[&18].name, "G729");
+    }
+
+    #[test]
+    fn test_parses_compact_response() {
+        let sip = SipPacket::parse(
+            b"SIP/2.0 180 Ringing\r\nv: SIP/2.0/UDP 10.0.0.1;branch=z9hG4bK1\r\n\
+              f: <sip:a@x>;tag=1\r\nt: <sip:b@y;transport=udp>;tag=2\r\ni: abc\r\n\
+              CSeq: 7 INVITE\r\nl: 0\r\n\r\n",
+        )
+        .unwrap();
+
+        assert_eq!(sip.status_code(), Some(180));
+        assert_eq!(sip.method(), Some("INVITE"));
+        assert_eq!(sip.call_id.as_deref(), Some("abc"));
+        assert_eq!(sip.to_tag.as_deref(), Some("2"));
+        assert_eq!(sip.branch.as_deref(), Some("z9hG4bK1"));
+        assert!(sip.sdp.is_none());
+        assert_eq!(sip.to_string(), "180 Ringing (INVITE)");
+    }
+
+    #[test]
+    fn test_rejects_other_text() {
+        assert!(SipPacket::parse(b"GET / HTTP/1.1\r\nHost: x\r\n\r\n").is_none());
+        assert!(SipPacket::parse(b"OPTIONS rtsp://cam/ RTSP/1.0\r\nCSeq: 1\r\n\r\n").is_none());
+        assert!(SipPacket::parse(b"SIP/2.0 OK\r\n\r\n").is_none());
+        assert!(SipPacket::parse(&[0x80, 0x60, 0, 1]).is_none());
+    }
+}
@@ -0,0 +1,339 @@
+use super::SipPacket;
+use crate::Sdp;
+use std::collections::HashMap;
+use std::time::Duration;
+
+// dialogs without any message for that long are forgotten, calls may be long
+const DIALOG_TIMEOUT: Duration = Duration::from_secs(3600);
+// terminated dialogs are kept a while for retransmissions
+const TERMINATED_TIMEOUT: Duration = Duration::from_secs(32);
+const PRUNE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Offer and answer of a completed SDP exchange, each describes where its sender receives.
+#[derive(Debug, Clone)]
+pub struct Negotiation {
+    pub call_id: String,
+    pub offer: Sdp,
+    pub answer: Sdp,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DialogState {
+    // provisional response with a To tag received
+    Early,
+    Confirmed,
+    Terminated,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Side {
+    Caller,
+    Callee,
+}
+
+#[derive(Debug, Default)]
+struct Transaction {
+    final_status: Option<u16>,
+    // offers answered by a provisional response are answered again by the 2xx
+    answered: bool,
+}
+
+// tag of the sender of the request, CSeq numbers are counted per direction
+type TransactionKey = (String, u32, String);
+
+#[derive(Debug)]
+struct Dialog {
+    caller_tag: String,
+    state: Option<DialogState>,
+    transactions: HashMap<TransactionKey, Transaction>,
+    pending_offer: Option<(Side, Sdp)>,
+    last_seen: Duration,
+}
+
+/// Tracks SIP dialogs by Call-ID and tags and pairs the SDP offers with their answers
+/// (RFC 3264), whichever of INVITE, provisional or 2xx responses, ACK, PRACK
+/// or UPDATE carries them.
+#[derive(Debug, Default)]
+pub struct SipDialogs {
+    dialogs: HashMap<String, Dialog>,
+    last_prune: Duration,
+}
+
+impl SipDialogs {
+    /// Updates the dialog of the message, returns the exchange it completes.
+    pub fn push(&mut self, sip: &SipPacket, timestamp: Duration) -> Option<Negotiation> {
+        self.prune(timestamp);
+
+        let call_id = sip.call_id.as_ref()?;
+        let from_tag = sip.from_tag.as_ref()?;
+        let cseq = sip.cseq.as_ref()?;
+
+        let dialog = self
+            .dialogs
+            .entry(call_id.clone())
+            .or_insert_with(|| Dialog {
+                caller_tag: from_tag.clone(),
+                state: None,
+                transactions: HashMap::new(),
+                pending_offer: None,
+                last_seen: timestamp,
+            });
+        dialog.last_seen = timestamp;
+
+        // requests are sent by the From party, responses by the To party
+        let sent_by_caller = *from_tag == dialog.caller_tag;
+        let side = match (sip.is_request(), sent_by_caller) {
+            (true, true) | (false, false) => Side::Caller,
+            _ => Side::Callee,
+        };
+
+        let key = (from_tag.clone(), cseq.number, cseq.method.clone());
+        let transaction = dialog.transactions.entry(key).or_default();
+        match sip.status_code() {
+            // retransmitted final responses don't change anything
+            Some(status_code) if transaction.final_status == Some(status_code) => return None,
+            Some(status_code) if status_code >= 200 => transaction.final_status = Some(status_code),
+            _ => {}
+        }
+        let answered = transaction.answered;
+        dialog.update_state(sip, &cseq.method);
+
+        let sdp = sip.sdp.clone()?;
+        if !sip.is_request() && answered {
+            return None;
+        }
+
+        match dialog.pending_offer.take() {
+            Some((offer_side, offer)) if offer_side != side => {
+                if !sip.is_request()
+                    && let Some(transaction) = dialog.transactions.get_mut(&(
+                        from_tag.clone(),
+                        cseq.number,
+                        cseq.method.clone(),
+                    ))
+                {
+                    transaction.answered = true;
+                }
+
+                Some(Negotiation {
+                    call_id: call_id.clone(),
+                    offer,
+                    answer: sdp,
+                })
+            }
+            // a new offer or the same one again, e.g. in a retransmitted request
+            _ => {
+                dialog.pending_offer = Some((side, sdp));
+                None
+            }
+        }
+    }
+
+    pub fn state(&self, call_id: &str) -> Option<DialogState> {
+        self.dialogs.get(call_id)?.state
+    }
+
+    pub fn reset(&mut self) {
+        self.dialogs.clear();
+        self.last_prune = Duration::ZERO;
+    }
+
+    fn prune(&mut self, now: Duration) {
+        if now.saturating_sub(self.last_prune) < PRUNE_INTERVAL {
+            return;
+        }
+
+        self.dialogs.retain(|_, dialog| {
+            let timeout = match dialog.state {
+                Some(DialogState::Terminated) => TERMINATED_TIMEOUT,
+                _ => DIALOG_TIMEOUT,
+            };
+            now.saturating_sub(dialog.last_seen) < timeout
+        });
+        self.last_prune = now;
+    }
+}
+
+impl Dialog {
+    fn update_state(&mut self, sip: &SipPacket, method: &str) {
+        let Some(status_code) = sip.status_code() else {
+            if method == "BYE" {
+                self.state = Some(DialogState::Terminated);
+            }
+            return;
+        };
+
+        match (method, status_code) {
+            ("INVITE", 101..=199) if sip.to_tag.is_some() && self.state.is_none() => {
+                self.state = Some(DialogState::Early);
+            }
+            ("INVITE", 200..=299) if self.state != Some(DialogState::Terminated) => {
+                self.state = Some(DialogState::Confirmed);
+            }
+            // the offer of a rejected request is withdrawn, a failed re-INVITE keeps the call
+            (_, 300..) => {
+                self.pending_offer = None;
+                if method == "INVITE" && self.state != Some(DialogState::Confirmed) {
+                    self.state = Some(DialogState::Terminated);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sip::{CSeq, SipStartLine};
+
+    fn sdp(port: u16) -> Sdp {
+        Sdp::build(format!(
+            "v=0\r\nc=IN IP4 10.0.0.1\r\nm=audio {} RTP/AVP 0\r\na=rtpmap:0 PCMU/8000\r\n",
+            port
+        ))
+        .unwrap()
+    }
+
+    fn message(
+        start_line: SipStartLine,
+        from_tag: &str,
+        cseq: (u32, &str),
+        body: Option<Sdp>,
+    ) -> SipPacket {
+        SipPacket {
+            start_line,
+            headers: Vec::new(),
+            call_id: Some("call@host".to_string()),
+            from_tag: Some(from_tag.to_string()),
+            to_tag: Some("callee".to_string()),
+            cseq: Some(CSeq {
+                number: cseq.0,
+                method: cseq.1.to_string(),
+            }),
+            branch: None,
+            content_type: None,
+            sdp: body,
+        }
+    }
+
+    fn request(method: &str, from_tag: &str, cseq: u32, body: Option<Sdp>) -> SipPacket {
+        let start_line = SipStartLine::Request {
+            method: method.to_string(),
+            uri: "sip:bob@host".to_string(),
+        };
+        message(start_line, from_tag, (cseq, method), body)
+    }
+
+    fn response(
+        status_code: u16,
+        from_tag: &str,
+        cseq: (u32, &str),
+        body: Option<Sdp>,
+    ) -> SipPacket {
+        let start_line = SipStartLine::Response {
+            status_code,
+            reason: String::new(),
+        };
+        message(start_line, from_tag, cseq, body)
+    }
+
+    fn ports(negotiation: Option<Negotiation>) -> Option<(u16, u16)> {
+        negotiation.map(|n| (n.offer.media[0].port, n.answer.media[0].port))
+    }
+
+    #[test]
+    fn test_offer_in_invite() {
+        let mut dialogs = SipDialogs::default();
+        let now = Duration::ZERO;
+
+        let invite = request("INVITE", "caller", 1, Some(sdp(4000)));
+        assert!(dialogs.push(&invite, now).is_none());
+        // retransmission of the same offer
+        assert!(dialogs.push(&invite, now).is_none());
+
+        let ringing = response(180, "caller", (1, "INVITE"), None);
+        assert!(dialogs.push(&ringing, now).is_none());
+        assert_eq!(dialogs.state("call@host"), Some(DialogState::Early));
+
+        let ok = response(200, "caller", (1, "INVITE"), Some(sdp(5000)));
+        assert_eq!(ports(dialogs.push(&ok, now)), Some((4000, 5000)));
+        assert_eq!(dialogs.state("call@host"), Some(DialogState::Confirmed));
+
+        let ack = request("ACK", "caller", 1, None);
+        assert!(dialogs.push(&ack, now).is_none());
+
+        // re-INVITE by the callee has the tags swapped
+        let reinvite = request("INVITE", "callee", 1, Some(sdp(5002)));
+        assert!(dialogs.push(&reinvite, now).is_none());
+        let ok = response(200, "callee", (1, "INVITE"), Some(sdp(4002)));
+        assert_eq!(ports(dialogs.push(&ok, now)), Some((5002, 4002)));
+
+        let bye = request("BYE", "caller", 2, None);
+        dialogs.push(&bye, now);
+        assert_eq!(dialogs.state("call@host"), Some(DialogState::Terminated));
+    }
+
+    #[test]
+    fn test_offer_in_response() {
+        let mut dialogs = SipDialogs::default();
+        let now = Duration::ZERO;
+
+        assert!(
+            dialogs
+                .push(&request("INVITE", "caller", 1, None), now)
+                .is_none()
+        );
+        let ok = response(200, "caller", (1, "INVITE"), Some(sdp(5000)));
+        assert!(dialogs.push(&ok, now).is_none());
+        // the same offer in the retransmitted 2xx
+        assert!(dialogs.push(&ok, now).is_none());
+
+        let ack = request("ACK", "caller", 1, Some(sdp(4000)));
+        assert_eq!(ports(dialogs.push(&ack, now)), Some((5000, 4000)));
+    }
+
+    #[test]
+    fn test_rejected_update() {
+        let mut dialogs = SipDialogs::default();
+        let now = Duration::ZERO;
+
+        dialogs.push(&request("INVITE", "caller", 1, Some(sdp(4000))), now);
+        dialogs.push(
+            &response(200, "caller", (1, "INVITE"), Some(sdp(5000))),
+            now,
+        );
+
+        dialogs.push(&request("UPDATE", "caller", 2, Some(sdp(4002))), now);
+        dialogs.push(&response(488, "caller", (2, "UPDATE"), None), now);
+        assert_eq!(dialogs.state("call@host"), Some(DialogState::Confirmed));
+
+        // the withdrawn offer isn't answered by the next one
+        dialogs.push(&request("UPDATE", "callee", 1, Some(sdp(5004))), now);
+        let ok = response(200, "callee", (1, "UPDATE"), Some(sdp(4004)));
+        assert_eq!(ports(dialogs.push(&ok, now)), Some((5004, 4004)));
+    }
+
+    #[test]
+    fn test_failed_call_is_terminated_and_pruned() {
+        let mut dialogs = SipDialogs::default();
+
+        dialogs.push(
+            &request("INVITE", "caller", 1, Some(sdp(4000))),
+            Duration::ZERO,
+        );
+        dialogs.push(
+            &response(486, "caller", (1, "INVITE"), None),
+            Duration::ZERO,
+        );
+        assert_eq!(dialogs.state("call@host"), Some(DialogState::Terminated));
+
+        let later = TERMINATED_TIMEOUT + PRUNE_INTERVAL;
+        let other = SipPacket {
+            call_id: Some("other@host".to_string()),
+            ..request("OPTIONS", "x", 1, None)
+        };
+        dialogs.push(&other, later);
+        assert_eq!(dialogs.state("call@host"), None);
+    }
+}
@@ -0,0 +1,59 @@
+use bincode::{Decode, Encode};
+use std::fmt;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use key_log::KeyLog;
+#[cfg(not(target_arch = "wasm32"))]
+pub use sessions::SrtpSessions;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod key_log;
+#[cfg(not(target_arch = "wasm32"))]
+mod keys;
+#[cfg(not(target_arch = "wasm32"))]
+mod sessions;
+
+#[derive(Decode, Encode, Debug, Clone, Copy, PartialEq)]
+pub enum SrtpStatus {
+    Decrypted,
+    // no master key of the sender gave the authentication tag, the packet is left encrypted
+    AuthFailed,
+    // authenticated, but the index was already received or is behind the replay window
+    Replayed,
+}
+
+impl fmt::Display for SrtpStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let res = match self {
+            Self::Decrypted => "decrypted",
+            Self::AuthFailed => "authentication failed",
+            Self::Replayed => "replayed",
+        };
+
+        write!(f, "{}", res)
+    }
+}
+
+/// SRTP or SRTCP packet of a sender with known master keys.
+#[derive(Decode, Encode, Debug, Clone, Copy, PartialEq)]
+pub struct SrtpInfo {
+    pub ssrc: u32,
+    pub rtcp: bool,
+    // rollover counter and sequence number, or the SRTCP index,
+    // not known without the key which authenticates the packet
+    pub index: Option<u64>,
+    pub status: SrtpStatus,
+}
+
+impl fmt::Display for SrtpInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let protocol = match self.rtcp {
+            true => "SRTCP",
+            false => "SRTP",
+        };
+        match self.index {
+            Some(index) => write!(f, "{} index {} {}", protocol, index, self.status),
+            None => write!(f, "{} {}", protocol, self.status),
+        }
+    }
+}
@@ -0,0 +1,294 @@
+use crate::dtls::VERSION_1_3;
+use ring::{digest, hmac};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+const CLIENT_RANDOM: &str = "CLIENT_RANDOM";
+const EXPORTER_SECRET: &str = "EXPORTER_SECRET";
+const EXPORTER_LABEL: &[u8] = b"EXTRACTOR-dtls_srtp";
+// HKDF labels of DTLS 1.3 (RFC 9147 section 5.9)
+const DTLS13_LABEL_PREFIX: &[u8] = b"dtls13";
+// the file is checked for new secrets at most this often
+const RELOAD_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Secrets of an NSS key log file, as written by browsers with `SSLKEYLOGFILE`.
+///
+/// The file is read again when a session isn't found, as it's appended to during the capture.
+#[derive(Debug)]
+pub struct KeyLog {
+    path: PathBuf,
+    // DTLS 1.2 master secrets and DTLS 1.3 exporter secrets by client random
+    master_secrets: HashMap<Vec<u8>, Vec<u8>>,
+    exporter_secrets: HashMap<Vec<u8>, Vec<u8>>,
+    modified: Option<SystemTime>,
+    last_check: Option<Instant>,
+}
+
+impl KeyLog {
+    pub fn new(path: PathBuf) -> Self {
+        let mut key_log = Self {
+            path,
+            master_secrets: HashMap::new(),
+            exporter_secrets: HashMap::new(),
+            modified: None,
+            last_check: None,
+        };
+        key_log.reload();
+        key_log
+    }
+
+    /// Exports the DTLS-SRTP keying material of the session (RFC 5705, RFC 8446 section 7.5).
+    pub fn export_srtp(
+        &mut self,
+        version: u16,
+        cipher_suite: u16,
+        client_random: &[u8],
+        server_random: &[u8],
+        len: usize,
+    ) -> Option<Vec<u8>> {
+        if self.find(version, client_random).is_none() {
+            self.reload();
+        }
+        let secret = self.find(version, client_random)?;
+        let (algorithm, hash) = hash_of(cipher_suite);
+
+        let material = match version {
+            VERSION_1_3 => {
+                let empty_hash = digest::digest(hash, &[]);
+                let secret = expand_label(
+                    algorithm,
+                    secret,
+                    EXPORTER_LABEL,
+                    empty_hash.as_ref(),
+                    hash.output_len(),
+                );
+                expand_label(algorithm, &secret, b"exporter", empty_hash.as_ref(), len)
+            }
+            _ => {
+                let seed = [client_random, server_random].concat();
+                prf(algorithm, secret, EXPORTER_LABEL, &seed, len)
+            }
+        };
+        Some(material)
+    }
+
+    fn find(&self, version: u16, client_random: &[u8]) -> Option<&[u8]> {
+        let secrets = match version {
+            VERSION_1_3 => &self.exporter_secrets,
+            _ => &self.master_secrets,
+        };
+        secrets.get(client_random).map(Vec::as_slice)
+    }
+
+    fn reload(&mut self) {
+        if self
+            .last_check
+            .is_some_and(|last_check| last_check.elapsed() < RELOAD_INTERVAL)
+        {
+            return;
+        }
+        self.last_check = Some(Instant::now());
+
+        let modified = std::fs::metadata(&self.path).and_then(|metadata| metadata.modified());
+        match modified {
+            Ok(modified) if self.modified != Some(modified) => self.modified = Some(modified),
+            _ => return,
+        }
+        if let Ok(text) = std::fs::read_to_string(&self.path) {
+            self.parse(&text);
+        }
+    }
+
+    // "<label> <client random> <secret>" lines in hex, other labels are of TLS 1.3 traffic
+    fn parse(&mut self, text: &str) {
+        for line in text.lines() {
+            let mut fields = line.split_whitespace();
+            let (Some(label), Some(client_random), Some(secret)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                continue;
+            };
+            let secrets = match label {
+                CLIENT_RANDOM => &mut self.master_secrets,
+                EXPORTER_SECRET => &mut self.exporter_secrets,
+                _ => continue,
+            };
+            if let (Some(client_random), Some(secret)) =
+                (decode_hex(client_random), decode_hex(secret))
+            {
+                secrets.insert(client_random, secret);
+            }
+        }
+    }
+}
+
+// the suites with SHA-384 in their name, the others use SHA-256
+fn hash_of(cipher_suite: u16) -> (hmac::Algorithm, &'static digest::Algorithm) {
+    match cipher_suite {
+        0x1302 | 0x009D | 0x009F | 0xC024 | 0xC028 | 0xC02C | 0xC030 => {
+            (hmac::HMAC_SHA384, &digest::SHA384)
+        }
+        _ => (hmac::HMAC_SHA256, &digest::SHA256),
+    }
+}
+
+// TLS 1.2 PRF (RFC 5246 section 5)
+fn prf(
+    algorithm: hmac::Algorithm,
+    secret: &[u8],
+    label: &[u8],
+    seed: &[u8],
+    len: usize,
+) -> Vec<u8> {
+    let key = hmac::Key::new(algorithm, secret);
+    let seed = [label, seed].concat();
+    let mut a = hmac::sign(&key, &seed);
+    let mut output = Vec::with_capacity(len);
+    while output.len() < len {
+        let mut context = hmac::Context::with_key(&key);
+        context.update(a.as_ref());
+        context.update(&seed);
+        output.extend_from_slice(context.sign().as_ref());
+        a = hmac::sign(&key, a.as_ref());
+    }
+    output.truncate(len);
+    output
+}
+
+// HKDF-Expand-Label (RFC 8446 section 7.1)
+fn expand_label(
+    algorithm: hmac::Algorithm,
+    secret: &[u8],
+    label: &[u8],
+    context: &[u8],
+    len: usize,
+) -> Vec<u8> {
+    let mut info = (len as u16).to_be_bytes().to_vec();
+    info.push((DTLS13_LABEL_PREFIX.len() + label.len()) as u8);
+    info.extend_from_slice(DTLS13_LABEL_PREFIX);
+    info.extend_from_slice(label);
+    info.push(context.len() as u8);
+    info.extend_from_slice(context);
+
+    let key = hmac::Key::new(algorithm, secret);
+    let mut output = Vec::with_capacity(len);
+    let mut block = Vec::new();
+    for counter in 1u8.. {
+        if output.len() >= len {
+            break;
+        }
+        let mut hmac_context = hmac::Context::with_key(&key);
+        hmac_context.update(&block);
+        hmac_context.update(&info);
+        hmac_context.update(&[counter]);
+        block = hmac_context.sign().as_ref().to_vec();
+        output.extend_from_slice(&block);
+    }
+    output.truncate(len);
+    output
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if text.len() % 2 != 0 {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dtls::VERSION_1_2;
+
+    fn key_log(text: &str) -> KeyLog {
+        let mut key_log = KeyLog {
+            path: PathBuf::new(),
+            master_secrets: HashMap::new(),
+            exporter_secrets: HashMap::new(),
+            modified: None,
+            last_check: Some(Instant::now()),
+        };
+        key_log.parse(text);
+        key_log
+    }
+
+    fn hex(text: &str) -> Vec<u8> {
+        decode_hex(text).unwrap()
+    }
+
+    #[test]
+    fn test_computes_prf() {
+        // the P_SHA256 test vector of the TLS working group
+        let output = prf(
+            hmac::HMAC_SHA256,
+            &hex("9bbe436ba940f017b17652849a71db35"),
+            b"test label",
+            &hex("a0ba9f936cda311827a6f796ffd5198c"),
+            100,
+        );
+        assert_eq!(
+            output,
+            hex(concat!(
+                "e3f229ba727be17b8d122620557cd453c2aab21d07c3d495329b52d4e61edb5a",
+                "6b301791e90d35c9c9a46b4e14baf9af0fa022f7077def17abfd3797c0564bab",
+                "4fbc91666e9def9b97fce34f796789baa48082d122ee42c5a72e5a5110fff701",
+                "87347b66"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_exports_keying_material() {
+        let client_random = [1; 32];
+        let server_random = [2; 32];
+        let secret: Vec<u8> = (0..48).collect();
+        let exporter_secret: Vec<u8> = (0..32).collect();
+        let text = format!(
+            "# comment\n{} {} {}\n{} {} {}\nCLIENT_TRAFFIC_SECRET_0 {} 00\n",
+            CLIENT_RANDOM,
+            "01".repeat(32),
+            hex_string(&secret),
+            EXPORTER_SECRET,
+            "01".repeat(32),
+            hex_string(&exporter_secret),
+            "01".repeat(32),
+        );
+        let mut key_log = key_log(&text);
+
+        let material = key_log
+            .export_srtp(VERSION_1_2, 0xC02B, &client_random, &server_random, 60)
+            .unwrap();
+        assert_eq!(
+            material,
+            hex(concat!(
+                "3ebf27561d536d433e259ebeff3fde1071c232877f3fb2174d0740e3c1256cc2",
+                "6535bdcdd33b3ec9727167b126e12a68ba0dbf07b7630fc976a1aeb9"
+            ))
+        );
+
+        let material = key_log
+            .export_srtp(VERSION_1_3, 0x1301, &client_random, &server_random, 60)
+            .unwrap();
+        assert_eq!(
+            material,
+            hex(concat!(
+                "eb8b7bc6476536f59509c76535c731b1e20e3081d2a8e0165d129870e254a371",
+                "341d47fed0a26bfd4b9a84967514696866115940ba7016013ca0320a"
+            ))
+        );
+
+        assert!(
+            key_log
+                .export_srtp(VERSION_1_2, 0xC02B, &server_random, &client_random, 60)
+                .is_none()
+        );
+    }
+
+    fn hex_string(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+}
@@ -0,0 +1,566 @@
+use crate::dtls::{
+    SRTP_AEAD_AES_128_GCM, SRTP_AEAD_AES_256_GCM, SRTP_AES128_CM_HMAC_SHA1_32,
+    SRTP_AES128_CM_HMAC_SHA1_80, SrtpProfile,
+};
+use crate::sdp::Crypto;
+use aes::{Aes128, Aes256};
+use ctr::cipher::{InnerIvInit, KeyInit, KeyIvInit, StreamCipher};
+use ctr::{Ctr128BE, CtrCore};
+use ring::{aead, hmac};
+
+// key derivation labels, the SRTCP ones follow at +3
+const LABEL_ENCRYPTION: u8 = 0x00;
+const LABEL_AUTHENTICATION: u8 = 0x01;
+const LABEL_SALT: u8 = 0x02;
+const LABEL_RTCP: u8 = 0x03;
+// 112 bits, the shorter GCM salts are padded with zeros
+const KDF_SALT_LEN: usize = 14;
+const AUTH_KEY_LEN: usize = 20;
+const GCM_TAG_LEN: usize = 16;
+const RTCP_HEADER_LEN: usize = 8;
+const RTCP_INDEX_LEN: usize = 4;
+const ENCRYPTED_FLAG: u32 = 0x8000_0000;
+
+/// Key, salt and tag lengths of a protection profile.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct Suite {
+    pub key_len: usize,
+    pub salt_len: usize,
+    // AES-GCM, or AES-CM with HMAC-SHA1
+    pub gcm: bool,
+    pub rtp_tag_len: usize,
+    pub rtcp_tag_len: usize,
+}
+
+impl Suite {
+    // the NULL profiles aren't supported
+    pub fn of(profile: SrtpProfile) -> Option<Self> {
+        let (key_len, salt_len, gcm, rtp_tag_len, rtcp_tag_len) = match profile {
+            SRTP_AES128_CM_HMAC_SHA1_80 => (16, 14, false, 10, 10),
+            SRTP_AES128_CM_HMAC_SHA1_32 => (16, 14, false, 4, 10),
+            SRTP_AEAD_AES_128_GCM => (16, 12, true, GCM_TAG_LEN, GCM_TAG_LEN),
+            SRTP_AEAD_AES_256_GCM => (32, 12, true, GCM_TAG_LEN, GCM_TAG_LEN),
+            _ => return None,
+        };
+
+        Some(Self {
+            key_len,
+            salt_len,
+            gcm,
+            rtp_tag_len,
+            rtcp_tag_len,
+        })
+    }
+
+    // length of the DTLS-SRTP keying material, the keys and salts of both sides
+    pub fn exporter_len(&self) -> usize {
+        2 * (self.key_len + self.salt_len)
+    }
+}
+
+/// Master key and salt a sender protects its packets with.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct MasterKey {
+    pub profile: SrtpProfile,
+    pub key: Vec<u8>,
+    pub salt: Vec<u8>,
+    pub mki_len: usize,
+}
+
+impl MasterKey {
+    pub fn from_crypto(crypto: &Crypto) -> Option<Self> {
+        let profile = match crypto.suite.as_str() {
+            "AES_CM_128_HMAC_SHA1_80" => SRTP_AES128_CM_HMAC_SHA1_80,
+            "AES_CM_128_HMAC_SHA1_32" => SRTP_AES128_CM_HMAC_SHA1_32,
+            "AEAD_AES_128_GCM" => SRTP_AEAD_AES_128_GCM,
+            "AEAD_AES_256_GCM" => SRTP_AEAD_AES_256_GCM,
+            _ => return None,
+        };
+        let suite = Suite::of(profile)?;
+        if crypto.key.len() != suite.key_len + suite.salt_len {
+            return None;
+        }
+
+        let (key, salt) = crypto.key.split_at(suite.key_len);
+        Some(Self {
+            profile,
+            key: key.to_vec(),
+            salt: salt.to_vec(),
+            mki_len: crypto.mki_length,
+        })
+    }
+
+    /// Keys of the DTLS client and server from the exported keying material (RFC 5764).
+    pub fn from_exporter(profile: SrtpProfile, material: &[u8]) -> Option<(Self, Self)> {
+        let suite = Suite::of(profile)?;
+        if material.len() != suite.exporter_len() {
+            return None;
+        }
+
+        let (keys, salts) = material.split_at(2 * suite.key_len);
+        let (client_key, server_key) = keys.split_at(suite.key_len);
+        let (client_salt, server_salt) = salts.split_at(suite.salt_len);
+        let key = |key: &[u8], salt: &[u8]| Self {
+            profile,
+            key: key.to_vec(),
+            salt: salt.to_vec(),
+            mki_len: 0,
+        };
+        Some((key(client_key, client_salt), key(server_key, server_salt)))
+    }
+}
+
+enum Cipher {
+    Cm { aes: Box<Aes128>, auth: hmac::Key },
+    Gcm(Box<aead::LessSafeKey>),
+}
+
+/// Session keys of SRTP or SRTCP derived from a master key.
+pub(super) struct SessionKeys {
+    cipher: Cipher,
+    salt: Vec<u8>,
+    tag_len: usize,
+    mki_len: usize,
+}
+
+impl std::fmt::Debug for SessionKeys {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let cipher = match self.cipher {
+            Cipher::Cm { .. } => "AES-CM",
+            Cipher::Gcm(_) => "AES-GCM",
+        };
+        write!(f, "SessionKeys({}, tag {})", cipher, self.tag_len)
+    }
+}
+
+impl SessionKeys {
+    pub fn derive(master: &MasterKey, rtcp: bool) -> Option<Self> {
+        let suite = Suite::of(master.profile)?;
+        let label = |label: u8| match rtcp {
+            true => label + LABEL_RTCP,
+            false => label,
+        };
+
+        let key = derive_key(master, label(LABEL_ENCRYPTION), suite.key_len)?;
+        let auth_key = match suite.gcm {
+            true => Vec::new(),
+            false => derive_key(master, label(LABEL_AUTHENTICATION), AUTH_KEY_LEN)?,
+        };
+        let salt = derive_key(master, label(LABEL_SALT), suite.salt_len)?;
+        Self::new(suite, &key, &auth_key, salt, rtcp, master.mki_len)
+    }
+
+    fn new(
+        suite: Suite,
+        key: &[u8],
+        auth_key: &[u8],
+        salt: Vec<u8>,
+        rtcp: bool,
+        mki_len: usize,
+    ) -> Option<Self> {
+        let cipher = match suite.gcm {
+            true => {
+                let algorithm = match suite.key_len {
+                    16 => &aead::AES_128_GCM,
+                    _ => &aead::AES_256_GCM,
+                };
+                let key = aead::UnboundKey::new(algorithm, key).ok()?;
+                Cipher::Gcm(Box::new(aead::LessSafeKey::new(key)))
+            }
+            false => Cipher::Cm {
+                aes: Box::new(Aes128::new_from_slice(key).ok()?),
+                auth: hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, auth_key),
+            },
+        };
+
+        Some(Self {
+            cipher,
+            salt,
+            tag_len: match rtcp {
+                true => suite.rtcp_tag_len,
+                false => suite.rtp_tag_len,
+            },
+            mki_len,
+        })
+    }
+
+    /// Authenticates and decrypts the SRTP packet, returns the RTP packet
+    /// without the MKI and the tag.
+    pub fn unprotect_rtp(&self, packet: &[u8], header_len: usize, index: u64) -> Option<Vec<u8>> {
+        let payload_end = packet.len().checked_sub(self.tag_len + self.mki_len)?;
+        if payload_end < header_len {
+            return None;
+        }
+        let (header, ssrc) = (&packet[..header_len], &packet[8..12]);
+        let roc = ((index >> 16) as u32).to_be_bytes();
+
+        match self.cipher {
+            // the MKI is authenticated, it comes before the tag
+            Cipher::Cm { ref aes, ref auth } => {
+                let authenticated = &packet[..payload_end];
+                let mut context = hmac::Context::with_key(auth);
+                context.update(authenticated);
+                context.update(&roc);
+                if !tag_matches(
+                    context.sign().as_ref(),
+                    &packet[payload_end + self.mki_len..],
+                ) {
+                    return None;
+                }
+
+                let mut plain = authenticated.to_vec();
+                cm_keystream(aes, self.cm_iv(ssrc, index), &mut plain[header_len..]);
+                Some(plain)
+            }
+            // the tag ends the ciphertext, followed by the MKI
+            Cipher::Gcm(ref key) => {
+                let mut nonce = [0; 12];
+                nonce[2..6].copy_from_slice(ssrc);
+                nonce[6..10].copy_from_slice(&roc);
+                nonce[10..].copy_from_slice(&packet[2..4]);
+
+                let ciphertext = &packet[header_len..payload_end + self.tag_len];
+                let payload = self.gcm_open(key, nonce, header, ciphertext)?;
+                Some([header, &payload].concat())
+            }
+        }
+    }
+
+    /// Authenticates and decrypts the SRTCP packet, returns the index and the RTCP packet.
+    pub fn unprotect_rtcp(&self, packet: &[u8]) -> Option<(u32, Vec<u8>)> {
+        // the index is authenticated with AES-CM, it's the additional data with AES-GCM
+        let index_end = match self.cipher {
+            Cipher::Cm { .. } => packet.len().checked_sub(self.tag_len + self.mki_len)?,
+            Cipher::Gcm(_) => packet.len().checked_sub(self.mki_len)?,
+        };
+        let index_start = index_end.checked_sub(RTCP_INDEX_LEN)?;
+        if index_start < RTCP_HEADER_LEN {
+            return None;
+        }
+        let e_index = u32::from_be_bytes(packet[index_start..index_end].try_into().ok()?);
+        let (encrypted, index) = (e_index & ENCRYPTED_FLAG != 0, e_index & !ENCRYPTED_FLAG);
+        let (header, ssrc) = (&packet[..RTCP_HEADER_LEN], &packet[4..8]);
+
+        match self.cipher {
+            Cipher::Cm { ref aes, ref auth } => {
+                let tag = hmac::sign(auth, &packet[..index_end]);
+                if !tag_matches(tag.as_ref(), &packet[index_end + self.mki_len..]) {
+                    return None;
+                }
+
+                let mut plain = packet[..index_start].to_vec();
+                if encrypted {
+                    let iv = self.cm_iv(ssrc, index as u64);
+                    cm_keystream(aes, iv, &mut plain[RTCP_HEADER_LEN..]);
+                }
+                Some((index, plain))
+            }
+            Cipher::Gcm(ref key) => {
+                let mut nonce = [0; 12];
+                nonce[2..6].copy_from_slice(ssrc);
+                nonce[8..].copy_from_slice(&index.to_be_bytes());
+
+                let e_index = e_index.to_be_bytes();
+                if encrypted {
+                    let aad = [header, &e_index].concat();
+                    let ciphertext = &packet[RTCP_HEADER_LEN..index_start];
+                    let payload = self.gcm_open(key, nonce, &aad, ciphertext)?;
+                    return Some((index, [header, &payload].concat()));
+                }
+
+                // only authenticated, the whole packet is the additional data
+                let tag_start = index_start.checked_sub(self.tag_len)?;
+                let aad = [&packet[..tag_start], &e_index].concat();
+                self.gcm_open(key, nonce, &aad, &packet[tag_start..index_start])?;
+                Some((index, packet[..tag_start].to_vec()))
+            }
+        }
+    }
+
+    // (salt * 2^16) XOR (SSRC * 2^64) XOR (index * 2^16)
+    fn cm_iv(&self, ssrc: &[u8], index: u64) -> [u8; 16] {
+        let mut iv = [0; 16];
+        iv[..self.salt.len()].copy_from_slice(&self.salt);
+        for (byte, ssrc) in iv[4..8].iter_mut().zip(ssrc) {
+            *byte ^= ssrc;
+        }
+        for (byte, index) in iv[8..14].iter_mut().zip(&index.to_be_bytes()[2..]) {
+            *byte ^= index;
+        }
+        iv
+    }
+
+    fn gcm_open(
+        &self,
+        key: &aead::LessSafeKey,
+        mut nonce: [u8; 12],
+        aad: &[u8],
+        ciphertext: &[u8],
+    ) -> Option<Vec<u8>> {
+        for (byte, salt) in nonce.iter_mut().zip(&self.salt) {
+            *byte ^= salt;
+        }
+        let mut buffer = ciphertext.to_vec();
+        let plain_len = key
+            .open_in_place(
+                aead::Nonce::assume_unique_for_key(nonce),
+                aead::Aad::from(aad),
+                &mut buffer,
+            )
+            .ok()?
+            .len();
+        buffer.truncate(plain_len);
+        Some(buffer)
+    }
+}
+
+// AES-CM PRF with a key derivation rate of zero (RFC 3711 section 4.3),
+// the master keys of AES-256-GCM derive with AES-256
+fn derive_key(master: &MasterKey, label: u8, len: usize) -> Option<Vec<u8>> {
+    let mut iv = [0; 16];
+    iv[..master.salt.len().min(KDF_SALT_LEN)].copy_from_slice(&master.salt);
+    iv[7] ^= label;
+
+    let mut key = vec![0; len];
+    match master.key.len() {
+        16 => Ctr128BE::<Aes128>::new_from_slices(&master.key, &iv)
+            .ok()?
+            .apply_keystream(&mut key),
+        _ => Ctr128BE::<Aes256>::new_from_slices(&master.key, &iv)
+            .ok()?
+            .apply_keystream(&mut key),
+    }
+    Some(key)
+}
+
+fn cm_keystream(aes: &Aes128, iv: [u8; 16], data: &mut [u8]) {
+    let core = CtrCore::inner_iv_init(aes.clone(), &iv.into());
+    Ctr128BE::<Aes128>::from_core(core).apply_keystream(data);
+}
+
+// the HMAC-SHA1 tags are truncated
+fn tag_matches(computed: &[u8], tag: &[u8]) -> bool {
+    !tag.is_empty() && computed.starts_with(tag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER_LEN: usize = 12;
+    const PAYLOAD: &[u8] = b"netpix srtp test payload, longer than a block";
+    // index 5 of the sender report
+    const RTCP_PAYLOAD: [u8; 20] = [
+        0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19,
+    ];
+
+    fn hex(text: &str) -> Vec<u8> {
+        (0..text.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&text[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    // RFC 3711 appendix B.3
+    fn master_key() -> MasterKey {
+        MasterKey {
+            profile: SRTP_AES128_CM_HMAC_SHA1_80,
+            key: hex("e1f97a0d3e018be0d64fa32c06de4139"),
+            salt: hex("0ec675ad498afeebb6960b3aabe6"),
+            mki_len: 0,
+        }
+    }
+
+    #[test]
+    fn test_derives_session_keys() {
+        // the SRTCP labels aren't listed in RFC 3711, their keys come from OpenSSL
+        let master = master_key();
+        let expected = [
+            (16, "c61e7a93744f39ee10734afe3ff7a087"),
+            (20, "cebe321f6ff7716b6fd4ab49af256a156d38baa4"),
+            (14, "30cbbc08863d8c85d49db34a9ae1"),
+            (16, "4c1aa45a81f73d61c800bbb00fbb1eaa"),
+            (20, "8d54534feb49ae8e7993a6bd0b844fc323a93dfd"),
+            (14, "9581c7ad87b3e530bf3e4454a8b3"),
+        ];
+        for (label, (len, key)) in expected.into_iter().enumerate() {
+            assert_eq!(derive_key(&master, label as u8, len), Some(hex(key)));
+        }
+
+        // RFC 6188 section 7.3
+        let master = MasterKey {
+            profile: SRTP_AEAD_AES_256_GCM,
+            key: hex("f0f04914b513f2763a1b1fa130f10e2998f6f6e43e4309d1e622a0e332b9f1b6"),
+            salt: hex("3b04803de51ee7c96423ab5b78d2"),
+            mki_len: 0,
+        };
+        assert_eq!(
+            derive_key(&master, LABEL_ENCRYPTION, 32),
+            Some(hex(
+                "5ba1064e30ec51613cad926c5a28ef731ec7fb397f70a960653caf06554cd8c4"
+            ))
+        );
+        assert_eq!(
+            derive_key(&master, LABEL_AUTHENTICATION, AUTH_KEY_LEN),
+            Some(hex("fd9c32d39ed5fbb5a9dc96b30818454d1313dc05"))
+        );
+        assert_eq!(
+            derive_key(&master, LABEL_SALT, KDF_SALT_LEN),
+            Some(hex("fa31791685ca444a9e07c6c64e93"))
+        );
+    }
+
+    #[test]
+    fn test_generates_aes_cm_keystream() {
+        // RFC 3711 appendix B.2, SSRC and index are zero
+        let suite = Suite::of(SRTP_AES128_CM_HMAC_SHA1_80).unwrap();
+        let keys = SessionKeys::new(
+            suite,
+            &hex("2b7e151628aed2a6abf7158809cf4f3c"),
+            &[0; AUTH_KEY_LEN],
+            hex("f0f1f2f3f4f5f6f7f8f9fafbfcfd"),
+            false,
+            0,
+        )
+        .unwrap();
+        let iv = keys.cm_iv(&[0; 4], 0);
+        assert_eq!(iv.to_vec(), hex("f0f1f2f3f4f5f6f7f8f9fafbfcfd0000"));
+
+        let Cipher::Cm { ref aes, .. } = keys.cipher else {
+            panic!("not AES-CM");
+        };
+        let mut keystream = [0; 48];
+        cm_keystream(aes, iv, &mut keystream);
+        assert_eq!(
+            keystream.to_vec(),
+            hex(concat!(
+                "e03ead0935c95e80e166b16dd92b4eb4",
+                "d23513162b02d0f72a43a2fe4a5f97ab",
+                "41e95b3bb0a2e8dd477901e4fca894c0"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_unprotects_aes_cm() {
+        let master = master_key();
+        let keys = SessionKeys::derive(&master, false).unwrap();
+        let mut packet = hex(concat!(
+            "80e01234decafbadcafebabed25cba137de3b594fd38fc14ac556d793ccd59f8",
+            "767924e1da8130e8c75a9ca3b82378077a80a418c2b6cb50ca64b4c5b04f3471",
+            "177fc3"
+        ));
+
+        let plain = keys.unprotect_rtp(&packet, HEADER_LEN, 0x1_1234).unwrap();
+        assert_eq!(&plain[..HEADER_LEN], &packet[..HEADER_LEN]);
+        assert_eq!(&plain[HEADER_LEN..], PAYLOAD);
+        // the rollover counter is authenticated
+        assert!(keys.unprotect_rtp(&packet, HEADER_LEN, 0x1234).is_none());
+        *packet.last_mut().unwrap() ^= 1;
+        assert!(keys.unprotect_rtp(&packet, HEADER_LEN, 0x1_1234).is_none());
+
+        let keys = SessionKeys::derive(&master, true).unwrap();
+        let packet = hex(concat!(
+            "80c80006cafebabe57e43cac3a8f5d32734ac5baca6f92fe1195c54880000005",
+            "bbd61154cc8aed28a07a"
+        ));
+        let (index, plain) = keys.unprotect_rtcp(&packet).unwrap();
+        assert_eq!(index, 5);
+        assert_eq!(&plain[..8], &packet[..8]);
+        assert_eq!(plain[8..], RTCP_PAYLOAD);
+    }
+
+    // RFC 7714 section 16
+    fn gcm_keys(profile: SrtpProfile, key: &str, rtcp: bool) -> SessionKeys {
+        let suite = Suite::of(profile).unwrap();
+        let salt = hex("517569642070726f2071756f");
+        SessionKeys::new(suite, &hex(key), &[], salt, rtcp, 0).unwrap()
+    }
+
+    #[test]
+    fn test_unprotects_aes_gcm() {
+        // RFC 7714 section 16.1.1
+        let keys = gcm_keys(
+            SRTP_AEAD_AES_128_GCM,
+            "000102030405060708090a0b0c0d0e0f",
+            false,
+        );
+        let mut packet = hex(concat!(
+            "8040f17b8041f8d35501a0b2f24de3a3fb34de6cacba861c9d7e4bcabe633bd5",
+            "0d294e6f42a5f47a51c7d19b36de3adf8833899d7f27beb16a9152cf765ee439",
+            "0cce"
+        ));
+
+        let plain = keys.unprotect_rtp(&packet, HEADER_LEN, 0xf17b).unwrap();
+        assert_eq!(&plain[..HEADER_LEN], &packet[..HEADER_LEN]);
+        assert_eq!(
+            &plain[HEADER_LEN..],
+            b"Gallia est omnis divisa in partes tres"
+        );
+        // the rollover counter is part of the nonce
+        assert!(keys.unprotect_rtp(&packet, HEADER_LEN, 0x1_f17b).is_none());
+        *packet.last_mut().unwrap() ^= 1;
+        assert!(keys.unprotect_rtp(&packet, HEADER_LEN, 0xf17b).is_none());
+
+        // RFC 7714 section 17.1.1
+        let keys = gcm_keys(
+            SRTP_AEAD_AES_256_GCM,
+            "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f",
+            false,
+        );
+        let packet = hex(concat!(
+            "8040f17b8041f8d35501a0b232b1de78a822fe12ef9f78fa332e33aab1801238",
+            "9a58e2f3b50b2a0276ffae0f1ba63799b87b7aa3db36dfffd6b0f9bb7878d7a7",
+            "6c13"
+        ));
+        let plain = keys.unprotect_rtp(&packet, HEADER_LEN, 0xf17b).unwrap();
+        assert_eq!(
+            &plain[HEADER_LEN..],
+            b"Gallia est omnis divisa in partes tres"
+        );
+    }
+
+    #[test]
+    fn test_unprotects_aes_gcm_rtcp() {
+        let keys = gcm_keys(
+            SRTP_AEAD_AES_128_GCM,
+            "000102030405060708090a0b0c0d0e0f",
+            true,
+        );
+        let rtcp = hex(concat!(
+            "81c8000d4d6172734e5450314e545032525450200000042a0000e9304c756e61",
+            "deadbeefdeadbeefdeadbeefdeadbeefdeadbeef"
+        ));
+
+        // RFC 7714 section 16.2.1
+        let packet = hex(concat!(
+            "81c8000d4d61727363e94885dcdab67ca727d7662f6b7e997ff5c0f76c06f32d",
+            "c676a5f1730d6fda4ce09b4686303ded0bb9275bc84aa45896cf4d2fc5abf872",
+            "45d9eade800005d4"
+        ));
+        assert_eq!(keys.unprotect_rtcp(&packet), Some((0x5d4, rtcp.clone())));
+
+        // RFC 7714 section 16.2.2, only authenticated
+        let mut packet = hex(concat!(
+            "81c8000d4d6172734e5450314e545032525450200000042a0000e9304c756e61",
+            "deadbeefdeadbeefdeadbeefdeadbeefdeadbeef841dd9683dd78ec92ae58790",
+            "125f62b3000005d4"
+        ));
+        assert_eq!(keys.unprotect_rtcp(&packet), Some((0x5d4, rtcp)));
+        packet[8] ^= 1;
+        assert!(keys.unprotect_rtcp(&packet).is_none());
+    }
+
+    #[test]
+    fn test_splits_keying_material() {
+        let material: Vec<u8> = (0..60).collect();
+        let (client, server) =
+            MasterKey::from_exporter(SRTP_AES128_CM_HMAC_SHA1_80, &material).unwrap();
+        assert_eq!(client.key, (0..16).collect::<Vec<_>>());
+        assert_eq!(server.key, (16..32).collect::<Vec<_>>());
+        assert_eq!(client.salt, (32..46).collect::<Vec<_>>());
+        assert_eq!(server.salt, (46..60).collect::<Vec<_>>());
+
+        assert!(MasterKey::from_exporter(SRTP_AEAD_AES_128_GCM, &material).is_none());
+    }
+}
@@ -0,0 +1,624 @@
+use super::keys::{MasterKey, SessionKeys, Suite};
+use super::{KeyLog, SrtpInfo, SrtpStatus};
+use crate::dtls::{DtlsPacket, HandshakeBody, SrtpProfile};
+use crate::packet::SessionPacket;
+use crate::{Packet, Sdp, SessionProtocol};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(300);
+const PRUNE_INTERVAL: Duration = Duration::from_secs(10);
+const RTP_HEADER_LEN: usize = 12;
+// RTCP packet types where RTP has the marker bit set (RFC 5761)
+const RTCP_TYPES: RangeInclusive<u8> = 192..=223;
+const REPLAY_WINDOW: u64 = 64;
+// the capture may start after the sequence numbers wrapped around a few times
+const ROLLOVER_GUESSES: u64 = 4;
+
+// sender and, for DTLS-SRTP, the receiver the keys are used towards
+type Sender = (SocketAddr, Option<SocketAddr>);
+// sender, receiver, SSRC, SRTCP or not, and the outermost tunnel which tells apart
+// the copies relayed over several hops
+type StreamKey = (SocketAddr, SocketAddr, u32, bool, Option<(IpAddr, IpAddr)>);
+
+// the hellos of a DTLS handshake, which identify its secret in the key log
+#[derive(Debug, Default)]
+struct DtlsSession {
+    client_random: Option<Vec<u8>>,
+    server_random: Option<Vec<u8>>,
+    version: u16,
+    cipher_suite: u16,
+    profile: Option<SrtpProfile>,
+    keyed: bool,
+    last_seen: Duration,
+}
+
+#[derive(Debug)]
+struct Stream {
+    // of every master key of the sender, until one of them authenticates a packet
+    keys: Vec<SessionKeys>,
+    chosen: Option<usize>,
+    // highest authenticated index and a bit for each of the indices below it
+    highest: Option<u64>,
+    window: u64,
+    last_seen: Duration,
+}
+
+/// Decrypts SRTP and SRTCP (RFC 3711, RFC 7714) with the master keys of SDP
+/// `a=crypto` attributes, or the DTLS-SRTP ones exported with the secrets of a key log.
+///
+/// Packets are authenticated before they're decrypted, the ones no key authenticates
+/// are left as they were and the authenticated ones with a known index are marked as replayed.
+#[derive(Debug, Default)]
+pub struct SrtpSessions {
+    key_log: Option<KeyLog>,
+    keys: HashMap<Sender, Vec<MasterKey>>,
+    // DTLS handshakes by client and server
+    handshakes: HashMap<(SocketAddr, SocketAddr), DtlsSession>,
+    streams: HashMap<StreamKey, Stream>,
+    last_prune: Duration,
+}
+
+impl SrtpSessions {
+    pub fn new(key_log: Option<PathBuf>) -> Self {
+        Self {
+            key_log: key_log.map(KeyLog::new),
+            ..Default::default()
+        }
+    }
+
+    /// Learns the SDES keys of the media sections. The senders are expected to use
+    /// symmetric RTP, sending from the port of the section and the RTCP port after it.
+    pub fn add_sdp(&mut self, sdp: &Sdp) {
+        for media in &sdp.media {
+            let Some(ip) = media.connection.filter(|ip| !ip.is_unspecified()) else {
+                continue;
+            };
+            let keys: Vec<_> = media
+                .crypto
+                .iter()
+                .filter_map(MasterKey::from_crypto)
+                .collect();
+            if media.port == 0 || keys.is_empty() {
+                continue;
+            }
+
+            for port in [media.port, media.port.wrapping_add(1)] {
+                self.set_keys((SocketAddr::new(ip, port), None), keys.clone());
+            }
+        }
+    }
+
+    /// Follows the DTLS handshakes and decrypts the packets of senders with known keys,
+    /// returns whether the packet was decrypted.
+    pub fn push(&mut self, packet: &mut Packet) -> bool {
+        self.prune(packet.timestamp);
+
+        if let SessionPacket::Dtls(ref dtls) = packet.contents {
+            self.push_dtls(packet, dtls);
+            return false;
+        }
+        if !matches!(
+            packet.session_protocol,
+            SessionProtocol::Rtp | SessionProtocol::Rtcp | SessionProtocol::Unknown
+        ) {
+            return false;
+        }
+        let Some(payload) = packet.payload.as_deref() else {
+            return false;
+        };
+        if payload.len() < RTP_HEADER_LEN || payload[0] >> 6 != 2 {
+            return false;
+        }
+
+        let rtcp = RTCP_TYPES.contains(&payload[1]);
+        let ssrc = match rtcp {
+            true => u32::from_be_bytes([payload[4], payload[5], payload[6], payload[7]]),
+            false => u32::from_be_bytes([payload[8], payload[9], payload[10], payload[11]]),
+        };
+        let outer = packet
+            .metadata
+            .tunnels
+            .first()
+            .map(|tunnel| (tunnel.source_addr, tunnel.destination_addr));
+        let key = (
+            packet.source_addr,
+            packet.destination_addr,
+            ssrc,
+            rtcp,
+            outer,
+        );
+
+        if !self.streams.contains_key(&key) {
+            let Some(master_keys) = self.find_keys(packet.source_addr, packet.destination_addr)
+            else {
+                return false;
+            };
+            let keys = master_keys
+                .iter()
+                .filter_map(|master_key| SessionKeys::derive(master_key, rtcp))
+                .collect();
+            self.streams.insert(key, Stream::new(keys));
+        }
+        let Some(stream) = self.streams.get_mut(&key) else {
+            return false;
+        };
+        stream.last_seen = packet.timestamp;
+
+        let unprotected = match rtcp {
+            true => stream.unprotect_rtcp(payload),
+            false => stream.unprotect_rtp(payload),
+        };
+        let Some((index, plain)) = unprotected else {
+            packet.metadata.srtp = Some(SrtpInfo {
+                ssrc,
+                rtcp,
+                index: None,
+                status: SrtpStatus::AuthFailed,
+            });
+            return false;
+        };
+
+        let status = match stream.replayed(index) {
+            true => SrtpStatus::Replayed,
+            false => SrtpStatus::Decrypted,
+        };
+        packet.metadata.srtp = Some(SrtpInfo {
+            ssrc,
+            rtcp,
+            index: Some(index),
+            status,
+        });
+        packet.payload = Some(plain);
+        packet.session_protocol = SessionProtocol::Unknown;
+        packet.contents = SessionPacket::Unknown;
+        packet.parse_as(match rtcp {
+            true => SessionProtocol::Rtcp,
+            false => SessionProtocol::Rtp,
+        });
+        true
+    }
+
+    pub fn reset(&mut self) {
+        self.keys.clear();
+        self.handshakes.clear();
+        self.streams.clear();
+        self.last_prune = Duration::ZERO;
+    }
+
+    fn push_dtls(&mut self, packet: &Packet, dtls: &DtlsPacket) {
+        for handshake in dtls.handshakes() {
+            match handshake.body {
+                HandshakeBody::ClientHello { ref random, .. } => {
+                    let session = self
+                        .handshakes
+                        .entry((packet.source_addr, packet.destination_addr))
+                        .or_default();
+                    // the hello is sent again with the same random after a HelloVerifyRequest
+                    if session.client_random.as_ref() != Some(random) {
+                        *session = DtlsSession {
+                            client_random: Some(random.clone()),
+                            ..Default::default()
+                        };
+                    }
+                    session.last_seen = packet.timestamp;
+                }
+                HandshakeBody::ServerHello {
+                    version,
+                    ref random,
+                    cipher_suite,
+                    srtp_profile,
+                } => {
+                    let session = self
+                        .handshakes
+                        .entry((packet.destination_addr, packet.source_addr))
+                        .or_default();
+                    if session.server_random.as_ref() != Some(random) {
+                        session.server_random = Some(random.clone());
+                        session.keyed = false;
+                    }
+                    session.version = version;
+                    session.cipher_suite = cipher_suite;
+                    session.profile = srtp_profile;
+                    session.last_seen = packet.timestamp;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // the DTLS-SRTP keys of the 5-tuple first, then the SDES ones of the sender
+    fn find_keys(
+        &mut self,
+        source_addr: SocketAddr,
+        destination_addr: SocketAddr,
+    ) -> Option<Vec<MasterKey>> {
+        self.export_keys(source_addr, destination_addr);
+        self.keys
+            .get(&(source_addr, Some(destination_addr)))
+            .or_else(|| self.keys.get(&(source_addr, None)))
+            .cloned()
+    }
+
+    // once the key log has the secret of the handshake between the addresses
+    fn export_keys(&mut self, addr: SocketAddr, other_addr: SocketAddr) {
+        let Some(ref mut key_log) = self.key_log else {
+            return;
+        };
+        let (client_addr, server_addr) = match self.handshakes.contains_key(&(addr, other_addr)) {
+            true => (addr, other_addr),
+            false => (other_addr, addr),
+        };
+        let Some(session) = self.handshakes.get_mut(&(client_addr, server_addr)) else {
+            return;
+        };
+        let (Some(client_random), Some(server_random), Some(profile)) = (
+            session.client_random.as_deref(),
+            session.server_random.as_deref(),
+            session.profile,
+        ) else {
+            return;
+        };
+        if session.keyed {
+            return;
+        }
+
+        let Some(suite) = Suite::of(profile) else {
+            return;
+        };
+        let material = key_log.export_srtp(
+            session.version,
+            session.cipher_suite,
+            client_random,
+            server_random,
+            suite.exporter_len(),
+        );
+        let Some((client_key, server_key)) =
+            material.and_then(|material| MasterKey::from_exporter(profile, &material))
+        else {
+            return;
+        };
+
+        session.keyed = true;
+        self.set_keys((client_addr, Some(server_addr)), vec![client_key]);
+        self.set_keys((server_addr, Some(client_addr)), vec![server_key]);
+    }
+
+    // the streams of the sender start over with new keys
+    fn set_keys(&mut self, sender: Sender, keys: Vec<MasterKey>) {
+        if self.keys.get(&sender) == Some(&keys) {
+            return;
+        }
+
+        let (source_addr, destination_addr) = sender;
+        self.streams.retain(|key, _| {
+            key.0 != source_addr || destination_addr.is_some_and(|addr| addr != key.1)
+        });
+        self.keys.insert(sender, keys);
+    }
+
+    fn prune(&mut self, now: Duration) {
+        if now.saturating_sub(self.last_prune) < PRUNE_INTERVAL {
+            return;
+        }
+
+        self.handshakes
+            .retain(|_, session| now.saturating_sub(session.last_seen) < TIMEOUT);
+        self.streams
+            .retain(|_, stream| now.saturating_sub(stream.last_seen) < TIMEOUT);
+        self.last_prune = now;
+    }
+}
+
+impl Stream {
+    fn new(keys: Vec<SessionKeys>) -> Self {
+        Self {
+            keys,
+            chosen: None,
+            highest: None,
+            window: 0,
+            last_seen: Duration::ZERO,
+        }
+    }
+
+    fn unprotect_rtp(&mut self, packet: &[u8]) -> Option<(u64, Vec<u8>)> {
+        let header_len = rtp_header_len(packet)?;
+        let index = estimate_index(self.highest, u16::from_be_bytes([packet[2], packet[3]]));
+        // the rollover counter isn't known before the first authenticated packet
+        let guesses = match self.highest {
+            Some(_) => 1,
+            None => ROLLOVER_GUESSES,
+        };
+
+        (0..guesses).find_map(|rollovers| {
+            let index = index + (rollovers << 16);
+            self.try_keys(|keys| keys.unprotect_rtp(packet, header_len, index))
+                .map(|plain| (index, plain))
+        })
+    }
+
+    fn unprotect_rtcp(&mut self, packet: &[u8]) -> Option<(u64, Vec<u8>)> {
+        self.try_keys(|keys| keys.unprotect_rtcp(packet))
+            .map(|(index, plain)| (index as u64, plain))
+    }
+
+    fn try_keys<T>(&mut self, unprotect: impl Fn(&SessionKeys) -> Option<T>) -> Option<T> {
+        if let Some(chosen) = self.chosen {
+            return unprotect(&self.keys[chosen]);
+        }
+
+        let (chosen, unprotected) = self
+            .keys
+            .iter()
+            .enumerate()
+            .find_map(|(i, keys)| Some((i, unprotect(keys)?)))?;
+        self.chosen = Some(chosen);
+        Some(unprotected)
+    }
+
+    // marks the index as received, returns whether it already was
+    fn replayed(&mut self, index: u64) -> bool {
+        let Some(highest) = self.highest else {
+            self.highest = Some(index);
+            self.window = 1;
+            return false;
+        };
+
+        if index > highest {
+            let ahead = index - highest;
+            self.window = match ahead < REPLAY_WINDOW {
+                true => self.window << ahead | 1,
+                false => 1,
+            };
+            self.highest = Some(index);
+            return false;
+        }
+
+        let behind = highest - index;
+        if behind >= REPLAY_WINDOW {
+            return true;
+        }
+        let bit = 1 << behind;
+        let replayed = self.window & bit != 0;
+        self.window |= bit;
+        replayed
+    }
+}
+
+fn rtp_header_len(packet: &[u8]) -> Option<usize> {
+    let mut len = RTP_HEADER_LEN + 4 * (packet[0] & 0x0F) as usize;
+    if packet[0] & 0x10 != 0 {
+        let words = packet.get(len + 2..len + 4)?;
+        len += 4 + 4 * u16::from_be_bytes([words[0], words[1]]) as usize;
+    }
+    (len <= packet.len()).then_some(len)
+}
+
+// RFC 3711 section 3.3.1, the rollover counter of the highest index is the one
+// before, the same or the one after it
+fn estimate_index(highest: Option<u64>, sequence_number: u16) -> u64 {
+    let sequence_number = sequence_number as u64;
+    let Some(highest) = highest else {
+        return sequence_number;
+    };
+
+    let (rollovers, highest_sequence_number) = (highest >> 16, highest & 0xFFFF);
+    let rollovers = match highest_sequence_number < 0x8000 {
+        true if sequence_number > highest_sequence_number + 0x8000 => rollovers.saturating_sub(1),
+        false if sequence_number + 0x8000 < highest_sequence_number => rollovers + 1,
+        _ => rollovers,
+    };
+    rollovers << 16 | sequence_number
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dtls::{
+        CONTENT_HANDSHAKE, DtlsContent, DtlsRecord, Handshake, SRTP_AES128_CM_HMAC_SHA1_80,
+        VERSION_1_2,
+    };
+    use crate::packet::{DecodeRules, PacketMetadata, TransportProtocol};
+    use crate::rtcp::RtcpPacket;
+    use std::time::SystemTime;
+
+    const CLIENT: &str = "10.0.0.1:4000";
+    const SERVER: &str = "10.0.0.2:5000";
+    const PAYLOAD_LEN: usize = 45;
+    // AES_CM_128_HMAC_SHA1_80 with the master key 0..16 and salt 16..30,
+    // rollover counter 1 and sequence number 0x1234
+    const SRTP: &str = concat!(
+        "80e01234decafbadcafebabe1652b1c008429a1e8137feb70ed9bfd52a12409c",
+        "9090f75f015bec9565fec2c4c53da1a50fefd880c656a80706490a2cca79a6a8",
+        "8909dc"
+    );
+    // a sender report with index 5
+    const SRTCP: &str = concat!(
+        "80c80006cafebabed161dcbfc16e4a89dc98ab8b419e6c3ef3729a5080000005",
+        "5e8590b3052d8f8442a6"
+    );
+    // the same RTP packet protected with the client key exported from the master
+    // secret 0..48 and the randoms [1; 32] and [2; 32], at rollover counter 0
+    const DTLS_SRTP: &str = concat!(
+        "80e01234decafbadcafebabeed556d41997a7cf039e3da9daca0b5c2ee9b9d4a",
+        "e323c38c209efa617fb946f659cfd4817f8684f755a3b254e6cbb5141effdf03",
+        "19edf6"
+    );
+
+    fn hex(text: &str) -> Vec<u8> {
+        (0..text.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&text[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn packet(source: &str, destination: &str, payload: Vec<u8>) -> Packet {
+        let mut packet = Packet {
+            length: payload.len() as u32,
+            payload: Some(payload),
+            id: 0,
+            timestamp: Duration::ZERO,
+            source_addr: source.parse().unwrap(),
+            destination_addr: destination.parse().unwrap(),
+            transport_protocol: TransportProtocol::Udp,
+            session_protocol: SessionProtocol::Unknown,
+            contents: SessionPacket::Unknown,
+            creation_time: SystemTime::now(),
+            metadata: PacketMetadata::default(),
+        };
+        packet.guess_payload(&DecodeRules::default());
+        packet
+    }
+
+    fn hello(source: &str, destination: &str, body: HandshakeBody) -> Packet {
+        let mut packet = packet(source, destination, Vec::new());
+        let handshake = Handshake {
+            message_type: 1,
+            length: 0,
+            message_seq: 0,
+            fragment_offset: 0,
+            fragment_length: 0,
+            body,
+        };
+        packet.contents = SessionPacket::Dtls(DtlsPacket {
+            records: vec![DtlsRecord {
+                content_type: CONTENT_HANDSHAKE,
+                version: Some(VERSION_1_2),
+                epoch: 0,
+                sequence_number: 0,
+                length: 0,
+                content: DtlsContent::Handshake(vec![handshake]),
+            }],
+        });
+        packet
+    }
+
+    #[test]
+    fn test_decrypts_with_sdes_keys() {
+        let sdp = Sdp::build(
+            "m=audio 4000 RTP/SAVP 96\nc=IN IP4 10.0.0.1\n\
+             a=crypto:1 AES_CM_128_HMAC_SHA1_32 inline:MDEyMzQ1Njc4OWFiY2RlZmdoaWprbG1ub3BxcnN0\n\
+             a=crypto:2 AES_CM_128_HMAC_SHA1_80 inline:AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwd\n"
+                .to_string(),
+        )
+        .unwrap();
+        let mut sessions = SrtpSessions::default();
+        sessions.add_sdp(&sdp);
+
+        // found after the rollover, with the second key
+        let mut srtp = packet(CLIENT, SERVER, hex(SRTP));
+        assert!(sessions.push(&mut srtp));
+        let SessionPacket::Rtp(ref rtp) = srtp.contents else {
+            panic!("not decrypted to RTP");
+        };
+        assert_eq!(rtp.payload_length, PAYLOAD_LEN);
+        assert_eq!(
+            srtp.metadata.srtp,
+            Some(SrtpInfo {
+                ssrc: 0xcafebabe,
+                rtcp: false,
+                index: Some(0x1_1234),
+                status: SrtpStatus::Decrypted,
+            })
+        );
+
+        let mut replayed = packet(CLIENT, SERVER, hex(SRTP));
+        assert!(sessions.push(&mut replayed));
+        assert_eq!(
+            replayed.metadata.srtp.map(|srtp| srtp.status),
+            Some(SrtpStatus::Replayed)
+        );
+
+        let mut tampered = hex(SRTP);
+        tampered[20] ^= 1;
+        let mut tampered = packet(CLIENT, SERVER, tampered);
+        assert!(!sessions.push(&mut tampered));
+        assert_eq!(
+            tampered.metadata.srtp.map(|srtp| srtp.status),
+            Some(SrtpStatus::AuthFailed)
+        );
+
+        let mut srtcp = packet("10.0.0.1:4001", SERVER, hex(SRTCP));
+        assert!(sessions.push(&mut srtcp));
+        let SessionPacket::Rtcp(ref rtcp) = srtcp.contents else {
+            panic!("not decrypted to RTCP");
+        };
+        assert!(matches!(rtcp[0], RtcpPacket::SenderReport(_)));
+        assert_eq!(srtcp.metadata.srtp.and_then(|srtp| srtp.index), Some(5));
+
+        // no keys for the other direction
+        let mut other = packet(SERVER, CLIENT, hex(SRTP));
+        assert!(!sessions.push(&mut other));
+        assert_eq!(other.metadata.srtp, None);
+    }
+
+    #[test]
+    fn test_decrypts_with_key_log() {
+        let path = std::env::temp_dir().join(format!("netpix-keylog-{}", std::process::id()));
+        let secret: String = (0..48u8).map(|byte| format!("{:02x}", byte)).collect();
+        std::fs::write(
+            &path,
+            format!("CLIENT_RANDOM {} {}\n", "01".repeat(32), secret),
+        )
+        .unwrap();
+        let mut sessions = SrtpSessions::new(Some(path.clone()));
+        std::fs::remove_file(path).unwrap();
+
+        let mut client_hello = hello(
+            CLIENT,
+            SERVER,
+            HandshakeBody::ClientHello {
+                version: VERSION_1_2,
+                random: vec![1; 32],
+                cookie: Vec::new(),
+                cipher_suites: vec![0xC02B],
+                srtp_profiles: vec![SRTP_AES128_CM_HMAC_SHA1_80],
+            },
+        );
+        let mut server_hello = hello(
+            SERVER,
+            CLIENT,
+            HandshakeBody::ServerHello {
+                version: VERSION_1_2,
+                random: vec![2; 32],
+                cipher_suite: 0xC02B,
+                srtp_profile: Some(SRTP_AES128_CM_HMAC_SHA1_80),
+            },
+        );
+        sessions.push(&mut client_hello);
+        sessions.push(&mut server_hello);
+
+        let mut srtp = packet(CLIENT, SERVER, hex(DTLS_SRTP));
+        assert!(sessions.push(&mut srtp));
+        assert_eq!(srtp.metadata.srtp.and_then(|srtp| srtp.index), Some(0x1234));
+
+        // the keys are bound to the 5-tuple
+        let mut srtp = packet(CLIENT, "10.0.0.3:5000", hex(DTLS_SRTP));
+        assert!(!sessions.push(&mut srtp));
+    }
+
+    #[test]
+    fn test_estimates_index() {
+        assert_eq!(estimate_index(None, 7), 7);
+        assert_eq!(estimate_index(Some(0x1_FFF0), 0x0005), 0x2_0005);
+        assert_eq!(estimate_index(Some(0x2_0005), 0xFFF0), 0x1_FFF0);
+        assert_eq!(estimate_index(Some(0x0_0005), 0xFFF0), 0x0_FFF0);
+        assert_eq!(estimate_index(Some(0x1_8000), 0x8100), 0x1_8100);
+    }
+
+    #[test]
+    fn test_detects_replays() {
+        let mut stream = Stream::new(Vec::new());
+        assert!(!stream.replayed(100));
+        assert!(!stream.replayed(98));
+        assert!(stream.replayed(98));
+        assert!(stream.replayed(100));
+        assert!(!stream.replayed(200));
+        // behind the window
+        assert!(stream.replayed(100));
+        assert!(!stream.replayed(199));
+    }
+}
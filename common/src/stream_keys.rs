@@ -1,4 +1,4 @@
-use crate::packet::TransportProtocol;
+use crate::packet::{Packet, TransportProtocol};
 use std::fmt;
 use std::net::SocketAddr;
 
@@ -7,10 +7,45 @@ pub struct PacketAssociationTable {
     pub source_addr: SocketAddr,
     pub destination_addr: SocketAddr,
     pub protocol: TransportProtocol,
+    pub vlan_id: Option<u16>,
 }
 
-pub type MpegtsStreamKey = (SocketAddr, SocketAddr, TransportProtocol);
-pub type RtpStreamKey = (SocketAddr, SocketAddr, TransportProtocol, u32);
+impl From<&Packet> for PacketAssociationTable {
+    fn from(packet: &Packet) -> Self {
+        Self {
+            source_addr: packet.source_addr,
+            destination_addr: packet.destination_addr,
+            protocol: packet.transport_protocol,
+            vlan_id: packet.metadata.vlan_id(),
+        }
+    }
+}
+
+// the VLAN ID is a part of the key, so the same addresses
+// reused on different VLANs end up in separate streams
+pub type MpegtsStreamKey = (SocketAddr, SocketAddr, TransportProtocol, Option<u16>);
+pub type RtpStreamKey = (SocketAddr, SocketAddr, TransportProtocol, u32, Option<u16>);
+
+impl Packet {
+    pub fn mpegts_stream_key(&self) -> MpegtsStreamKey {
+        (
+            self.source_addr,
+            self.destination_addr,
+            self.transport_protocol,
+            self.metadata.vlan_id(),
+        )
+    }
+
+    pub fn rtp_stream_key(&self, ssrc: u32) -> RtpStreamKey {
+        (
+            self.source_addr,
+            self.destination_addr,
+            self.transport_protocol,
+            ssrc,
+            self.metadata.vlan_id(),
+        )
+    }
+}
 
 impl fmt::Display for PacketAssociationTable {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -18,6 +53,12 @@ impl fmt::Display for PacketAssociationTable {
             f,
             "{} -> {} ({:?})",
             self.source_addr, self.destination_addr, self.protocol
-        )
+        )?;
+
+        if let Some(vlan_id) = self.vlan_id {
+            write!(f, " [VLAN {}]", vlan_id)?;
+        }
+
+        Ok(())
     }
 }
@@ -3,11 +3,15 @@ pub use class::MessageClass;
 pub use message_type::MessageType;
 pub use method::Method;
 pub use stun_attribute::StunAttribute;
+#[cfg(not(target_arch = "wasm32"))]
+pub use turn::TurnRelays;
 
 pub mod class;
 pub mod message_type;
 pub mod method;
 pub mod stun_attribute;
+#[cfg(not(target_arch = "wasm32"))]
+mod turn;
 
 #[derive(Decode, Encode, Debug, Clone)]
 pub struct StunPacket {
@@ -17,6 +21,14 @@ pub struct StunPacket {
     pub attributes: Vec<StunAttribute>,
 }
 
+impl StunPacket {
+    pub fn attribute(&self, attribute_type: u16) -> Option<&StunAttribute> {
+        self.attributes
+            .iter()
+            .find(|attribute| attribute.attribute_type == attribute_type)
+    }
+}
+
 #[cfg(not(target_arch = "wasm32"))]
 impl From<&stun::message::Message> for StunPacket {
     fn from(msg: &stun::message::Message) -> Self {
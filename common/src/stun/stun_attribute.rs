@@ -1,5 +1,5 @@
 use bincode::{Decode, Encode};
-use std::net::{Ipv4Addr, Ipv6Addr};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
 
 #[derive(Decode, Encode, Debug, Clone)]
 pub struct StunAttribute {
@@ -70,6 +70,15 @@ impl StunAttribute {
         s.to_string()
     }
 
+    /// Address of the XOR-MAPPED-ADDRESS, XOR-PEER-ADDRESS or XOR-RELAYED-ADDRESS attribute.
+    pub fn xor_address(&self, txid: &[u8; 12]) -> Option<SocketAddr> {
+        match self.value.get(1).copied()? {
+            0x01 => parse_xor_address_v4(&self.value).map(SocketAddr::from),
+            0x02 => parse_xor_address_v6(&self.value, txid).map(SocketAddr::from),
+            _ => None,
+        }
+    }
+
     pub fn get_value_with_txid(&self, txid: &[u8; 12]) -> String {
         match self.attribute_type {
             ATTR_USERNAME | ATTR_REALM | ATTR_NONCE | ATTR_SOFTWARE => {
@@ -0,0 +1,309 @@
+use super::StunPacket;
+use super::class::{CLASS_INDICATION, CLASS_REQUEST, CLASS_SUCCESS_RESPONSE};
+use super::method::{METHOD_ALLOCATE, METHOD_CHANNEL_BIND, METHOD_DATA, METHOD_SEND};
+use super::stun_attribute::{
+    ATTR_CHANNEL_NUMBER, ATTR_DATA, ATTR_XOR_PEER_ADDRESS, ATTR_XOR_RELAYED_ADDRESS,
+};
+use crate::packet::{DecodeRules, Encapsulation, SessionPacket, TransportProtocol, Tunnel};
+use crate::{Packet, SessionProtocol};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::ops::RangeInclusive;
+use std::time::Duration;
+
+// allocations and channel bindings last 10 minutes unless refreshed
+const TIMEOUT: Duration = Duration::from_secs(600);
+const PRUNE_INTERVAL: Duration = Duration::from_secs(10);
+const CHANNELS: RangeInclusive<u16> = 0x4000..=0x7FFF;
+const CHANNEL_DATA_HEADER_LEN: usize = 4;
+
+// client and server of the allocation
+type AllocationKey = (SocketAddr, SocketAddr);
+// source and destination as seen by the peer, the channel and the relayed data
+type Unwrapped = (SocketAddr, SocketAddr, Option<u16>, Vec<u8>);
+
+#[derive(Debug, Default)]
+struct Allocation {
+    relayed_addr: Option<SocketAddr>,
+    // peers of the channels, by channel number
+    channels: HashMap<u16, SocketAddr>,
+    // ChannelBind requests waiting for their response, by transaction id
+    pending_binds: HashMap<[u8; 12], (u16, SocketAddr)>,
+    last_seen: Duration,
+}
+
+/// Follows TURN allocations (RFC 8656) and unwraps the data relayed through them.
+///
+/// Data of Send and Data indications and of ChannelData messages on channels bound
+/// by an observed ChannelBind transaction is decoded as if it was sent between
+/// the relayed address of the client and the peer, the TURN server is kept as a tunnel.
+#[derive(Debug, Default)]
+pub struct TurnRelays {
+    allocations: HashMap<AllocationKey, Allocation>,
+    last_prune: Duration,
+}
+
+impl TurnRelays {
+    /// Learns from the TURN messages and unwraps the relayed data, returns whether the packet was unwrapped.
+    pub fn push(&mut self, packet: &mut Packet, rules: &DecodeRules) -> bool {
+        self.prune(packet.timestamp);
+
+        if packet.transport_protocol != TransportProtocol::Udp {
+            return false;
+        }
+
+        let unwrapped = match packet.contents {
+            SessionPacket::Stun(ref stun) => self.push_stun(packet, stun),
+            SessionPacket::Unknown => self.channel_data(packet),
+            _ => None,
+        };
+        let Some((source_addr, destination_addr, channel, data)) = unwrapped else {
+            return false;
+        };
+
+        packet.metadata.tunnels.push(Tunnel {
+            source_addr: packet.source_addr.ip(),
+            destination_addr: packet.destination_addr.ip(),
+            encapsulation: Encapsulation::Turn { channel },
+        });
+        packet.source_addr = source_addr;
+        packet.destination_addr = destination_addr;
+        packet.payload = Some(data);
+        packet.session_protocol = SessionProtocol::Unknown;
+        packet.contents = SessionPacket::Unknown;
+        packet.guess_payload(rules);
+        true
+    }
+
+    pub fn reset(&mut self) {
+        self.allocations.clear();
+        self.last_prune = Duration::ZERO;
+    }
+
+    fn push_stun(&mut self, packet: &Packet, stun: &StunPacket) -> Option<Unwrapped> {
+        let txid = &stun.transaction_id;
+        let message_type = stun.message_type;
+        let from_client = message_type.class == CLASS_REQUEST || message_type.method == METHOD_SEND;
+        let key = match from_client {
+            true => (packet.source_addr, packet.destination_addr),
+            false => (packet.destination_addr, packet.source_addr),
+        };
+
+        match (message_type.method, message_type.class) {
+            (METHOD_ALLOCATE, CLASS_SUCCESS_RESPONSE) => {
+                let relayed_addr = stun
+                    .attribute(ATTR_XOR_RELAYED_ADDRESS)?
+                    .xor_address(txid)?;
+                self.allocation(key, packet.timestamp).relayed_addr = Some(relayed_addr);
+                None
+            }
+            (METHOD_CHANNEL_BIND, CLASS_REQUEST) => {
+                let channel = stun.attribute(ATTR_CHANNEL_NUMBER)?.value.get(..2)?;
+                let channel = u16::from_be_bytes([channel[0], channel[1]]);
+                let peer_addr = stun.attribute(ATTR_XOR_PEER_ADDRESS)?.xor_address(txid)?;
+                if CHANNELS.contains(&channel) {
+                    let allocation = self.allocation(key, packet.timestamp);
+                    allocation.pending_binds.insert(*txid, (channel, peer_addr));
+                }
+                None
+            }
+            (METHOD_CHANNEL_BIND, CLASS_SUCCESS_RESPONSE) => {
+                let allocation = self.allocations.get_mut(&key)?;
+                let (channel, peer_addr) = allocation.pending_binds.remove(txid)?;
+                allocation.channels.insert(channel, peer_addr);
+                None
+            }
+            (METHOD_SEND | METHOD_DATA, CLASS_INDICATION) => {
+                let peer_addr = stun.attribute(ATTR_XOR_PEER_ADDRESS)?.xor_address(txid)?;
+                let data = stun.attribute(ATTR_DATA)?.value.clone();
+                let allocation = self.allocation(key, packet.timestamp);
+                // the client stands in for its relayed address until the allocation is seen
+                let relayed_addr = allocation.relayed_addr.unwrap_or(key.0);
+                match from_client {
+                    true => Some((relayed_addr, peer_addr, None, data)),
+                    false => Some((peer_addr, relayed_addr, None, data)),
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn channel_data(&mut self, packet: &Packet) -> Option<Unwrapped> {
+        let payload = packet.payload.as_deref()?;
+        let header = payload.get(..CHANNEL_DATA_HEADER_LEN)?;
+        let channel = u16::from_be_bytes([header[0], header[1]]);
+        let len = u16::from_be_bytes([header[2], header[3]]) as usize;
+        if !CHANNELS.contains(&channel) {
+            return None;
+        }
+        // the data may be followed by padding
+        let data = payload.get(CHANNEL_DATA_HEADER_LEN..CHANNEL_DATA_HEADER_LEN + len)?;
+
+        let to_server = (packet.source_addr, packet.destination_addr);
+        let to_client = (packet.destination_addr, packet.source_addr);
+        let (key, from_client) = match self.allocations.contains_key(&to_server) {
+            true => (to_server, true),
+            false => (to_client, false),
+        };
+        let allocation = self.allocations.get_mut(&key)?;
+        let peer_addr = *allocation.channels.get(&channel)?;
+        allocation.last_seen = packet.timestamp;
+
+        let relayed_addr = allocation.relayed_addr.unwrap_or(key.0);
+        match from_client {
+            true => Some((relayed_addr, peer_addr, Some(channel), data.to_vec())),
+            false => Some((peer_addr, relayed_addr, Some(channel), data.to_vec())),
+        }
+    }
+
+    fn allocation(&mut self, key: AllocationKey, timestamp: Duration) -> &mut Allocation {
+        let allocation = self.allocations.entry(key).or_default();
+        allocation.last_seen = timestamp;
+        allocation
+    }
+
+    fn prune(&mut self, now: Duration) {
+        if now.saturating_sub(self.last_prune) < PRUNE_INTERVAL {
+            return;
+        }
+
+        self.allocations
+            .retain(|_, allocation| now.saturating_sub(allocation.last_seen) < TIMEOUT);
+        self.last_prune = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::PacketMetadata;
+    use crate::stun::stun_attribute::MAGIC_COOKIE;
+    use std::time::SystemTime;
+
+    const CLIENT: &str = "192.168.0.204:50079";
+    const SERVER: &str = "192.168.0.175:3478";
+    const RELAYED: &str = "192.168.0.175:53682";
+    const PEER: &str = "192.168.0.175:53038";
+    const RTP: [u8; 12] = [0x80, 0x6f, 0x00, 0x01, 0, 0, 0, 160, 0x11, 0x22, 0x33, 0x44];
+
+    fn packet(source: &str, destination: &str, payload: Vec<u8>) -> Packet {
+        let mut packet = Packet {
+            length: payload.len() as u32,
+            payload: Some(payload),
+            id: 0,
+            timestamp: Duration::ZERO,
+            source_addr: source.parse().unwrap(),
+            destination_addr: destination.parse().unwrap(),
+            transport_protocol: TransportProtocol::Udp,
+            session_protocol: SessionProtocol::Unknown,
+            contents: SessionPacket::Unknown,
+            creation_time: SystemTime::now(),
+            metadata: PacketMetadata::default(),
+        };
+        packet.guess_payload(&DecodeRules::default());
+        packet
+    }
+
+    fn xor_address(addr: &str) -> Vec<u8> {
+        let SocketAddr::V4(addr) = addr.parse().unwrap() else {
+            panic!("not an IPv4 address");
+        };
+        let mut value = vec![0x00, 0x01];
+        value.extend_from_slice(&(addr.port() ^ (MAGIC_COOKIE >> 16) as u16).to_be_bytes());
+        value.extend_from_slice(&(u32::from(*addr.ip()) ^ MAGIC_COOKIE).to_be_bytes());
+        value
+    }
+
+    fn message(message_type: u16, txid: u8, attributes: &[(u16, Vec<u8>)]) -> Vec<u8> {
+        let mut body = Vec::new();
+        for (attribute_type, value) in attributes {
+            body.extend_from_slice(&attribute_type.to_be_bytes());
+            body.extend_from_slice(&(value.len() as u16).to_be_bytes());
+            body.extend_from_slice(value);
+            body.resize(body.len().next_multiple_of(4), 0);
+        }
+
+        let mut message = message_type.to_be_bytes().to_vec();
+        message.extend_from_slice(&(body.len() as u16).to_be_bytes());
+        message.extend_from_slice(&MAGIC_COOKIE.to_be_bytes());
+        message.extend_from_slice(&[txid; 12]);
+        message.extend_from_slice(&body);
+        message
+    }
+
+    fn allocate(relays: &mut TurnRelays) {
+        let mut response = packet(
+            SERVER,
+            CLIENT,
+            message(
+                0x0103,
+                1,
+                &[(ATTR_XOR_RELAYED_ADDRESS, xor_address(RELAYED))],
+            ),
+        );
+        assert!(!relays.push(&mut response, &DecodeRules::default()));
+    }
+
+    #[test]
+    fn test_unwraps_indications() {
+        let mut relays = TurnRelays::default();
+        allocate(&mut relays);
+
+        let attributes = [
+            (ATTR_XOR_PEER_ADDRESS, xor_address(PEER)),
+            (ATTR_DATA, RTP.to_vec()),
+        ];
+        let mut send = packet(CLIENT, SERVER, message(0x0016, 2, &attributes));
+        assert!(relays.push(&mut send, &DecodeRules::default()));
+        assert_eq!(send.source_addr, RELAYED.parse().unwrap());
+        assert_eq!(send.destination_addr, PEER.parse().unwrap());
+        assert_eq!(send.session_protocol, SessionProtocol::Rtp);
+        assert_eq!(
+            send.metadata.tunnels[0].to_string(),
+            "TURN 192.168.0.204 → 192.168.0.175"
+        );
+
+        let mut data = packet(SERVER, CLIENT, message(0x0017, 3, &attributes));
+        assert!(relays.push(&mut data, &DecodeRules::default()));
+        assert_eq!(data.source_addr, PEER.parse().unwrap());
+        assert_eq!(data.destination_addr, RELAYED.parse().unwrap());
+        assert_eq!(data.session_protocol, SessionProtocol::Rtp);
+    }
+
+    #[test]
+    fn test_unwraps_bound_channels() {
+        let mut relays = TurnRelays::default();
+        allocate(&mut relays);
+
+        let mut channel_data = vec![0x40, 0x00, 0x00, RTP.len() as u8];
+        channel_data.extend_from_slice(&RTP);
+        let mut unbound = packet(CLIENT, SERVER, channel_data.clone());
+        assert!(!relays.push(&mut unbound, &DecodeRules::default()));
+
+        let attributes = [
+            (ATTR_CHANNEL_NUMBER, vec![0x40, 0x00, 0x00, 0x00]),
+            (ATTR_XOR_PEER_ADDRESS, xor_address(PEER)),
+        ];
+        let mut request = packet(CLIENT, SERVER, message(0x0009, 4, &attributes));
+        let mut response = packet(SERVER, CLIENT, message(0x0109, 4, &[]));
+        assert!(!relays.push(&mut request, &DecodeRules::default()));
+        assert!(!relays.push(&mut response, &DecodeRules::default()));
+
+        let mut sent = packet(CLIENT, SERVER, channel_data.clone());
+        assert!(relays.push(&mut sent, &DecodeRules::default()));
+        assert_eq!(sent.source_addr, RELAYED.parse().unwrap());
+        assert_eq!(sent.destination_addr, PEER.parse().unwrap());
+        assert_eq!(sent.session_protocol, SessionProtocol::Rtp);
+        assert_eq!(
+            sent.metadata.tunnels[0].encapsulation,
+            Encapsulation::Turn {
+                channel: Some(0x4000)
+            }
+        );
+
+        let mut received = packet(SERVER, CLIENT, channel_data);
+        assert!(relays.push(&mut received, &DecodeRules::default()));
+        assert_eq!(received.source_addr, PEER.parse().unwrap());
+        assert_eq!(received.destination_addr, RELAYED.parse().unwrap());
+    }
+}
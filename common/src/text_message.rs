@@ -0,0 +1,177 @@
+//! Messages of the text protocols shaped like HTTP, SIP (RFC 3261) and RTSP (RFC 2326).
+
+use bincode::{Decode, Encode};
+use std::fmt;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{Sdp, sip::SDP_CONTENT_TYPE};
+
+#[derive(Decode, Encode, Debug, Clone, PartialEq)]
+pub enum StartLine {
+    Request { method: String, uri: String },
+    Response { status_code: u16, reason: String },
+}
+
+impl fmt::Display for StartLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Request { method, uri } => write!(f, "{} {}", method, uri),
+            Self::Response {
+                status_code,
+                reason,
+            } => write!(f, "{} {}", status_code, reason),
+        }
+    }
+}
+
+pub(crate) fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(header, _)| header.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) struct TextMessage<'a> {
+    pub start_line: StartLine,
+    // as received, folded lines joined
+    pub headers: Vec<(String, String)>,
+    body: &'a [u8],
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<'a> TextMessage<'a> {
+    /// Parses a message of the protocol with the given version, e.g. `SIP/2.0`.
+    /// Lines may end with a bare LF instead of CRLF.
+    pub fn parse(payload: &'a [u8], version: &str) -> Option<Self> {
+        let (head, body) = split_head(payload);
+        let head = std::str::from_utf8(head).ok()?;
+        let mut lines = head.lines();
+        let start_line = parse_start_line(lines.next()?, version)?;
+
+        // folded lines continue the previous header
+        let mut headers: Vec<(String, String)> = Vec::new();
+        for line in lines {
+            if line.starts_with([' ', '\t']) {
+                let (_, value) = headers.last_mut()?;
+                value.push(' ');
+                value.push_str(line.trim());
+                continue;
+            }
+
+            let (name, value) = line.split_once(':')?;
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+
+        Some(Self {
+            start_line,
+            headers,
+            body,
+        })
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        header(&self.headers, name)
+    }
+
+    /// The body up to its `Content-Length`, when its content type is SDP.
+    pub fn sdp(&self) -> Option<Sdp> {
+        let is_sdp = self.header("Content-Type").is_some_and(|content_type| {
+            let media_type = content_type.split(';').next().unwrap_or_default();
+            media_type.trim().eq_ignore_ascii_case(SDP_CONTENT_TYPE)
+        });
+        let body_len = self
+            .header("Content-Length")
+            .and_then(|len| len.parse().ok())
+            .unwrap_or(self.body.len())
+            .min(self.body.len());
+        if !is_sdp || body_len == 0 {
+            return None;
+        }
+
+        let body = String::from_utf8_lossy(&self.body[..body_len]);
+        Sdp::build(body.into_owned())
+    }
+}
+
+// the head ends with the first empty line
+#[cfg(not(target_arch = "wasm32"))]
+fn split_head(payload: &[u8]) -> (&[u8], &[u8]) {
+    let mut start = 0;
+    while let Some(pos) = payload[start..].iter().position(|&byte| byte == b'\n') {
+        let end = start + pos + 1;
+        if matches!(&payload[start..end], b"\n" | b"\r\n") {
+            return (&payload[..start], &payload[end..]);
+        }
+        start = end;
+    }
+    (payload, &[])
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn parse_start_line(line: &str, version: &str) -> Option<StartLine> {
+    if let Some(status) = line
+        .strip_prefix(version)
+        .and_then(|rest| rest.strip_prefix(' '))
+    {
+        let (status_code, reason) = status.split_once(' ').unwrap_or((status, ""));
+        let status_code = status_code
+            .parse()
+            .ok()
+            .filter(|code| (100..700).contains(code))?;
+        return Some(StartLine::Response {
+            status_code,
+            reason: reason.to_string(),
+        });
+    }
+
+    let mut parts = line.split(' ');
+    let (method, uri, request_version) = (parts.next()?, parts.next()?, parts.next()?);
+    let is_token = !method.is_empty()
+        && method
+            .bytes()
+            .all(|byte| byte.is_ascii_uppercase() || byte == b'_');
+    if !is_token || request_version != version || parts.next().is_some() {
+        return None;
+    }
+
+    Some(StartLine::Request {
+        method: method.to_string(),
+        uri: uri.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parses_bare_lf_and_folded_headers() {
+        let message = TextMessage::parse(
+            b"OPTIONS rtsp://cam/ RTSP/1.0\nCSeq: 2\nRequire: implicit-play,\n onvif-replay\n\nbody",
+            "RTSP/1.0",
+        )
+        .unwrap();
+
+        assert_eq!(
+            message.start_line,
+            StartLine::Request {
+                method: "OPTIONS".to_string(),
+                uri: "rtsp://cam/".to_string(),
+            }
+        );
+        assert_eq!(message.header("cseq"), Some("2"));
+        assert_eq!(
+            message.header("Require"),
+            Some("implicit-play, onvif-replay")
+        );
+        assert_eq!(message.body, b"body");
+    }
+
+    #[test]
+    fn test_rejects_other_versions() {
+        assert!(TextMessage::parse(b"SIP/2.0 200 OK\r\n\r\n", "RTSP/1.0").is_none());
+        assert!(TextMessage::parse(b"GET / HTTP/1.1\r\n\r\n", "RTSP/1.0").is_none());
+        assert!(TextMessage::parse(b"RTSP/1.0 99 Early\r\n\r\n", "RTSP/1.0").is_none());
+    }
+}
@@ -20,6 +20,7 @@ fn parse_pcaps_without_panics() {
 
         found = true;
         let mut cap = pcap::Capture::from_file(&path).expect("failed to open pcap file");
+        let link_type = cap.get_datalink();
 
         let mut i = 0usize;
         loop {
@@ -30,9 +31,9 @@ fn parse_pcaps_without_panics() {
             };
 
             let res = panic::catch_unwind(|| {
-                let maybe_packet = netpix_common::packet::Packet::build(&pkt, i);
+                let maybe_packet = netpix_common::packet::Packet::build(&pkt, i, link_type);
                 if let Some(mut packet) = maybe_packet {
-                    packet.guess_payload();
+                    packet.guess_payload(&Default::default());
                 }
             });
 
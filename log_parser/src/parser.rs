@@ -196,6 +196,7 @@ impl Parser {
                         PacketMetadata {
                             direction: PacketDirection::Outgoing,
                             is_synthetic_addr: true,
+                            ..Default::default()
                         },
                     ),
                     _ => (
@@ -204,6 +205,7 @@ impl Parser {
                         PacketMetadata {
                             direction: PacketDirection::Incoming,
                             is_synthetic_addr: true,
+                            ..Default::default()
                         },
                     ),
                 };
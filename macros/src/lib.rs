@@ -35,29 +35,38 @@ pub fn setup_packet_handlers(_input: TokenStream) -> TokenStream {
 #[proc_macro]
 pub fn setup_routes(_input: TokenStream) -> TokenStream {
     // Example usage:
-    // setup_routes!(clients, source_to_packets)
+    // setup_routes!(clients, source_to_packets, exports)
     let input = parse_macro_input!(_input as syn::ExprTuple);
     let clients = &input.elems[0];
     let source_to_packets = &input.elems[1];
+    let exports = &input.elems[2];
 
     let expanded = quote! {
         {
             let clients_filter = warp::any().map(move || #clients.clone());
             let source_to_packets_filter = warp::any().map(move || #source_to_packets.clone());
+            let exports_filter = warp::any().map(move || #exports.clone());
 
             let ws = warp::path(crate::server::constants::WEBSOCKET_PATH)
                 .and(warp::ws())
                 .and(clients_filter)
                 .and(source_to_packets_filter)
-                .map(|ws: warp::ws::Ws, clients_cl, source_to_packets_cl| {
+                .and(exports_filter.clone())
+                .map(|ws: warp::ws::Ws, clients_cl, source_to_packets_cl, exports_cl| {
                     ws.on_upgrade(move |socket| {
-                        crate::server::client::handle_connection(socket, clients_cl, source_to_packets_cl)
+                        crate::server::client::handle_connection(socket, clients_cl, source_to_packets_cl, exports_cl)
                     })
                 });
 
+            let export = warp::path(crate::server::constants::EXPORT_PATH)
+                .and(warp::path::param::<u64>())
+                .and(warp::path::end())
+                .and(exports_filter)
+                .and_then(crate::server::export::serve);
+
             let index_html = warp::path::end().and_then(crate::server::asset::serve_index);
             let other = warp::path::tail().and_then(crate::server::asset::serve);
-            ws.or(index_html).or(other)
+            ws.or(export).or(index_html).or(other)
         }
     };
     expanded.into()
@@ -1,8 +1,12 @@
 use crate::server;
 use crate::server::config::Config;
-use crate::sniffer::{Error, Sniffer};
+use crate::server::trigger::{Condition, TriggerConfig};
+use crate::sniffer::{Error, RecordConfig, Sniffer};
+use netpix_common::packet::{DecodeRule, DecodeRules};
 use std::collections::HashMap;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 const DEFAULT_PORT: u16 = 3550;
 const DEFAULT_IP: IpAddr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
@@ -10,15 +14,77 @@ const DEFAULT_PROMISC: bool = false;
 const DEFAULT_PACKET_BUFFER_SIZE: usize = 32_768;
 const DEFAULT_MAXIMUM_PACKAGE_AGE: u64 = 300;
 const DEFAULT_CLIENT_MESSAGE_INTERVAL_MS: u64 = 5; // ~ 300 messages per second
+const DEFAULT_RECORD_FILE_SIZE_MB: usize = 100;
+const DEFAULT_RECORD_FILES: usize = 10;
+const DEFAULT_TRIGGER_DIR: &str = "triggers";
+const DEFAULT_TRIGGER_PRE_SECS: u64 = 10;
+const DEFAULT_TRIGGER_POST_SECS: u64 = 5;
 
 #[derive(Debug, clap::Args)]
 pub struct Run {
-    /// Pcap files to capture the packets from
+    /// Pcap files to capture the packets from, "-" or a named pipe are read as they are written
     #[arg(short, long, num_args = 1..)]
     files: Vec<String>,
+    /// Merge the pcap files into a single source ordered by packet timestamps
+    #[arg(long, default_value_t = false)]
+    merge: bool,
+    /// Directories with rotating pcap files (e.g. from "tcpdump -G 60 -w %s.pcap"), read
+    /// continuously as new files are closed
+    #[arg(short, long = "watch-dir", num_args = 1..)]
+    watch_dirs: Vec<String>,
+    /// Replay the pcap files paced by packet timestamps at the given speed factor
+    /// (0.01 to 1000, e.g. 1, 0.5, 10), 0 starts paused and packets are released step by step
+    #[arg(short = 'r', long)]
+    replay_speed: Option<f64>,
     /// Network interfaces to capture the packets from
     #[arg(short, long, num_args = 1..)]
     interfaces: Vec<String>,
+    /// Record the packets captured from the interfaces to a ring of pcapng files in the
+    /// directory, the files can be reopened later with "run -f"
+    #[arg(long, value_name = "DIR")]
+    record: Option<PathBuf>,
+    /// Size in megabytes after which the recorded file is rotated
+    #[arg(long, default_value_t = DEFAULT_RECORD_FILE_SIZE_MB)]
+    record_size: usize,
+    /// Time in seconds after which the recorded file is rotated
+    #[arg(long)]
+    record_interval: Option<u64>,
+    /// Number of recorded files kept per interface, the oldest ones are removed
+    #[arg(long, default_value_t = DEFAULT_RECORD_FILES)]
+    record_files: usize,
+    /// Stream health conditions which save the packets around the event to a pcapng file:
+    /// "loss:<packets>" (RTP loss burst), "jitter:<ms>" (RTP jitter), "cc" (MPEG-TS
+    /// continuity counter error) or "stop:<seconds>" (stream stopped)
+    #[arg(short, long, num_args = 1..)]
+    trigger: Vec<Condition>,
+    /// Directory where the triggered captures are saved
+    #[arg(long, value_name = "DIR", default_value = DEFAULT_TRIGGER_DIR)]
+    trigger_dir: PathBuf,
+    /// Seconds of packets saved before the trigger event
+    #[arg(long, default_value_t = DEFAULT_TRIGGER_PRE_SECS)]
+    trigger_pre: u64,
+    /// Seconds of packets saved after the trigger event
+    #[arg(long, default_value_t = DEFAULT_TRIGGER_POST_SECS)]
+    trigger_post: u64,
+    /// Rules forcing the protocol of matching packets before any guessing, in
+    /// `<match>:<protocol>` format, e.g. "udp.port==5004:rtp", "dst=239.1.1.0/24:mpegts" or
+    /// "udp.port==1900:unknown" to leave noisy ports undecoded. Conditions on
+    /// [udp.|tcp.]port/srcport/dstport and src/dst/addr can be joined with "&&"
+    #[arg(short, long, num_args = 1..)]
+    decode_as: Vec<DecodeRule>,
+    /// TOML file with more decode as rules, checked after the command line ones, e.g.
+    /// [[decode_as]] match = "udp.port==5004" protocol = "rtp"
+    #[arg(long, value_name = "FILE")]
+    decode_as_file: Option<PathBuf>,
+    /// NSS key log file (e.g. written by browsers with SSLKEYLOGFILE) with the DTLS secrets
+    /// used to decrypt DTLS-SRTP, the keys of SDP "a=crypto" lines are used without it
+    #[arg(long, value_name = "FILE")]
+    keylog: Option<PathBuf>,
+    /// UDP sockets to receive the packets from, in `<group>:<port>[@source][%iface]` format,
+    /// e.g. "239.1.1.1:5004@10.0.0.1%eth0" or "[ff3e::1]:5004". Joins multicast groups
+    /// (source-specific when the source is given) without capture privileges
+    #[arg(short, long, num_args = 1..)]
+    udp: Vec<String>,
     /// rtc_event_log files to capture packets from
     #[arg(short = 'l', long = "log-files", num_args = 1..)]
     log_files: Vec<String>,
@@ -54,14 +120,36 @@ impl Run {
         }
 
         let live_filter = self.create_capture_filter();
+        let record_config = self.record_config();
+        let trigger_config = self.trigger_config();
+        let decode_as = match self.decode_rules() {
+            Ok(rules) => rules,
+            Err(err) => {
+                println!("Error: invalid decode as rules, reason: {}", err);
+                return;
+            }
+        };
 
-        let mut file_sniffers = get_sniffers(self.files, Sniffer::from_file);
+        let mut file_sniffers = if self.merge && self.files.len() > 1 {
+            get_merged_sniffer(&self.files)
+        } else {
+            get_sniffers(self.files, Sniffer::from_file)
+        };
+        if let Some(speed) = self.replay_speed {
+            for sniffer in file_sniffers.values_mut() {
+                sniffer.enable_replay(speed);
+            }
+        }
+
+        let mut dir_sniffers = get_sniffers(self.watch_dirs, Sniffer::from_dir);
         let mut interface_sniffers = get_sniffers(self.interfaces, |dev| {
             Sniffer::from_device(dev, self.promisc)
         });
         let log_sniffers = get_sniffers(self.log_files, Sniffer::from_logs);
+        let udp_sniffers = get_sniffers(self.udp, Sniffer::from_udp);
 
-        let file_res = apply_filters(&mut file_sniffers, &self.capture);
+        let file_res = apply_filters(&mut file_sniffers, &self.capture)
+            .and_then(|_| apply_filters(&mut dir_sniffers, &self.capture));
         let interface_res = apply_filters(&mut interface_sniffers, &live_filter);
 
         if file_res.is_err() || interface_res.is_err() {
@@ -69,10 +157,25 @@ impl Run {
             return;
         }
 
+        if let Some(config) = record_config {
+            for (interface, sniffer) in interface_sniffers.iter_mut() {
+                if let Err(err) = sniffer.record(interface, config.clone()) {
+                    println!(
+                        "Error: failed to record to {}, reason: {:?}",
+                        config.dir.display(),
+                        err
+                    );
+                    return;
+                }
+            }
+        }
+
         let sniffers: HashMap<_, _> = file_sniffers
             .into_iter()
+            .chain(dir_sniffers)
             .chain(interface_sniffers)
             .chain(log_sniffers)
+            .chain(udp_sniffers)
             .collect();
 
         if sniffers.is_empty() {
@@ -87,11 +190,41 @@ impl Run {
             .max_packets_age(self.maximum_package_age)
             .packet_buffer_size(self.buffer_size)
             .addr(address)
+            .maybe_trigger(trigger_config)
+            .decode_as(decode_as)
+            .maybe_keylog(self.keylog)
             .build();
 
         server::run(sniffers, config).await;
     }
 
+    fn trigger_config(&self) -> Option<TriggerConfig> {
+        (!self.trigger.is_empty()).then(|| TriggerConfig {
+            dir: self.trigger_dir.clone(),
+            conditions: self.trigger.clone(),
+            pre: Duration::from_secs(self.trigger_pre),
+            post: Duration::from_secs(self.trigger_post),
+        })
+    }
+
+    fn decode_rules(&self) -> Result<DecodeRules, String> {
+        let mut rules = self.decode_as.clone();
+        if let Some(ref path) = self.decode_as_file {
+            rules.extend(load_decode_rules(path)?);
+        }
+
+        Ok(DecodeRules::new(rules))
+    }
+
+    fn record_config(&self) -> Option<RecordConfig> {
+        Some(RecordConfig {
+            dir: self.record.clone()?,
+            max_file_size: self.record_size * 1024 * 1024,
+            max_file_duration: self.record_interval.map(Duration::from_secs),
+            max_files: self.record_files,
+        })
+    }
+
     fn create_capture_filter(&self) -> String {
         // to filter out RTPeeker own WebSocket/HTTP messages
         let own_filter = if self.address.is_unspecified() {
@@ -129,6 +262,50 @@ where
         .collect()
 }
 
+fn get_merged_sniffer(files: &[String]) -> HashMap<String, Sniffer> {
+    let source = files.join("+");
+    match Sniffer::from_files(files) {
+        Ok(sniffer) => HashMap::from([(source, sniffer)]),
+        Err(err) => {
+            println!(
+                "Failed to capture packets from source {}, reason: {:?}",
+                source, err
+            );
+            HashMap::new()
+        }
+    }
+}
+
+// [[decode_as]] tables with `match` conditions and the `protocol`
+fn load_decode_rules(path: &Path) -> Result<Vec<DecodeRule>, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|err| format!("failed to read {}: {}", path.display(), err))?;
+    let document: toml_edit::DocumentMut = content
+        .parse()
+        .map_err(|err| format!("failed to parse {}: {}", path.display(), err))?;
+
+    let Some(item) = document.get("decode_as") else {
+        return Ok(Vec::new());
+    };
+    let tables = item
+        .as_array_of_tables()
+        .ok_or_else(|| "\"decode_as\" must be an array of tables".to_string())?;
+
+    tables
+        .iter()
+        .enumerate()
+        .map(|(i, table)| {
+            let field = |name| {
+                table
+                    .get(name)
+                    .and_then(|value| value.as_str())
+                    .ok_or_else(|| format!("missing \"{}\" string in rule {}", name, i + 1))
+            };
+            format!("{}:{}", field("match")?, field("protocol")?).parse()
+        })
+        .collect()
+}
+
 fn apply_filters(sniffers: &mut HashMap<String, Sniffer>, filter: &str) -> Result<(), Error> {
     for (_, sniffer) in sniffers.iter_mut() {
         if let err @ Err(_) = sniffer.apply_filter(filter) {
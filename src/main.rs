@@ -2,6 +2,7 @@
 use clap::{Parser, Subcommand};
 
 mod cmd;
+mod pcapng;
 mod server;
 mod sniffer;
 
@@ -29,6 +30,8 @@ impl NetpixArgs {
     }
 }
 
+// parsed once at startup, the size doesn't matter
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Subcommand)]
 enum NetpixSubcommands {
     /// Run the app. E.g "run -f rtp.pcap webex.pcap -i etn0 wireless". Obtain help with "run --help"
@@ -0,0 +1,283 @@
+use pcap::Linktype;
+use std::io::{self, Write};
+use std::time::Duration;
+
+const SECTION_HEADER_BLOCK: u32 = 0x0A0D0D0A;
+const INTERFACE_DESCRIPTION_BLOCK: u32 = 0x00000001;
+pub(crate) const ENHANCED_PACKET_BLOCK: u32 = 0x00000006;
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B3C4D;
+
+const OPT_END_OF_OPT: u16 = 0;
+pub(crate) const OPT_COMMENT: u16 = 1;
+const SHB_USERAPPL: u16 = 4;
+const IF_NAME: u16 = 2;
+const IF_DESCRIPTION: u16 = 3;
+const IF_TSRESOL: u16 = 9;
+const IF_FILTER: u16 = 11;
+// if_filter holding a libpcap filter string
+const FILTER_TYPE_LIBPCAP: u8 = 0;
+// 10^-9 seconds
+const TSRESOL_NANOSECONDS: u8 = 9;
+
+const SNAPLEN: u32 = 262144;
+
+#[derive(Debug, Clone)]
+pub struct Interface {
+    pub link_type: Linktype,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub filter: Option<String>,
+}
+
+/// Minimal pcapng writer, a single section with timestamps in nanoseconds.
+pub struct PcapngWriter<W: Write> {
+    writer: W,
+    written: usize,
+}
+
+impl<W: Write> PcapngWriter<W> {
+    pub fn new(writer: W, interfaces: &[Interface]) -> io::Result<Self> {
+        let mut pcapng = Self { writer, written: 0 };
+
+        let mut options = Options::default();
+        options.string(SHB_USERAPPL, concat!("netpix ", env!("CARGO_PKG_VERSION")));
+
+        let mut body = Vec::new();
+        body.extend_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
+        body.extend_from_slice(&1u16.to_le_bytes());
+        body.extend_from_slice(&0u16.to_le_bytes());
+        // section length not specified
+        body.extend_from_slice(&(-1i64).to_le_bytes());
+        body.extend_from_slice(&options.finish());
+        pcapng.write_block(SECTION_HEADER_BLOCK, &body)?;
+
+        for interface in interfaces {
+            pcapng.write_interface(interface)?;
+        }
+
+        Ok(pcapng)
+    }
+
+    /// Number of bytes written so far.
+    pub fn written(&self) -> usize {
+        self.written
+    }
+
+    pub fn write_packet(
+        &mut self,
+        interface_id: u32,
+        timestamp: Duration,
+        data: &[u8],
+        original_len: u32,
+        comment: Option<&str>,
+    ) -> io::Result<()> {
+        let timestamp = timestamp.as_nanos() as u64;
+
+        let mut body = Vec::with_capacity(20 + data.len() + 4);
+        body.extend_from_slice(&interface_id.to_le_bytes());
+        body.extend_from_slice(&((timestamp >> 32) as u32).to_le_bytes());
+        body.extend_from_slice(&(timestamp as u32).to_le_bytes());
+        body.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        body.extend_from_slice(&original_len.to_le_bytes());
+        body.extend_from_slice(data);
+        pad(&mut body);
+
+        if let Some(comment) = comment {
+            let mut options = Options::default();
+            options.string(OPT_COMMENT, comment);
+            body.extend_from_slice(&options.finish());
+        }
+
+        self.write_block(ENHANCED_PACKET_BLOCK, &body)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn write_interface(&mut self, interface: &Interface) -> io::Result<()> {
+        let mut options = Options::default();
+        if let Some(ref name) = interface.name {
+            options.string(IF_NAME, name);
+        }
+        if let Some(ref description) = interface.description {
+            options.string(IF_DESCRIPTION, description);
+        }
+        options.push(IF_TSRESOL, &[TSRESOL_NANOSECONDS]);
+        if let Some(ref filter) = interface.filter
+            && !filter.is_empty()
+        {
+            let mut value = vec![FILTER_TYPE_LIBPCAP];
+            value.extend_from_slice(filter.as_bytes());
+            options.push(IF_FILTER, &value);
+        }
+
+        let mut body = Vec::new();
+        body.extend_from_slice(&(interface.link_type.0 as u16).to_le_bytes());
+        body.extend_from_slice(&0u16.to_le_bytes());
+        body.extend_from_slice(&SNAPLEN.to_le_bytes());
+        body.extend_from_slice(&options.finish());
+
+        self.write_block(INTERFACE_DESCRIPTION_BLOCK, &body)
+    }
+
+    fn write_block(&mut self, block_type: u32, body: &[u8]) -> io::Result<()> {
+        let total_len = (body.len() + 12) as u32;
+
+        self.writer.write_all(&block_type.to_le_bytes())?;
+        self.writer.write_all(&total_len.to_le_bytes())?;
+        self.writer.write_all(body)?;
+        self.writer.write_all(&total_len.to_le_bytes())?;
+
+        self.written += total_len as usize;
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct Options(Vec<u8>);
+
+impl Options {
+    fn push(&mut self, code: u16, value: &[u8]) {
+        self.0.extend_from_slice(&code.to_le_bytes());
+        self.0
+            .extend_from_slice(&(value.len() as u16).to_le_bytes());
+        self.0.extend_from_slice(value);
+        pad(&mut self.0);
+    }
+
+    fn string(&mut self, code: u16, value: &str) {
+        self.push(code, value.as_bytes());
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if !self.0.is_empty() {
+            self.0.extend_from_slice(&OPT_END_OF_OPT.to_le_bytes());
+            self.0.extend_from_slice(&0u16.to_le_bytes());
+        }
+        self.0
+    }
+}
+
+/// Makes a name (an interface, a source) safe to use in a file name,
+/// e.g. `\Device\NPF_{...}` on Windows.
+pub fn file_stem(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+// blocks and options are aligned to 32 bits
+fn pad(data: &mut Vec<u8>) {
+    data.resize(data.len().next_multiple_of(4), 0);
+}
+
+/// Blocks of a little endian section, by type with their bodies.
+#[cfg(test)]
+pub(crate) fn read_blocks(mut data: &[u8]) -> Vec<(u32, &[u8])> {
+    let mut blocks = Vec::new();
+    while data.len() >= 12 {
+        let block_type = u32::from_le_bytes(data[..4].try_into().unwrap());
+        let total_len = u32::from_le_bytes(data[4..8].try_into().unwrap()) as usize;
+        blocks.push((block_type, &data[8..total_len - 4]));
+        data = &data[total_len..];
+    }
+    blocks
+}
+
+/// Options by code with their values, up to the end of options.
+#[cfg(test)]
+pub(crate) fn read_options(mut data: &[u8]) -> Vec<(u16, &[u8])> {
+    let mut options = Vec::new();
+    while data.len() >= 4 {
+        let code = u16::from_le_bytes([data[0], data[1]]);
+        let len = u16::from_le_bytes([data[2], data[3]]) as usize;
+        if code == OPT_END_OF_OPT {
+            break;
+        }
+        options.push((code, &data[4..4 + len]));
+        data = &data[(4 + len).next_multiple_of(4)..];
+    }
+    options
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reads_back_interface_and_packets() {
+        let interface = Interface {
+            link_type: Linktype::LINUX_SLL,
+            name: Some("any".to_string()),
+            description: None,
+            filter: Some("udp portrange 5000-6000".to_string()),
+        };
+        let mut writer = PcapngWriter::new(Vec::new(), &[interface]).unwrap();
+        writer
+            .write_packet(0, Duration::new(1, 500_000_000), &[1; 5], 60, None)
+            .unwrap();
+        writer
+            .write_packet(0, Duration::from_secs(2), &[2; 8], 8, Some("marked"))
+            .unwrap();
+        let data = writer.into_inner();
+
+        let path = std::env::temp_dir().join(format!("netpix-pcapng-{}", std::process::id()));
+        std::fs::write(&path, &data).unwrap();
+        let mut capture = pcap::Capture::from_file(&path).unwrap();
+        assert_eq!(capture.get_datalink(), Linktype::LINUX_SLL);
+        let mut packets = Vec::new();
+        while let Ok(packet) = capture.next_packet() {
+            let ts = (packet.header.ts.tv_sec, packet.header.ts.tv_usec);
+            packets.push((ts, packet.header.len, packet.data.to_vec()));
+        }
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            packets,
+            [((1, 500_000), 60, vec![1; 5]), ((2, 0), 8, vec![2; 8])]
+        );
+
+        // libpcap doesn't expose the interface options
+        let blocks = read_blocks(&data);
+        let (block_type, body) = blocks[1];
+        assert_eq!(block_type, INTERFACE_DESCRIPTION_BLOCK);
+        let options = read_options(&body[8..]);
+        assert_eq!(
+            options,
+            [
+                (IF_NAME, &b"any"[..]),
+                (IF_TSRESOL, &[TSRESOL_NANOSECONDS]),
+                (IF_FILTER, b"\0udp portrange 5000-6000"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_leaves_out_empty_filter() {
+        let interface = Interface {
+            link_type: Linktype::ETHERNET,
+            name: None,
+            description: None,
+            filter: Some(String::new()),
+        };
+        let data = PcapngWriter::new(Vec::new(), &[interface])
+            .unwrap()
+            .into_inner();
+
+        let (_, body) = read_blocks(&data)[1];
+        assert_eq!(
+            read_options(&body[8..]),
+            [(IF_TSRESOL, &[TSRESOL_NANOSECONDS][..])]
+        );
+    }
+}
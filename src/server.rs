@@ -2,7 +2,9 @@ mod asset;
 mod client;
 pub mod config;
 mod constants;
+mod export;
 mod handler;
+pub mod trigger;
 
 use crate::sniffer::Sniffer;
 use config::Config;
@@ -15,10 +17,11 @@ use netpix_macros::{
 
 pub async fn run(sniffers: HashMap<String, Sniffer>, config: Config) {
     let clients = setup_clients!();
-    let source_to_packets = setup_packet_handlers!((sniffers, clients, config));
+    let exports = export::new_exports();
+    let source_to_packets = setup_packet_handlers!((sniffers, clients, config.clone()));
     let sender_clients = clients.clone();
 
-    let routes = setup_routes!((clients, source_to_packets));
+    let routes = setup_routes!((clients, source_to_packets, exports));
 
     spawn_message_sender!((sender_clients, config.client_message_interval_ms,));
 
@@ -1,5 +1,6 @@
 use crate::server::handler::{handle_messages, send_pcap_filenames};
 
+use super::export::Exports;
 use super::handler::PacketsMap;
 use futures_util::{SinkExt, StreamExt, TryFutureExt};
 use log::{error, info};
@@ -40,7 +41,12 @@ pub fn new_clients() -> Clients {
     Clients::default()
 }
 
-pub async fn handle_connection(ws: WebSocket, clients: Clients, packets: PacketsMap) {
+pub async fn handle_connection(
+    ws: WebSocket,
+    clients: Clients,
+    packets: PacketsMap,
+    exports: Exports,
+) {
     let client_id = NEXT_CLIENT_ID.fetch_add(1, Ordering::Relaxed);
 
     info!("New client connected, assigned id: {}", client_id);
@@ -64,7 +70,7 @@ pub async fn handle_connection(ws: WebSocket, clients: Clients, packets: Packets
 
     clients.write().await.insert(client_id, Client::new(tx));
 
-    handle_messages(client_id, ws_rx, &clients, &packets).await;
+    handle_messages(client_id, ws_rx, &clients, &packets, &exports).await;
 
     info!("Client disconnected, client_id: {}", client_id);
     clients.write().await.remove(&client_id);
@@ -1,10 +1,18 @@
+use super::trigger::TriggerConfig;
 use bon::Builder;
+use netpix_common::packet::DecodeRules;
 use std::net::SocketAddr;
+use std::path::PathBuf;
 
-#[derive(Debug, Builder, Clone, Copy)]
+#[derive(Debug, Builder, Clone)]
 pub struct Config {
     pub max_packets_age: u64,
     pub client_message_interval_ms: u64,
     pub packet_buffer_size: usize,
     pub addr: SocketAddr,
+    pub trigger: Option<TriggerConfig>,
+    #[builder(default)]
+    pub decode_as: DecodeRules,
+    // NSS key log with the DTLS secrets of the SRTP sessions
+    pub keylog: Option<PathBuf>,
 }
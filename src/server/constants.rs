@@ -1 +1,2 @@
 pub const WEBSOCKET_PATH: &str = "ws";
+pub const EXPORT_PATH: &str = "export";
@@ -0,0 +1,432 @@
+use super::handler::PacketRingBuffer;
+use crate::pcapng::{self, Interface, PcapngWriter};
+use log::warn;
+use netpix_common::packet::{SessionPacket, TcpFraming, TransportProtocol};
+use netpix_common::{ExportSelection, Packet, Response, Source};
+use pcap::Linktype;
+use ringbuf::traits::Consumer;
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use warp::Rejection;
+use warp::http::HeaderValue;
+use warp::reply::{self, Reply};
+
+// exports are kept until downloaded or, when they never are, for that long
+const EXPORT_TTL: Duration = Duration::from_secs(600);
+const TCP_FLAGS_PSH_ACK: u8 = 0x18;
+const IP_PROTOCOL_TCP: u8 = 6;
+const IP_PROTOCOL_UDP: u8 = 17;
+// payload which fits a single frame with IPv6 and TCP headers, the longer messages are split
+const MAX_TCP_SEGMENT_PAYLOAD: usize = u16::MAX as usize - 40 - 20;
+const MAX_UDP_PAYLOAD: usize = u16::MAX as usize - 40 - 8;
+
+static NEXT_EXPORT_ID: AtomicU64 = AtomicU64::new(1);
+
+pub struct ExportFile {
+    name: String,
+    data: Vec<u8>,
+    created: Instant,
+}
+
+pub type Exports = Arc<RwLock<HashMap<u64, ExportFile>>>;
+
+pub fn new_exports() -> Exports {
+    Exports::default()
+}
+
+/// Writes the selected packets to a pcapng file ready to be downloaded,
+/// returns its id and the number of packets.
+pub async fn export(
+    exports: &Exports,
+    source: &Source,
+    selection: &ExportSelection,
+    packets: &PacketRingBuffer,
+) -> io::Result<(u64, usize)> {
+    // every stored packet is looked up in the ids
+    let sorted;
+    let selection = match selection {
+        ExportSelection::Ids(ids) => {
+            let mut ids = ids.clone();
+            ids.sort_unstable();
+            sorted = ExportSelection::Ids(ids);
+            &sorted
+        }
+        selection => selection,
+    };
+
+    let mut selected: Vec<_> = packets
+        .iter()
+        .filter_map(|response| match response {
+            Response::Packet(packet) if matches(packet, selection) => Some(packet),
+            _ => None,
+        })
+        .collect();
+    selected.sort_by_key(|packet| packet.id);
+
+    let data = write_pcapng(source, &selected)?;
+    let id = NEXT_EXPORT_ID.fetch_add(1, Ordering::Relaxed);
+    let name = format!("{}-{}.pcapng", source_file_stem(source), id);
+
+    let mut exports = exports.write().await;
+    exports.retain(|_, export| export.created.elapsed() < EXPORT_TTL);
+    exports.insert(
+        id,
+        ExportFile {
+            name,
+            data,
+            created: Instant::now(),
+        },
+    );
+
+    Ok((id, selected.len()))
+}
+
+pub async fn serve(id: u64, exports: Exports) -> Result<impl Reply, Rejection> {
+    let export = exports
+        .write()
+        .await
+        .remove(&id)
+        .ok_or_else(warp::reject::not_found)?;
+
+    let mut res = reply::Response::new(export.data.into());
+    let headers = res.headers_mut();
+    headers.insert(
+        "content-type",
+        HeaderValue::from_static("application/octet-stream"),
+    );
+    if let Ok(disposition) =
+        HeaderValue::from_str(&format!("attachment; filename=\"{}\"", export.name))
+    {
+        headers.insert("content-disposition", disposition);
+    }
+    Ok(res)
+}
+
+// ids of the selection are sorted
+fn matches(packet: &Packet, selection: &ExportSelection) -> bool {
+    match selection {
+        ExportSelection::IdRange(first, last) => (*first..=*last).contains(&packet.id),
+        ExportSelection::Ids(ids) => ids.binary_search(&packet.id).is_ok(),
+        ExportSelection::RtpStream(key) => match packet.contents {
+            SessionPacket::Rtp(ref rtp) => packet.rtp_stream_key(rtp.ssrc) == *key,
+            _ => false,
+        },
+    }
+}
+
+fn source_file_stem(source: &Source) -> String {
+    let name = match source {
+        Source::File(name) => std::path::Path::new(name)
+            .file_stem()
+            .and_then(|name| name.to_str())
+            .unwrap_or(name),
+        Source::Interface(name) => name.split_whitespace().next().unwrap_or(name),
+        Source::Socket(name) => name,
+    };
+    pcapng::file_stem(name)
+}
+
+// packets don't keep their link-layer bytes, so the IP and transport headers are rebuilt,
+// VLAN tags and tunnels of the original frames are lost
+fn write_pcapng(source: &Source, packets: &[&Packet]) -> io::Result<Vec<u8>> {
+    let interface = Interface {
+        link_type: Linktype(101), // LINKTYPE_RAW, IPv4 or IPv6
+        name: None,
+        description: Some(source.to_string()),
+        filter: None,
+    };
+
+    let mut writer = PcapngWriter::new(Vec::new(), std::slice::from_ref(&interface))?;
+    for packet in packets {
+        let comment = format!("netpix packet {}", packet.id);
+        for frame in rebuild_frames(packet) {
+            writer.write_packet(
+                0,
+                packet.timestamp,
+                &frame,
+                frame.len() as u32,
+                Some(&comment),
+            )?;
+        }
+    }
+
+    Ok(writer.into_inner())
+}
+
+// reassembled TCP messages longer than a frame are written as several segments
+fn rebuild_frames(packet: &Packet) -> Vec<Vec<u8>> {
+    let payload = framed_payload(packet);
+    let (source, destination) = same_family(packet.source_addr, packet.destination_addr);
+
+    match packet.transport_protocol {
+        TransportProtocol::Udp => {
+            if payload.len() > MAX_UDP_PAYLOAD {
+                warn!(
+                    "Packet {} with {} bytes of UDP payload doesn't fit a frame, not exported",
+                    packet.id,
+                    payload.len()
+                );
+                return Vec::new();
+            }
+
+            let mut header = Vec::with_capacity(8 + payload.len());
+            header.extend_from_slice(&source.port().to_be_bytes());
+            header.extend_from_slice(&destination.port().to_be_bytes());
+            header.extend_from_slice(&((8 + payload.len()) as u16).to_be_bytes());
+            header.extend_from_slice(&0u16.to_be_bytes());
+            vec![ip_frame(
+                source,
+                destination,
+                IP_PROTOCOL_UDP,
+                header,
+                &payload,
+            )]
+        }
+        TransportProtocol::Tcp => {
+            let (sequence_number, acknowledgement_number) =
+                packet.metadata.tcp.as_ref().map_or((0, 0), |tcp| {
+                    (tcp.sequence_number, tcp.acknowledgement_number)
+                });
+
+            let mut segments: Vec<_> = payload.chunks(MAX_TCP_SEGMENT_PAYLOAD).collect();
+            if segments.is_empty() {
+                segments.push(&[]);
+            }
+
+            let mut offset = 0;
+            segments
+                .into_iter()
+                .map(|segment| {
+                    let mut header = Vec::with_capacity(20 + segment.len());
+                    header.extend_from_slice(&source.port().to_be_bytes());
+                    header.extend_from_slice(&destination.port().to_be_bytes());
+                    header.extend_from_slice(
+                        &sequence_number.wrapping_add(offset as u32).to_be_bytes(),
+                    );
+                    header.extend_from_slice(&acknowledgement_number.to_be_bytes());
+                    // 5 words of header, no options
+                    header.push(5 << 4);
+                    header.push(TCP_FLAGS_PSH_ACK);
+                    header.extend_from_slice(&u16::MAX.to_be_bytes());
+                    header.extend_from_slice(&[0; 4]);
+                    offset += segment.len();
+                    ip_frame(source, destination, IP_PROTOCOL_TCP, header, segment)
+                })
+                .collect()
+        }
+    }
+}
+
+fn ip_frame(
+    source: SocketAddr,
+    destination: SocketAddr,
+    protocol: u8,
+    mut transport: Vec<u8>,
+    payload: &[u8],
+) -> Vec<u8> {
+    transport.extend_from_slice(payload);
+
+    let checksum_offset = match protocol {
+        IP_PROTOCOL_UDP => 6,
+        _ => 16,
+    };
+    let checksum = transport_checksum(source.ip(), destination.ip(), protocol, &transport);
+    transport[checksum_offset..checksum_offset + 2].copy_from_slice(&checksum.to_be_bytes());
+
+    let mut frame = ip_header(source.ip(), destination.ip(), protocol, transport.len());
+    frame.extend_from_slice(&transport);
+    frame
+}
+
+// framing of TCP messages is stripped by the reassembly, it's needed to decode them again
+fn framed_payload(packet: &Packet) -> Vec<u8> {
+    let payload = packet.payload.as_deref().unwrap_or_default();
+    let len = (payload.len() as u16).to_be_bytes();
+
+    let mut framed = Vec::with_capacity(payload.len() + 4);
+    match packet.metadata.framing {
+        Some(TcpFraming::Rfc4571) => framed.extend_from_slice(&len),
+        Some(TcpFraming::Interleaved { channel }) => {
+            framed.extend_from_slice(&[b'$', channel]);
+            framed.extend_from_slice(&len);
+        }
+        Some(TcpFraming::RtspMessage | TcpFraming::SipMessage) | None => {}
+    }
+    framed.extend_from_slice(payload);
+    framed
+}
+
+// addresses of synthetic packets may mix the families
+fn same_family(source: SocketAddr, destination: SocketAddr) -> (SocketAddr, SocketAddr) {
+    match (source.ip(), destination.ip()) {
+        (IpAddr::V4(_), IpAddr::V6(_)) => (to_ipv6(source), destination),
+        (IpAddr::V6(_), IpAddr::V4(_)) => (source, to_ipv6(destination)),
+        _ => (source, destination),
+    }
+}
+
+fn to_ipv6(addr: SocketAddr) -> SocketAddr {
+    match addr.ip() {
+        IpAddr::V4(ip) => SocketAddr::new(IpAddr::V6(ip.to_ipv6_mapped()), addr.port()),
+        IpAddr::V6(_) => addr,
+    }
+}
+
+fn ip_header(source: IpAddr, destination: IpAddr, protocol: u8, payload_len: usize) -> Vec<u8> {
+    match (source, destination) {
+        (IpAddr::V4(source), IpAddr::V4(destination)) => {
+            let mut header = Vec::with_capacity(20);
+            header.extend_from_slice(&[0x45, 0]);
+            header.extend_from_slice(&((20 + payload_len) as u16).to_be_bytes());
+            // identification, don't fragment
+            header.extend_from_slice(&[0, 0, 0x40, 0]);
+            header.extend_from_slice(&[64, protocol, 0, 0]);
+            header.extend_from_slice(&source.octets());
+            header.extend_from_slice(&destination.octets());
+            let checksum = checksum(&header, 0);
+            header[10..12].copy_from_slice(&checksum.to_be_bytes());
+            header
+        }
+        _ => {
+            let mut header = Vec::with_capacity(40);
+            header.extend_from_slice(&[0x60, 0, 0, 0]);
+            header.extend_from_slice(&(payload_len as u16).to_be_bytes());
+            header.extend_from_slice(&[protocol, 64]);
+            header.extend_from_slice(&ipv6_octets(source));
+            header.extend_from_slice(&ipv6_octets(destination));
+            header
+        }
+    }
+}
+
+fn ipv6_octets(ip: IpAddr) -> [u8; 16] {
+    match ip {
+        IpAddr::V4(ip) => ip.to_ipv6_mapped().octets(),
+        IpAddr::V6(ip) => ip.octets(),
+    }
+}
+
+fn transport_checksum(source: IpAddr, destination: IpAddr, protocol: u8, segment: &[u8]) -> u16 {
+    let mut pseudo_header = Vec::with_capacity(40);
+    match (source, destination) {
+        (IpAddr::V4(source), IpAddr::V4(destination)) => {
+            pseudo_header.extend_from_slice(&source.octets());
+            pseudo_header.extend_from_slice(&destination.octets());
+            pseudo_header.extend_from_slice(&[0, protocol]);
+            pseudo_header.extend_from_slice(&(segment.len() as u16).to_be_bytes());
+        }
+        _ => {
+            pseudo_header.extend_from_slice(&ipv6_octets(source));
+            pseudo_header.extend_from_slice(&ipv6_octets(destination));
+            pseudo_header.extend_from_slice(&(segment.len() as u32).to_be_bytes());
+            pseudo_header.extend_from_slice(&[0, 0, 0, protocol]);
+        }
+    }
+
+    let sum = checksum(segment, sum_words(&pseudo_header));
+    // zero means no checksum for UDP
+    if sum == 0 { 0xFFFF } else { sum }
+}
+
+fn sum_words(data: &[u8]) -> u64 {
+    data.chunks(2)
+        .map(|word| match *word {
+            [high, low] => u16::from_be_bytes([high, low]) as u64,
+            [high] => u16::from_be_bytes([high, 0]) as u64,
+            _ => 0,
+        })
+        .sum()
+}
+
+// RFC 1071 Internet checksum
+fn checksum(data: &[u8], initial: u64) -> u16 {
+    let mut sum = initial + sum_words(data);
+    while sum > 0xFFFF {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use netpix_common::packet::{
+        PacketMetadata, SessionProtocol, TcpReassembler, TcpSegment, TransportProtocol,
+    };
+    use std::time::SystemTime;
+
+    fn segment(seq: u32, payload: &[u8]) -> Packet {
+        Packet {
+            payload: Some(payload.to_vec()),
+            id: seq as usize,
+            timestamp: Duration::from_secs(1),
+            length: payload.len() as u32,
+            source_addr: "10.0.0.1:50000".parse().unwrap(),
+            destination_addr: "10.0.0.2:554".parse().unwrap(),
+            transport_protocol: TransportProtocol::Tcp,
+            session_protocol: SessionProtocol::Unknown,
+            contents: SessionPacket::Unknown,
+            creation_time: SystemTime::now(),
+            metadata: PacketMetadata {
+                tcp: Some(TcpSegment {
+                    sequence_number: seq,
+                    acknowledgement_number: 1,
+                    flags: TCP_FLAGS_PSH_ACK,
+                    payload_len: payload.len() as u32,
+                    truncated: false,
+                }),
+                ..Default::default()
+            },
+        }
+    }
+
+    fn rfc4571(sequence_number: u8) -> Vec<u8> {
+        let mut data = vec![0, 12, 0x80, 0x60, 0, sequence_number];
+        data.extend_from_slice(&[0; 8]);
+        data
+    }
+
+    #[test]
+    fn test_exports_messages_with_their_own_seqs() {
+        // two messages in the first segment, the third one spans both
+        let mut data: Vec<u8> = (1..=3).flat_map(rfc4571).collect();
+        let second = data.split_off(34);
+        let mut reassembler = TcpReassembler::default();
+        let mut packets = reassembler.push(segment(1000, &data));
+        packets.extend(reassembler.push(segment(1034, &second)));
+        assert_eq!(packets.len(), 3);
+
+        let packets: Vec<_> = packets.iter().collect();
+        let path =
+            std::env::temp_dir().join(format!("netpix-export-{}.pcapng", std::process::id()));
+        std::fs::write(
+            &path,
+            write_pcapng(&Source::File("test".into()), &packets).unwrap(),
+        )
+        .unwrap();
+
+        let mut capture = pcap::Capture::from_file(&path).unwrap();
+        let mut seqs = Vec::new();
+        while let Ok(frame) = capture.next_packet() {
+            let (ip, tcp) = frame.data.split_at(20);
+            assert_eq!(checksum(ip, 0), 0);
+            let pseudo_header = [
+                &ip[12..20],
+                &[0, IP_PROTOCOL_TCP],
+                &(tcp.len() as u16).to_be_bytes(),
+            ];
+            assert_eq!(checksum(tcp, sum_words(&pseudo_header.concat())), 0);
+
+            let seq = u32::from_be_bytes(tcp[4..8].try_into().unwrap());
+            seqs.push((seq, tcp.len() - 20));
+        }
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(seqs, [(1000, 14), (1014, 14), (1028, 14)]);
+    }
+}
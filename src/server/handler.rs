@@ -1,3 +1,6 @@
+use super::constants::EXPORT_PATH;
+use super::export::{self, Exports};
+use super::trigger::{self, Snippet, TriggerEngine};
 use super::{client::Clients, config::Config};
 use crate::sniffer::Sniffer;
 use flate2::Compression;
@@ -7,19 +10,41 @@ use futures_util::{
     stream::{SplitSink, SplitStream},
 };
 use log::{error, info, warn};
-use netpix_common::{PacketsStats, Request, Response, RtpStreamKey, Sdp, Source};
+use netpix_common::packet::{ReassemblyStats, TcpStats};
+use netpix_common::packet::{Reclassified, RtpClassifier, SessionPacket, SessionProtocol};
+use netpix_common::rtsp::RtspSessions;
+use netpix_common::sip::{Negotiation, SipDialogs};
+use netpix_common::srtp::SrtpSessions;
+use netpix_common::stun::TurnRelays;
+use netpix_common::{
+    ExportReady, ExportSelection, Packet, PacketAssociationTable, PacketsStats, ReparseScope,
+    ReplayControl, ReplayState, Request, Response, RtpStreamKey, Sdp, Source,
+};
 use ringbuf::{
     HeapRb,
     traits::{Consumer, Observer, RingBuffer},
 };
-use std::time::SystemTime;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
 use std::{collections::HashMap, io::Write, sync::Arc};
 use tokio::sync::{RwLock, mpsc, mpsc::UnboundedSender};
 use warp::ws::{Message, WebSocket};
 
 pub type PacketRingBuffer = HeapRb<Response>;
 pub type Packets = Arc<RwLock<PacketRingBuffer>>;
-pub type PacketsMap = Arc<HashMap<Source, (Packets, mpsc::Sender<()>)>>;
+// packets of the source, cancellation and controls of its sniffer
+pub type SourceHandle = (Packets, mpsc::Sender<()>, mpsc::Sender<SourceControl>);
+pub type PacketsMap = Arc<HashMap<Source, SourceHandle>>;
+
+const SOURCE_CONTROL_BUFFER_SIZE: usize = 16;
+
+// requests from the clients applied by the sniffer task of a source
+#[derive(Debug)]
+pub enum SourceControl {
+    Replay(ReplayControl),
+    // pasted by a client, its keys decrypt the SRTP streams of the source from then on
+    Sdp(Sdp),
+}
 
 pub async fn setup_packet_handlers(
     sniffers: HashMap<String, Sniffer>,
@@ -31,11 +56,24 @@ pub async fn setup_packet_handlers(
     for (_file, sniffer) in sniffers {
         let packets = Arc::new(RwLock::new(HeapRb::new(config.packet_buffer_size)));
         let (cancel_tx, cancel_rx) = mpsc::channel(1);
-        source_to_packets.insert(sniffer.source.clone(), (packets.clone(), cancel_tx));
+        let (control_tx, control_rx) = mpsc::channel(SOURCE_CONTROL_BUFFER_SIZE);
+        source_to_packets.insert(
+            sniffer.source.clone(),
+            (packets.clone(), cancel_tx, control_tx),
+        );
 
         let cloned_clients = clients.clone();
+        let config = config.clone();
         tokio::task::spawn(async move {
-            sniff(sniffer, packets, cloned_clients, config, cancel_rx).await;
+            sniff(
+                sniffer,
+                packets,
+                cloned_clients,
+                config,
+                cancel_rx,
+                control_rx,
+            )
+            .await;
         });
     }
 
@@ -45,7 +83,7 @@ pub async fn setup_packet_handlers(
 pub async fn send_pcap_filenames(
     client_id: &usize,
     ws_tx: &mut SplitSink<WebSocket, Message>,
-    source_to_packets: &Arc<HashMap<Source, (Packets, mpsc::Sender<()>)>>,
+    source_to_packets: &PacketsMap,
 ) {
     let sources = source_to_packets.keys().cloned().collect();
     let response = Response::Sources(sources);
@@ -64,10 +102,20 @@ pub async fn send_pcap_filenames(
         .await;
 }
 
-async fn send_stats(clients: &Clients, discharged: usize, overwritten: usize) {
+async fn send_stats(
+    clients: &Clients,
+    discharged: usize,
+    overwritten: usize,
+    reassembly_stats: ReassemblyStats,
+    tcp_stats: TcpStats,
+) {
     let stats = PacketsStats {
         discharged,
         overwritten,
+        fragments: reassembly_stats.fragments,
+        reassembly_failures: reassembly_stats.failures,
+        tcp_gaps: tcp_stats.gaps,
+        tcp_lost_bytes: tcp_stats.lost_bytes,
     };
     let response = Response::PacketsStats(stats);
     for (_, client) in clients.write().await.iter_mut() {
@@ -77,20 +125,102 @@ async fn send_stats(clients: &Clients, discharged: usize, overwritten: usize) {
     }
 }
 
+async fn send_reset(clients: &Clients, sniffer: &Source) {
+    let response = Response::Reset(sniffer.clone());
+    let Ok(encoded) = response.encode() else {
+        error!("Sniffer: failed to encode reset");
+        return;
+    };
+
+    let msg = Message::binary(encoded);
+    for (_, client) in clients.write().await.iter_mut() {
+        if client.source.as_ref() == Some(sniffer) {
+            client.queue.push_back(msg.clone());
+        }
+    }
+}
+
+async fn send_replay_state(clients: &Clients, sniffer: &Source, state: ReplayState) {
+    let response = Response::ReplayState(sniffer.clone(), state);
+    let Ok(encoded) = response.encode() else {
+        error!("Sniffer: failed to encode replay state");
+        return;
+    };
+
+    let msg = Message::binary(encoded);
+    for (_, client) in clients.write().await.iter_mut() {
+        if client.source.as_ref() == Some(sniffer) {
+            client.queue.push_back(msg.clone());
+        }
+    }
+}
+
+fn source_name(source: &Source) -> &str {
+    match source {
+        Source::File(name) => Path::new(name)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or(name),
+        Source::Socket(name) => name,
+        // followed by the promiscuous mode marker
+        Source::Interface(name) => name.split_whitespace().next().unwrap_or(name),
+    }
+}
+
+fn save_snippet(snippet: Option<Snippet>, dir: &Path, source: &Source) {
+    let Some(snippet) = snippet else {
+        return;
+    };
+
+    if snippet.frames.is_empty() {
+        warn!(
+            "Trigger on {} fired, but the source has no raw frames to save",
+            source
+        );
+        return;
+    }
+
+    if snippet.truncated {
+        warn!(
+            "Trigger snippet of {} reached its size limit, the later frames are left out",
+            source
+        );
+    }
+
+    let dir = dir.to_path_buf();
+    let name = source_name(source).to_string();
+    tokio::task::spawn_blocking(move || match trigger::write_snippet(&dir, &name, snippet) {
+        Ok(path) => info!("Trigger snippet saved to {}", path.display()),
+        Err(err) => error!(
+            "Failed to save trigger snippet to {}: {}",
+            dir.display(),
+            err
+        ),
+    });
+}
+
+fn report_events(source: &Source, events: Vec<trigger::Event>) {
+    for event in events {
+        info!("Trigger on {}: {}", source, event.description);
+    }
+}
+
 async fn discharge_old_packets(packets: &mut PacketRingBuffer, max_packets_age: u64) -> usize {
     let now = SystemTime::now();
     let mut discharged_count = 0;
     while let Some(packet) = packets.try_peek() {
-        if let Response::Packet(p) = packet {
-            match now.duration_since(p.creation_time) {
-                Ok(age) if age.as_secs() <= max_packets_age => break,
-                _ => {
-                    packets.try_pop();
-                    discharged_count += 1;
-                }
+        // sessions stored with the packets are discharged with the packet before them
+        let Response::Packet(p) = packet else {
+            packets.try_pop();
+            continue;
+        };
+
+        match now.duration_since(p.creation_time) {
+            Ok(age) if age.as_secs() <= max_packets_age => break,
+            _ => {
+                packets.try_pop();
+                discharged_count += 1;
             }
-        } else {
-            break;
         }
     }
 
@@ -103,55 +233,180 @@ async fn sniff(
     clients: Clients,
     config: Config,
     mut cancel_rx: mpsc::Receiver<()>,
+    mut control_rx: mpsc::Receiver<SourceControl>,
 ) {
     let mut overwritten_count = 0;
     let mut total_discharged_count = 0;
     let mut last_stats_time = SystemTime::now();
 
+    let mut trigger = config.trigger.clone().map(|trigger_config| {
+        sniffer.collect_raw_frames();
+        TriggerEngine::new(trigger_config)
+    });
+    // live sources may go silent, the stopped streams are noticed by the wall clock then
+    let mut trigger_tick = tokio::time::interval(Duration::from_secs(1));
+    let tick_trigger = trigger.is_some() && sniffer.is_live();
+    let mut classifier = RtpClassifier::default();
+    let mut dialogs = SipDialogs::default();
+    let mut rtsp_sessions = RtspSessions::default();
+    let mut turn_relays = TurnRelays::default();
+    let mut srtp_sessions = SrtpSessions::new(config.keylog.clone());
+    // pasted by the clients, so they are kept when the sessions reset
+    let mut pasted_sdps = Vec::new();
+
     loop {
         tokio::select! {
             _ = cancel_rx.recv() => {
                 // Source changed, stop processing
                 break;
             }
+            Some(control) = control_rx.recv() => {
+                let control = match control {
+                    SourceControl::Replay(control) => control,
+                    SourceControl::Sdp(sdp) => {
+                        srtp_sessions.add_sdp(&sdp);
+                        pasted_sdps.push(sdp);
+                        continue;
+                    }
+                };
+                let rewind = match control {
+                    ReplayControl::Seek(offset) => sniffer
+                        .replay_state()
+                        .is_some_and(|state| offset < state.position),
+                    _ => false,
+                };
+                if let Err(err) = sniffer.control_replay(control) {
+                    warn!("Failed to apply {:?} to {}: {:?}", control, sniffer.source, err);
+                } else if rewind {
+                    // the packets are sent again, the stored ones would be duplicated
+                    packets.write().await.clear();
+                    send_reset(&clients, &sniffer.source).await;
+                }
+                if let ReplayControl::Seek(_) = control {
+                    classifier.reset();
+                    dialogs.reset();
+                    rtsp_sessions.reset();
+                    turn_relays.reset();
+                    srtp_sessions.reset();
+                    for sdp in &pasted_sdps {
+                        srtp_sessions.add_sdp(sdp);
+                    }
+                    if let Some(ref mut engine) = trigger {
+                        save_snippet(engine.take_incomplete(), engine.dir(), &sniffer.source);
+                        engine.reset();
+                    }
+                }
+                if let Some(state) = sniffer.replay_state() {
+                    send_replay_state(&clients, &sniffer.source, state).await;
+                }
+            }
+            _ = trigger_tick.tick(), if tick_trigger => {
+                if let Some(ref mut engine) = trigger {
+                    let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default();
+                    report_events(&sniffer.source, engine.advance(now));
+                    save_snippet(engine.take_complete(), engine.dir(), &sniffer.source);
+                }
+            }
             result = sniffer.next_packet() => {
                 match result {
                     Some(Ok(mut pack)) => {
-                        pack.guess_payload();
-                        let response = Response::Packet(pack);
+                        pack.guess_payload(&config.decode_as);
+                        turn_relays.push(&mut pack, &config.decode_as);
+                        srtp_sessions.push(&mut pack);
+                        // protocols forced by the rules or set up over RTSP aren't second guessed
+                        let reclassified = match config.decode_as.find(&pack) {
+                            Some(_) => None,
+                            None if rtsp_sessions.configure(&mut pack) => None,
+                            None => classifier.classify(&mut pack),
+                        };
+
+                        if let Some(ref mut engine) = trigger {
+                            engine.push_frames(sniffer.take_raw_frames());
+                            report_events(&sniffer.source, engine.inspect(&pack));
+                            save_snippet(engine.take_complete(), engine.dir(), &sniffer.source);
+                        }
 
-                        let Ok(encoded) = response.encode() else {
-                            error!("Sniffer: failed to encode packet");
-                            continue;
+                        let negotiation = match pack.contents {
+                            SessionPacket::Sip(ref sip) => dialogs.push(sip, pack.timestamp),
+                            _ => None,
                         };
-                        let msg = Message::binary(encoded);
+                        if let SessionPacket::Rtsp(ref rtsp) = pack.contents
+                            && let Some(transport) = rtsp_sessions.push(&pack, rtsp) {
+                                info!("RTSP stream set up with {} on {}", transport, sniffer.source);
+                            }
+                        let description = rtsp_sessions.describe(&pack);
+                        if let SessionPacket::Sap(ref sap) = pack.contents
+                            && let Some(ref sdp) = sap.sdp {
+                                srtp_sessions.add_sdp(sdp);
+                            }
 
-                        for (_, client) in clients.write().await.iter_mut() {
-                            if let Some(src) = &client.source
-                                && *src == sniffer.source {
-                                    client.queue.push_back(msg.clone());
-                                }
+                        // sessions follow the packet, so they are sent again on refetch
+                        let mut responses = vec![Response::Packet(pack)];
+                        if let Some(Negotiation { call_id, offer, answer }) = negotiation {
+                            info!("Negotiated SDP of SIP call {} on {}", call_id, sniffer.source);
+                            srtp_sessions.add_sdp(&offer);
+                            srtp_sessions.add_sdp(&answer);
+                            responses.push(Response::Sdp(None, offer));
+                            responses.push(Response::Sdp(None, answer));
+                        }
+                        if let Some((stream_key, sdp)) = description {
+                            srtp_sessions.add_sdp(&sdp);
+                            responses.push(Response::Sdp(Some(stream_key), sdp));
                         }
 
-                        let mut packets = packets.write().await;
+                        for response in responses {
+                            let Ok(encoded) = response.encode() else {
+                                error!("Sniffer: failed to encode packet");
+                                continue;
+                            };
+                            let msg = Message::binary(encoded);
+
+                            for (_, client) in clients.write().await.iter_mut() {
+                                if let Some(src) = &client.source
+                                    && *src == sniffer.source {
+                                        client.queue.push_back(msg.clone());
+                                    }
+                            }
+
+                            let mut stored = packets.write().await;
+
+                            let discharged = discharge_old_packets(&mut stored, config.max_packets_age).await;
+                            total_discharged_count += discharged;
 
-                        let discharged = discharge_old_packets(&mut packets, config.max_packets_age).await;
-                        total_discharged_count += discharged;
+                            if stored.is_full() {
+                                overwritten_count += 1;
+                                warn!("Packet buffer full, discarding oldest packet");
+                            }
+                            stored.push_overwrite(response);
+                        }
 
-                        if packets.is_full() {
-                            overwritten_count += 1;
-                            warn!("Packet buffer full, discarding oldest packet");
+                        if let Some(Reclassified { ids, protocol }) = reclassified {
+                            reparse_stored(&clients, &sniffer.source, &packets, protocol, |packet| {
+                                ids.binary_search(&packet.id).is_ok()
+                            })
+                            .await;
                         }
-                        packets.push_overwrite(response);
 
                         if let Ok(elapsed) = last_stats_time.elapsed()
                             && elapsed.as_secs() >= 5 {
-                                send_stats(&clients, total_discharged_count, overwritten_count).await;
+                                let reassembly_stats = sniffer.reassembly_stats();
+                                let tcp_stats = sniffer.tcp_stats();
+                                let replay_state = sniffer.replay_state();
+                                send_stats(&clients, total_discharged_count, overwritten_count, reassembly_stats, tcp_stats).await;
+                                if let Some(state) = replay_state {
+                                    send_replay_state(&clients, &sniffer.source, state).await;
+                                }
                                 last_stats_time = SystemTime::now();
                             }
                     }
                     Some(Err(err)) => info!("Error when capturing a packet: {:?}", err),
-                    None => break,
+                    None => {
+                        if let Some(ref mut engine) = trigger {
+                            engine.push_frames(sniffer.take_raw_frames());
+                            save_snippet(engine.take_incomplete(), engine.dir(), &sniffer.source);
+                        }
+                        break;
+                    }
                 }
             }
         }
@@ -206,6 +461,7 @@ async fn send_all_packets(client_id: usize, packets: &Packets, clients: &Clients
 async fn parse_sdp(
     client_id: usize,
     clients: &Clients,
+    packets: &PacketsMap,
     cur_source: &Source,
     stream_key: RtpStreamKey,
     raw_sdp: String,
@@ -218,7 +474,11 @@ async fn parse_sdp(
         return;
     };
 
-    let Ok(encoded) = Response::Sdp(stream_key, sdp).encode() else {
+    if let Some((_, _, control_tx)) = packets.get(cur_source) {
+        let _ = control_tx.send(SourceControl::Sdp(sdp.clone())).await;
+    }
+
+    let Ok(encoded) = Response::Sdp(Some(stream_key), sdp).encode() else {
         error!("Failed to encode sdp, client_id: {}", client_id);
         return;
     };
@@ -236,18 +496,166 @@ async fn parse_sdp(
     }
 }
 
+fn in_flow(flow: &PacketAssociationTable, packet: &Packet) -> bool {
+    let other = PacketAssociationTable::from(packet);
+    other == *flow
+        || (other.source_addr == flow.destination_addr
+            && other.destination_addr == flow.source_addr
+            && other.protocol == flow.protocol
+            && other.vlan_id == flow.vlan_id)
+}
+
+async fn reparse_packets(
+    clients: &Clients,
+    packets: &PacketsMap,
+    cur_source: &Source,
+    id: usize,
+    protocol: SessionProtocol,
+    scope: ReparseScope,
+) {
+    let Some((source_packets, _, _)) = packets.get(cur_source) else {
+        return;
+    };
+
+    let flow = source_packets
+        .read()
+        .await
+        .iter()
+        .find_map(|response| match response {
+            Response::Packet(packet) if packet.id == id => {
+                Some(PacketAssociationTable::from(packet))
+            }
+            _ => None,
+        });
+    let Some(flow) = flow else {
+        warn!("Packet {} to reparse is no longer stored", id);
+        return;
+    };
+
+    let count = reparse_stored(
+        clients,
+        cur_source,
+        source_packets,
+        protocol,
+        |packet| match scope {
+            ReparseScope::Packet => packet.id == id,
+            ReparseScope::Flow => in_flow(&flow, packet),
+        },
+    )
+    .await;
+
+    info!(
+        "Reparsed {} packets as {}, source: {:?}",
+        count, protocol, cur_source
+    );
+}
+
+// decodes the selected stored packets again,
+// the ones which changed are sent to every client of the source
+async fn reparse_stored(
+    clients: &Clients,
+    source: &Source,
+    packets: &Packets,
+    protocol: SessionProtocol,
+    selected: impl Fn(&Packet) -> bool,
+) -> usize {
+    let mut packets = packets.write().await;
+    let mut reparsed = Vec::new();
+    for response in packets.iter_mut() {
+        let Response::Packet(packet) = response else {
+            continue;
+        };
+
+        if !selected(packet) || packet.session_protocol == protocol {
+            continue;
+        }
+
+        // packets which can't be decoded that way stay as they were
+        packet.parse_as(protocol);
+        if packet.session_protocol == protocol {
+            reparsed.push(packet.clone());
+        }
+    }
+    drop(packets);
+
+    let count = reparsed.len();
+    if count == 0 {
+        return 0;
+    }
+    // a single message, so that the clients rebuild their streams once
+    let Ok(encoded) = Response::Reparsed(reparsed).encode() else {
+        error!("Sniffer: failed to encode reparsed packets");
+        return 0;
+    };
+
+    let msg = Message::binary(encoded);
+    for (_, client) in clients.write().await.iter_mut() {
+        if client.source.as_ref() == Some(source) {
+            client.queue.push_back(msg.clone());
+        }
+    }
+
+    count
+}
+
+async fn export_packets(
+    client_id: usize,
+    clients: &Clients,
+    packets: &PacketsMap,
+    exports: &Exports,
+    source: Source,
+    selection: ExportSelection,
+) {
+    let Some((source_packets, _, _)) = packets.get(&source) else {
+        warn!(
+            "Attempted to export from unknown source: {:?}, client_id: {}",
+            source, client_id
+        );
+        return;
+    };
+
+    let source_packets = source_packets.read().await;
+    let (id, packet_count) =
+        match export::export(exports, &source, &selection, &source_packets).await {
+            Ok(export) => export,
+            Err(err) => {
+                error!(
+                    "Failed to export packets: {}, client_id: {}",
+                    err, client_id
+                );
+                return;
+            }
+        };
+    drop(source_packets);
+
+    let response = Response::Export(ExportReady {
+        url: format!("{}/{}", EXPORT_PATH, id),
+        packet_count,
+    });
+    let Ok(encoded) = response.encode() else {
+        error!("Failed to encode export, client_id: {}", client_id);
+        return;
+    };
+
+    if let Some(client) = clients.read().await.get(&client_id)
+        && let Err(e) = client.sender.send(Message::binary(encoded))
+    {
+        error!("Sniffer: error while sending export: {}", e);
+    }
+}
+
 async fn handle_source_change(
     client_id: usize,
     new_source: Source,
     clients: &Clients,
     packets: &PacketsMap,
 ) -> bool {
-    if let Some((new_packets, _)) = packets.get(&new_source) {
+    if let Some((new_packets, _, _)) = packets.get(&new_source) {
         let mut wr_clients = clients.write().await;
         let client = wr_clients.get_mut(&client_id).unwrap();
 
         if let Some(old_source) = &client.source
-            && let Some((_, cancel_tx)) = packets.get(old_source)
+            && let Some((_, cancel_tx, _)) = packets.get(old_source)
         {
             let _ = cancel_tx.send(()).await;
         }
@@ -269,6 +677,7 @@ pub async fn handle_messages(
     mut ws_rx: SplitStream<WebSocket>,
     clients: &Clients,
     packets: &PacketsMap,
+    exports: &Exports,
 ) {
     let rd_clients = clients.read().await;
     let client = rd_clients.get(&client_id).unwrap();
@@ -292,7 +701,7 @@ pub async fn handle_messages(
                 match req {
                     (Request::FetchAll, _) => {
                         if let Some(ref cur_source) = source {
-                            if let Some((packets, _)) = packets.get(cur_source) {
+                            if let Some((packets, _, _)) = packets.get(cur_source) {
                                 send_all_packets(client_id, packets, clients).await;
                             } else {
                                 warn!(
@@ -317,7 +726,8 @@ pub async fn handle_messages(
 
                     (Request::ParseSdp(stream_key, sdp), _) => {
                         if let Some(cur_source) = &source {
-                            parse_sdp(client_id, clients, cur_source, stream_key, sdp).await;
+                            parse_sdp(client_id, clients, packets, cur_source, stream_key, sdp)
+                                .await;
                         } else {
                             warn!(
                                 "Received ParseSdp request without a selected source, client_id: {}",
@@ -326,6 +736,36 @@ pub async fn handle_messages(
                         }
                     }
 
+                    (Request::Replay(control), _) => {
+                        if let Some(ref cur_source) = source
+                            && let Some((_, _, control_tx)) = packets.get(cur_source)
+                        {
+                            let _ = control_tx.send(SourceControl::Replay(control)).await;
+                        } else {
+                            warn!(
+                                "Received Replay request without a selected source, client_id: {}",
+                                client_id
+                            );
+                        }
+                    }
+
+                    (Request::Reparse(id, protocol, scope), _) => {
+                        if let Some(ref cur_source) = source {
+                            reparse_packets(clients, packets, cur_source, id, protocol, scope)
+                                .await;
+                        } else {
+                            warn!(
+                                "Received Reparse request without a selected source, client_id: {}",
+                                client_id
+                            );
+                        }
+                    }
+
+                    (Request::Export { source, selection }, _) => {
+                        export_packets(client_id, clients, packets, exports, source, selection)
+                            .await;
+                    }
+
                     (Request::PacketsStats(stats), _) => {
                         let response = Response::PacketsStats(stats);
                         if let Ok(encoded) = response.encode() {
@@ -336,10 +776,6 @@ pub async fn handle_messages(
                             }
                         }
                     }
-
-                    _ => {
-                        warn!("Unhandled request: {:?}, client_id: {}", req, client_id);
-                    }
                 }
             }
             Err(e) => {
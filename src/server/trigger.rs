@@ -0,0 +1,726 @@
+use crate::pcapng::{self, Interface, PcapngWriter};
+use crate::sniffer::RawFrame;
+use netpix_common::mpegts::header::{AdaptationFieldControl, PIDTable};
+use netpix_common::packet::SessionPacket;
+use netpix_common::{MpegtsStreamKey, Packet, RtpStreamKey};
+use pcap::Linktype;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+// bound the memory when the trigger windows are long and the traffic heavy,
+// older frames leave the pre-trigger buffer and newer ones are left out of the snippet
+const MAX_PRE_BUFFER_BYTES: usize = 256 * 1024 * 1024;
+const MAX_SNIPPET_BYTES: usize = 512 * 1024 * 1024;
+// streams aren't checked for a stop on every packet
+const STOP_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Stream health condition which saves a capture snippet when it fires.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Condition {
+    /// at least that many RTP packets missing in a single gap
+    LossBurst(u16),
+    /// RTP interarrival jitter (RFC 3550) above that many milliseconds
+    Jitter(f64),
+    /// MPEG-TS continuity counter error
+    CcError,
+    /// no packets of a stream for that long
+    StreamStop(Duration),
+}
+
+impl Condition {
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::LossBurst(_) => "loss",
+            Self::Jitter(_) => "jitter",
+            Self::CcError => "cc",
+            Self::StreamStop(_) => "stop",
+        }
+    }
+}
+
+impl FromStr for Condition {
+    type Err = String;
+
+    /// `loss:<packets>`, `jitter:<ms>`, `cc` or `stop:<seconds>`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, value) = s.split_once(':').unwrap_or((s, ""));
+        let invalid = || format!("invalid trigger condition: {}", s);
+
+        match kind {
+            "loss" => value.parse().map(Self::LossBurst).map_err(|_| invalid()),
+            "jitter" => value.parse().map(Self::Jitter).map_err(|_| invalid()),
+            "cc" if value.is_empty() => Ok(Self::CcError),
+            "stop" => value
+                .parse()
+                .ok()
+                .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                .map(Self::StreamStop)
+                .ok_or_else(invalid),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::LossBurst(packets) => write!(f, "loss:{}", packets),
+            Self::Jitter(ms) => write!(f, "jitter:{}", ms),
+            Self::CcError => write!(f, "cc"),
+            Self::StreamStop(duration) => write!(f, "stop:{}", duration.as_secs_f64()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TriggerConfig {
+    pub dir: PathBuf,
+    pub conditions: Vec<Condition>,
+    /// captured before the event
+    pub pre: Duration,
+    /// captured after the event
+    pub post: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct Event {
+    pub condition: Condition,
+    pub timestamp: Duration,
+    pub description: String,
+}
+
+/// Frames around one or more events, complete once the capture goes past `end`.
+#[derive(Debug)]
+pub struct Snippet {
+    pub events: Vec<Event>,
+    pub frames: Vec<RawFrame>,
+    // frames were left out once the snippet reached its size limit
+    pub truncated: bool,
+    bytes: usize,
+    limit: usize,
+    end: Duration,
+}
+
+impl Snippet {
+    fn push(&mut self, frame: RawFrame) {
+        if self.bytes + frame.data.len() > self.limit {
+            self.truncated = true;
+            return;
+        }
+        self.bytes += frame.data.len();
+        self.frames.push(frame);
+    }
+}
+
+#[derive(Debug)]
+struct RtpState {
+    sequence_number: u16,
+    arrival: Duration,
+    rtp_timestamp: u32,
+    // in RTP timestamp units
+    jitter: f64,
+    jitter_exceeded: bool,
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, Copy)]
+enum StreamKey {
+    Rtp(RtpStreamKey),
+    Mpegts(MpegtsStreamKey),
+}
+
+impl fmt::Display for StreamKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rtp((source, destination, _, ssrc, _)) => {
+                write!(f, "RTP {} -> {} SSRC {:#010x}", source, destination, ssrc)
+            }
+            Self::Mpegts((source, destination, _, _)) => {
+                write!(f, "MPEG-TS {} -> {}", source, destination)
+            }
+        }
+    }
+}
+
+/// Evaluates the conditions on the decoded packets of a source and cuts the snippets
+/// of its raw frames around the events, `pre` before the first event and `post` after the last.
+#[derive(Debug)]
+pub struct TriggerEngine {
+    config: TriggerConfig,
+    rtp: HashMap<RtpStreamKey, RtpState>,
+    continuity_counters: HashMap<(MpegtsStreamKey, u16), u8>,
+    // last packet of the stream and whether it was reported as stopped
+    last_seen: HashMap<StreamKey, (Duration, bool)>,
+    last_stop_check: Duration,
+    pre_buffer: VecDeque<RawFrame>,
+    pre_buffer_bytes: usize,
+    pre_buffer_limit: usize,
+    snippet_limit: usize,
+    snippet: Option<Snippet>,
+    // newest capture timestamp seen
+    clock: Duration,
+}
+
+impl TriggerEngine {
+    pub fn new(config: TriggerConfig) -> Self {
+        Self {
+            config,
+            rtp: HashMap::new(),
+            continuity_counters: HashMap::new(),
+            last_seen: HashMap::new(),
+            last_stop_check: Duration::ZERO,
+            pre_buffer: VecDeque::new(),
+            pre_buffer_bytes: 0,
+            pre_buffer_limit: MAX_PRE_BUFFER_BYTES,
+            snippet_limit: MAX_SNIPPET_BYTES,
+            snippet: None,
+            clock: Duration::ZERO,
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.config.dir
+    }
+
+    /// Forgets the streams and the buffered frames, e.g. when a replay jumps to another position.
+    pub fn reset(&mut self) {
+        *self = Self::new(self.config.clone());
+    }
+
+    pub fn push_frames(&mut self, frames: Vec<RawFrame>) {
+        for frame in frames {
+            self.clock = self.clock.max(frame.timestamp);
+            if let Some(ref mut snippet) = self.snippet {
+                snippet.push(frame.clone());
+            }
+            self.pre_buffer_bytes += frame.data.len();
+            self.pre_buffer.push_back(frame);
+        }
+
+        let oldest = self.clock.saturating_sub(self.config.pre);
+        while self.pre_buffer_bytes > self.pre_buffer_limit
+            || self
+                .pre_buffer
+                .front()
+                .is_some_and(|frame| frame.timestamp < oldest)
+        {
+            let Some(frame) = self.pre_buffer.pop_front() else {
+                break;
+            };
+            self.pre_buffer_bytes -= frame.data.len();
+        }
+    }
+
+    /// Evaluates the conditions on the packet, returns the events it fired.
+    pub fn inspect(&mut self, packet: &Packet) -> Vec<Event> {
+        self.clock = self.clock.max(packet.timestamp);
+
+        let mut events = Vec::new();
+        match packet.contents {
+            SessionPacket::Rtp(ref rtp) => {
+                let key = packet.rtp_stream_key(rtp.ssrc);
+                self.mark_seen(StreamKey::Rtp(key), packet.timestamp);
+                let clock_rate = rtp.payload_type.clock_rate;
+                self.inspect_rtp(
+                    key,
+                    rtp.sequence_number,
+                    rtp.timestamp,
+                    clock_rate,
+                    packet.timestamp,
+                    &mut events,
+                );
+            }
+            SessionPacket::Mpegts(ref mpegts) => {
+                let key = packet.mpegts_stream_key();
+                self.mark_seen(StreamKey::Mpegts(key), packet.timestamp);
+                for fragment in mpegts.fragments.iter() {
+                    let PIDTable::PID(pid) = fragment.header.pid else {
+                        continue;
+                    };
+                    let discontinuity = fragment
+                        .adaptation_field
+                        .as_ref()
+                        .is_some_and(|field| field.discontinuity_indicator);
+                    let has_payload = matches!(
+                        fragment.header.adaptation_field_control,
+                        AdaptationFieldControl::PayloadOnly
+                            | AdaptationFieldControl::AdaptationFieldAndPayload
+                    );
+                    self.inspect_continuity(
+                        key,
+                        pid,
+                        fragment.header.continuity_counter,
+                        has_payload && !discontinuity,
+                        packet.timestamp,
+                        &mut events,
+                    );
+                }
+            }
+            _ => {}
+        }
+
+        self.check_stops(&mut events);
+        self.start_snippet(&events);
+        events
+    }
+
+    /// Moves the clock of a live source when no packets arrive, so that stopped streams
+    /// are noticed and the snippets are completed.
+    pub fn advance(&mut self, clock: Duration) -> Vec<Event> {
+        self.clock = self.clock.max(clock);
+
+        let mut events = Vec::new();
+        self.check_stops(&mut events);
+        self.start_snippet(&events);
+        events
+    }
+
+    /// Snippet which already covers the whole post-trigger window.
+    pub fn take_complete(&mut self) -> Option<Snippet> {
+        if self
+            .snippet
+            .as_ref()
+            .is_some_and(|snippet| self.clock > snippet.end)
+        {
+            return self.snippet.take();
+        }
+        None
+    }
+
+    /// Snippet in progress, when the source ends before its post-trigger window does.
+    pub fn take_incomplete(&mut self) -> Option<Snippet> {
+        self.snippet.take()
+    }
+
+    fn inspect_rtp(
+        &mut self,
+        key: RtpStreamKey,
+        sequence_number: u16,
+        rtp_timestamp: u32,
+        clock_rate: Option<u32>,
+        arrival: Duration,
+        events: &mut Vec<Event>,
+    ) {
+        let Some(state) = self.rtp.get_mut(&key) else {
+            self.rtp.insert(
+                key,
+                RtpState {
+                    sequence_number,
+                    arrival,
+                    rtp_timestamp,
+                    jitter: 0.0,
+                    jitter_exceeded: false,
+                },
+            );
+            return;
+        };
+
+        let gap = sequence_number.wrapping_sub(state.sequence_number);
+        // late or duplicated packets don't move the stream forward
+        if gap == 0 || gap >= 0x8000 {
+            return;
+        }
+
+        let lost = gap - 1;
+        if let Some(clock_rate) = clock_rate {
+            let transit = (arrival.as_secs_f64() - state.arrival.as_secs_f64()) * clock_rate as f64
+                - rtp_timestamp.wrapping_sub(state.rtp_timestamp) as i32 as f64;
+            state.jitter += (transit.abs() - state.jitter) / 16.0;
+        }
+
+        state.sequence_number = sequence_number;
+        state.arrival = arrival;
+        state.rtp_timestamp = rtp_timestamp;
+        let jitter_ms = clock_rate.map(|clock_rate| state.jitter / clock_rate as f64 * 1000.0);
+
+        for condition in self.config.conditions.iter() {
+            match *condition {
+                Condition::LossBurst(packets) if lost > 0 && lost >= packets => {
+                    events.push(Event {
+                        condition: *condition,
+                        timestamp: arrival,
+                        description: format!(
+                            "{} packets lost before sequence number {} in {}",
+                            lost,
+                            sequence_number,
+                            StreamKey::Rtp(key)
+                        ),
+                    });
+                }
+                Condition::Jitter(threshold) => {
+                    let Some(jitter_ms) = jitter_ms else {
+                        continue;
+                    };
+                    // fires once when the jitter crosses the threshold
+                    let exceeded = jitter_ms > threshold;
+                    if exceeded && !state.jitter_exceeded {
+                        events.push(Event {
+                            condition: *condition,
+                            timestamp: arrival,
+                            description: format!(
+                                "jitter {:.2} ms above {} ms in {}",
+                                jitter_ms,
+                                threshold,
+                                StreamKey::Rtp(key)
+                            ),
+                        });
+                    }
+                    state.jitter_exceeded = exceeded;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn inspect_continuity(
+        &mut self,
+        key: MpegtsStreamKey,
+        pid: u16,
+        counter: u8,
+        // counter only increments on fragments with payload, and it's reset by the discontinuity indicator
+        checked: bool,
+        timestamp: Duration,
+        events: &mut Vec<Event>,
+    ) {
+        let previous = self.continuity_counters.insert((key, pid), counter);
+        let Some(previous) = previous else {
+            return;
+        };
+
+        let expected = (previous + 1) & 0x0F;
+        // a single duplicate is allowed
+        if !checked || counter == expected || counter == previous {
+            return;
+        }
+
+        if self.config.conditions.contains(&Condition::CcError) {
+            events.push(Event {
+                condition: Condition::CcError,
+                timestamp,
+                description: format!(
+                    "continuity counter error on PID {:#X} (expected {}, got {}) in {}",
+                    pid,
+                    expected,
+                    counter,
+                    StreamKey::Mpegts(key)
+                ),
+            });
+        }
+    }
+
+    fn mark_seen(&mut self, key: StreamKey, timestamp: Duration) {
+        self.last_seen.insert(key, (timestamp, false));
+    }
+
+    fn check_stops(&mut self, events: &mut Vec<Event>) {
+        let Some(stop) = self
+            .config
+            .conditions
+            .iter()
+            .find_map(|condition| match condition {
+                Condition::StreamStop(duration) => Some(*duration),
+                _ => None,
+            })
+        else {
+            return;
+        };
+
+        if self.clock < self.last_stop_check + STOP_CHECK_INTERVAL {
+            return;
+        }
+        self.last_stop_check = self.clock;
+
+        for (key, (last_seen, stopped)) in self.last_seen.iter_mut() {
+            if !*stopped && self.clock.saturating_sub(*last_seen) >= stop {
+                *stopped = true;
+                events.push(Event {
+                    condition: Condition::StreamStop(stop),
+                    timestamp: *last_seen,
+                    description: format!("no packets for {:?} in {}", stop, key),
+                });
+            }
+        }
+    }
+
+    fn start_snippet(&mut self, events: &[Event]) {
+        let Some(last) = events.iter().map(|event| event.timestamp).max() else {
+            return;
+        };
+
+        // events during the post-trigger window extend the snippet
+        if let Some(ref mut snippet) = self.snippet {
+            snippet.events.extend_from_slice(events);
+            snippet.end = snippet.end.max(last.saturating_add(self.config.post));
+            return;
+        }
+
+        let first = events
+            .iter()
+            .map(|event| event.timestamp)
+            .min()
+            .unwrap_or(last);
+        let start = first.saturating_sub(self.config.pre);
+        let mut snippet = Snippet {
+            events: events.to_vec(),
+            frames: Vec::new(),
+            truncated: false,
+            bytes: 0,
+            limit: self.snippet_limit,
+            end: last.saturating_add(self.config.post),
+        };
+        for frame in self.pre_buffer.iter() {
+            if frame.timestamp >= start {
+                snippet.push(frame.clone());
+            }
+        }
+        self.snippet = Some(snippet);
+    }
+}
+
+/// Writes the snippet to a pcapng file in `dir`, the events are attached
+/// as comments to the first frames captured at or after them.
+pub fn write_snippet(dir: &Path, source: &str, mut snippet: Snippet) -> io::Result<PathBuf> {
+    std::fs::create_dir_all(dir)?;
+
+    snippet.frames.sort_by_key(|frame| frame.timestamp);
+    snippet.events.sort_by_key(|event| event.timestamp);
+
+    let first = &snippet.events[0];
+    let name = format!(
+        "{}-{}-{}",
+        pcapng::file_stem(source),
+        first.timestamp.as_millis(),
+        first.condition.kind()
+    );
+    let (path, file) = create_unique(dir, &name)?;
+
+    // one interface per link type, as merged files don't have to share it
+    let mut link_types: Vec<Linktype> = Vec::new();
+    for frame in snippet.frames.iter() {
+        if !link_types.contains(&frame.link_type) {
+            link_types.push(frame.link_type);
+        }
+    }
+    let interfaces: Vec<_> = link_types
+        .iter()
+        .map(|link_type| Interface {
+            link_type: *link_type,
+            name: None,
+            description: Some(source.to_string()),
+            filter: None,
+        })
+        .collect();
+
+    let file = BufWriter::new(file);
+    let mut writer = PcapngWriter::new(file, &interfaces)?;
+
+    let mut events = snippet.events.iter().peekable();
+    for frame in snippet.frames.iter() {
+        let mut comments = Vec::new();
+        while let Some(event) = events.next_if(|event| event.timestamp <= frame.timestamp) {
+            comments.push(event.description.as_str());
+        }
+        let comment = (!comments.is_empty()).then(|| comments.join("; "));
+
+        let interface_id = link_types
+            .iter()
+            .position(|link_type| *link_type == frame.link_type)
+            .unwrap_or_default();
+        writer.write_packet(
+            interface_id as u32,
+            frame.timestamp,
+            &frame.data,
+            frame.original_len,
+            comment.as_deref(),
+        )?;
+    }
+
+    writer.flush()?;
+    Ok(path)
+}
+
+// snippets of events in the same millisecond get a counter after the name
+fn create_unique(dir: &Path, name: &str) -> io::Result<(PathBuf, File)> {
+    let mut path = dir.join(format!("{}.pcapng", name));
+    for counter in 1.. {
+        match File::create_new(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                path = dir.join(format!("{}-{}.pcapng", name, counter));
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    unreachable!()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use netpix_common::packet::{PacketMetadata, SessionProtocol, TransportProtocol};
+    use std::time::SystemTime;
+
+    fn config(pre: Duration, post: Duration) -> TriggerConfig {
+        TriggerConfig {
+            dir: PathBuf::new(),
+            conditions: vec![Condition::LossBurst(1)],
+            pre,
+            post,
+        }
+    }
+
+    fn frame(secs: u64) -> RawFrame {
+        RawFrame {
+            timestamp: Duration::from_secs(secs),
+            link_type: Linktype::ETHERNET,
+            data: vec![secs as u8; 4],
+            original_len: 4,
+        }
+    }
+
+    fn rtp(sequence_number: u16, secs: u64) -> Packet {
+        let mut payload = vec![0x80, 0x00];
+        payload.extend_from_slice(&sequence_number.to_be_bytes());
+        payload.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1]);
+        let mut packet = Packet {
+            payload: Some(payload),
+            id: sequence_number as usize,
+            timestamp: Duration::from_secs(secs),
+            length: 12,
+            source_addr: "10.0.0.1:5004".parse().unwrap(),
+            destination_addr: "10.0.0.2:5004".parse().unwrap(),
+            transport_protocol: TransportProtocol::Udp,
+            session_protocol: SessionProtocol::Unknown,
+            contents: SessionPacket::Unknown,
+            creation_time: SystemTime::now(),
+            metadata: PacketMetadata::default(),
+        };
+        packet.parse_as(SessionProtocol::Rtp);
+        packet
+    }
+
+    // the frame of the packet comes first, as in the handler
+    fn push(engine: &mut TriggerEngine, sequence_number: u16, secs: u64) -> Vec<Event> {
+        engine.push_frames(vec![frame(secs)]);
+        engine.inspect(&rtp(sequence_number, secs))
+    }
+
+    #[test]
+    fn test_merges_events_within_post_window() {
+        let mut engine = TriggerEngine::new(config(Duration::from_secs(1), Duration::from_secs(2)));
+
+        for (sequence_number, secs) in [(1, 1), (2, 2)] {
+            assert!(push(&mut engine, sequence_number, secs).is_empty());
+        }
+        assert_eq!(push(&mut engine, 4, 3).len(), 1);
+        // lost again before the post window of the first loss ends
+        assert_eq!(push(&mut engine, 6, 4).len(), 1);
+        for (sequence_number, secs) in [(7, 5), (8, 6)] {
+            assert!(push(&mut engine, sequence_number, secs).is_empty());
+            assert!(engine.take_complete().is_none());
+        }
+        assert!(push(&mut engine, 9, 7).is_empty());
+
+        let snippet = engine.take_complete().unwrap();
+        assert_eq!(snippet.events.len(), 2);
+        let timestamps: Vec<_> = snippet
+            .frames
+            .iter()
+            .map(|frame| frame.timestamp.as_secs())
+            .collect();
+        assert_eq!(timestamps, [2, 3, 4, 5, 6, 7]);
+        assert!(engine.take_complete().is_none());
+    }
+
+    #[test]
+    fn test_long_post_window_does_not_overflow() {
+        let mut engine = TriggerEngine::new(config(Duration::ZERO, Duration::MAX));
+
+        push(&mut engine, 1, 1);
+        assert_eq!(push(&mut engine, 3, 2).len(), 1);
+        assert_eq!(push(&mut engine, 5, 3).len(), 1);
+        assert!(engine.take_complete().is_none());
+        assert_eq!(engine.take_incomplete().unwrap().events.len(), 2);
+    }
+
+    #[test]
+    fn test_limits_buffered_bytes() {
+        let mut engine =
+            TriggerEngine::new(config(Duration::from_secs(60), Duration::from_secs(60)));
+        engine.pre_buffer_limit = 10;
+        engine.snippet_limit = 14;
+
+        for (sequence_number, secs) in [(1, 1), (2, 2), (3, 3)] {
+            assert!(push(&mut engine, sequence_number, secs).is_empty());
+        }
+        // the oldest frame left the buffer, though it's within the pre-trigger window
+        assert_eq!(engine.pre_buffer.len(), 2);
+        assert_eq!(engine.pre_buffer_bytes, 8);
+
+        assert_eq!(push(&mut engine, 5, 4).len(), 1);
+        for (sequence_number, secs) in [(6, 5), (7, 6)] {
+            push(&mut engine, sequence_number, secs);
+        }
+        let snippet = engine.take_incomplete().unwrap();
+        let timestamps: Vec<_> = snippet
+            .frames
+            .iter()
+            .map(|frame| frame.timestamp.as_secs())
+            .collect();
+        assert_eq!(timestamps, [3, 4, 5]);
+        assert!(snippet.truncated);
+    }
+
+    #[test]
+    fn test_writes_snippets_with_unique_names() {
+        let dir = std::env::temp_dir().join(format!("netpix-trigger-{}", std::process::id()));
+        let snippet = || Snippet {
+            events: vec![Event {
+                condition: Condition::CcError,
+                timestamp: Duration::from_millis(1500),
+                description: "continuity counter error".to_string(),
+            }],
+            frames: vec![frame(2), frame(1)],
+            truncated: false,
+            bytes: 8,
+            limit: MAX_SNIPPET_BYTES,
+            end: Duration::from_secs(2),
+        };
+
+        let first = write_snippet(&dir, "capture.pcap", snippet()).unwrap();
+        let second = write_snippet(&dir, "capture.pcap", snippet()).unwrap();
+
+        assert_eq!(first.file_name().unwrap(), "capture.pcap-1500-cc.pcapng");
+        assert_eq!(second.file_name().unwrap(), "capture.pcap-1500-cc-1.pcapng");
+        // the frames in timestamp order
+        let mut capture = pcap::Capture::from_file(&first).unwrap();
+        assert_eq!(capture.get_datalink(), Linktype::ETHERNET);
+        for secs in [1, 2] {
+            let packet = capture.next_packet().unwrap();
+            assert_eq!(packet.header.ts.tv_sec, secs);
+            assert_eq!(packet.data, [secs as u8; 4]);
+        }
+        assert!(capture.next_packet().is_err());
+
+        // the event is attached to the first frame after it
+        let data = std::fs::read(&first).unwrap();
+        let comments: Vec<_> = pcapng::read_blocks(&data)
+            .into_iter()
+            .filter(|(block_type, _)| *block_type == pcapng::ENHANCED_PACKET_BLOCK)
+            .map(|(_, body)| {
+                // 4 bytes of frame data after the 20 bytes of the header
+                pcapng::read_options(&body[24..])
+                    .into_iter()
+                    .find(|(code, _)| *code == pcapng::OPT_COMMENT)
+                    .map(|(_, value)| value.to_vec())
+            })
+            .collect();
+        assert_eq!(comments, [None, Some(b"continuity counter error".to_vec())]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
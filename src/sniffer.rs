@@ -1,9 +1,27 @@
 use futures_util::StreamExt;
 use log_parser::parser::Parser;
-use netpix_common::{Packet, Source};
-use pcap::{Capture, PacketCodec, PacketStream};
+use merge::MergedStream;
+use netpix_common::packet::{IpReassembler, ReassemblyStats, TcpReassembler, TcpStats};
+use netpix_common::{Packet, ReplayControl, ReplayState, Source};
+use pcap::{Capture, Linktype, PacketCodec, PacketHeader, PacketStream};
+use pipe::PipeStream;
+pub use record::RecordConfig;
+use record::Recorder;
+use replay::Replay;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
 use tokio::sync::mpsc;
 use tokio::sync::mpsc::Receiver;
+use udp::{UdpSource, UdpStream};
+use watch::WatchStream;
+
+mod merge;
+mod pipe;
+mod record;
+mod replay;
+mod udp;
+mod watch;
 
 const LOG_CHANNEL_BUFFER_SIZE: usize = 100;
 
@@ -16,24 +34,85 @@ pub enum Error {
     UnsupportedPacketType,
     InvalidFilter,
     PacketStreamUnavailable,
+    InvalidUdpSource,
+    SocketUnavailable,
+    SeekUnsupported,
+    RecordingUnavailable,
+}
+
+// shared with the decoder, as `PacketStream` doesn't expose its codec
+#[derive(Debug, Default)]
+struct Reassembly {
+    ip: IpReassembler,
+    tcp: TcpReassembler,
 }
+
+/// Link-layer frame as it was captured, decoded packets don't keep these bytes.
+#[derive(Debug, Clone)]
+pub struct RawFrame {
+    pub timestamp: Duration,
+    pub link_type: Linktype,
+    pub data: Vec<u8>,
+    pub original_len: u32,
+}
+
+// collected only when someone takes them, shared as `PacketStream` doesn't expose its codec
+type RawFrames = Arc<Mutex<Option<Vec<RawFrame>>>>;
+
 #[derive(Debug)]
 struct PacketDecoder {
     packet_id: usize,
+    link_type: Linktype,
+    reassembler: Arc<Mutex<Reassembly>>,
+    // set once the capture filter is known, as the files carry it
+    recorder: Arc<OnceLock<Recorder>>,
+    raw_frames: RawFrames,
 }
 
 impl PacketDecoder {
-    pub fn new() -> Self {
-        Self { packet_id: 1 }
+    pub fn new(link_type: Linktype, reassembler: Arc<Mutex<Reassembly>>) -> Self {
+        Self {
+            packet_id: 1,
+            link_type,
+            reassembler,
+            recorder: Arc::default(),
+            raw_frames: Arc::default(),
+        }
     }
 }
 
 impl PacketCodec for PacketDecoder {
-    type Item = Result<Packet, Error>;
+    // empty when the packet is a fragment of a not yet complete datagram
+    // or a TCP segment without a complete framed message,
+    // several packets when a segment carries multiple framed messages
+    type Item = Result<Vec<Packet>, Error>;
 
     fn decode(&mut self, packet: pcap::Packet<'_>) -> Self::Item {
-        let res = match Packet::build(&packet, self.packet_id) {
-            Some(packet) => Ok(packet),
+        if let Some(recorder) = self.recorder.get() {
+            recorder.write(packet.header, packet.data);
+        }
+
+        if let Some(ref mut frames) = *self.raw_frames.lock().unwrap() {
+            frames.push(RawFrame {
+                timestamp: packet_timestamp(packet.header),
+                link_type: self.link_type,
+                data: packet.data.to_vec(),
+                original_len: packet.header.len,
+            });
+        }
+
+        let mut reassembly = self.reassembler.lock().unwrap();
+        let fragments = reassembly.ip.stats().fragments;
+
+        // messages extracted from a single segment keep the frame's id
+        let res = match Packet::build_reassembled(
+            &packet,
+            self.packet_id,
+            self.link_type,
+            &mut reassembly.ip,
+        ) {
+            Some(packet) => Ok(reassembly.tcp.push(packet)),
+            None if reassembly.ip.stats().fragments > fragments => Ok(Vec::new()),
             None => Err(Error::UnsupportedPacketType),
         };
 
@@ -53,7 +132,7 @@ impl OfflineStream {
         Self { capture, decoder }
     }
 
-    pub fn next(&mut self) -> Option<Result<Result<Packet, Error>, pcap::Error>> {
+    pub fn next(&mut self) -> Option<Result<Result<Vec<Packet>, Error>, pcap::Error>> {
         let packet = match self.capture.next_packet() {
             Err(pcap::Error::NoMorePackets) => return None,
             Err(err) => return Some(Err(err)),
@@ -62,6 +141,51 @@ impl OfflineStream {
 
         Some(Ok(self.decoder.decode(packet)))
     }
+
+    /// Reopens the file and skips packets captured less than `offset` after `start`
+    /// (or after the first packet), returns the start and the first packet past the offset.
+    pub fn seek(
+        &mut self,
+        path: &str,
+        filter: Option<&str>,
+        offset: Duration,
+        start: Option<Duration>,
+    ) -> Result<(Duration, Vec<Packet>), Error> {
+        self.capture = open_file(path, filter)?;
+        // ids stay the same as when the file is read from its beginning
+        self.decoder.packet_id = 1;
+        let mut start = start;
+
+        loop {
+            let packet = match self.capture.next_packet() {
+                Ok(packet) => packet,
+                Err(pcap::Error::NoMorePackets) => return Ok((start.unwrap_or_default(), vec![])),
+                Err(_) => return Err(Error::CouldntReceivePacket),
+            };
+
+            let timestamp = packet_timestamp(packet.header);
+            let start = *start.get_or_insert(timestamp);
+            if timestamp >= start.saturating_add(offset) {
+                let packets = self.decoder.decode(packet).unwrap_or_default();
+                return Ok((start, packets));
+            }
+            self.decoder.packet_id += 1;
+        }
+    }
+}
+
+fn open_file(path: &str, filter: Option<&str>) -> Result<Capture<pcap::Offline>, Error> {
+    let mut capture = pcap::Capture::from_file(path).map_err(|_| Error::FileNotFound)?;
+    if let Some(filter) = filter {
+        capture
+            .filter(filter, true)
+            .map_err(|_| Error::InvalidFilter)?;
+    }
+    Ok(capture)
+}
+
+fn packet_timestamp(header: &PacketHeader) -> Duration {
+    Duration::new(header.ts.tv_sec as u64, header.ts.tv_usec as u32 * 1000)
 }
 
 struct LogStream {
@@ -80,30 +204,108 @@ impl LogStream {
 
 enum CaptureType {
     Offline(OfflineStream),
+    Merged(MergedStream),
+    Watched(WatchStream),
+    Piped(PipeStream),
     Online(PacketStream<pcap::Active, PacketDecoder>),
     RtcLogging(LogStream),
+    Socket(UdpStream),
 }
 
 pub struct Sniffer {
     capture: CaptureType,
+    // one per file of a merged capture
+    reassemblers: Vec<Arc<Mutex<Reassembly>>>,
+    recorder: Arc<OnceLock<Recorder>>,
+    raw_frames: RawFrames,
+    pending: VecDeque<Packet>,
+    filter: Option<String>,
+    replay: Option<Replay>,
     pub source: Source,
 }
 
 impl Sniffer {
     pub fn from_file(file: &str) -> Result<Self, Error> {
+        if pipe::is_pipe(file) {
+            return Ok(Self::from_pipe(file));
+        }
+
         let Ok(capture) = pcap::Capture::from_file(file) else {
             return Err(Error::FileNotFound);
         };
 
-        let decoder = PacketDecoder::new();
+        let reassembler = Arc::new(Mutex::new(Reassembly::default()));
+        let decoder = PacketDecoder::new(capture.get_datalink(), reassembler.clone());
+        let raw_frames = decoder.raw_frames.clone();
         let stream = OfflineStream::new(capture, decoder);
 
         Ok(Self {
             capture: CaptureType::Offline(stream),
+            reassemblers: vec![reassembler],
+            recorder: Arc::default(),
+            raw_frames,
+            pending: VecDeque::new(),
+            filter: None,
+            replay: None,
             source: Source::File(file.to_string()),
         })
     }
 
+    fn from_pipe(file: &str) -> Self {
+        let reassembler = Arc::new(Mutex::new(Reassembly::default()));
+        // link type is set once the file header is read
+        let decoder = PacketDecoder::new(Linktype::ETHERNET, reassembler.clone());
+        let raw_frames = decoder.raw_frames.clone();
+        let name = if file == "-" { "stdin" } else { file };
+
+        Self {
+            capture: CaptureType::Piped(PipeStream::new(file, decoder)),
+            reassemblers: vec![reassembler],
+            recorder: Arc::default(),
+            raw_frames,
+            pending: VecDeque::new(),
+            filter: None,
+            replay: None,
+            source: Source::File(name.to_string()),
+        }
+    }
+
+    pub fn from_files(files: &[String]) -> Result<Self, Error> {
+        let stream = MergedStream::new(files)?;
+        let reassemblers = stream.reassemblers();
+        let raw_frames = stream.raw_frames.clone();
+
+        Ok(Self {
+            capture: CaptureType::Merged(stream),
+            reassemblers,
+            recorder: Arc::default(),
+            raw_frames,
+            pending: VecDeque::new(),
+            filter: None,
+            replay: None,
+            source: Source::File(files.join("+")),
+        })
+    }
+
+    pub fn from_dir(dir: &str) -> Result<Self, Error> {
+        let reassembler = Arc::new(Mutex::new(Reassembly::default()));
+        // link type is set for every file when it's opened
+        let decoder = PacketDecoder::new(Linktype::ETHERNET, reassembler.clone());
+        let raw_frames = decoder.raw_frames.clone();
+        let stream = WatchStream::new(dir, decoder)?;
+
+        Ok(Self {
+            capture: CaptureType::Watched(stream),
+            reassemblers: vec![reassembler],
+            recorder: Arc::default(),
+            raw_frames,
+            pending: VecDeque::new(),
+            filter: None,
+            replay: None,
+            source: Source::File(dir.to_string()),
+        })
+    }
+
     pub fn from_device(device: &str, promisc: bool) -> Result<Self, Error> {
         let Ok(capture) = pcap::Capture::from_device(device) else {
             return Err(Error::DeviceNotFound);
@@ -117,17 +319,42 @@ impl Sniffer {
             return Err(Error::DeviceUnavailable);
         };
 
-        let decoder = PacketDecoder::new();
+        let reassembler = Arc::new(Mutex::new(Reassembly::default()));
+        let decoder = PacketDecoder::new(capture.get_datalink(), reassembler.clone());
+        let raw_frames = decoder.raw_frames.clone();
+        let recorder = decoder.recorder.clone();
         let Ok(stream) = capture.stream(decoder) else {
             return Err(Error::PacketStreamUnavailable);
         };
 
         Ok(Self {
             capture: CaptureType::Online(stream),
+            reassemblers: vec![reassembler],
+            recorder,
+            raw_frames,
+            pending: VecDeque::new(),
+            filter: None,
+            replay: None,
             source: Source::Interface(format!("{} {}", device, if promisc { "👁️" } else { "" })),
         })
     }
 
+    pub fn from_udp(source: &str) -> Result<Self, Error> {
+        let source: UdpSource = source.parse()?;
+        let stream = UdpStream::open(&source)?;
+
+        Ok(Self {
+            capture: CaptureType::Socket(stream),
+            reassemblers: Vec::new(),
+            recorder: Arc::default(),
+            raw_frames: Arc::default(),
+            pending: VecDeque::new(),
+            filter: None,
+            replay: None,
+            source: Source::Socket(source.to_string()),
+        })
+    }
+
     pub fn from_logs(file: &str) -> Result<Self, Error> {
         let (tx, rx) = mpsc::channel(LOG_CHANNEL_BUFFER_SIZE);
 
@@ -142,6 +369,12 @@ impl Sniffer {
 
         Ok(Self {
             capture: CaptureType::RtcLogging(log_stream),
+            reassemblers: Vec::new(),
+            recorder: Arc::default(),
+            raw_frames: Arc::default(),
+            pending: VecDeque::new(),
+            filter: None,
+            replay: None,
             source: Source::File(file.to_string()),
         })
     }
@@ -150,26 +383,190 @@ impl Sniffer {
         match self.capture {
             CaptureType::Online(ref mut stream) => stream.capture_mut().filter(filter, true),
             CaptureType::Offline(ref mut stream) => stream.capture.filter(filter, true),
-            CaptureType::RtcLogging(_) => Ok(()),
+            CaptureType::Merged(ref mut stream) => stream.apply_filter(filter),
+            CaptureType::Watched(ref mut stream) => stream.apply_filter(filter),
+            CaptureType::Piped(ref mut stream) => stream.apply_filter(filter),
+            CaptureType::RtcLogging(_) | CaptureType::Socket(_) => Ok(()),
+        }
+        .map_err(|_| Error::InvalidFilter)?;
+
+        self.filter = Some(filter.to_string());
+        Ok(())
+    }
+
+    /// Writes the captured packets to a ring of pcapng files, live captures only.
+    pub fn record(&mut self, interface: &str, config: RecordConfig) -> Result<(), Error> {
+        let CaptureType::Online(ref mut stream) = self.capture else {
+            return Ok(());
+        };
+
+        let link_type = stream.capture_mut().get_datalink();
+        let interface = record::interface(interface, link_type, self.filter.as_deref());
+        let recorder = Recorder::start(config, interface)?;
+        // recording twice isn't supported, the first ring is kept
+        let _ = self.recorder.set(recorder);
+        Ok(())
+    }
+
+    /// Whether the packets are timestamped with the wall clock as they arrive.
+    pub fn is_live(&self) -> bool {
+        matches!(
+            self.capture,
+            CaptureType::Online(_) | CaptureType::Socket(_)
+        )
+    }
+
+    /// Starts collecting the raw frames, to be taken with [`Sniffer::take_raw_frames`].
+    pub fn collect_raw_frames(&mut self) {
+        self.raw_frames.lock().unwrap().get_or_insert_default();
+    }
+
+    /// Raw frames decoded since the last call, the sources which aren't read
+    /// with libpcap (sockets and logs) have none.
+    pub fn take_raw_frames(&mut self) -> Vec<RawFrame> {
+        self.raw_frames
+            .lock()
+            .unwrap()
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Paces the packets by their capture timestamps, `0` starts paused in stepped mode.
+    pub fn enable_replay(&mut self, speed: f64) {
+        self.replay = Some(Replay::new(speed));
+    }
+
+    pub fn replay_state(&self) -> Option<ReplayState> {
+        self.replay.as_ref().map(Replay::state)
+    }
+
+    pub fn control_replay(&mut self, control: ReplayControl) -> Result<(), Error> {
+        let Some(ref mut replay) = self.replay else {
+            return Ok(());
+        };
+
+        if let ReplayControl::Seek(offset) = control {
+            let start = replay.start();
+            let filter = self.filter.as_deref();
+            // state of the skipped part of the capture is meaningless now
+            for reassembler in self.reassemblers.iter() {
+                *reassembler.lock().unwrap() = Reassembly::default();
+            }
+
+            let (start, packets) = match (&mut self.capture, &self.source) {
+                (CaptureType::Offline(stream), Source::File(path)) => {
+                    stream.seek(path, filter, offset, start)?
+                }
+                (CaptureType::Merged(stream), _) => (stream.seek(offset, start)?, vec![]),
+                _ => return Err(Error::SeekUnsupported),
+            };
+
+            self.pending = packets.into();
+            replay.set_start(start);
+        }
+
+        replay.control(control);
+        Ok(())
+    }
+
+    pub fn reassembly_stats(&self) -> ReassemblyStats {
+        let mut total = ReassemblyStats::default();
+        for reassembler in self.reassemblers.iter() {
+            let stats = reassembler.lock().unwrap().ip.stats();
+            total.fragments += stats.fragments;
+            total.reassembled += stats.reassembled;
+            total.failures += stats.failures;
+        }
+        total
+    }
+
+    pub fn tcp_stats(&self) -> TcpStats {
+        let mut total = TcpStats::default();
+        for reassembler in self.reassemblers.iter() {
+            let stats = reassembler.lock().unwrap().tcp.stats();
+            total.gaps += stats.gaps;
+            total.lost_bytes += stats.lost_bytes;
         }
-        .map_err(|_| Error::InvalidFilter)
+        total
     }
 
     pub async fn next_packet(&mut self) -> Option<Result<Packet, Error>> {
-        match self.capture {
-            CaptureType::Offline(ref mut stream) => stream.next().map(|res| {
-                res.map_err(|_arg0: pcap::Error| Error::CouldntReceivePacket)
-                    .and_then(|inner_res| inner_res)
-            }),
-
-            CaptureType::Online(ref mut stream) => stream.next().await.map(|res| {
-                res.map_err(|_arg0: pcap::Error| Error::CouldntReceivePacket)
-                    .and_then(|inner_res| inner_res)
-            }),
-
-            CaptureType::RtcLogging(ref mut stream) => stream.next().await.map(|res| {
-                res.map_err(|_arg0: tokio::time::error::Error| Error::CouldntReceivePacket)
-            }),
+        loop {
+            if let Some(timestamp) = self.pending.front().map(|packet| packet.timestamp) {
+                if let Some(ref mut replay) = self.replay {
+                    replay.wait_for(timestamp).await;
+                }
+                return self.pending.pop_front().map(Ok);
+            }
+
+            let result = match self.capture {
+                CaptureType::Offline(ref mut stream) => stream.next().map(|res| {
+                    res.map_err(|_arg0: pcap::Error| Error::CouldntReceivePacket)
+                        .and_then(|inner_res| inner_res)
+                }),
+
+                CaptureType::Merged(ref mut stream) => stream.next(),
+
+                CaptureType::Watched(ref mut stream) => stream.next().await,
+
+                CaptureType::Piped(ref mut stream) => stream.next().await,
+
+                CaptureType::Online(ref mut stream) => stream.next().await.map(|res| {
+                    res.map_err(|_arg0: pcap::Error| Error::CouldntReceivePacket)
+                        .and_then(|inner_res| inner_res)
+                }),
+
+                CaptureType::RtcLogging(ref mut stream) => stream.next().await.map(|res| {
+                    res.map(|packet| vec![packet])
+                        .map_err(|_arg0: tokio::time::error::Error| Error::CouldntReceivePacket)
+                }),
+
+                CaptureType::Socket(ref mut stream) => stream
+                    .next()
+                    .await
+                    .map(|res| res.map(|packet| vec![packet])),
+            };
+
+            match result {
+                Some(Ok(packets)) => self.pending.extend(packets),
+                Some(Err(err)) => return Some(Err(err)),
+                None => return None,
+            }
         }
     }
 }
+
+/// pcap file of UDP datagrams from 10.0.0.1:5000 to 10.0.0.2:6000 captured at the given times.
+#[cfg(test)]
+fn udp_capture(datagrams: &[(Duration, &[u8])]) -> Vec<u8> {
+    // microsecond timestamps, Ethernet frames
+    let mut file = Vec::new();
+    file.extend_from_slice(&0xa1b2c3d4u32.to_le_bytes());
+    file.extend_from_slice(&2u16.to_le_bytes());
+    file.extend_from_slice(&4u16.to_le_bytes());
+    file.extend_from_slice(&[0; 8]);
+    file.extend_from_slice(&65535u32.to_le_bytes());
+    file.extend_from_slice(&1u32.to_le_bytes());
+
+    for (timestamp, payload) in datagrams {
+        let mut frame = vec![0x02, 0, 0, 0, 0, 2, 0x02, 0, 0, 0, 0, 1, 0x08, 0x00];
+        let udp_len = 8 + payload.len() as u16;
+        frame.extend_from_slice(&[0x45, 0]);
+        frame.extend_from_slice(&(20 + udp_len).to_be_bytes());
+        frame.extend_from_slice(&[0, 0, 0x40, 0, 64, 17, 0, 0, 10, 0, 0, 1, 10, 0, 0, 2]);
+        frame.extend_from_slice(&5000u16.to_be_bytes());
+        frame.extend_from_slice(&6000u16.to_be_bytes());
+        frame.extend_from_slice(&udp_len.to_be_bytes());
+        frame.extend_from_slice(&[0, 0]);
+        frame.extend_from_slice(payload);
+
+        file.extend_from_slice(&(timestamp.as_secs() as u32).to_le_bytes());
+        file.extend_from_slice(&timestamp.subsec_micros().to_le_bytes());
+        file.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        file.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        file.extend_from_slice(&frame);
+    }
+
+    file
+}
@@ -0,0 +1,232 @@
+use super::{Error, PacketDecoder, RawFrames, Reassembly, open_file, packet_timestamp};
+use log::warn;
+use netpix_common::Packet;
+use pcap::{Capture, PacketCodec, PacketHeader};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+struct MergeInput {
+    capture: Capture<pcap::Offline>,
+    // every file is reassembled on its own, the same traffic captured in two files
+    // would otherwise look like retransmissions, and fragments of the files would mix
+    decoder: PacketDecoder,
+    origin: String,
+    next: Option<(PacketHeader, Vec<u8>)>,
+}
+
+impl MergeInput {
+    fn next_timestamp(&self) -> Option<Duration> {
+        self.next
+            .as_ref()
+            .map(|(header, _)| packet_timestamp(header))
+    }
+
+    // a broken file ends its part of the merge, the others carry on
+    fn read_next_or_warn(&mut self) {
+        if let Err(err) = self.read_next() {
+            warn!("Failed to read {}: {}", self.origin, err);
+        }
+    }
+
+    fn read_next(&mut self) -> Result<(), pcap::Error> {
+        self.next = match self.capture.next_packet() {
+            Ok(packet) => Some((*packet.header, packet.data.to_vec())),
+            Err(pcap::Error::NoMorePackets) => None,
+            Err(err) => {
+                self.next = None;
+                return Err(err);
+            }
+        };
+        Ok(())
+    }
+}
+
+/// Offline captures merged into a single source ordered by packet timestamps,
+/// packet ids are assigned in the merged order.
+pub struct MergedStream {
+    inputs: Vec<MergeInput>,
+    // (timestamp, input index), ties are resolved in the order the files were passed
+    queue: BinaryHeap<Reverse<(Duration, usize)>>,
+    // files are read lazily, so that the capture filter applies to their first packets too
+    started: bool,
+    filter: Option<String>,
+    packet_id: usize,
+    pub raw_frames: RawFrames,
+}
+
+impl MergedStream {
+    pub fn new(files: &[String]) -> Result<Self, Error> {
+        let mut stream = Self {
+            inputs: Vec::with_capacity(files.len()),
+            queue: BinaryHeap::with_capacity(files.len()),
+            started: false,
+            filter: None,
+            packet_id: 1,
+            raw_frames: RawFrames::default(),
+        };
+
+        for file in files {
+            let capture = Capture::from_file(file).map_err(|_| Error::FileNotFound)?;
+            let mut decoder = PacketDecoder::new(capture.get_datalink(), Arc::default());
+            decoder.raw_frames = stream.raw_frames.clone();
+
+            stream.inputs.push(MergeInput {
+                capture,
+                decoder,
+                origin: file.clone(),
+                next: None,
+            });
+        }
+
+        Ok(stream)
+    }
+
+    pub fn reassemblers(&self) -> Vec<Arc<Mutex<Reassembly>>> {
+        self.inputs
+            .iter()
+            .map(|input| input.decoder.reassembler.clone())
+            .collect()
+    }
+
+    pub fn apply_filter(&mut self, filter: &str) -> Result<(), pcap::Error> {
+        for input in self.inputs.iter_mut() {
+            input.capture.filter(filter, true)?;
+        }
+
+        self.filter = Some(filter.to_string());
+        Ok(())
+    }
+
+    /// Reopens the files and skips packets captured less than `offset` after `start`
+    /// (or after the earliest packet of all files), returns the start.
+    pub fn seek(&mut self, offset: Duration, start: Option<Duration>) -> Result<Duration, Error> {
+        for input in self.inputs.iter_mut() {
+            input.capture = open_file(&input.origin, self.filter.as_deref())?;
+            input.read_next_or_warn();
+        }
+
+        let start = start
+            .or_else(|| {
+                self.inputs
+                    .iter()
+                    .filter_map(MergeInput::next_timestamp)
+                    .min()
+            })
+            .unwrap_or_default();
+
+        // ids stay the same as when the files are merged from their beginning
+        self.packet_id = 1;
+        let position = start.saturating_add(offset);
+        for input in self.inputs.iter_mut() {
+            while input
+                .next_timestamp()
+                .is_some_and(|timestamp| timestamp < position)
+            {
+                input.read_next_or_warn();
+                self.packet_id += 1;
+            }
+        }
+
+        self.started = true;
+        self.queue = self
+            .inputs
+            .iter()
+            .enumerate()
+            .filter_map(|(index, input)| Some(Reverse((input.next_timestamp()?, index))))
+            .collect();
+
+        Ok(start)
+    }
+
+    pub fn next(&mut self) -> Option<Result<Vec<Packet>, Error>> {
+        if !self.started {
+            self.started = true;
+            for index in 0..self.inputs.len() {
+                self.advance_or_warn(index);
+            }
+        }
+
+        let Reverse((_, index)) = self.queue.pop()?;
+        let input = &mut self.inputs[index];
+        let (header, data) = input.next.take()?;
+
+        // ids follow the merged order, not the order within the file
+        input.decoder.packet_id = self.packet_id;
+        self.packet_id += 1;
+        let origin = input.origin.clone();
+        let result = input
+            .decoder
+            .decode(pcap::Packet::new(&header, &data))
+            .map(|packets| {
+                packets
+                    .into_iter()
+                    .map(|mut packet| {
+                        packet.metadata.origin = Some(origin.clone());
+                        packet
+                    })
+                    .collect()
+            });
+
+        self.advance_or_warn(index);
+        Some(result)
+    }
+
+    fn advance_or_warn(&mut self, index: usize) {
+        let input = &mut self.inputs[index];
+        input.read_next_or_warn();
+
+        if let Some(timestamp) = input.next_timestamp() {
+            self.queue.push(Reverse((timestamp, index)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sniffer::udp_capture;
+
+    #[test]
+    fn test_merges_by_timestamps() {
+        let dir = std::env::temp_dir().join(format!("netpix-merge-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let secs = Duration::from_secs;
+        let first = dir.join("first.pcap");
+        let second = dir.join("second.pcap");
+        std::fs::write(&first, udp_capture(&[(secs(1), b"a"), (secs(3), b"c")])).unwrap();
+        std::fs::write(&second, udp_capture(&[(secs(2), b"b"), (secs(3), b"d")])).unwrap();
+
+        let files = [first, second].map(|path| path.to_string_lossy().into_owned());
+        let mut stream = MergedStream::new(&files).unwrap();
+        let mut merged = Vec::new();
+        while let Some(result) = stream.next() {
+            for packet in result.unwrap() {
+                let origin = packet.metadata.origin.unwrap();
+                merged.push((packet.id, packet.timestamp, packet.payload.unwrap(), origin));
+            }
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // the tie goes to the file passed first
+        let expected = [
+            (1, 1, b"a", 0),
+            (2, 2, b"b", 1),
+            (3, 3, b"c", 0),
+            (4, 3, b"d", 1),
+        ];
+        assert_eq!(merged.len(), expected.len());
+        for (packet, (id, secs, payload, file)) in merged.iter().zip(expected) {
+            assert_eq!(
+                *packet,
+                (
+                    id,
+                    Duration::from_secs(secs),
+                    payload.to_vec(),
+                    files[file].clone()
+                )
+            );
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use super::{Error, PacketDecoder};
+use log::{info, warn};
+use netpix_common::Packet;
+use pcap::{Capture, Linktype, PacketCodec};
+use std::thread;
+use tokio::sync::mpsc;
+
+const PIPE_CHANNEL_BUFFER_SIZE: usize = 1024;
+
+/// Capture read from stdin (`-`) or a named pipe, e.g. `ssh probe tcpdump -w - | netpix run -f -`.
+///
+/// Reads block until the writer produces data, so the capture is opened and read
+/// on a dedicated thread, started on the first poll so that the filter can be set before.
+pub struct PipeStream {
+    path: String,
+    filter: Option<String>,
+    decoder: Option<PacketDecoder>,
+    receiver: Option<mpsc::Receiver<Result<Vec<Packet>, Error>>>,
+}
+
+impl PipeStream {
+    pub fn new(path: &str, decoder: PacketDecoder) -> Self {
+        Self {
+            path: path.to_string(),
+            filter: None,
+            decoder: Some(decoder),
+            receiver: None,
+        }
+    }
+
+    // the link type isn't known until the header arrives, check the syntax only
+    pub fn apply_filter(&mut self, filter: &str) -> Result<(), pcap::Error> {
+        Capture::dead(Linktype::ETHERNET)?.compile(filter, true)?;
+        self.filter = Some(filter.to_string());
+        Ok(())
+    }
+
+    pub async fn next(&mut self) -> Option<Result<Vec<Packet>, Error>> {
+        if self.receiver.is_none() {
+            self.receiver = Some(self.spawn_reader()?);
+        }
+
+        self.receiver.as_mut()?.recv().await
+    }
+
+    fn spawn_reader(&mut self) -> Option<mpsc::Receiver<Result<Vec<Packet>, Error>>> {
+        let (tx, rx) = mpsc::channel(PIPE_CHANNEL_BUFFER_SIZE);
+        let path = self.path.clone();
+        let filter = self.filter.clone();
+        let decoder = self.decoder.take()?;
+
+        let spawned = thread::Builder::new()
+            .name(format!("pipe reader {}", path))
+            .spawn(move || read_pipe(&path, filter.as_deref(), decoder, tx));
+
+        if let Err(err) = spawned {
+            warn!("Failed to start reading {}: {}", self.path, err);
+            return None;
+        }
+
+        Some(rx)
+    }
+}
+
+fn read_pipe(
+    path: &str,
+    filter: Option<&str>,
+    mut decoder: PacketDecoder,
+    tx: mpsc::Sender<Result<Vec<Packet>, Error>>,
+) {
+    // blocks until the writer sends the file header
+    let mut capture = match Capture::from_file(path) {
+        Ok(capture) => capture,
+        Err(err) => {
+            warn!("Failed to open {}: {}", path, err);
+            let _ = tx.blocking_send(Err(Error::FileNotFound));
+            return;
+        }
+    };
+
+    if let Some(filter) = filter
+        && capture.filter(filter, true).is_err()
+    {
+        let _ = tx.blocking_send(Err(Error::InvalidFilter));
+        return;
+    }
+
+    decoder.link_type = capture.get_datalink();
+
+    loop {
+        let result = match capture.next_packet() {
+            Ok(packet) => decoder.decode(packet),
+            Err(pcap::Error::NoMorePackets) => {
+                info!("End of capture {}", path);
+                return;
+            }
+            Err(err) => {
+                warn!("Failed to read {}: {}", path, err);
+                return;
+            }
+        };
+
+        // the receiver is gone when the source was closed
+        if tx.blocking_send(result).is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(unix)]
+pub fn is_pipe(path: &str) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+
+    path == "-" || std::fs::metadata(path).is_ok_and(|metadata| metadata.file_type().is_fifo())
+}
+
+#[cfg(not(unix))]
+pub fn is_pipe(path: &str) -> bool {
+    path == "-"
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::sniffer::udp_capture;
+    use std::ffi::CString;
+    use std::io::Write;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_reads_from_pipe() {
+        let path = std::env::temp_dir().join(format!("netpix-pipe-{}", std::process::id()));
+        let path = path.to_string_lossy().into_owned();
+        let c_path = CString::new(path.clone()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) }, 0);
+        assert!(is_pipe(&path));
+        assert!(is_pipe("-"));
+
+        let decoder = PacketDecoder::new(Linktype::ETHERNET, Default::default());
+        let mut stream = PipeStream::new(&path, decoder);
+        stream.apply_filter("udp").unwrap();
+
+        // opening the pipe blocks until the reader opens it too
+        let writer = {
+            let path = path.clone();
+            thread::spawn(move || {
+                let capture = udp_capture(&[
+                    (Duration::from_secs(1), b"first"),
+                    (Duration::from_secs(2), b"second"),
+                ]);
+                let mut pipe = std::fs::OpenOptions::new().write(true).open(path).unwrap();
+                // the header and the packets arrive separately
+                pipe.write_all(&capture[..24]).unwrap();
+                pipe.flush().unwrap();
+                thread::sleep(Duration::from_millis(50));
+                pipe.write_all(&capture[24..]).unwrap();
+            })
+        };
+
+        let mut payloads = Vec::new();
+        while let Some(result) = stream.next().await {
+            for packet in result.unwrap() {
+                payloads.push(packet.payload.unwrap());
+            }
+        }
+        writer.join().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(payloads, [b"first".to_vec(), b"second".to_vec()]);
+    }
+}
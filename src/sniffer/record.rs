@@ -0,0 +1,274 @@
+use super::{Error, packet_timestamp};
+use crate::pcapng::{self, Interface, PcapngWriter};
+use log::{info, warn};
+use pcap::{Linktype, PacketHeader};
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, SyncSender, TrySendError};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+const RECORD_CHANNEL_BUFFER_SIZE: usize = 16_384;
+// the buffered packets are written out at least that often, so the files can be opened right away
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+const FILE_EXTENSION: &str = "pcapng";
+
+#[derive(Debug, Clone)]
+pub struct RecordConfig {
+    pub dir: PathBuf,
+    /// file is rotated once it grows past that size
+    pub max_file_size: usize,
+    /// file is rotated once it's open for that long
+    pub max_file_duration: Option<Duration>,
+    /// oldest files are removed when there's more of them
+    pub max_files: usize,
+}
+
+struct RecordedPacket {
+    timestamp: Duration,
+    data: Vec<u8>,
+    original_len: u32,
+}
+
+/// Writes raw packets of a live capture to a ring of pcapng files, on a dedicated thread
+/// so that the disk never stalls the capture. Packets are dropped when the disk can't keep up.
+#[derive(Debug)]
+pub struct Recorder {
+    sender: SyncSender<RecordedPacket>,
+    lagging: AtomicBool,
+}
+
+impl Recorder {
+    pub fn start(config: RecordConfig, interface: Interface) -> Result<Self, Error> {
+        fs::create_dir_all(&config.dir).map_err(|_| Error::RecordingUnavailable)?;
+
+        let name = interface
+            .name
+            .clone()
+            .unwrap_or_else(|| "capture".to_string());
+        let ring = Ring {
+            prefix: format!("{}-", pcapng::file_stem(&name)),
+            config,
+            interface,
+            current: None,
+            sequence: 0,
+        };
+
+        let (sender, receiver) = mpsc::sync_channel(RECORD_CHANNEL_BUFFER_SIZE);
+        thread::Builder::new()
+            .name(format!("recorder {}", name))
+            .spawn(move || ring.run(receiver))
+            .map_err(|_| Error::RecordingUnavailable)?;
+
+        Ok(Self {
+            sender,
+            lagging: AtomicBool::new(false),
+        })
+    }
+
+    pub fn write(&self, header: &PacketHeader, data: &[u8]) {
+        let packet = RecordedPacket {
+            timestamp: packet_timestamp(header),
+            data: data.to_vec(),
+            original_len: header.len,
+        };
+
+        match self.sender.try_send(packet) {
+            Ok(()) => self.lagging.store(false, Ordering::Relaxed),
+            Err(TrySendError::Full(_)) => {
+                if !self.lagging.swap(true, Ordering::Relaxed) {
+                    warn!("Recording can't keep up with the capture, dropping packets");
+                }
+            }
+            // the writer already reported why it stopped
+            Err(TrySendError::Disconnected(_)) => {}
+        }
+    }
+}
+
+struct Ring {
+    config: RecordConfig,
+    interface: Interface,
+    prefix: String,
+    current: Option<(PcapngWriter<BufWriter<File>>, Instant)>,
+    sequence: usize,
+}
+
+impl Ring {
+    fn run(mut self, receiver: mpsc::Receiver<RecordedPacket>) {
+        loop {
+            let result = match receiver.recv_timeout(FLUSH_INTERVAL) {
+                Ok(packet) => self.write(packet),
+                Err(RecvTimeoutError::Timeout) => self.flush(),
+                Err(RecvTimeoutError::Disconnected) => {
+                    let _ = self.flush();
+                    return;
+                }
+            };
+
+            if let Err(err) = result {
+                warn!(
+                    "Recording to {} stopped: {}",
+                    self.config.dir.display(),
+                    err
+                );
+                return;
+            }
+        }
+    }
+
+    fn write(&mut self, packet: RecordedPacket) -> io::Result<()> {
+        if self.should_rotate() {
+            self.rotate()?;
+        }
+
+        let (writer, _) = self
+            .current
+            .as_mut()
+            .expect("file is opened by the rotation");
+        writer.write_packet(0, packet.timestamp, &packet.data, packet.original_len, None)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.current {
+            Some((ref mut writer, _)) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+
+    fn should_rotate(&self) -> bool {
+        let Some((ref writer, opened)) = self.current else {
+            return true;
+        };
+
+        writer.written() >= self.config.max_file_size
+            || self
+                .config
+                .max_file_duration
+                .is_some_and(|duration| opened.elapsed() >= duration)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.flush()?;
+
+        let secs = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        // names sort in the order the files were written
+        let path = self.config.dir.join(format!(
+            "{}{:010}-{:06}.{}",
+            self.prefix, secs, self.sequence, FILE_EXTENSION
+        ));
+        self.sequence += 1;
+
+        let file = BufWriter::new(File::create(&path)?);
+        let writer = PcapngWriter::new(file, std::slice::from_ref(&self.interface))?;
+        self.current = Some((writer, Instant::now()));
+        info!("Recording to {}", path.display());
+
+        self.remove_oldest();
+        Ok(())
+    }
+
+    fn remove_oldest(&self) {
+        let mut files = list_recorded(&self.config.dir, &self.prefix);
+        files.sort();
+
+        let excess = files.len().saturating_sub(self.config.max_files.max(1));
+        for path in files.into_iter().take(excess) {
+            if let Err(err) = fs::remove_file(&path) {
+                warn!("Failed to remove {}: {}", path.display(), err);
+            }
+        }
+    }
+}
+
+pub fn interface(name: &str, link_type: Linktype, filter: Option<&str>) -> Interface {
+    Interface {
+        link_type,
+        name: Some(name.to_string()),
+        description: None,
+        filter: filter.map(str::to_string),
+    }
+}
+
+fn list_recorded(dir: &Path, prefix: &str) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(prefix))
+                && path.extension().is_some_and(|ext| ext == FILE_EXTENSION)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotates_by_size_and_keeps_newest_files() {
+        let dir = std::env::temp_dir().join(format!("netpix-record-{}", std::process::id()));
+        let interface = interface("eth0", Linktype::ETHERNET, None);
+        let header_len = PcapngWriter::new(Vec::new(), std::slice::from_ref(&interface))
+            .unwrap()
+            .written();
+        // 4 bytes of data in a 32 byte enhanced packet block
+        let packet_len = 36;
+
+        let mut ring = Ring {
+            prefix: "eth0-".to_string(),
+            config: RecordConfig {
+                dir: dir.clone(),
+                max_file_size: header_len + 2 * packet_len,
+                max_file_duration: None,
+                max_files: 2,
+            },
+            interface,
+            current: None,
+            sequence: 0,
+        };
+        fs::create_dir_all(&dir).unwrap();
+        // left alone by the rotation
+        fs::write(dir.join("other.pcapng"), b"").unwrap();
+
+        for secs in 1..=5 {
+            let packet = RecordedPacket {
+                timestamp: Duration::from_secs(secs),
+                data: vec![secs as u8; 4],
+                original_len: 4,
+            };
+            ring.write(packet).unwrap();
+        }
+        ring.flush().unwrap();
+
+        let mut files = list_recorded(&dir, "eth0-");
+        files.sort();
+        let recorded: Vec<Vec<i64>> = files
+            .iter()
+            .map(|path| {
+                let mut capture = pcap::Capture::from_file(path).unwrap();
+                let mut secs = Vec::new();
+                while let Ok(packet) = capture.next_packet() {
+                    assert_eq!(packet.data, [packet.header.ts.tv_sec as u8; 4]);
+                    secs.push(packet.header.ts.tv_sec);
+                }
+                secs
+            })
+            .collect();
+        assert!(dir.join("other.pcapng").exists());
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(recorded, [vec![3, 4], vec![5]]);
+    }
+}
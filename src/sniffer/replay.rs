@@ -0,0 +1,190 @@
+use netpix_common::{ReplayControl, ReplayState};
+use std::time::Duration;
+use tokio::time::Instant;
+
+// beyond these the delays between packets are either hours long or meaningless
+const MIN_SPEED: f64 = 0.01;
+const MAX_SPEED: f64 = 1000.0;
+
+/// Paces packets of an offline source by their capture timestamps.
+///
+/// Speed of `0` starts the replay paused, packets are then released one by one with `Step`,
+/// other speeds are clamped to `MIN_SPEED..=MAX_SPEED`.
+#[derive(Debug)]
+pub struct Replay {
+    speed: f64,
+    paused: bool,
+    steps: usize,
+    // capture timestamp of the first packet
+    start: Option<Duration>,
+    position: Duration,
+    // wall clock time at which the packet with the given timestamp is due
+    anchor: Option<(Instant, Duration)>,
+}
+
+impl Replay {
+    pub fn new(speed: f64) -> Self {
+        Self {
+            speed: clamp_speed(speed).unwrap_or(1.0),
+            paused: clamp_speed(speed).is_none(),
+            steps: 0,
+            start: None,
+            position: Duration::ZERO,
+            anchor: None,
+        }
+    }
+
+    pub fn start(&self) -> Option<Duration> {
+        self.start
+    }
+
+    pub fn state(&self) -> ReplayState {
+        ReplayState {
+            paused: self.paused,
+            speed: self.speed,
+            position: self.position,
+        }
+    }
+
+    /// Waits until the packet is due, cancel safe, so it can be raced
+    /// against the controls in `select!` without losing the packet.
+    pub async fn wait_for(&mut self, timestamp: Duration) {
+        let start = *self.start.get_or_insert(timestamp);
+
+        if self.paused {
+            if self.steps == 0 {
+                std::future::pending::<()>().await;
+            }
+            self.steps -= 1;
+        } else {
+            let (instant, anchor) = *self.anchor.get_or_insert((Instant::now(), timestamp));
+            let delay = timestamp.saturating_sub(anchor).as_secs_f64() / self.speed;
+            let delay = Duration::try_from_secs_f64(delay).unwrap_or(Duration::MAX);
+            match instant.checked_add(delay) {
+                Some(due) => tokio::time::sleep_until(due).await,
+                // due after the end of time, only a control gets the replay going again
+                None => std::future::pending::<()>().await,
+            }
+        }
+
+        self.position = timestamp.saturating_sub(start);
+    }
+
+    pub fn control(&mut self, control: ReplayControl) {
+        match control {
+            ReplayControl::Pause => {
+                self.paused = true;
+                self.steps = 0;
+            }
+            ReplayControl::Resume => {
+                self.paused = false;
+                self.anchor = None;
+            }
+            ReplayControl::Step if self.paused => self.steps += 1,
+            ReplayControl::Step => {}
+            ReplayControl::Speed(speed) => {
+                if let Some(speed) = clamp_speed(speed) {
+                    self.speed = speed;
+                    self.anchor = None;
+                }
+            }
+            ReplayControl::Seek(offset) => {
+                self.position = offset;
+                self.anchor = None;
+            }
+        }
+    }
+
+    pub fn set_start(&mut self, start: Duration) {
+        self.start = Some(start);
+    }
+}
+
+// None for zero, negative and NaN speeds
+fn clamp_speed(speed: f64) -> Option<f64> {
+    (speed > 0.0).then(|| speed.clamp(MIN_SPEED, MAX_SPEED))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::FutureExt;
+
+    fn secs(secs: f64) -> Duration {
+        Duration::from_secs_f64(secs)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_paces_by_timestamps() {
+        let mut replay = Replay::new(2.0);
+        let started = Instant::now();
+
+        replay.wait_for(secs(10.0)).await;
+        replay.wait_for(secs(14.0)).await;
+        assert_eq!(started.elapsed(), secs(2.0));
+        assert_eq!(replay.state().position, secs(4.0));
+
+        // a timestamp going back is due at once
+        replay.wait_for(secs(12.0)).await;
+        assert_eq!(started.elapsed(), secs(2.0));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_clamps_speed() {
+        let mut replay = Replay::new(1e-300);
+        assert_eq!(replay.state().speed, MIN_SPEED);
+        assert!(!replay.state().paused);
+
+        replay.control(ReplayControl::Speed(f64::INFINITY));
+        assert_eq!(replay.state().speed, MAX_SPEED);
+        replay.control(ReplayControl::Speed(f64::NAN));
+        replay.control(ReplayControl::Speed(-1.0));
+        assert_eq!(replay.state().speed, MAX_SPEED);
+
+        replay.control(ReplayControl::Speed(1e-300));
+        let started = Instant::now();
+        replay.wait_for(Duration::ZERO).await;
+        replay.wait_for(secs(1.0)).await;
+        assert_eq!(started.elapsed(), secs(100.0));
+
+        // too far in the future to be scheduled, but no panic
+        let mut far = Box::pin(replay.wait_for(Duration::MAX));
+        tokio::time::advance(secs(3600.0)).await;
+        assert!((&mut far).now_or_never().is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_steps_when_paused() {
+        let mut replay = Replay::new(0.0);
+        assert!(replay.state().paused);
+        assert!(replay.wait_for(secs(1.0)).now_or_never().is_none());
+
+        replay.control(ReplayControl::Step);
+        replay.control(ReplayControl::Step);
+        assert!(replay.wait_for(secs(1.0)).now_or_never().is_some());
+        assert!(replay.wait_for(secs(5.0)).now_or_never().is_some());
+        assert!(replay.wait_for(secs(6.0)).now_or_never().is_none());
+        assert_eq!(replay.state().position, secs(4.0));
+
+        // steps are ignored while playing
+        replay.control(ReplayControl::Resume);
+        replay.control(ReplayControl::Step);
+        replay.control(ReplayControl::Pause);
+        assert!(replay.wait_for(secs(7.0)).now_or_never().is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_seek_restarts_pacing() {
+        let mut replay = Replay::new(1.0);
+        replay.wait_for(secs(100.0)).await;
+
+        replay.control(ReplayControl::Seek(secs(50.0)));
+        assert_eq!(replay.state().position, secs(50.0));
+        let started = Instant::now();
+        // the first packet after the seek is due at once, the next one by its offset
+        replay.wait_for(secs(150.0)).await;
+        replay.wait_for(secs(151.0)).await;
+        assert_eq!(started.elapsed(), secs(1.0));
+        assert_eq!(replay.state().position, secs(51.0));
+    }
+}
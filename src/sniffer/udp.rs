@@ -0,0 +1,285 @@
+use super::Error;
+use netpix_common::Packet;
+use netpix_common::packet::{PacketMetadata, SessionPacket, SessionProtocol, TransportProtocol};
+use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::net::UdpSocket;
+
+// large enough for any UDP datagram
+const RECV_BUFFER_LEN: usize = 65536;
+// absorbs bursts of high bitrate streams while the handler is busy
+const SOCKET_RECV_BUFFER_SIZE: usize = 4 * 1024 * 1024;
+
+/// Parsed `<group>:<port>[@source][%iface]` source,
+/// IPv6 groups have to be written in brackets, e.g. `[ff3e::8000:1]:5004`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UdpSource {
+    pub group: SocketAddr,
+    pub source: Option<IpAddr>,
+    pub interface: Option<String>,
+}
+
+impl FromStr for UdpSource {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (rest, interface) = match s.rsplit_once('%') {
+            Some((rest, interface)) if !interface.is_empty() => (rest, Some(interface.to_string())),
+            Some(_) => return Err(Error::InvalidUdpSource),
+            None => (s, None),
+        };
+
+        let (group, source) = match rest.split_once('@') {
+            Some((group, source)) => {
+                let source = source.trim_start_matches('[').trim_end_matches(']');
+                let source: IpAddr = source.parse().map_err(|_| Error::InvalidUdpSource)?;
+                (group, Some(source))
+            }
+            None => (rest, None),
+        };
+
+        let group: SocketAddr = group.parse().map_err(|_| Error::InvalidUdpSource)?;
+
+        if let Some(source) = source
+            && (!group.ip().is_multicast() || source.is_ipv4() != group.is_ipv4())
+        {
+            return Err(Error::InvalidUdpSource);
+        }
+
+        Ok(Self {
+            group,
+            source,
+            interface,
+        })
+    }
+}
+
+impl fmt::Display for UdpSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.group)?;
+        if let Some(source) = self.source {
+            write!(f, "@{}", source)?;
+        }
+        if let Some(ref interface) = self.interface {
+            write!(f, "%{}", interface)?;
+        }
+        Ok(())
+    }
+}
+
+/// Receives datagrams from a socket bound to the group port,
+/// the kernel sends the IGMP/MLD reports, so no capture privileges are needed.
+pub struct UdpStream {
+    socket: UdpSocket,
+    destination_addr: SocketAddr,
+    buffer: Vec<u8>,
+    packet_id: usize,
+}
+
+impl UdpStream {
+    pub fn open(source: &UdpSource) -> Result<Self, Error> {
+        let interface = match source.interface {
+            Some(ref name) => interface_index(name).ok_or(Error::DeviceNotFound)?,
+            None => 0,
+        };
+
+        let socket = bind(source, interface).map_err(|_| Error::SocketUnavailable)?;
+        join(&socket, source, interface).map_err(|_| Error::SocketUnavailable)?;
+
+        let socket = UdpSocket::from_std(socket.into()).map_err(|_| Error::SocketUnavailable)?;
+        // port 0 lets the kernel pick one
+        let mut destination_addr = source.group;
+        if let Ok(local_addr) = socket.local_addr() {
+            destination_addr.set_port(local_addr.port());
+        }
+
+        Ok(Self {
+            socket,
+            destination_addr,
+            buffer: vec![0; RECV_BUFFER_LEN],
+            packet_id: 1,
+        })
+    }
+
+    pub async fn next(&mut self) -> Option<Result<Packet, Error>> {
+        let Ok((len, source_addr)) = self.socket.recv_from(&mut self.buffer).await else {
+            return Some(Err(Error::CouldntReceivePacket));
+        };
+
+        let packet = self.build_packet(len, source_addr);
+        self.packet_id += 1;
+        Some(Ok(packet))
+    }
+
+    fn build_packet(&self, len: usize, source_addr: SocketAddr) -> Packet {
+        let creation_time = SystemTime::now();
+
+        Packet {
+            payload: Some(self.buffer[..len].to_vec()),
+            id: self.packet_id,
+            timestamp: creation_time.duration_since(UNIX_EPOCH).unwrap_or_default(),
+            length: len as u32,
+            source_addr,
+            destination_addr: self.destination_addr,
+            transport_protocol: TransportProtocol::Udp,
+            session_protocol: SessionProtocol::Unknown,
+            contents: SessionPacket::Unknown,
+            creation_time,
+            // addresses come from the socket, not from the IP header
+            metadata: PacketMetadata {
+                is_synthetic_addr: true,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+fn bind(source: &UdpSource, interface: u32) -> std::io::Result<Socket> {
+    let group = source.group;
+    let socket = Socket::new(Domain::for_address(group), Type::DGRAM, Some(Protocol::UDP))?;
+
+    // several listeners (e.g. netpix next to the actual receiver) can share the port
+    socket.set_reuse_address(true)?;
+    #[cfg(unix)]
+    socket.set_reuse_port(true)?;
+    // best effort, the kernel may cap it at rmem_max
+    let _ = socket.set_recv_buffer_size(SOCKET_RECV_BUFFER_SIZE);
+    socket.set_nonblocking(true)?;
+
+    // binding to the group filters out datagrams of other groups sharing the port
+    let bind_addr = match group {
+        SocketAddr::V6(mut addr) => {
+            addr.set_scope_id(interface);
+            SocketAddr::V6(addr)
+        }
+        addr => addr,
+    };
+
+    if let SocketAddr::V6(_) = group {
+        socket.set_only_v6(true)?;
+    }
+
+    socket.bind(&SockAddr::from(bind_addr))?;
+    Ok(socket)
+}
+
+fn join(socket: &Socket, source: &UdpSource, interface: u32) -> std::io::Result<()> {
+    match (source.group.ip(), source.source) {
+        (group, _) if !group.is_multicast() => Ok(()),
+        (group, Some(source)) => join_source_group(socket, group, source, interface),
+        (IpAddr::V4(group), None) => {
+            socket.join_multicast_v4_n(&group, &socket2::InterfaceIndexOrAddress::Index(interface))
+        }
+        (IpAddr::V6(group), None) => socket.join_multicast_v6(&group, interface),
+    }
+}
+
+// `group_source_req` from RFC 3678, not exposed by libc
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[repr(C)]
+struct GroupSourceReq {
+    interface: u32,
+    group: libc::sockaddr_storage,
+    source: libc::sockaddr_storage,
+}
+
+/// Source-specific join (IGMPv3/MLDv2) through the protocol independent
+/// `MCAST_JOIN_SOURCE_GROUP`, as `IP_ADD_SOURCE_MEMBERSHIP` is IPv4 only.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn join_source_group(
+    socket: &Socket,
+    group: IpAddr,
+    source: IpAddr,
+    interface: u32,
+) -> std::io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let to_storage = |addr: IpAddr| {
+        let addr = SockAddr::from(SocketAddr::new(addr, 0));
+        // SAFETY: `SockAddr` is backed by a `sockaddr_storage`
+        unsafe { *(addr.as_ptr() as *const libc::sockaddr_storage) }
+    };
+
+    let request = GroupSourceReq {
+        interface,
+        group: to_storage(group),
+        source: to_storage(source),
+    };
+    let level = match group {
+        IpAddr::V4(_) => libc::IPPROTO_IP,
+        IpAddr::V6(_) => libc::IPPROTO_IPV6,
+    };
+
+    // SAFETY: `request` outlives the call and the passed length matches its type
+    let res = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            level,
+            libc::MCAST_JOIN_SOURCE_GROUP,
+            &request as *const GroupSourceReq as *const libc::c_void,
+            size_of::<GroupSourceReq>() as libc::socklen_t,
+        )
+    };
+
+    if res == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn join_source_group(
+    socket: &Socket,
+    group: IpAddr,
+    source: IpAddr,
+    interface: u32,
+) -> std::io::Result<()> {
+    match (group, source) {
+        (IpAddr::V4(group), IpAddr::V4(source)) if interface == 0 => {
+            socket.join_ssm_v4(&source, &group, &std::net::Ipv4Addr::UNSPECIFIED)
+        }
+        _ => Err(std::io::ErrorKind::Unsupported.into()),
+    }
+}
+
+#[cfg(unix)]
+fn interface_index(name: &str) -> Option<u32> {
+    let name = std::ffi::CString::new(name).ok()?;
+    // SAFETY: `name` is a valid NUL terminated string
+    let index = unsafe { libc::if_nametoindex(name.as_ptr()) };
+    (index != 0).then_some(index)
+}
+
+#[cfg(not(unix))]
+fn interface_index(name: &str) -> Option<u32> {
+    name.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_receives_loopback_datagram() {
+        let source: UdpSource = "127.0.0.1:0".parse().unwrap();
+        let mut stream = UdpStream::open(&source).unwrap();
+        let destination_addr = stream.destination_addr;
+        assert_ne!(destination_addr.port(), 0);
+
+        let sender = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let payload = [0x80, 0x60, 0x00, 0x01, 0xde, 0xad];
+        sender.send_to(&payload, destination_addr).unwrap();
+
+        let packet = stream.next().await.unwrap().unwrap();
+        assert_eq!(packet.id, 1);
+        assert_eq!(packet.source_addr, sender.local_addr().unwrap());
+        assert_eq!(packet.destination_addr, destination_addr);
+        assert_eq!(packet.transport_protocol, TransportProtocol::Udp);
+        assert_eq!(packet.payload.as_deref(), Some(&payload[..]));
+        assert_eq!(packet.length, payload.len() as u32);
+    }
+}
@@ -0,0 +1,189 @@
+use super::{Error, OfflineStream, PacketDecoder};
+use log::{info, warn};
+use netpix_common::Packet;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+// the newest file may still be written to, unless it wasn't touched for a while
+const CLOSE_IDLE_TIME: Duration = Duration::from_secs(10);
+
+/// Reads capture files appearing in a directory (e.g. written by `tcpdump -G` or `-C`)
+/// one after another, oldest first, as a single continuous source.
+///
+/// A file is read once a newer one appears, or once it's idle for `CLOSE_IDLE_TIME`.
+pub struct WatchStream {
+    dir: PathBuf,
+    filter: Option<String>,
+    processed: HashSet<PathBuf>,
+    current: Option<(PathBuf, OfflineStream)>,
+    // owned by the current stream while a file is being read
+    decoder: Option<PacketDecoder>,
+}
+
+impl WatchStream {
+    pub fn new(dir: &str, decoder: PacketDecoder) -> Result<Self, Error> {
+        let dir = PathBuf::from(dir);
+        if !dir.is_dir() {
+            return Err(Error::FileNotFound);
+        }
+
+        Ok(Self {
+            dir,
+            filter: None,
+            processed: HashSet::new(),
+            current: None,
+            decoder: Some(decoder),
+        })
+    }
+
+    pub fn apply_filter(&mut self, filter: &str) -> Result<(), pcap::Error> {
+        if let Some((_, ref mut stream)) = self.current {
+            stream.capture.filter(filter, true)?;
+        }
+
+        self.filter = Some(filter.to_string());
+        Ok(())
+    }
+
+    pub async fn next(&mut self) -> Option<Result<Vec<Packet>, Error>> {
+        loop {
+            if let Some((ref path, ref mut stream)) = self.current {
+                match stream.next() {
+                    Some(Ok(result)) => return Some(result),
+                    Some(Err(err)) => warn!("Failed to read {}: {}", path.display(), err),
+                    None => {}
+                }
+
+                let (_, stream) = self.current.take().unwrap();
+                self.decoder = Some(stream.decoder);
+                continue;
+            }
+
+            match self.next_closed_file() {
+                Some(path) => self.open(path),
+                None => tokio::time::sleep(POLL_INTERVAL).await,
+            }
+        }
+    }
+
+    fn open(&mut self, path: PathBuf) {
+        self.processed.insert(path.clone());
+
+        let mut capture = match pcap::Capture::from_file(&path) {
+            Ok(capture) => capture,
+            Err(err) => {
+                warn!("Skipping {}: {}", path.display(), err);
+                return;
+            }
+        };
+
+        if let Some(ref filter) = self.filter
+            && let Err(err) = capture.filter(filter, true)
+        {
+            warn!("Skipping {}: {}", path.display(), err);
+            return;
+        }
+
+        info!("Reading {}", path.display());
+        let mut decoder = self
+            .decoder
+            .take()
+            .expect("decoder is owned by the watcher");
+        decoder.link_type = capture.get_datalink();
+        self.current = Some((path, OfflineStream::new(capture, decoder)));
+    }
+
+    fn next_closed_file(&mut self) -> Option<PathBuf> {
+        let mut files = list_files(&self.dir);
+        // forget files removed by the rotation, so the set doesn't grow forever
+        self.processed
+            .retain(|path| files.iter().any(|(file, _)| file == path));
+
+        files.sort_by(|(a_path, a_time), (b_path, b_time)| {
+            a_time.cmp(b_time).then_with(|| a_path.cmp(b_path))
+        });
+
+        let newest = files.last().cloned();
+        files
+            .into_iter()
+            .filter(|(path, _)| !self.processed.contains(path))
+            .find(|(path, modified)| {
+                Some(path) != newest.as_ref().map(|(newest, _)| newest)
+                    || modified.elapsed().unwrap_or_default() >= CLOSE_IDLE_TIME
+            })
+            .map(|(path, _)| path)
+    }
+}
+
+// hidden files are skipped, as most tools write temporary files that way
+fn list_files(dir: &Path) -> Vec<(PathBuf, SystemTime)> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    entries
+        .filter_map(Result::ok)
+        .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            metadata
+                .is_file()
+                .then(|| Some((entry.path(), metadata.modified().ok()?)))
+                .flatten()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sniffer::udp_capture;
+    use futures_util::FutureExt;
+    use pcap::Linktype;
+    use std::fs::File;
+
+    fn write(path: &Path, secs: u64, payload: &[u8]) {
+        fs::write(path, udp_capture(&[(Duration::from_secs(secs), payload)])).unwrap();
+        // written one after another a second apart, all of them recently
+        touch(path, SystemTime::now() - Duration::from_secs(5 - secs));
+    }
+
+    fn touch(path: &Path, modified: SystemTime) {
+        let file = File::options().write(true).open(path).unwrap();
+        file.set_modified(modified).unwrap();
+    }
+
+    async fn next_payload(stream: &mut WatchStream) -> Vec<u8> {
+        let mut packets = stream.next().await.unwrap().unwrap();
+        packets.remove(0).payload.unwrap()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_reads_rotated_files() {
+        let dir = std::env::temp_dir().join(format!("netpix-watch-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        write(&dir.join("capture0"), 1, b"first");
+        write(&dir.join("capture1"), 2, b"second");
+        write(&dir.join(".capture2.tmp"), 3, b"hidden");
+
+        let decoder = PacketDecoder::new(Linktype::ETHERNET, Default::default());
+        let mut stream = WatchStream::new(&dir.to_string_lossy(), decoder).unwrap();
+        assert_eq!(next_payload(&mut stream).await, b"first");
+        // the newest file may still be written to
+        assert!(stream.next().now_or_never().is_none());
+
+        // rotated by the capturing tool, the previous file is complete then
+        fs::remove_file(dir.join("capture0")).unwrap();
+        write(&dir.join("capture2"), 3, b"third");
+        assert_eq!(next_payload(&mut stream).await, b"second");
+        assert!(stream.next().now_or_never().is_none());
+
+        // the capture stopped, so the newest file is idle
+        touch(&dir.join("capture2"), SystemTime::now() - CLOSE_IDLE_TIME);
+        assert_eq!(next_payload(&mut stream).await, b"third");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}